@@ -0,0 +1,417 @@
+//! Background sync
+//!
+//! When `sync_enabled` is set, a task started at launch syncs with
+//! `server_url` every `sync_interval_minutes`. Failed syncs back off
+//! exponentially, up to `MAX_BACKOFF_MINUTES`, so an offline server isn't
+//! hammered. Meanwhile its health check is polled while changes are queued,
+//! and they're pushed as soon as it answers. A server that rate-limits a
+//! sync is left alone for as long as it asks. Manual and background syncs
+//! share one flag, so only one sync runs at a time.
+//!
+//! While auto sync is on, a second task also listens to the server's change
+//! notifications and pulls as soon as another device pushes. Bursts of
+//! notifications are coalesced into one pull, the stream is reconnected with
+//! backoff, and the interval sync keeps going whether or not it's connected.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rusqlite::Connection;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+
+use crate::db::Database;
+use crate::error::{AppError, Result};
+use crate::events;
+use crate::settings;
+use crate::sync;
+use crate::validation;
+
+/// Longest wait between retries after repeated failures
+const MAX_BACKOFF_MINUTES: u64 = 60;
+
+/// How often an unreachable server is checked for coming back
+const RECONNECT_CHECK: Duration = Duration::from_secs(60);
+
+/// Quiet time after a change notification before pulling, so a burst of
+/// pushes costs one pull
+const COALESCE: Duration = Duration::from_secs(2);
+
+/// Longest wait before reconnecting to the change notifications
+const MAX_RECONNECT_SECS: u64 = 300;
+
+/// Shared between the background task and the sync commands
+#[derive(Default)]
+pub struct AutoSync {
+    running: AtomicBool,
+    /// Wakes the task so it re-reads its settings
+    reconfigured: Notify,
+    /// Same, for the live update task
+    live_reconfigured: Notify,
+}
+
+/// Held for the duration of a sync; releases the flag on drop
+pub struct Running<'a>(&'a AtomicBool);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl AutoSync {
+    /// Claim the sync flag, or fail if another sync is in progress
+    pub fn begin(&self) -> Result<Running<'_>> {
+        self.running
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| Running(&self.running))
+            .map_err(|_| AppError::Sync("A sync is already in progress".to_string()))
+    }
+
+    /// Make both background tasks re-read their settings
+    fn reconfigure(&self) {
+        self.reconfigured.notify_one();
+        self.live_reconfigured.notify_one();
+    }
+}
+
+struct Config {
+    interval_minutes: u64,
+    server_url: String,
+}
+
+/// The background sync settings, or `None` when it's off or has no server
+fn config(conn: &Connection) -> Result<Option<Config>> {
+    if settings::get(conn, settings::SYNC_ENABLED)?.as_deref() != Some("true") {
+        return Ok(None);
+    }
+    let Some(server_url) = settings::get(conn, settings::SERVER_URL)? else {
+        return Ok(None);
+    };
+    let server_url = validation::normalize_server_url(&server_url)?;
+    let interval =
+        settings::get_i64(conn, settings::SYNC_INTERVAL_MINUTES, settings::DEFAULT_SYNC_INTERVAL_MINUTES)?;
+    Ok(Some(Config {
+        interval_minutes: interval.max(1) as u64,
+        server_url,
+    }))
+}
+
+/// Wait before the next sync: the interval, doubled for each failure in a
+/// row, capped at `MAX_BACKOFF_MINUTES` (never below the interval itself)
+fn next_delay(interval_minutes: u64, failures: u32) -> Duration {
+    let backoff = interval_minutes.saturating_mul(1 << failures.min(16));
+    let minutes = backoff.min(MAX_BACKOFF_MINUTES.max(interval_minutes));
+    Duration::from_secs(minutes * 60)
+}
+
+/// Whether to stop waiting for the next sync: the server failed before, and
+/// now answers while changes are waiting for it
+async fn back_online(db: &Database, server_url: &str) -> bool {
+    let queued = sync::push_queue(&db.conn()).map(|queue| queue.total() > 0);
+    let token = sync::auth_token(&db.conn(), None);
+    let client = sync::http_client(&db.conn());
+    match (queued, token, client) {
+        (Ok(true), Ok(token), Ok(client)) => {
+            sync::server_reachable(&client, server_url, token.as_deref()).await.unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+/// Wait before reconnecting to the change notifications: one second,
+/// doubled for each failure in a row, capped at `MAX_RECONNECT_SECS`
+fn reconnect_delay(failures: u32) -> Duration {
+    Duration::from_secs((1u64 << failures.min(16)).min(MAX_RECONNECT_SECS))
+}
+
+/// When to pull after `revisions` arrived: `COALESCE` after the first one
+/// this device hasn't pulled, keeping a pull already due so a burst of
+/// events doesn't keep pushing it back
+fn schedule_pull(
+    due: Option<tokio::time::Instant>,
+    revisions: &[i64],
+    pulled: i64,
+    now: tokio::time::Instant,
+) -> Option<tokio::time::Instant> {
+    if due.is_none() && revisions.iter().any(|&revision| revision > pulled) {
+        return Some(now + COALESCE);
+    }
+    due
+}
+
+/// Why listening to the change notifications stopped
+enum Listen {
+    Reconfigured,
+    Disconnected,
+}
+
+/// Start the background tasks; they idle until auto sync is enabled
+pub fn start(app: &AppHandle) {
+    app.manage(AutoSync::default());
+    start_live_updates(app.clone());
+    let app = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let db = app.state::<Database>();
+        let auto_sync = app.state::<AutoSync>();
+        let mut failures = 0;
+        // How long the server last asked to wait, if it rate-limited us
+        let mut rate_limited: Option<Duration> = None;
+
+        loop {
+            let config = config(&db.conn());
+            let config = match config {
+                Ok(Some(config)) => config,
+                _ => {
+                    auto_sync.reconfigured.notified().await;
+                    failures = 0;
+                    continue;
+                }
+            };
+
+            // Sleep until the next sync is due, in steps while the server is
+            // failing so it can be checked in between
+            let delay = next_delay(config.interval_minutes, failures).max(rate_limited.take().unwrap_or_default());
+            let due = tokio::time::Instant::now() + delay;
+            let mut reconfigured = false;
+            loop {
+                let left = due.saturating_duration_since(tokio::time::Instant::now());
+                let step = if failures > 0 { left.min(RECONNECT_CHECK) } else { left };
+                if tokio::time::timeout(step, auto_sync.reconfigured.notified()).await.is_ok() {
+                    reconfigured = true;
+                    break;
+                }
+                if left <= step || back_online(&db, &config.server_url).await {
+                    break;
+                }
+            }
+            if reconfigured {
+                failures = 0;
+                continue;
+            }
+
+            // A manual sync is running; it counts as this round
+            let Ok(_running) = auto_sync.begin() else {
+                continue;
+            };
+            let result = sync::run_sync_notifying(&app, &db, &config.server_url, None, None)
+                .await
+                .and_then(|result| match (&result.push_error, result.retry_after_secs) {
+                    (Some(_), Some(retry_after_secs)) => Err(AppError::RateLimited { retry_after_secs }),
+                    (Some(error), None) => Err(AppError::Sync(error.clone())),
+                    (None, _) => Ok(result),
+                });
+            match result {
+                Ok(_) => failures = 0,
+                // The server is up, just busy: wait as long as it asked, without
+                // counting a failure
+                Err(e @ AppError::RateLimited { retry_after_secs }) => {
+                    rate_limited = Some(Duration::from_secs(retry_after_secs));
+                    events::emit_sync_failed(&app, &e, 0);
+                }
+                Err(e) => {
+                    failures += 1;
+                    events::emit_sync_failed(&app, &e, failures);
+                }
+            }
+        }
+    });
+}
+
+fn start_live_updates(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let db = app.state::<Database>();
+        let auto_sync = app.state::<AutoSync>();
+        let mut failures = 0;
+
+        loop {
+            let config = config(&db.conn());
+            let Ok(Some(config)) = config else {
+                auto_sync.live_reconfigured.notified().await;
+                failures = 0;
+                continue;
+            };
+
+            let client = sync::http_client(&db.conn());
+            let token = sync::auth_token(&db.conn(), None);
+            let opened = match (client, token) {
+                (Ok(client), Ok(token)) => sync::open_events(&client, &config.server_url, token.as_deref()).await,
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            };
+            let listen = match opened {
+                Ok(response) => {
+                    failures = 0;
+                    listen(&app, &db, &auto_sync, &config.server_url, response).await
+                }
+                // Older servers have no event stream; the interval sync carries on
+                Err(_) => Listen::Disconnected,
+            };
+            if let Listen::Reconfigured = listen {
+                failures = 0;
+                continue;
+            }
+
+            failures += 1;
+            let delay = reconnect_delay(failures);
+            if tokio::time::timeout(delay, auto_sync.live_reconfigured.notified()).await.is_ok() {
+                failures = 0;
+            }
+        }
+    });
+}
+
+/// Pull after each burst of `revision` events this device hasn't pulled yet,
+/// until the stream ends or the settings change
+async fn listen(
+    app: &AppHandle,
+    db: &Database,
+    auto_sync: &AutoSync,
+    server_url: &str,
+    mut response: reqwest::Response,
+) -> Listen {
+    let mut buffer = String::new();
+    let mut due: Option<tokio::time::Instant> = None;
+
+    loop {
+        let pull_at = due.unwrap_or_else(tokio::time::Instant::now);
+        tokio::select! {
+            _ = auto_sync.live_reconfigured.notified() => return Listen::Reconfigured,
+            _ = tokio::time::sleep_until(pull_at), if due.is_some() => {
+                // A sync already running may miss the new revision; try again after it
+                let Ok(_running) = auto_sync.begin() else {
+                    due = Some(tokio::time::Instant::now() + COALESCE);
+                    continue;
+                };
+                due = None;
+                match sync::run_pull(db, server_url).await {
+                    Ok((stats, _)) if stats != sync::SyncStats::default() => events::emit_pulled(app, &stats),
+                    Ok(_) => {}
+                    Err(AppError::RateLimited { retry_after_secs }) => {
+                        due = Some(tokio::time::Instant::now() + Duration::from_secs(retry_after_secs));
+                    }
+                    Err(e) => events::emit_sync_failed(app, &e, 0),
+                }
+            }
+            chunk = response.chunk() => {
+                let Ok(Some(chunk)) = chunk else {
+                    return Listen::Disconnected;
+                };
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+                let pulled = sync::get_sync_state(db).map(|state| state.last_pull_revision).unwrap_or(0);
+                let revisions = sync::drain_revision_events(&mut buffer);
+                due = schedule_pull(due, &revisions, pulled, tokio::time::Instant::now());
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Tauri Commands
+// =============================================================================
+
+/// Turn on background sync every `interval_minutes`, optionally switching server
+#[tauri::command]
+pub fn enable_auto_sync(
+    db: State<'_, Database>,
+    auto_sync: State<'_, AutoSync>,
+    interval_minutes: i64,
+    server_url: Option<String>,
+) -> Result<()> {
+    let conn = db.conn();
+    if let Some(server_url) = server_url {
+        settings::set(&conn, settings::SERVER_URL, &validation::normalize_server_url(&server_url)?)?;
+    }
+    if settings::get(&conn, settings::SERVER_URL)?.is_none() {
+        return Err(AppError::Validation("Set a server URL before enabling auto sync".to_string()));
+    }
+    settings::set(&conn, settings::SYNC_INTERVAL_MINUTES, &interval_minutes.to_string())?;
+    settings::set(&conn, settings::SYNC_ENABLED, "true")?;
+
+    auto_sync.reconfigure();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn disable_auto_sync(db: State<'_, Database>, auto_sync: State<'_, AutoSync>) -> Result<()> {
+    settings::set(&db.conn(), settings::SYNC_ENABLED, "false")?;
+    auto_sync.reconfigure();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let minutes = |failures| next_delay(5, failures).as_secs() / 60;
+        assert_eq!([minutes(0), minutes(1), minutes(2), minutes(3), minutes(4)], [5, 10, 20, 40, 60]);
+        assert_eq!(minutes(40), 60);
+        // An interval longer than the cap is never shortened
+        assert_eq!(next_delay(120, 3).as_secs() / 60, 120);
+
+        let seconds = |failures| reconnect_delay(failures).as_secs();
+        assert_eq!([seconds(0), seconds(1), seconds(4), seconds(9), seconds(40)], [1, 2, 16, 300, 300]);
+
+        let auto_sync = AutoSync::default();
+        let running = auto_sync.begin().unwrap();
+        assert!(auto_sync.begin().is_err());
+        drop(running);
+        assert!(auto_sync.begin().is_ok());
+    }
+
+    #[test]
+    fn test_a_burst_of_events_is_one_pull() {
+        let start = tokio::time::Instant::now();
+        let later = |secs| start + Duration::from_millis(secs);
+
+        // Revisions already pulled don't schedule anything
+        assert_eq!(schedule_pull(None, &[3, 5], 5, start), None);
+
+        // The first new revision schedules a pull; the rest of the burst,
+        // read in later chunks, leaves it where it is
+        let due = schedule_pull(None, &[5, 6], 5, start);
+        assert_eq!(due, Some(start + COALESCE));
+        let due = [(300, vec![7]), (900, vec![8, 9]), (1500, vec![])]
+            .into_iter()
+            .fold(due, |due, (at, revisions)| schedule_pull(due, &revisions, 5, later(at)));
+        assert_eq!(due, Some(start + COALESCE));
+
+        // Once it has run, the next event schedules a new one
+        assert_eq!(schedule_pull(None, &[10], 9, later(2500)), Some(later(2500) + COALESCE));
+    }
+
+    #[test]
+    fn test_config_needs_enabled_flag_and_server() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        settings::set(&conn, settings::SYNC_ENABLED, "true").unwrap();
+        assert!(config(&conn).unwrap().is_none());
+
+        settings::set(&conn, settings::SERVER_URL, "http://localhost:3000").unwrap();
+        let config = super::config(&conn).unwrap().unwrap();
+        assert_eq!(config.interval_minutes, settings::DEFAULT_SYNC_INTERVAL_MINUTES as u64);
+
+        settings::set(&conn, settings::SYNC_ENABLED, "false").unwrap();
+        assert!(super::config(&conn).unwrap().is_none());
+        assert!(settings::set(&conn, settings::SYNC_ENABLED, "yes").is_err());
+    }
+
+    #[test]
+    fn test_back_online_needs_queued_changes_and_an_answer() {
+        let db = Database::in_memory();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // Nothing queued: the server isn't even asked
+        assert!(!tauri::async_runtime::block_on(back_online(&db, &url)));
+        assert!(listener.accept().is_err());
+
+        // Queued, but nothing answers
+        db.conn().execute("INSERT INTO notes (id) VALUES ('n')", []).unwrap();
+        drop(listener);
+        assert!(!tauri::async_runtime::block_on(back_online(&db, &url)));
+    }
+}
@@ -0,0 +1,255 @@
+//! Batch operations
+//!
+//! Runs several writes as one unit: `batch_execute` applies every op inside
+//! a single transaction, and any failure rolls the whole batch back.
+//! Callers that need to reference an entity created earlier in the batch
+//! supply its id up front (see `CreateNoteInput::id` etc.).
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use ts_rs::TS;
+
+use crate::commands::{notebooks, notes, tags};
+use crate::db::Database;
+use crate::error::{AppError, Result};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::models::{
+    CreateNoteInput, CreateNotebookInput, CreateTagInput, UpdateNoteInput, UpdateNotebookInput,
+};
+
+// =============================================================================
+// Types
+// =============================================================================
+
+/// One write in a batch, tagged by `op`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOp {
+    CreateNote { input: CreateNoteInput },
+    UpdateNote { id: String, input: UpdateNoteInput },
+    DeleteNote { id: String, hard: Option<bool> },
+    CreateNotebook { input: CreateNotebookInput },
+    UpdateNotebook { id: String, input: UpdateNotebookInput },
+    CreateTag { input: CreateTagInput },
+}
+
+impl BatchOp {
+    fn change(&self) -> (ChangeEvent, ChangeKind) {
+        match self {
+            Self::CreateNote { .. } => (ChangeEvent::Note, ChangeKind::Created),
+            Self::UpdateNote { .. } => (ChangeEvent::Note, ChangeKind::Updated),
+            Self::DeleteNote { .. } => (ChangeEvent::Note, ChangeKind::Deleted),
+            Self::CreateNotebook { .. } => (ChangeEvent::Notebook, ChangeKind::Created),
+            Self::UpdateNotebook { .. } => (ChangeEvent::Notebook, ChangeKind::Updated),
+            Self::CreateTag { .. } => (ChangeEvent::Tag, ChangeKind::Created),
+        }
+    }
+}
+
+// =============================================================================
+// Execution
+// =============================================================================
+
+/// Apply `ops` in order inside one transaction.
+/// Returns the id of the entity each op touched, or `AppError::Batch`
+/// naming the first op that failed.
+pub fn execute_batch(db: &Database, ops: Vec<BatchOp>) -> Result<Vec<String>> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    let mut ids = Vec::with_capacity(ops.len());
+
+    for (index, op) in ops.into_iter().enumerate() {
+        let result = match op {
+            BatchOp::CreateNote { input } => notes::insert_note(&tx, input),
+            BatchOp::UpdateNote { id, input } => notes::write_note_update(&tx, &id, input).map(|_| id),
+            BatchOp::DeleteNote { id, hard } => {
+                if hard.unwrap_or(false) {
+                    notes::purge_note_with_tombstone(&tx, &id).map(|_| id)
+                } else {
+                    notes::trash_note(&tx, &id).map(|_| id)
+                }
+            }
+            BatchOp::CreateNotebook { input } => notebooks::insert_notebook(&tx, input),
+            BatchOp::UpdateNotebook { id, input } => {
+                notebooks::write_notebook_update(&tx, &id, input).map(|_| id)
+            }
+            BatchOp::CreateTag { input } => tags::insert_tag(&tx, input),
+        };
+
+        // Dropping the transaction on return rolls everything back
+        let id = result.map_err(|e| AppError::Batch {
+            index,
+            source: Box::new(e),
+        })?;
+        ids.push(id);
+    }
+
+    tx.commit()?;
+    Ok(ids)
+}
+
+// =============================================================================
+// Tauri Commands
+// =============================================================================
+
+/// Run several writes atomically
+#[tauri::command]
+pub fn batch_execute(app: AppHandle, db: State<'_, Database>, ops: Vec<BatchOp>) -> Result<Vec<String>> {
+    let changes: Vec<(ChangeEvent, ChangeKind)> = ops.iter().map(BatchOp::change).collect();
+    let ids = execute_batch(&db, ops)?;
+
+    for ((event, kind), id) in changes.into_iter().zip(&ids) {
+        events::emit(&app, event, id, kind);
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+
+    fn create_notebook_op(id: &str) -> BatchOp {
+        BatchOp::CreateNotebook {
+            input: CreateNotebookInput {
+                id: Some(id.to_string()),
+                name: "Project".to_string(),
+                color: None,
+                icon: None,
+                parent_id: None,
+            },
+        }
+    }
+
+    fn create_note_op(notebook_id: &str, title: &str) -> BatchOp {
+        BatchOp::CreateNote {
+            input: CreateNoteInput {
+                id: None,
+                title: Some(title.to_string()),
+                content: None,
+                notebook_id: Some(Some(notebook_id.to_string())),
+                tags: Some(vec!["project".to_string()]),
+            },
+        }
+    }
+
+    fn count(db: &Database, table: &str) -> i64 {
+        db.conn()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_batch_applies_all_ops() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let notebook_id = uuid::Uuid::new_v4().to_string();
+
+        let ids = execute_batch(
+            &db,
+            vec![
+                create_notebook_op(&notebook_id),
+                create_note_op(&notebook_id, "One"),
+                create_note_op(&notebook_id, "Two"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[0], notebook_id);
+        let in_notebook: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM notes WHERE notebook_id = ?", [&notebook_id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(in_notebook, 2);
+    }
+
+    #[test]
+    fn test_batch_rolls_back_on_failure() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let notebook_id = uuid::Uuid::new_v4().to_string();
+
+        let result = execute_batch(
+            &db,
+            vec![
+                create_notebook_op(&notebook_id),
+                create_note_op(&notebook_id, "One"),
+                BatchOp::UpdateNote {
+                    id: "missing".to_string(),
+                    input: UpdateNoteInput {
+                        title: Some("Nope".to_string()),
+                        content: None,
+                        notebook_id: None,
+                        tags: None,
+                        status: None,
+                        is_pinned: None,
+                    },
+                },
+            ],
+        );
+
+        match result {
+            Err(AppError::Batch { index, source }) => {
+                assert_eq!(index, 2);
+                assert!(matches!(*source, AppError::NotFound(_)));
+            }
+            other => panic!("expected batch error, got {:?}", other),
+        }
+        assert_eq!(count(&db, "notebooks"), 0);
+        assert_eq!(count(&db, "notes"), 0);
+    }
+
+    fn create_tag_op(name: &str) -> BatchOp {
+        BatchOp::CreateTag {
+            input: CreateTagInput {
+                id: None,
+                name: name.to_string(),
+                color: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_batch_sees_its_own_writes_and_undoes_them() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+
+        // The second tag clashes with the first, created earlier in the batch
+        match execute_batch(&db, vec![create_tag_op("work"), create_tag_op("work")]) {
+            Err(AppError::Batch { index, source }) => {
+                assert_eq!(index, 1);
+                assert!(matches!(*source, AppError::Conflict(_)));
+            }
+            other => panic!("expected batch error, got {:?}", other),
+        }
+        assert_eq!(count(&db, "tags"), 0);
+
+        // A hard delete rolled back takes its tombstone with it
+        let notebook_id = uuid::Uuid::new_v4().to_string();
+        let ids =
+            execute_batch(&db, vec![create_notebook_op(&notebook_id), create_note_op(&notebook_id, "Keep")]).unwrap();
+        let result = execute_batch(
+            &db,
+            vec![
+                BatchOp::DeleteNote {
+                    id: ids[1].clone(),
+                    hard: Some(true),
+                },
+                create_note_op("no-such-notebook", "Orphan"),
+            ],
+        );
+        assert!(matches!(result, Err(AppError::Batch { index: 1, .. })));
+        assert_eq!(count(&db, "notes"), 1);
+        assert_eq!(count(&db, "deleted_entities"), 0);
+
+        assert!(execute_batch(&db, Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_op_json_shape() {
+        let op: BatchOp = serde_json::from_str(r#"{"op": "delete_note", "id": "abc", "hard": null}"#).unwrap();
+        assert!(matches!(op, BatchOp::DeleteNote { ref id, hard: None } if id == "abc"));
+    }
+}
@@ -0,0 +1,129 @@
+//! Integrity checksums for sync request and response bodies
+//!
+//! The checksum is a SHA-256 over the body minus its `checksum` field, with
+//! object keys sorted at every level, so both sides hash the same bytes no
+//! matter in which order their structs declare fields. It catches a body
+//! that was cut short yet still decodes, e.g. into an empty payload.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, Result};
+
+/// Hex SHA-256 of `body`, ignoring its top-level `checksum` field
+pub fn of(body: &Value) -> String {
+    let mut canonical = String::new();
+    write_canonical(body, true, &mut canonical);
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+fn write_canonical(value: &Value, top: bool, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().filter(|key| !(top && *key == "checksum")).collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(&map[key], false, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, false, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Serialize `body` with its checksum added
+pub fn sign(body: &impl Serialize) -> Result<Value> {
+    let mut value = serde_json::to_value(body).map_err(|e| AppError::Sync(e.to_string()))?;
+    let checksum = of(&value);
+    if let Value::Object(map) = &mut value {
+        map.insert("checksum".to_string(), Value::from(checksum));
+    }
+    Ok(value)
+}
+
+/// Decode a body after checking its checksum. Servers that predate checksums
+/// don't send one; their bodies are taken as they are.
+pub fn verified<T: DeserializeOwned>(body: Value) -> Result<T> {
+    if let Some(expected) = body.get("checksum").and_then(Value::as_str) {
+        let actual = of(&body);
+        if actual != expected {
+            return Err(AppError::Sync(format!(
+                "response checksum mismatch (expected {}, got {}); the body was altered or cut short",
+                expected, actual
+            )));
+        }
+    }
+    serde_json::from_value(body).map_err(|e| AppError::Sync(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_checksum_ignores_key_order() {
+        let a = json!({"notes": [{"id": "n", "revision": 2}], "server_revision": 5});
+        let b = json!({"server_revision": 5, "notes": [{"revision": 2, "id": "n"}]});
+        assert_eq!(of(&a), of(&b));
+        assert_ne!(of(&a), of(&json!({"notes": [], "server_revision": 5})));
+    }
+
+    #[test]
+    fn test_verified_rejects_altered_bodies() {
+        let mut body = sign(&json!({"notes": [{"id": "n"}], "server_revision": 5})).unwrap();
+        assert!(verified::<Value>(body.clone()).is_ok());
+
+        // What a cut-short body that still decodes looks like
+        body["notes"] = json!([]);
+        match verified::<Value>(body.clone()) {
+            Err(AppError::Sync(message)) => assert!(message.contains("checksum mismatch"), "{}", message),
+            other => panic!("altered body accepted: {:?}", other),
+        }
+
+        // Nothing to check against
+        body.as_object_mut().unwrap().remove("checksum");
+        assert!(verified::<Value>(body).is_ok());
+    }
+
+    #[test]
+    fn test_checksum_leaves_out_only_the_top_level_field() {
+        // Pinned so a change here that the server doesn't make shows up
+        let body = json!({"b": [{"checksum": "x", "c": "é"}], "a": 1, "checksum": "ignored"});
+        assert_eq!(of(&body), "f6dde752daa284377c7b70ee409ef7c516b2b222efa8bf594bc96897eb9cb958");
+    }
+
+    #[test]
+    fn test_checksum_of_the_wrong_case_fails_but_a_null_one_is_missing() {
+        let mut signed = sign(&json!({"server_revision": 5})).unwrap();
+        let upper = signed["checksum"].as_str().unwrap().to_uppercase();
+        signed["checksum"] = Value::from(upper);
+        assert!(verified::<Value>(signed.clone()).is_err());
+        // Like older servers
+        signed["checksum"] = Value::Null;
+        assert!(verified::<Value>(signed).is_ok());
+    }
+
+    #[test]
+    fn test_matching_body_of_the_wrong_type_is_still_an_error() {
+        let signed = sign(&json!({"notes": "five", "notebooks": 0, "tags": 0})).unwrap();
+        assert!(matches!(verified::<crate::sync::SyncStats>(signed), Err(AppError::Sync(_))));
+    }
+}
@@ -1,9 +1,16 @@
-use rusqlite::params;
-use tauri::State;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, State};
 
 use crate::db::Database;
 use crate::error::{AppError, Result};
-use crate::models::{CreateNotebookInput, Notebook, UpdateNotebookInput};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::models::{
+    CreateNotebookInput, Notebook, NotebookArchiveSummary, NotebookCount, NotebookDeleteMode,
+    NotebookDeleteSummary, NotebookStats, TagUsage, UpdateNotebookInput,
+};
+use crate::settings;
+use crate::sync;
+use crate::validation;
 
 fn row_to_notebook(row: &rusqlite::Row) -> rusqlite::Result<Notebook> {
     Ok(Notebook {
@@ -16,20 +23,23 @@ fn row_to_notebook(row: &rusqlite::Row) -> rusqlite::Result<Notebook> {
         created_at: row.get(6)?,
         updated_at: row.get(7)?,
         deleted_at: row.get(8)?,
+        is_archived: row.get::<_, i32>(9)? != 0,
+        sync_excluded: row.get::<_, i32>(10)? != 0,
     })
 }
 
 #[tauri::command]
-pub fn list_notebooks(db: State<'_, Database>) -> Result<Vec<Notebook>> {
+pub fn list_notebooks(db: State<'_, Database>, include_archived: Option<bool>) -> Result<Vec<Notebook>> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
-         FROM notebooks WHERE deleted_at IS NULL ORDER BY name",
+        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived,
+                sync_excluded
+         FROM notebooks WHERE deleted_at IS NULL AND (? OR is_archived = 0) ORDER BY name",
     )?;
 
     let notebooks = stmt
-        .query_map([], row_to_notebook)?
+        .query_map(params![include_archived.unwrap_or(false)], row_to_notebook)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(notebooks)
@@ -40,7 +50,8 @@ pub fn get_notebook(db: State<'_, Database>, id: String) -> Result<Notebook> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
+        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived,
+                sync_excluded
          FROM notebooks WHERE id = ?",
     )?;
 
@@ -49,85 +60,625 @@ pub fn get_notebook(db: State<'_, Database>, id: String) -> Result<Notebook> {
 }
 
 #[tauri::command]
-pub fn create_notebook(db: State<'_, Database>, input: CreateNotebookInput) -> Result<Notebook> {
-    let id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
+pub fn create_notebook(
+    app: AppHandle,
+    db: State<'_, Database>,
+    input: CreateNotebookInput,
+) -> Result<Notebook> {
+    let id = insert_notebook(&db.conn(), input)?;
 
-    {
-        let conn = db.conn();
-        conn.execute(
-            "INSERT INTO notebooks (id, name, color, icon, parent_id, revision, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, 1, ?, ?)",
-            params![id, input.name, input.color, input.icon, input.parent_id, now, now],
-        )?;
-    }
+    let notebook = get_notebook(db, id)?;
+    events::emit(&app, ChangeEvent::Notebook, &notebook.id, ChangeKind::Created);
+    Ok(notebook)
+}
 
-    get_notebook(db, id)
+/// Insert a new notebook, returning its id
+pub fn insert_notebook(conn: &Connection, input: CreateNotebookInput) -> Result<String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let id = validation::new_entity_id(conn, "notebooks", input.id)?;
+    if let Some(color) = &input.color {
+        validation::color(color)?;
+    }
+    if let Some(icon) = &input.icon {
+        validation::notebook_icon(icon)?;
+    }
+    if let Some(parent_id) = &input.parent_id {
+        check_parent(conn, &id, parent_id)?;
+    }
+    check_sibling_name(conn, &id, input.parent_id.as_deref(), &input.name)?;
+    conn.execute(
+        "INSERT INTO notebooks (id, name, color, icon, parent_id, revision, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, 1, ?, ?)",
+        params![id, input.name, input.color, input.icon, input.parent_id, now, now],
+    )?;
+    Ok(id)
 }
 
 #[tauri::command]
-pub fn update_notebook(db: State<'_, Database>, id: String, input: UpdateNotebookInput) -> Result<Notebook> {
+pub fn update_notebook(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    input: UpdateNotebookInput,
+) -> Result<Notebook> {
+    write_notebook_update(&db.conn(), &id, input)?;
+
+    let notebook = get_notebook(db, id)?;
+    events::emit(&app, ChangeEvent::Notebook, &notebook.id, ChangeKind::Updated);
+    Ok(notebook)
+}
+
+/// Apply a partial update to a notebook
+pub fn write_notebook_update(conn: &Connection, id: &str, input: UpdateNotebookInput) -> Result<()> {
     let existing = {
-        let conn = db.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
+            "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived,
+                    sync_excluded
              FROM notebooks WHERE id = ?",
         )?;
-        stmt.query_row(params![&id], row_to_notebook)
+        stmt.query_row(params![id], row_to_notebook)
             .map_err(|_| AppError::NotFound(format!("Notebook {} not found", id)))?
     };
 
     let now = chrono::Utc::now().to_rfc3339();
     let new_revision = existing.revision + 1;
 
+    if let Some(Some(color)) = &input.color {
+        validation::color(color)?;
+    }
+    if let Some(Some(icon)) = &input.icon {
+        validation::notebook_icon(icon)?;
+    }
+    if let Some(Some(new_parent)) = &input.parent_id {
+        check_parent(conn, id, new_parent)?;
+    }
+    let renamed_or_moved = input.name.is_some() || input.parent_id.is_some();
+
     let name = input.name.unwrap_or(existing.name);
-    let color = input.color.or(existing.color);
-    let icon = input.icon.or(existing.icon);
-    let parent_id = input.parent_id.or(existing.parent_id);
+    let color = input.color.unwrap_or(existing.color);
+    let icon = input.icon.unwrap_or(existing.icon);
+    let parent_id = input.parent_id.unwrap_or(existing.parent_id);
 
-    {
-        let conn = db.conn();
-        conn.execute(
-            "UPDATE notebooks SET name = ?, color = ?, icon = ?, parent_id = ?, revision = ?, updated_at = ?
-             WHERE id = ?",
-            params![name, color, icon, parent_id, new_revision, now, id],
-        )?;
+    if renamed_or_moved {
+        check_sibling_name(conn, id, parent_id.as_deref(), &name)?;
     }
 
-    get_notebook(db, id)
+    conn.execute(
+        "UPDATE notebooks SET name = ?, color = ?, icon = ?, parent_id = ?, revision = ?, needs_push = 1, updated_at = ?
+         WHERE id = ?",
+        params![name, color, icon, parent_id, new_revision, now, id],
+    )?;
+
+    Ok(())
 }
 
-#[tauri::command]
-pub fn delete_notebook(db: State<'_, Database>, id: String, hard: Option<bool>) -> Result<()> {
-    let conn = db.conn();
+/// Reject a name already used by another live notebook under the same
+/// parent, ignoring case. Only interactive writes check this; import and
+/// sync keep whatever names they carry.
+fn check_sibling_name(conn: &Connection, id: &str, parent_id: Option<&str>, name: &str) -> Result<()> {
+    let siblings: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM notebooks
+             WHERE parent_id IS ? AND id != ? AND deleted_at IS NULL",
+        )?
+        .query_map(params![parent_id, id], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
 
-    if hard.unwrap_or(false) {
-        conn.execute(
-            "UPDATE notes SET notebook_id = NULL, revision = revision + 1, updated_at = datetime('now') WHERE notebook_id = ?",
+    let lowered = name.to_lowercase();
+    if siblings.iter().any(|sibling| sibling.to_lowercase() == lowered) {
+        return Err(AppError::Conflict(format!("Notebook '{}' already exists here", name)));
+    }
+    Ok(())
+}
+
+/// Notebook id stored under a settings key, if that notebook still exists
+pub fn notebook_from_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let Some(id) = settings::get(conn, key)? else {
+        return Ok(None);
+    };
+    let exists = conn
+        .prepare("SELECT 1 FROM notebooks WHERE id = ? AND deleted_at IS NULL")?
+        .exists(params![&id])?;
+    Ok(exists.then_some(id))
+}
+
+/// Guard against runaway chains in rows that already contain a cycle
+const MAX_CHAIN_WALK: i64 = 1000;
+
+/// Make sure `parent_id` can hold notebook `id`: it must exist outside the
+/// trash, must not be `id` itself or one of its descendants, and the moved
+/// subtree must stay within the configured nesting depth.
+fn check_parent(conn: &Connection, id: &str, parent_id: &str) -> Result<()> {
+    // The proposed parent followed by its ancestors, nearest first
+    let chain: Vec<String> = conn
+        .prepare(
+            "WITH RECURSIVE chain(id, parent_id, depth) AS (
+                 SELECT id, parent_id, 1 FROM notebooks WHERE id = ?1 AND deleted_at IS NULL
+                 UNION ALL
+                 SELECT nb.id, nb.parent_id, chain.depth + 1
+                 FROM notebooks nb JOIN chain ON nb.id = chain.parent_id
+                 WHERE chain.depth < ?2
+             )
+             SELECT id FROM chain ORDER BY depth",
+        )?
+        .query_map(params![parent_id, MAX_CHAIN_WALK], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if chain.is_empty() {
+        return Err(AppError::NotFound(format!("Notebook {} not found", parent_id)));
+    }
+    if chain.iter().any(|ancestor| ancestor == id) {
+        return Err(AppError::Validation("would create a cycle".to_string()));
+    }
+
+    // Levels in the subtree being moved, counting the notebook itself
+    let height: i64 = conn.query_row(
+        "WITH RECURSIVE subtree(id, level) AS (
+             SELECT ?1, 1
+             UNION ALL
+             SELECT nb.id, subtree.level + 1
+             FROM notebooks nb JOIN subtree ON nb.parent_id = subtree.id
+             WHERE nb.deleted_at IS NULL AND subtree.level < ?2
+         )
+         SELECT MAX(level) FROM subtree",
+        params![id, MAX_CHAIN_WALK],
+        |row| row.get(0),
+    )?;
+
+    let max_depth =
+        settings::get_i64(conn, settings::MAX_NOTEBOOK_DEPTH, settings::DEFAULT_MAX_NOTEBOOK_DEPTH)?;
+    let parent_depth = chain.len() as i64;
+    if parent_depth + height > max_depth {
+        return Err(AppError::Validation(format!(
+            "Notebooks can be nested at most {} levels deep; the parent is already at depth {} \
+             and this would reach depth {}",
+            max_depth,
+            parent_depth,
+            parent_depth + height
+        )));
+    }
+    Ok(())
+}
+
+/// `notes_destination` value that sends notes to the deleted notebook's parent
+pub const NOTES_TO_PARENT: &str = "parent";
+
+/// Delete a notebook inside the caller's transaction, handling its children
+/// and notes according to `mode`. `notes_destination` overrides where notes
+/// go: a notebook id, or `"parent"`. Hard deletes leave tombstones for sync.
+pub fn remove_notebook(
+    conn: &Connection,
+    id: &str,
+    mode: NotebookDeleteMode,
+    notes_destination: Option<&str>,
+    hard: bool,
+) -> Result<NotebookDeleteSummary> {
+    let parent_id: Option<String> = conn
+        .query_row(
+            "SELECT parent_id FROM notebooks WHERE id = ? AND deleted_at IS NULL",
             params![id],
-        )?;
-        conn.execute("DELETE FROM notebooks WHERE id = ?", params![id])?;
-    } else {
-        let now = chrono::Utc::now().to_rfc3339();
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Notebook {} not found", id)))?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    // The notebook plus, when deleting children, every live descendant
+    let notebooks_deleted = notebook_scope(conn, id, mode == NotebookDeleteMode::DeleteChildren)?;
+    let mut summary = NotebookDeleteSummary {
+        notebooks_deleted,
+        ..Default::default()
+    };
+
+    // Checked before anything is written
+    let notes_destination = match (notes_destination, mode) {
+        (Some(NOTES_TO_PARENT), _) | (None, NotebookDeleteMode::MoveNotesToParent) => parent_id.clone(),
+        (Some(target), _) => {
+            if summary.notebooks_deleted.iter().any(|deleted| deleted == target) {
+                return Err(AppError::Validation(
+                    "Notes can't be moved into a notebook that is being deleted".to_string(),
+                ));
+            }
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM notebooks WHERE id = ? AND deleted_at IS NULL)",
+                params![target],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Err(AppError::NotFound(format!("Notebook {} not found", target)));
+            }
+            Some(target.to_string())
+        }
+        (None, _) => None,
+    };
+
+    if mode != NotebookDeleteMode::DeleteChildren {
+        summary.children_promoted = conn
+            .prepare("SELECT id FROM notebooks WHERE parent_id = ? AND deleted_at IS NULL")?
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
         conn.execute(
-            "UPDATE notes SET notebook_id = NULL, revision = revision + 1, updated_at = ? WHERE notebook_id = ?",
-            params![now, id],
+            "UPDATE notebooks SET parent_id = ?, revision = revision + 1, needs_push = 1, updated_at = ?
+             WHERE parent_id = ? AND deleted_at IS NULL",
+            params![parent_id, now, id],
         )?;
+    }
+
+    for notebook_id in &summary.notebooks_deleted {
+        let note_ids: Vec<String> = conn
+            .prepare("SELECT id FROM notes WHERE notebook_id = ?")?
+            .query_map(params![notebook_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
         conn.execute(
-            "UPDATE notebooks SET deleted_at = ?, revision = revision + 1, updated_at = ? WHERE id = ?",
-            params![now, now, id],
+            "UPDATE notes SET notebook_id = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE notebook_id = ?",
+            params![notes_destination, now, notebook_id],
         )?;
+        summary.notes_moved += note_ids.len() as i32;
+        for note_id in &note_ids {
+            sync::note_moved(conn, note_id)?;
+        }
+
+        if hard {
+            purge_notebook_with_tombstone(conn, notebook_id)?;
+        } else {
+            conn.execute(
+                "UPDATE notebooks SET deleted_at = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+                params![now, now, notebook_id],
+            )?;
+        }
+
+        // Settings pointing at the notebook fall back to their defaults
+        settings::reset_if(conn, settings::DEFAULT_NOTEBOOK_ID, notebook_id)?;
+        settings::reset_if(conn, settings::CAPTURE_NOTEBOOK_ID, notebook_id)?;
+    }
+
+    Ok(summary)
+}
+
+/// Hard-delete a notebook row, leaving a tombstone so other devices drop it
+fn purge_notebook_with_tombstone(conn: &Connection, id: &str) -> Result<()> {
+    let revision: i64 = conn.query_row("SELECT revision FROM notebooks WHERE id = ?", params![id], |row| row.get(0))?;
+    conn.execute("DELETE FROM notebooks WHERE id = ?", params![id])?;
+    sync::record_deletion(conn, "notebook", id, revision + 1)
+}
+
+/// Permanently remove a notebook that is already in the trash
+pub fn purge_trashed_notebook(conn: &Connection, id: &str) -> Result<()> {
+    let deleted_at: Option<String> = conn
+        .query_row("SELECT deleted_at FROM notebooks WHERE id = ?", params![id], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Notebook {} not found", id)))?;
+    if deleted_at.is_none() {
+        return Err(AppError::Validation(format!(
+            "Notebook {} is not in the trash; delete it before purging",
+            id
+        )));
+    }
+    purge_notebook_with_tombstone(conn, id)
+}
+
+/// Purge trashed notebooks deleted at least `older_than_days` ago,
+/// returning their ids
+pub fn purge_expired_notebooks(conn: &Connection, older_than_days: i64) -> Result<Vec<String>> {
+    let ids: Vec<String> = conn
+        .prepare(
+            "SELECT id FROM notebooks
+             WHERE deleted_at IS NOT NULL AND julianday('now') - julianday(deleted_at) >= ?",
+        )?
+        .query_map(params![older_than_days], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for id in &ids {
+        purge_notebook_with_tombstone(conn, id)?;
+    }
+    Ok(ids)
+}
+
+/// Soft-deleted notebooks, most recently deleted first
+#[tauri::command]
+pub fn get_trashed_notebooks(db: State<'_, Database>) -> Result<Vec<Notebook>> {
+    let conn = db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived,
+                sync_excluded
+         FROM notebooks WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+    )?;
+
+    let notebooks = stmt
+        .query_map([], row_to_notebook)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(notebooks)
+}
+
+#[tauri::command]
+pub fn purge_notebook(app: AppHandle, db: State<'_, Database>, id: String) -> Result<()> {
+    {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        purge_trashed_notebook(&tx, &id)?;
+        tx.commit()?;
     }
 
+    events::emit(&app, ChangeEvent::Notebook, &id, ChangeKind::Deleted);
     Ok(())
 }
 
+/// Purge trashed notebooks older than `older_than_days`, or the
+/// `trash_retention_days` setting when omitted
+#[tauri::command]
+pub fn empty_notebook_trash(
+    app: AppHandle,
+    db: State<'_, Database>,
+    older_than_days: Option<i64>,
+) -> Result<Vec<String>> {
+    let purged = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let days = match older_than_days {
+            Some(days) => days,
+            None => settings::get_i64(
+                &tx,
+                settings::TRASH_RETENTION_DAYS,
+                settings::DEFAULT_TRASH_RETENTION_DAYS,
+            )?,
+        };
+        let purged = purge_expired_notebooks(&tx, days)?;
+        tx.commit()?;
+        purged
+    };
+
+    for id in &purged {
+        events::emit(&app, ChangeEvent::Notebook, id, ChangeKind::Deleted);
+    }
+    Ok(purged)
+}
+
+#[tauri::command]
+pub fn delete_notebook(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    hard: Option<bool>,
+    mode: Option<NotebookDeleteMode>,
+    notes_destination: Option<String>,
+) -> Result<NotebookDeleteSummary> {
+    let summary = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let summary = remove_notebook(
+            &tx,
+            &id,
+            mode.unwrap_or_default(),
+            notes_destination.as_deref(),
+            hard.unwrap_or(false),
+        )?;
+        tx.commit()?;
+        summary
+    };
+
+    for notebook_id in &summary.notebooks_deleted {
+        events::emit(&app, ChangeEvent::Notebook, notebook_id, ChangeKind::Deleted);
+    }
+    for notebook_id in &summary.children_promoted {
+        events::emit(&app, ChangeEvent::Notebook, notebook_id, ChangeKind::Updated);
+    }
+    Ok(summary)
+}
+
+/// The notebook plus, when `recursive`, every live descendant
+pub fn notebook_scope(conn: &Connection, id: &str, recursive: bool) -> Result<Vec<String>> {
+    if !recursive {
+        return Ok(vec![id.to_string()]);
+    }
+    let ids = conn
+        .prepare(
+            "WITH RECURSIVE subtree(id, level) AS (
+                 SELECT ?1, 0
+                 UNION ALL
+                 SELECT nb.id, subtree.level + 1
+                 FROM notebooks nb JOIN subtree ON nb.parent_id = subtree.id
+                 WHERE nb.deleted_at IS NULL AND subtree.level < ?2
+             )
+             SELECT id FROM subtree ORDER BY level",
+        )?
+        .query_map(params![id, MAX_CHAIN_WALK], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(ids)
+}
+
+/// Archive or unarchive a notebook (and its subtree when `recursive`).
+/// Archiving moves the active notes inside to `archived`, remembering which
+/// notebook did it; unarchiving only brings back the notes it archived.
+pub fn set_notebook_archived(
+    conn: &Connection,
+    id: &str,
+    recursive: bool,
+    archived: bool,
+) -> Result<NotebookArchiveSummary> {
+    let exists = conn
+        .prepare("SELECT 1 FROM notebooks WHERE id = ? AND deleted_at IS NULL")?
+        .exists(params![id])?;
+    if !exists {
+        return Err(AppError::NotFound(format!("Notebook {} not found", id)));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut summary = NotebookArchiveSummary::default();
+
+    for notebook_id in notebook_scope(conn, id, recursive)? {
+        let note_ids: Vec<String> = if archived {
+            conn.prepare("SELECT id FROM notes WHERE notebook_id = ? AND status = 'active' AND deleted_at IS NULL")?
+                .query_map(params![notebook_id], |row| row.get(0))?
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        } else {
+            conn.prepare(
+                "SELECT id FROM notes
+                 WHERE archived_by_notebook = ? AND status = 'archived' AND deleted_at IS NULL",
+            )?
+            .query_map(params![notebook_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+        };
+
+        let (status, marker) = if archived {
+            ("archived", Some(notebook_id.as_str()))
+        } else {
+            ("active", None)
+        };
+        for note_id in &note_ids {
+            conn.execute(
+                "UPDATE notes SET status = ?, archived_by_notebook = ?, revision = revision + 1, needs_push = 1, updated_at = ?
+                 WHERE id = ?",
+                params![status, marker, now, note_id],
+            )?;
+        }
+        summary.note_ids.extend(note_ids);
+
+        let changed = conn.execute(
+            "UPDATE notebooks SET is_archived = ?, revision = revision + 1, needs_push = 1, updated_at = ?
+             WHERE id = ? AND is_archived != ?",
+            params![archived, now, notebook_id, archived],
+        )?;
+        if changed > 0 {
+            summary.notebook_ids.push(notebook_id);
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Keep a notebook and its notes on this device only, or share them again.
+/// Excluding pushes tombstones so other devices drop their copies; including
+/// queues everything in it for the next push. Returns whether the flag changed.
+pub fn set_sync_excluded(conn: &Connection, id: &str, excluded: bool) -> Result<bool> {
+    let current: Option<(bool, i64)> = conn
+        .query_row(
+            "SELECT sync_excluded, revision FROM notebooks WHERE id = ?",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((current, revision)) = current else {
+        return Err(AppError::NotFound(format!("Notebook {} not found", id)));
+    };
+    if current == excluded {
+        return Ok(false);
+    }
+
+    conn.execute("UPDATE notebooks SET sync_excluded = ? WHERE id = ?", params![excluded, id])?;
+    let notes: Vec<(String, i64)> = conn
+        .prepare("SELECT id, revision FROM notes WHERE notebook_id = ?")?
+        .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if excluded {
+        sync::record_deletion(conn, "notebook", id, revision + 1)?;
+        for (note_id, revision) in &notes {
+            sync::record_deletion(conn, "note", note_id, revision + 1)?;
+        }
+        return Ok(true);
+    }
+
+    // A fresh updated_at, so the copies are newer than the tombstones other
+    // devices already applied
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE notebooks SET revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+        params![now, id],
+    )?;
+    conn.execute(
+        "UPDATE notes SET revision = revision + 1, needs_push = 1, updated_at = ? WHERE notebook_id = ?",
+        params![now, id],
+    )?;
+    conn.execute(
+        "UPDATE reminders SET revision = revision + 1, needs_push = 1, updated_at = ?
+         WHERE note_id IN (SELECT id FROM notes WHERE notebook_id = ?)",
+        params![now, id],
+    )?;
+    sync::forget_deletion(conn, "notebook", id)?;
+    for (note_id, _) in &notes {
+        sync::forget_deletion(conn, "note", note_id)?;
+    }
+    Ok(true)
+}
+
+/// Stop or resume syncing a notebook and the notes filed in it
+#[tauri::command]
+pub fn set_notebook_sync_excluded(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    excluded: bool,
+) -> Result<Notebook> {
+    let changed = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let changed = set_sync_excluded(&tx, &id, excluded)?;
+        tx.commit()?;
+        changed
+    };
+
+    let notebook = get_notebook(db, id)?;
+    if changed {
+        events::emit(&app, ChangeEvent::Notebook, &notebook.id, ChangeKind::Updated);
+    }
+    Ok(notebook)
+}
+
+fn archive_command(
+    app: &AppHandle,
+    db: &Database,
+    id: &str,
+    recursive: bool,
+    archived: bool,
+) -> Result<NotebookArchiveSummary> {
+    let summary = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let summary = set_notebook_archived(&tx, id, recursive, archived)?;
+        tx.commit()?;
+        summary
+    };
+
+    for notebook_id in &summary.notebook_ids {
+        events::emit(app, ChangeEvent::Notebook, notebook_id, ChangeKind::Updated);
+    }
+    for note_id in &summary.note_ids {
+        events::emit(app, ChangeEvent::Note, note_id, ChangeKind::Updated);
+    }
+    Ok(summary)
+}
+
+/// Archive a notebook and the active notes in it
+#[tauri::command]
+pub fn archive_notebook(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    recursive: bool,
+) -> Result<NotebookArchiveSummary> {
+    archive_command(&app, &db, &id, recursive, true)
+}
+
+/// Undo `archive_notebook`, restoring only the notes it archived
+#[tauri::command]
+pub fn unarchive_notebook(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    recursive: bool,
+) -> Result<NotebookArchiveSummary> {
+    archive_command(&app, &db, &id, recursive, false)
+}
+
 #[tauri::command]
 pub fn get_root_notebooks(db: State<'_, Database>) -> Result<Vec<Notebook>> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
+        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived,
+                sync_excluded
          FROM notebooks WHERE parent_id IS NULL AND deleted_at IS NULL ORDER BY name",
     )?;
 
@@ -143,7 +694,8 @@ pub fn get_child_notebooks(db: State<'_, Database>, parent_id: String) -> Result
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
+        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived,
+                sync_excluded
          FROM notebooks WHERE parent_id = ? AND deleted_at IS NULL ORDER BY name",
     )?;
 
@@ -153,3 +705,924 @@ pub fn get_child_notebooks(db: State<'_, Database>, parent_id: String) -> Result
 
     Ok(notebooks)
 }
+
+/// Active/archived note counts per notebook, including an unfiled entry
+pub fn count_notes_by_notebook(conn: &Connection) -> Result<Vec<NotebookCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT notebook_id,
+                SUM(CASE WHEN status = 'active' THEN 1 ELSE 0 END),
+                SUM(CASE WHEN status = 'archived' THEN 1 ELSE 0 END)
+         FROM notes
+         WHERE deleted_at IS NULL AND status != 'trashed'
+         GROUP BY notebook_id",
+    )?;
+
+    let counts = stmt
+        .query_map([], |row| {
+            Ok(NotebookCount {
+                notebook_id: row.get(0)?,
+                active_count: row.get(1)?,
+                archived_count: row.get(2)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(counts)
+}
+
+#[tauri::command]
+pub fn get_notebook_counts(db: State<'_, Database>) -> Result<Vec<NotebookCount>> {
+    count_notes_by_notebook(&db.conn())
+}
+
+/// How many tags `get_notebook_stats` reports
+const TOP_TAGS: i64 = 10;
+
+/// Counts, last edit, content size and top tags for a notebook's notes.
+/// Trashed notes only show up in `trashed_count`.
+pub fn notebook_stats(conn: &Connection, id: &str, recursive: bool) -> Result<NotebookStats> {
+    let exists = conn
+        .prepare("SELECT 1 FROM notebooks WHERE id = ? AND deleted_at IS NULL")?
+        .exists(params![id])?;
+    if !exists {
+        return Err(AppError::NotFound(format!("Notebook {} not found", id)));
+    }
+
+    let scope = serde_json::to_string(&notebook_scope(conn, id, recursive)?).unwrap();
+    let in_scope = "notebook_id IN (SELECT value FROM json_each(?1))";
+    let live = "deleted_at IS NULL AND status != 'trashed'";
+
+    let mut stats = conn.query_row(
+        &format!(
+            "SELECT SUM(CASE WHEN status = 'active' AND deleted_at IS NULL THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN status = 'archived' AND deleted_at IS NULL THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN status = 'trashed' OR deleted_at IS NOT NULL THEN 1 ELSE 0 END),
+                    MAX(CASE WHEN {live} THEN updated_at END),
+                    SUM(CASE WHEN {live} THEN length(CAST(content AS BLOB)) ELSE 0 END)
+             FROM notes WHERE {in_scope}",
+        ),
+        params![scope],
+        |row| {
+            Ok(NotebookStats {
+                notebook_id: id.to_string(),
+                active_count: row.get::<_, Option<i32>>(0)?.unwrap_or(0),
+                archived_count: row.get::<_, Option<i32>>(1)?.unwrap_or(0),
+                trashed_count: row.get::<_, Option<i32>>(2)?.unwrap_or(0),
+                last_updated_at: row.get(3)?,
+                total_content_bytes: row.get::<_, Option<i64>>(4)?.unwrap_or(0),
+                top_tags: Vec::new(),
+            })
+        },
+    )?;
+
+    stats.top_tags = conn
+        .prepare(&format!(
+            "SELECT t.value, COUNT(*) AS uses
+             FROM notes, json_each(notes.tags) t
+             WHERE {in_scope} AND {live}
+             GROUP BY t.value
+             ORDER BY uses DESC, t.value
+             LIMIT ?2",
+        ))?
+        .query_map(params![scope, TOP_TAGS], |row| {
+            Ok(TagUsage {
+                name: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(stats)
+}
+
+#[tauri::command]
+pub fn get_notebook_stats(db: State<'_, Database>, id: String, recursive: bool) -> Result<NotebookStats> {
+    notebook_stats(&db.conn(), &id, recursive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{allow_writes, fail_writes};
+
+    #[test]
+    fn test_notebook_counts_skip_trashed() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        conn.execute_batch(
+            "INSERT INTO notebooks (id, name) VALUES ('work', 'Work');
+             INSERT INTO notes (id, notebook_id, status) VALUES
+                 ('a', 'work', 'active'), ('b', 'work', 'active'), ('c', 'work', 'archived'),
+                 ('d', 'work', 'trashed'), ('e', NULL, 'active');
+             INSERT INTO notes (id, notebook_id, status, deleted_at) VALUES
+                 ('f', 'work', 'active', datetime('now'));",
+        )
+        .unwrap();
+
+        let mut counts = count_notes_by_notebook(&conn).unwrap();
+        counts.sort_by(|a, b| a.notebook_id.cmp(&b.notebook_id));
+        assert_eq!(
+            counts,
+            vec![
+                NotebookCount { notebook_id: None, active_count: 1, archived_count: 0 },
+                NotebookCount { notebook_id: Some("work".to_string()), active_count: 2, archived_count: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_notebook_counts_leave_out_empty_notebooks() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        assert!(count_notes_by_notebook(&conn).unwrap().is_empty());
+
+        // Trashed notes only, or none at all: no badge, and no Unfiled row
+        conn.execute_batch(
+            "INSERT INTO notebooks (id, name) VALUES ('empty', 'Empty'), ('cleared', 'Cleared'), ('old', 'Old');
+             INSERT INTO notes (id, notebook_id, status) VALUES ('a', 'cleared', 'trashed'), ('b', 'old', 'archived');
+             INSERT INTO notes (id, notebook_id, status, deleted_at) VALUES ('c', NULL, 'trashed', datetime('now'));",
+        )
+        .unwrap();
+        assert_eq!(
+            count_notes_by_notebook(&conn).unwrap(),
+            vec![NotebookCount { notebook_id: Some("old".to_string()), active_count: 0, archived_count: 1 }]
+        );
+    }
+
+    fn notebook(conn: &Connection, parent_id: Option<&str>) -> String {
+        insert_notebook(
+            conn,
+            CreateNotebookInput {
+                id: None,
+                name: uuid::Uuid::new_v4().to_string(),
+                color: None,
+                icon: None,
+                parent_id: parent_id.map(str::to_string),
+            },
+        )
+        .unwrap()
+    }
+
+    fn reparent(conn: &Connection, id: &str, parent_id: &str) -> Result<()> {
+        write_notebook_update(
+            conn,
+            id,
+            UpdateNotebookInput {
+                name: None,
+                color: None,
+                icon: None,
+                parent_id: Some(Some(parent_id.to_string())),
+            },
+        )
+    }
+
+    fn is_cycle(result: Result<()>) -> bool {
+        matches!(result, Err(AppError::Validation(msg)) if msg == "would create a cycle")
+    }
+
+    #[test]
+    fn test_reparent_rejects_cycles() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let a = notebook(&conn, None);
+        let b = notebook(&conn, Some(&a));
+        let c = notebook(&conn, Some(&b));
+        let d = notebook(&conn, Some(&c));
+
+        assert!(is_cycle(reparent(&conn, &a, &a)));
+        assert!(is_cycle(reparent(&conn, &a, &b)));
+        assert!(is_cycle(reparent(&conn, &a, &d)));
+
+        // Moving within the tree without a loop is fine
+        let e = notebook(&conn, None);
+        reparent(&conn, &d, &e).unwrap();
+        reparent(&conn, &e, &a).unwrap();
+    }
+
+    #[test]
+    fn test_reparent_refusals_leave_the_tree_alone() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let a = notebook(&conn, None);
+        let b = notebook(&conn, Some(&a));
+        let parent_of = |id: &str| -> Option<String> {
+            conn.query_row("SELECT parent_id FROM notebooks WHERE id = ?", [id], |row| row.get(0)).unwrap()
+        };
+
+        assert!(matches!(reparent(&conn, &a, "missing"), Err(AppError::NotFound(_))));
+        let trashed = notebook(&conn, None);
+        conn.execute("UPDATE notebooks SET deleted_at = datetime('now') WHERE id = ?", [&trashed]).unwrap();
+        assert!(matches!(reparent(&conn, &b, &trashed), Err(AppError::NotFound(_))));
+        assert!(is_cycle(reparent(&conn, &a, &b)));
+        assert_eq!((parent_of(&a), parent_of(&b)), (None, Some(a.clone())));
+
+        // Rows that already loop (e.g. from an old import) end the walk
+        // instead of hanging it
+        conn.execute_batch(
+            "INSERT INTO notebooks (id, name, parent_id) VALUES ('x', 'X', 'y'), ('y', 'Y', 'x');",
+        )
+        .unwrap();
+        assert!(reparent(&conn, &a, "x").is_err());
+        assert_eq!(parent_of(&a), None);
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        settings::set(&conn, settings::MAX_NOTEBOOK_DEPTH, "3").unwrap();
+
+        let a = notebook(&conn, None);
+        let b = notebook(&conn, Some(&a));
+        let c = notebook(&conn, Some(&b));
+        let too_deep = insert_notebook(
+            &conn,
+            CreateNotebookInput {
+                id: None,
+                name: "Too deep".to_string(),
+                color: None,
+                icon: None,
+                parent_id: Some(c),
+            },
+        );
+        assert!(matches!(too_deep, Err(AppError::Validation(_))));
+
+        // A two-level subtree can't go under a depth-2 notebook
+        let x = notebook(&conn, None);
+        notebook(&conn, Some(&x));
+        assert!(matches!(reparent(&conn, &x, &b), Err(AppError::Validation(_))));
+        reparent(&conn, &x, &a).unwrap();
+    }
+
+    /// Three notebooks nested as deep as a limit of 3 allows
+    fn chain_at_depth_limit(conn: &Connection) -> [String; 3] {
+        settings::set(conn, settings::MAX_NOTEBOOK_DEPTH, "3").unwrap();
+        let a = notebook(conn, None);
+        let b = notebook(conn, Some(&a));
+        let c = notebook(conn, Some(&b));
+        [a, b, c]
+    }
+
+    #[test]
+    fn test_depth_error_names_the_limit_and_both_depths() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let [_, _, c] = chain_at_depth_limit(&conn);
+        let x = notebook(&conn, None);
+        match reparent(&conn, &x, &c) {
+            Err(AppError::Validation(message)) => {
+                assert!(message.contains("at most 3") && message.contains("depth 3") && message.contains("depth 4"));
+            }
+            other => panic!("expected a depth error, got {:?}", other),
+        }
+        assert_eq!(notebook_row(&conn, &x), (None, false));
+    }
+
+    #[test]
+    fn test_a_notebook_past_the_depth_limit_can_be_renamed_and_moved_up() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let [_, b, c] = chain_at_depth_limit(&conn);
+        conn.execute("INSERT INTO notebooks (id, name, parent_id) VALUES ('deep', 'Deep', ?)", params![c])
+            .unwrap();
+
+        write_notebook_update(&conn, "deep", serde_json::from_str(r#"{"name": "Renamed"}"#).unwrap()).unwrap();
+        assert!(matches!(reparent(&conn, "deep", &c), Err(AppError::Validation(_))));
+        reparent(&conn, "deep", &b).unwrap();
+    }
+
+    #[test]
+    fn test_lowering_the_depth_limit_only_affects_later_moves() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let [a, b, c] = chain_at_depth_limit(&conn);
+        let x = notebook(&conn, None);
+
+        settings::set(&conn, settings::MAX_NOTEBOOK_DEPTH, "1").unwrap();
+        assert_eq!(notebook_row(&conn, &c), (Some(b.clone()), false));
+        assert!(matches!(reparent(&conn, &x, &a), Err(AppError::Validation(_))));
+    }
+
+    fn notebook_row(conn: &Connection, id: &str) -> (Option<String>, bool) {
+        conn.query_row(
+            "SELECT parent_id, deleted_at IS NOT NULL FROM notebooks WHERE id = ?",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    fn note_in(conn: &Connection, notebook_id: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        conn.execute("INSERT INTO notes (id, notebook_id) VALUES (?, ?)", params![id, notebook_id])
+            .unwrap();
+        id
+    }
+
+    fn note_notebook(conn: &Connection, id: &str) -> Option<String> {
+        conn.query_row("SELECT notebook_id FROM notes WHERE id = ?", params![id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_delete_notebook_modes() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+
+        // root > mid > leaf, with a note in mid
+        let root = notebook(&conn, None);
+        let mid = notebook(&conn, Some(&root));
+        let leaf = notebook(&conn, Some(&mid));
+        let note = note_in(&conn, &mid);
+
+        let summary = remove_notebook(&conn, &mid, NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        assert_eq!(summary.notebooks_deleted, vec![mid.clone()]);
+        assert_eq!(summary.children_promoted, vec![leaf.clone()]);
+        assert_eq!(summary.notes_moved, 1);
+        assert_eq!(notebook_row(&conn, &leaf), (Some(root.clone()), false));
+        assert_eq!(note_notebook(&conn, &note), None);
+
+        let mid = notebook(&conn, Some(&root));
+        let leaf = notebook(&conn, Some(&mid));
+        let note = note_in(&conn, &mid);
+        remove_notebook(&conn, &mid, NotebookDeleteMode::MoveNotesToParent, None, false).unwrap();
+        assert_eq!(notebook_row(&conn, &leaf), (Some(root.clone()), false));
+        assert_eq!(note_notebook(&conn, &note), Some(root.clone()));
+
+        let leaf_note = note_in(&conn, &leaf);
+        let summary = remove_notebook(&conn, &root, NotebookDeleteMode::DeleteChildren, None, false).unwrap();
+        // root plus the two leaves promoted into it above
+        assert_eq!(summary.notebooks_deleted.len(), 3);
+        assert_eq!(summary.notebooks_deleted[0], root);
+        assert!(summary.children_promoted.is_empty());
+        assert_eq!(summary.notes_moved, 2);
+        assert!(notebook_row(&conn, &leaf).1);
+        assert_eq!(note_notebook(&conn, &leaf_note), None);
+    }
+
+    #[test]
+    fn test_hard_delete_subtree_leaves_tombstones() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let root = notebook(&conn, None);
+        let child = notebook(&conn, Some(&root));
+
+        remove_notebook(&conn, &root, NotebookDeleteMode::DeleteChildren, None, true).unwrap();
+
+        let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM notebooks", [], |row| row.get(0)).unwrap();
+        let tombstones: i64 = conn
+            .query_row("SELECT COUNT(*) FROM deleted_entities WHERE entity_type = 'notebook'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!((remaining, tombstones), (0, 2));
+        assert!(matches!(
+            remove_notebook(&conn, &child, NotebookDeleteMode::PromoteChildren, None, false),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    /// root > (mid > leaf), plus a child of root trashed on its own earlier
+    fn subtree_with_trashed_child(conn: &Connection) -> [String; 4] {
+        let root = notebook(conn, None);
+        let mid = notebook(conn, Some(&root));
+        let leaf = notebook(conn, Some(&mid));
+        let trashed = notebook(conn, Some(&root));
+        conn.execute(
+            "UPDATE notebooks SET deleted_at = '2020-01-01T00:00:00Z' WHERE id = ?",
+            params![trashed],
+        )
+        .unwrap();
+        [root, mid, leaf, trashed]
+    }
+
+    #[test]
+    fn test_a_trashed_notebook_cant_be_deleted_again() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let [_, _, _, trashed] = subtree_with_trashed_child(&conn);
+        assert!(matches!(
+            remove_notebook(&conn, &trashed, NotebookDeleteMode::DeleteChildren, None, false),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_subtree_rolls_back_whole() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        let [root, mid, leaf, _] = subtree_with_trashed_child(&conn);
+        let note = note_in(&conn, &leaf);
+
+        // A failure on the deepest notebook undoes everything done before it
+        fail_writes(&conn, &format!("UPDATE OF deleted_at ON notebooks WHEN NEW.id = '{}'", leaf));
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(remove_notebook(&tx, &root, NotebookDeleteMode::DeleteChildren, None, false).is_err());
+        }
+        allow_writes(&conn);
+        for id in [&root, &mid, &leaf] {
+            assert!(!notebook_row(&conn, id).1);
+        }
+        assert_eq!(note_notebook(&conn, &note), Some(leaf.clone()));
+    }
+
+    #[test]
+    fn test_delete_subtree_skips_trashed_children_and_keeps_their_date() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let [root, mid, leaf, trashed] = subtree_with_trashed_child(&conn);
+        note_in(&conn, &leaf);
+
+        let summary = remove_notebook(&conn, &root, NotebookDeleteMode::DeleteChildren, None, false).unwrap();
+        assert_eq!(summary.notebooks_deleted, vec![root, mid, leaf]);
+        assert_eq!(summary.notes_moved, 1);
+        let deleted_at: String = conn
+            .query_row("SELECT deleted_at FROM notebooks WHERE id = ?", params![trashed], |row| row.get(0))
+            .unwrap();
+        assert_eq!(deleted_at, "2020-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_delete_notebook_notes_destination() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let root = notebook(&conn, None);
+        let mid = notebook(&conn, Some(&root));
+        let leaf = notebook(&conn, Some(&mid));
+        let other = notebook(&conn, None);
+        let note = note_in(&conn, &mid);
+        let revision = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT revision FROM notes WHERE id = ?", params![note], |row| row.get(0))
+                .unwrap()
+        };
+        let before = revision(&conn);
+
+        // Destinations inside the deleted subtree or missing are rejected
+        let into_subtree = remove_notebook(&conn, &mid, NotebookDeleteMode::DeleteChildren, Some(&leaf), false);
+        assert!(matches!(into_subtree, Err(AppError::Validation(_))));
+        let missing = remove_notebook(&conn, &mid, NotebookDeleteMode::PromoteChildren, Some("nope"), false);
+        assert!(matches!(missing, Err(AppError::NotFound(_))));
+
+        remove_notebook(&conn, &mid, NotebookDeleteMode::PromoteChildren, Some(&other), false).unwrap();
+        assert_eq!(note_notebook(&conn, &note), Some(other));
+        assert!(revision(&conn) > before);
+
+        conn.execute("UPDATE notes SET notebook_id = ? WHERE id = ?", params![leaf, note]).unwrap();
+        remove_notebook(&conn, &leaf, NotebookDeleteMode::PromoteChildren, Some(NOTES_TO_PARENT), false)
+            .unwrap();
+        assert_eq!(note_notebook(&conn, &note), Some(root));
+    }
+
+    #[test]
+    fn test_refused_notes_destination_changes_nothing() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let root = notebook(&conn, None);
+        let mid = notebook(&conn, Some(&root));
+        let leaf = notebook(&conn, Some(&mid));
+        let trashed = notebook(&conn, None);
+        conn.execute("UPDATE notebooks SET deleted_at = '2020-01-01T00:00:00Z' WHERE id = ?", params![trashed])
+            .unwrap();
+        let note = note_in(&conn, &mid);
+
+        // Refusals come before the children are promoted
+        for target in [trashed.as_str(), mid.as_str(), "nope"] {
+            assert!(remove_notebook(&conn, &mid, NotebookDeleteMode::PromoteChildren, Some(target), false).is_err());
+            assert_eq!(notebook_row(&conn, &leaf), (Some(mid.clone()), false));
+            assert_eq!(notebook_row(&conn, &mid), (Some(root.clone()), false));
+            assert_eq!(note_notebook(&conn, &note), Some(mid.clone()));
+        }
+
+        // "parent" of a top-level notebook means no notebook
+        let top_note = note_in(&conn, &root);
+        remove_notebook(&conn, &root, NotebookDeleteMode::DeleteChildren, Some(NOTES_TO_PARENT), false).unwrap();
+        assert_eq!(note_notebook(&conn, &top_note), None);
+        assert_eq!(note_notebook(&conn, &note), None);
+    }
+
+    #[test]
+    fn test_update_input_omitted_null_and_value() {
+        let omitted: UpdateNotebookInput = serde_json::from_str(r#"{"name": "Work"}"#).unwrap();
+        assert_eq!((omitted.color, omitted.icon, omitted.parent_id), (None, None, None));
+
+        let cleared: UpdateNotebookInput =
+            serde_json::from_str(r#"{"name": null, "color": null, "icon": null, "parent_id": null}"#).unwrap();
+        assert_eq!((cleared.color, cleared.icon, cleared.parent_id), (Some(None), Some(None), Some(None)));
+
+        let set: UpdateNotebookInput =
+            serde_json::from_str(r##"{"name": null, "color": "#fff", "parent_id": "p"}"##).unwrap();
+        assert_eq!(set.color, Some(Some("#fff".to_string())));
+        assert_eq!(set.icon, None);
+        assert_eq!(set.parent_id, Some(Some("p".to_string())));
+
+        // Serializing keeps omitted fields omitted, so they don't come back as null
+        for json in [r#"{"name": "Work"}"#, r#"{"name": null, "color": null, "icon": null, "parent_id": null}"#] {
+            let input: UpdateNotebookInput = serde_json::from_str(json).unwrap();
+            let back: UpdateNotebookInput = serde_json::from_str(&serde_json::to_string(&input).unwrap()).unwrap();
+            assert_eq!((back.color, back.icon, back.parent_id), (input.color, input.icon, input.parent_id));
+        }
+    }
+
+    #[test]
+    fn test_update_notebook_clears_fields() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let root = notebook(&conn, None);
+        let child = notebook(&conn, Some(&root));
+        let styled: UpdateNotebookInput =
+            serde_json::from_str(r##"{"name": null, "color": "#ff0000", "icon": "book"}"##).unwrap();
+        write_notebook_update(&conn, &child, styled).unwrap();
+
+        // Omitted fields are untouched
+        let rename: UpdateNotebookInput = serde_json::from_str(r#"{"name": "Renamed"}"#).unwrap();
+        write_notebook_update(&conn, &child, rename).unwrap();
+        let row = |conn: &Connection| -> (String, Option<String>, Option<String>, Option<String>) {
+            conn.query_row(
+                "SELECT name, color, icon, parent_id FROM notebooks WHERE id = ?",
+                params![child],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            row(&conn),
+            ("Renamed".to_string(), Some("#ff0000".to_string()), Some("book".to_string()), Some(root))
+        );
+
+        let clear: UpdateNotebookInput =
+            serde_json::from_str(r#"{"name": null, "color": null, "icon": null, "parent_id": null}"#).unwrap();
+        write_notebook_update(&conn, &child, clear).unwrap();
+        assert_eq!(row(&conn), ("Renamed".to_string(), None, None, None));
+    }
+
+    #[test]
+    fn test_refused_updates_leave_the_notebook_alone() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let root = notebook(&conn, None);
+        let child = notebook(&conn, Some(&root));
+        let update = |json: &str| write_notebook_update(&conn, &child, serde_json::from_str(json).unwrap());
+        update(r##"{"name": "Ideas", "color": "#00ff00"}"##).unwrap();
+        let row = || -> (Option<String>, Option<String>, i64) {
+            conn.query_row(
+                "SELECT parent_id, color, revision FROM notebooks WHERE id = ?",
+                params![child],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap()
+        };
+        let before = row();
+
+        // Moving to the root next to a root notebook with the same name
+        write_notebook_update(&conn, &root, serde_json::from_str(r#"{"name": "ideas"}"#).unwrap()).unwrap();
+        assert!(matches!(update(r#"{"name": null, "parent_id": null}"#), Err(AppError::Conflict(_))));
+        // A bad color alongside a valid move
+        assert!(matches!(
+            update(r##"{"name": null, "color": "#12345", "parent_id": null}"##),
+            Err(AppError::Validation(_))
+        ));
+        assert_eq!(row(), before);
+
+        assert!(matches!(
+            write_notebook_update(&conn, "nope", serde_json::from_str(r#"{"name": null, "parent_id": null}"#).unwrap()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_icons_are_refused_and_repaired() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let with_icon = |icon: &str| {
+            insert_notebook(
+                &conn,
+                CreateNotebookInput {
+                    id: None,
+                    name: uuid::Uuid::new_v4().to_string(),
+                    color: None,
+                    icon: Some(icon.to_string()),
+                    parent_id: None,
+                },
+            )
+        };
+        assert!(matches!(with_icon("<svg/>"), Err(AppError::Validation(_))));
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM notebooks", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+
+        let id = with_icon("📁").unwrap();
+        let row = || -> (Option<String>, i64) {
+            conn.query_row("SELECT icon, revision FROM notebooks WHERE id = ?", params![id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap()
+        };
+        let update = |json: &str| write_notebook_update(&conn, &id, serde_json::from_str(json).unwrap());
+        assert!(matches!(update(r#"{"name": "Renamed", "icon": "📁📁"}"#), Err(AppError::Validation(_))));
+        assert_eq!(row(), (Some("📁".to_string()), 1));
+
+        // A row that got past validation is cleared and resent
+        conn.execute("UPDATE notebooks SET icon = 'two words', needs_push = 0 WHERE id = ?", params![id])
+            .unwrap();
+        assert_eq!(crate::maintenance::cleanup_orphan_rows(&conn).unwrap().invalid_icons, 1);
+        assert_eq!(row(), (None, 2));
+        let needs_push: bool = conn
+            .query_row("SELECT needs_push FROM notebooks WHERE id = ?", params![id], |row| row.get(0))
+            .unwrap();
+        assert!(needs_push);
+    }
+
+    #[test]
+    fn test_sibling_names_are_unique_ignoring_case() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let named = |name: &str, parent_id: Option<&str>| {
+            insert_notebook(
+                &conn,
+                CreateNotebookInput {
+                    id: None,
+                    name: name.to_string(),
+                    color: None,
+                    icon: None,
+                    parent_id: parent_id.map(str::to_string),
+                },
+            )
+        };
+
+        let work = named("Work", None).unwrap();
+        let ideas = named("Ideas", Some(&work)).unwrap();
+        assert!(matches!(named("IDEAS", Some(&work)), Err(AppError::Conflict(_))));
+        assert!(matches!(named("work", None), Err(AppError::Conflict(_))));
+
+        // Same name under another parent is fine
+        let root_ideas = named("Ideas", None).unwrap();
+
+        // Renaming onto a sibling, or moving next to one, conflicts
+        let rename: UpdateNotebookInput = serde_json::from_str(r#"{"name": "Ideas"}"#).unwrap();
+        assert!(matches!(write_notebook_update(&conn, &work, rename), Err(AppError::Conflict(_))));
+        assert!(matches!(reparent(&conn, &root_ideas, &work), Err(AppError::Conflict(_))));
+
+        // Changing only the case of its own name is allowed
+        let recase: UpdateNotebookInput = serde_json::from_str(r#"{"name": "ideas"}"#).unwrap();
+        write_notebook_update(&conn, &ideas, recase).unwrap();
+
+        // Deleted siblings don't count
+        remove_notebook(&conn, &ideas, NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        named("Ideas", Some(&work)).unwrap();
+    }
+
+    fn note_status(conn: &Connection, id: &str) -> String {
+        conn.query_row("SELECT status FROM notes WHERE id = ?", params![id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_archive_notebook_round_trip() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let project = notebook(&conn, None);
+        let sub = notebook(&conn, Some(&project));
+        let active = note_in(&conn, &project);
+        let nested = note_in(&conn, &sub);
+        let already_archived = note_in(&conn, &project);
+        conn.execute("UPDATE notes SET status = 'archived' WHERE id = ?", params![already_archived])
+            .unwrap();
+
+        let summary = set_notebook_archived(&conn, &project, true, true).unwrap();
+        assert_eq!(summary.notebook_ids, vec![project.clone(), sub.clone()]);
+        assert_eq!(summary.note_ids.len(), 2);
+        assert_eq!(note_status(&conn, &active), "archived");
+        assert_eq!(note_status(&conn, &nested), "archived");
+        let visible: i64 = conn
+            .query_row("SELECT COUNT(*) FROM notebooks WHERE is_archived = 0", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(visible, 0);
+
+        // Only the notes archived by the notebook come back
+        let summary = set_notebook_archived(&conn, &project, true, false).unwrap();
+        assert_eq!(summary.note_ids.len(), 2);
+        assert_eq!(note_status(&conn, &active), "active");
+        assert_eq!(note_status(&conn, &nested), "active");
+        assert_eq!(note_status(&conn, &already_archived), "archived");
+    }
+
+    /// A project with a sub-notebook, and a note in each
+    fn project_with_sub(conn: &Connection) -> (String, String, String, String) {
+        let project = notebook(conn, None);
+        let sub = notebook(conn, Some(&project));
+        let top = note_in(conn, &project);
+        let nested = note_in(conn, &sub);
+        (project, sub, top, nested)
+    }
+
+    #[test]
+    fn test_archiving_a_missing_notebook_is_refused() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        assert!(matches!(set_notebook_archived(&conn, "nope", true, true), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_archiving_without_recursion_then_with_it() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let (project, sub, top, nested) = project_with_sub(&conn);
+
+        // The sub-notebook and its notes are left out
+        let summary = set_notebook_archived(&conn, &project, false, true).unwrap();
+        assert_eq!((summary.notebook_ids, summary.note_ids), (vec![project.clone()], vec![top.clone()]));
+        assert_eq!(note_status(&conn, &nested), "active");
+
+        // Archiving again only picks up what is still active
+        let summary = set_notebook_archived(&conn, &project, true, true).unwrap();
+        assert_eq!((summary.notebook_ids, summary.note_ids), (vec![sub], vec![nested]));
+    }
+
+    #[test]
+    fn test_a_failed_unarchive_undoes_all_of_it() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        let (project, sub, top, _) = project_with_sub(&conn);
+        set_notebook_archived(&conn, &project, true, true).unwrap();
+
+        fail_writes(&conn, &format!("UPDATE OF is_archived ON notebooks WHEN NEW.id = '{}'", sub));
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(set_notebook_archived(&tx, &project, true, false).is_err());
+        }
+        allow_writes(&conn);
+        assert_eq!(note_status(&conn, &top), "archived");
+    }
+
+    #[test]
+    fn test_a_note_deleted_while_archived_stays_archived_on_unarchive() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let (project, _, top, nested) = project_with_sub(&conn);
+        set_notebook_archived(&conn, &project, true, true).unwrap();
+        conn.execute("UPDATE notes SET deleted_at = datetime('now') WHERE id = ?", params![nested]).unwrap();
+
+        let summary = set_notebook_archived(&conn, &project, true, false).unwrap();
+        assert_eq!(summary.note_ids, vec![top.clone()]);
+        assert_eq!(note_status(&conn, &top), "active");
+        assert_eq!(note_status(&conn, &nested), "archived");
+    }
+
+    #[test]
+    fn test_notebook_stats_recursive() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let parent = notebook(&conn, None);
+        let child = notebook(&conn, Some(&parent));
+        let add = |notebook_id: &str, status: &str, content: &str, tags: &str, updated_at: &str| {
+            conn.execute(
+                "INSERT INTO notes (id, notebook_id, status, content, tags, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+                params![uuid::Uuid::new_v4().to_string(), notebook_id, status, content, tags, updated_at],
+            )
+            .unwrap();
+        };
+        add(&parent, "active", "abc", r#"["work","ideas"]"#, "2024-01-01T00:00:00Z");
+        add(&parent, "trashed", "ignored", r#"["work"]"#, "2024-06-01T00:00:00Z");
+        add(&child, "active", "héllo", r#"["work"]"#, "2024-03-01T00:00:00Z");
+        add(&child, "archived", "", r#"["deep"]"#, "2024-02-01T00:00:00Z");
+
+        let shallow = notebook_stats(&conn, &parent, false).unwrap();
+        assert_eq!((shallow.active_count, shallow.archived_count, shallow.trashed_count), (1, 0, 1));
+        assert_eq!(shallow.total_content_bytes, 3);
+        assert_eq!(shallow.last_updated_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+
+        let deep = notebook_stats(&conn, &parent, true).unwrap();
+        assert_eq!((deep.active_count, deep.archived_count, deep.trashed_count), (2, 1, 1));
+        assert_eq!(deep.total_content_bytes, 3 + "héllo".len() as i64);
+        assert_eq!(deep.last_updated_at.as_deref(), Some("2024-03-01T00:00:00Z"));
+        assert_eq!(
+            deep.top_tags,
+            vec![
+                TagUsage { name: "work".to_string(), count: 2 },
+                TagUsage { name: "deep".to_string(), count: 1 },
+                TagUsage { name: "ideas".to_string(), count: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_of_an_empty_notebook_are_zero() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let parent = notebook(&conn, None);
+        let empty = notebook_stats(&conn, &parent, true).unwrap();
+        assert_eq!((empty.active_count, empty.archived_count, empty.trashed_count), (0, 0, 0));
+        assert_eq!((empty.last_updated_at, empty.total_content_bytes), (None, 0));
+        assert!(empty.top_tags.is_empty());
+    }
+
+    #[test]
+    fn test_stats_keep_the_top_tags_and_skip_trashed_sub_notebooks() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let parent = notebook(&conn, None);
+        let trashed_child = notebook(&conn, Some(&parent));
+
+        // Twelve tags used once each, plus a note in a trashed sub-notebook
+        let tags: Vec<String> = (0..12).map(|i| format!("tag{:02}", i)).collect();
+        conn.execute(
+            "INSERT INTO notes (id, notebook_id, tags) VALUES (?, ?, ?)",
+            params![uuid::Uuid::new_v4().to_string(), parent, serde_json::to_string(&tags).unwrap()],
+        )
+        .unwrap();
+        note_in(&conn, &trashed_child);
+        conn.execute("UPDATE notebooks SET deleted_at = datetime('now') WHERE id = ?", params![trashed_child])
+            .unwrap();
+
+        let stats = notebook_stats(&conn, &parent, true).unwrap();
+        assert_eq!(stats.active_count, 1);
+        let names: Vec<&str> = stats.top_tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(names, tags[..TOP_TAGS as usize].iter().map(String::as_str).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_stats_of_trashed_or_missing_notebooks_are_refused() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let trashed = notebook(&conn, None);
+        conn.execute("UPDATE notebooks SET deleted_at = datetime('now') WHERE id = ?", params![trashed]).unwrap();
+        for missing in [trashed.as_str(), "nope"] {
+            assert!(matches!(notebook_stats(&conn, missing, false), Err(AppError::NotFound(_))));
+        }
+    }
+
+    #[test]
+    fn test_purge_notebook_requires_trash_and_respects_retention() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let live = notebook(&conn, None);
+        assert!(matches!(purge_trashed_notebook(&conn, &live), Err(AppError::Validation(_))));
+        assert!(matches!(purge_trashed_notebook(&conn, "missing"), Err(AppError::NotFound(_))));
+
+        let recent = notebook(&conn, None);
+        let old = notebook(&conn, None);
+        remove_notebook(&conn, &recent, NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        remove_notebook(&conn, &old, NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        conn.execute(
+            "UPDATE notebooks SET deleted_at = datetime('now', '-40 days') WHERE id = ?",
+            params![old],
+        )
+        .unwrap();
+
+        assert_eq!(purge_expired_notebooks(&conn, 30).unwrap(), vec![old.clone()]);
+        purge_trashed_notebook(&conn, &recent).unwrap();
+
+        let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM notebooks", [], |row| row.get(0)).unwrap();
+        let tombstones: i64 = conn
+            .query_row("SELECT COUNT(*) FROM deleted_entities WHERE entity_type = 'notebook'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!((remaining, tombstones), (1, 2));
+    }
+
+    /// A trashed root and child, both past a 30 day retention
+    fn expired_pair(conn: &Connection) -> (String, String) {
+        let root = notebook(conn, None);
+        let child = notebook(conn, Some(&root));
+        remove_notebook(conn, &root, NotebookDeleteMode::DeleteChildren, None, false).unwrap();
+        conn.execute("UPDATE notebooks SET deleted_at = datetime('now', '-40 days')", []).unwrap();
+        (root, child)
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_a_failed_purge_purges_nothing_and_leaves_no_tombstone() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        let (_, child) = expired_pair(&conn);
+
+        fail_writes(&conn, &format!("DELETE ON notebooks WHEN OLD.id = '{}'", child));
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(purge_expired_notebooks(&tx, 30).is_err());
+        }
+        allow_writes(&conn);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM notebooks"), 2);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM deleted_entities"), 0);
+    }
+
+    #[test]
+    fn test_purge_tombstones_carry_the_next_revision() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let (_, child) = expired_pair(&conn);
+        let revision: i64 = conn
+            .query_row("SELECT revision FROM notebooks WHERE id = ?", params![child], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(purge_expired_notebooks(&conn, 30).unwrap().len(), 2);
+        let tombstone: i64 = conn
+            .query_row("SELECT revision FROM deleted_entities WHERE entity_id = ?", params![child], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tombstone, revision + 1);
+
+        // Purged notebooks are gone for good
+        assert!(matches!(purge_trashed_notebook(&conn, &child), Err(AppError::NotFound(_))));
+        assert!(purge_expired_notebooks(&conn, 30).unwrap().is_empty());
+    }
+}
@@ -1,10 +1,21 @@
-use rusqlite::params;
-use tauri::State;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
 
 use crate::crypto;
 use crate::db::Database;
 use crate::error::{AppError, Result};
-use crate::models::{CreateNoteInput, ListNotesFilter, Note, NoteStatus, UpdateNoteInput};
+use crate::commands::{notebooks, tags};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::markdown;
+use crate::settings;
+use crate::sync;
+use crate::tasks;
+use crate::validation;
+use crate::models::{
+    ArchiveSummary, CreateNoteInput, CreateNotebookInput, ListNotesFilter, Note, NoteStatus, TagMode,
+    TrashedNoteView, UpdateNoteInput,
+};
 
 fn row_to_note(row: &rusqlite::Row) -> rusqlite::Result<Note> {
     let tags_json: String = row.get(4)?;
@@ -30,58 +41,121 @@ fn row_to_note(row: &rusqlite::Row) -> rusqlite::Result<Note> {
         created_at: row.get(8)?,
         updated_at: row.get(9)?,
         deleted_at: row.get(10)?,
+        pinned_order: row.get(11)?,
     })
 }
 
 #[tauri::command]
 pub fn list_notes(db: State<'_, Database>, filter: Option<ListNotesFilter>) -> Result<Vec<Note>> {
+    query_notes(&db, filter.unwrap_or_default())
+}
+
+/// Notes carrying exactly the tag `name`, in the usual list order
+#[tauri::command]
+pub fn get_notes_by_tag(
+    db: State<'_, Database>,
+    name: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Vec<Note>> {
+    notes_by_tag(&db, name, limit, offset)
+}
+
+pub fn notes_by_tag(db: &Database, name: String, limit: Option<i64>, offset: Option<i64>) -> Result<Vec<Note>> {
+    let filter = ListNotesFilter {
+        tag: Some(name),
+        limit,
+        offset,
+        ..Default::default()
+    };
+    query_notes(db, filter)
+}
+
+pub fn query_notes(db: &Database, filter: ListNotesFilter) -> Result<Vec<Note>> {
     let conn = db.conn();
 
-    let filter = filter.unwrap_or(ListNotesFilter {
-        notebook_id: None,
-        status: None,
-        tag: None,
-        search: None,
-        limit: None,
-        offset: None,
-    });
-
-    let mut sql = String::from(
-        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at
+    // Summary mode swaps the full body for the stored excerpt (raw content
+    // prefix for rows that haven't been backfilled yet)
+    let content_column = if filter.summary.unwrap_or(false) {
+        format!("COALESCE(excerpt, substr(content, 1, {}))", markdown::EXCERPT_LENGTH)
+    } else {
+        "content".to_string()
+    };
+
+    let mut sql = format!(
+        "SELECT id, title, {}, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order
          FROM notes WHERE deleted_at IS NULL",
+        content_column
     );
 
-    let mut conditions = Vec::new();
+    let mut conditions: Vec<String> = Vec::new();
     let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
     if let Some(ref notebook_id) = filter.notebook_id {
-        conditions.push("notebook_id = ?");
+        conditions.push("notebook_id = ?".to_string());
         params_vec.push(Box::new(notebook_id.clone()));
     }
 
     if let Some(ref status) = filter.status {
-        conditions.push("status = ?");
+        conditions.push("status = ?".to_string());
         params_vec.push(Box::new(status.as_str().to_string()));
     }
 
-    if let Some(ref tag) = filter.tag {
-        conditions.push("tags LIKE ?");
-        params_vec.push(Box::new(format!("%\"{}\"", tag)));
+    // Match whole tag names through note_tags; LIKE would also match substrings
+    let mut tags: Vec<String> = filter.tags.unwrap_or_default();
+    tags.extend(filter.tag);
+    tags.sort();
+    tags.dedup();
+    if !tags.is_empty() {
+        let placeholders = vec!["?"; tags.len()].join(", ");
+        let cond = match filter.tag_mode.unwrap_or_default() {
+            TagMode::All => format!(
+                "id IN (SELECT nt.note_id FROM tags t JOIN note_tags nt ON nt.tag_id = t.id
+                        WHERE t.name IN ({}) GROUP BY nt.note_id HAVING COUNT(*) = {})",
+                placeholders,
+                tags.len()
+            ),
+            TagMode::Any => format!(
+                "id IN (SELECT nt.note_id FROM tags t JOIN note_tags nt ON nt.tag_id = t.id
+                        WHERE t.name IN ({}))",
+                placeholders
+            ),
+        };
+        conditions.push(cond);
+        for tag in tags {
+            params_vec.push(Box::new(tag));
+        }
     }
 
     if let Some(ref search) = filter.search {
-        conditions.push("(title LIKE ? OR content LIKE ?)");
+        conditions.push("(title LIKE ? OR content LIKE ?)".to_string());
         let pattern = format!("%{}%", search);
         params_vec.push(Box::new(pattern.clone()));
         params_vec.push(Box::new(pattern));
     }
 
+    // Stored timestamps mix RFC3339 and SQLite's `datetime('now')` format, so
+    // both sides go through datetime() (backed by an expression index)
+    let date_bounds = [
+        ("created_after", &filter.created_after, "datetime(created_at) >= ?"),
+        ("created_before", &filter.created_before, "datetime(created_at) < ?"),
+        ("updated_after", &filter.updated_after, "datetime(updated_at) >= ?"),
+        ("updated_before", &filter.updated_before, "datetime(updated_at) < ?"),
+    ];
+    for (field, value, cond) in date_bounds {
+        if let Some(value) = value {
+            conditions.push(cond.to_string());
+            params_vec.push(Box::new(validation::filter_timestamp(field, value)?));
+        }
+    }
+
     for cond in conditions {
         sql.push_str(" AND ");
-        sql.push_str(cond);
+        sql.push_str(&cond);
     }
 
-    sql.push_str(" ORDER BY is_pinned DESC, updated_at DESC");
+    // Newly pinned notes (no order yet) sort ahead of the arranged ones
+    sql.push_str(" ORDER BY is_pinned DESC, pinned_order ASC, updated_at DESC");
 
     if let Some(limit) = filter.limit {
         sql.push_str(&format!(" LIMIT {}", limit));
@@ -105,7 +179,7 @@ pub fn get_note(db: State<'_, Database>, id: String) -> Result<Note> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at
+        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order
          FROM notes WHERE id = ?",
     )?;
 
@@ -113,43 +187,209 @@ pub fn get_note(db: State<'_, Database>, id: String) -> Result<Note> {
         .map_err(|_| AppError::NotFound(format!("Note {} not found", id)))
 }
 
-#[tauri::command]
-pub fn create_note(db: State<'_, Database>, input: CreateNoteInput) -> Result<Note> {
+/// Stay well below SQLite's bound-parameter limit (999 on older builds)
+const IDS_PER_QUERY: usize = 500;
+
+/// Fetch many notes in as few queries as possible.
+/// Missing and trashed ids are left out; the result follows the order of
+/// `ids`, an id given twice coming back twice.
+pub fn notes_by_ids(db: &Database, ids: &[String]) -> Result<Vec<Note>> {
     let conn = db.conn();
+    let mut found: HashMap<String, Note> = HashMap::with_capacity(ids.len());
+
+    for chunk in ids.chunks(IDS_PER_QUERY) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let sql = format!(
+            "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order
+             FROM notes WHERE id IN ({}) AND deleted_at IS NULL AND status != 'trashed'",
+            placeholders
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+        let notes = stmt.query_map(params_from_iter(chunk), row_to_note)?;
+        for note in notes {
+            let note = note?;
+            found.insert(note.id.clone(), note);
+        }
+    }
+
+    Ok(ids.iter().filter_map(|id| found.get(id).cloned()).collect())
+}
+
+#[tauri::command]
+pub fn get_notes_by_ids(db: State<'_, Database>, ids: Vec<String>) -> Result<Vec<Note>> {
+    notes_by_ids(&db, &ids)
+}
+
+#[tauri::command]
+pub fn create_note(app: AppHandle, db: State<'_, Database>, input: CreateNoteInput) -> Result<Note> {
+    let id = insert_note(&db.conn(), input)?;
+
+    let note = get_note(db, id)?;
+    events::emit(&app, ChangeEvent::Note, &note.id, ChangeKind::Created);
+    Ok(note)
+}
 
-    let id = uuid::Uuid::new_v4().to_string();
+/// Validate and insert a new note, returning its id
+pub fn insert_note(conn: &Connection, input: CreateNoteInput) -> Result<String> {
+    let id = validation::new_entity_id(conn, "notes", input.id)?;
     let now = chrono::Utc::now().to_rfc3339();
     let raw_title = input.title.unwrap_or_default();
     let raw_content = input.content.unwrap_or_default();
+    let tags = input.tags.unwrap_or_default();
+    let notebook_id = match input.notebook_id {
+        Some(notebook_id) => notebook_id,
+        None => notebooks::notebook_from_setting(conn, settings::DEFAULT_NOTEBOOK_ID)?,
+    };
+
+    validation::note_title(&raw_title)?;
+    validation::note_content(conn, &raw_content)?;
+    validation::tag_names(&tags)?;
 
     // Encrypt if encryption is enabled
     let title = crypto::maybe_encrypt(&raw_title)?;
     let content = crypto::maybe_encrypt(&raw_content)?;
+    let excerpt = crypto::maybe_encrypt(&markdown::excerpt(&raw_content))?;
 
-    let tags_json = serde_json::to_string(&input.tags.unwrap_or_default()).unwrap();
+    let tags_json = serde_json::to_string(&tags).unwrap();
+    tags::ensure_tags(conn, &tags)?;
 
     conn.execute(
-        "INSERT INTO notes (id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at)
-         VALUES (?, ?, ?, ?, ?, 'active', 0, 1, ?, ?)",
-        params![id, title, content, input.notebook_id, tags_json, now, now],
+        "INSERT INTO notes (id, title, content, excerpt, notebook_id, tags, status, is_pinned, revision, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, 'active', 0, 1, ?, ?)",
+        params![id, title, content, excerpt, notebook_id, tags_json, now, now],
+    )?;
+    tasks::sync_note_tasks(conn, &id, &raw_content)?;
+
+    Ok(id)
+}
+
+/// Longest title derived by quick capture, in characters
+const CAPTURE_TITLE_LENGTH: usize = 80;
+
+/// Name of the notebook quick capture falls back to
+const INBOX_NOTEBOOK: &str = "Inbox";
+
+/// Split captured text into a title (first non-empty line, without header
+/// markers) and content (the rest). When the first line is too long for a
+/// title, the content keeps the whole text.
+fn split_capture(text: &str) -> (String, String) {
+    let text = text.trim();
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let line = first.trim().trim_start_matches('#').trim();
+
+    match line.char_indices().nth(CAPTURE_TITLE_LENGTH) {
+        Some((idx, _)) => (line[..idx].trim_end().to_string(), text.to_string()),
+        None => (line.to_string(), rest.trim_start_matches(['\r', '\n']).to_string()),
+    }
+}
+
+/// Notebook a quick capture goes to without creating one: the configured
+/// capture or default notebook if it still exists, otherwise an existing
+/// root "Inbox" notebook
+fn existing_capture_notebook(conn: &Connection) -> Result<Option<String>> {
+    for key in [settings::CAPTURE_NOTEBOOK_ID, settings::DEFAULT_NOTEBOOK_ID] {
+        if let Some(id) = notebooks::notebook_from_setting(conn, key)? {
+            return Ok(Some(id));
+        }
+    }
+
+    Ok(conn
+        .query_row(
+            "SELECT id FROM notebooks WHERE name = ? AND parent_id IS NULL AND deleted_at IS NULL
+             ORDER BY created_at LIMIT 1",
+            params![INBOX_NOTEBOOK],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Notebook for a quick capture: see `existing_capture_notebook`, with the
+/// root "Inbox" notebook created on first use
+fn capture_notebook(app: &AppHandle, db: State<'_, Database>) -> Result<String> {
+    if let Some(id) = existing_capture_notebook(&db.conn())? {
+        return Ok(id);
+    }
+
+    let notebook = notebooks::create_notebook(
+        app.clone(),
+        db,
+        CreateNotebookInput {
+            id: None,
+            name: INBOX_NOTEBOOK.to_string(),
+            color: None,
+            icon: None,
+            parent_id: None,
+        },
     )?;
+    Ok(notebook.id)
+}
 
-    drop(conn);
-    get_note(db, id)
+/// Create a note from raw text in one call, for the quick capture window.
+/// The note is tagged with the capture tag and filed in `notebook_id`, or
+/// the capture notebook when omitted.
+#[tauri::command]
+pub fn quick_capture(
+    app: AppHandle,
+    db: State<'_, Database>,
+    text: String,
+    notebook_id: Option<String>,
+) -> Result<Note> {
+    if text.trim().is_empty() {
+        return Err(AppError::Validation("Nothing to capture".to_string()));
+    }
+
+    let notebook_id = match notebook_id {
+        Some(id) => id,
+        None => capture_notebook(&app, db.clone())?,
+    };
+
+    let input = capture_input(&db.conn(), &text, notebook_id)?;
+    create_note(app, db, input)
+}
+
+/// The note a quick capture of `text` creates in `notebook_id`. Writing it
+/// creates the capture tag, or revives it if it was deleted.
+fn capture_input(conn: &Connection, text: &str, notebook_id: String) -> Result<CreateNoteInput> {
+    let (title, content) = split_capture(text);
+    let tag = settings::get(conn, settings::CAPTURE_TAG)?.unwrap_or_else(|| settings::DEFAULT_CAPTURE_TAG.to_string());
+    Ok(CreateNoteInput {
+        id: None,
+        title: Some(title),
+        content: Some(content),
+        notebook_id: Some(Some(notebook_id)),
+        tags: Some(vec![tag]),
+    })
 }
 
 #[tauri::command]
-pub fn update_note(db: State<'_, Database>, id: String, input: UpdateNoteInput) -> Result<Note> {
+pub fn update_note(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    input: UpdateNoteInput,
+) -> Result<Note> {
+    write_note_update(&db.conn(), &id, input)?;
+
+    let note = get_note(db, id)?;
+    events::emit(&app, ChangeEvent::Note, &note.id, ChangeKind::Updated);
+    Ok(note)
+}
+
+/// Validate and apply a partial update to a note
+pub fn write_note_update(conn: &Connection, id: &str, input: UpdateNoteInput) -> Result<()> {
+    if let Some(ref title) = input.title {
+        validation::note_title(title)?;
+    }
+    if let Some(ref content) = input.content {
+        validation::note_content(conn, content)?;
+    }
+    if let Some(ref tags) = input.tags {
+        validation::tag_names(tags)?;
+    }
+
     // First check if note exists (row_to_note will decrypt the existing values)
-    let existing = {
-        let conn = db.conn();
-        let mut stmt = conn.prepare(
-            "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at
-             FROM notes WHERE id = ?",
-        )?;
-        stmt.query_row(params![&id], row_to_note)
-            .map_err(|_| AppError::NotFound(format!("Note {} not found", id)))?
-    };
+    let existing = note_by_id(conn, id)?;
 
     let now = chrono::Utc::now().to_rfc3339();
     let new_revision = existing.revision + 1;
@@ -161,65 +401,409 @@ pub fn update_note(db: State<'_, Database>, id: String, input: UpdateNoteInput)
     // Encrypt if encryption is enabled
     let title = crypto::maybe_encrypt(&raw_title)?;
     let content = crypto::maybe_encrypt(&raw_content)?;
+    let excerpt = crypto::maybe_encrypt(&markdown::excerpt(&raw_content))?;
 
-    let notebook_id = input.notebook_id.or(existing.notebook_id);
+    let notebook_id = input.notebook_id.or(existing.notebook_id.clone());
+    let moved = notebook_id != existing.notebook_id;
+    if let Some(ref tags) = input.tags {
+        tags::ensure_tags(conn, tags)?;
+    }
     let tags = input.tags.unwrap_or(existing.tags);
     let status = input.status.unwrap_or(existing.status);
     let is_pinned = input.is_pinned.unwrap_or(existing.is_pinned);
+    let pinned_order = if is_pinned { existing.pinned_order } else { None };
 
     let tags_json = serde_json::to_string(&tags).unwrap();
 
-    {
-        let conn = db.conn();
-        conn.execute(
-            "UPDATE notes SET title = ?, content = ?, notebook_id = ?, tags = ?, status = ?, is_pinned = ?, revision = ?, updated_at = ?
-             WHERE id = ?",
-            params![
-                title,
-                content,
-                notebook_id,
-                tags_json,
-                status.as_str(),
-                is_pinned as i32,
-                new_revision,
-                now,
-                id
-            ],
-        )?;
+    conn.execute(
+        "UPDATE notes SET title = ?, content = ?, excerpt = ?, notebook_id = ?, tags = ?, status = ?, is_pinned = ?, pinned_order = ?, revision = ?, needs_push = 1, updated_at = ?
+         WHERE id = ?",
+        params![
+            title,
+            content,
+            excerpt,
+            notebook_id,
+            tags_json,
+            status.as_str(),
+            is_pinned as i32,
+            pinned_order,
+            new_revision,
+            now,
+            id
+        ],
+    )?;
+    tasks::sync_note_tasks(conn, id, &raw_content)?;
+    if moved {
+        sync::note_moved(conn, id)?;
     }
 
-    get_note(db, id)
+    Ok(())
 }
 
 #[tauri::command]
-pub fn delete_note(db: State<'_, Database>, id: String, hard: Option<bool>) -> Result<()> {
-    let conn = db.conn();
-
+pub fn delete_note(app: AppHandle, db: State<'_, Database>, id: String, hard: Option<bool>) -> Result<()> {
     if hard.unwrap_or(false) {
-        conn.execute("DELETE FROM notes WHERE id = ?", params![id])?;
+        hard_delete_note(&db, &id)?;
     } else {
-        let now = chrono::Utc::now().to_rfc3339();
-        conn.execute(
-            "UPDATE notes SET deleted_at = ?, status = 'trashed', revision = revision + 1, updated_at = ? WHERE id = ?",
-            params![now, now, id],
-        )?;
+        trash_note(&db.conn(), &id)?;
     }
 
+    events::emit(&app, ChangeEvent::Note, &id, ChangeKind::Deleted);
+    Ok(())
+}
+
+/// Soft delete: move a note to the trash
+pub fn trash_note(conn: &Connection, id: &str) -> Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE notes SET deleted_at = ?, status = 'trashed', revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+        params![now, now, id],
+    )?;
+    Ok(())
+}
+
+/// Remove a note with its reminders and tasks, leaving a tombstone so other
+/// devices drop it too
+pub fn hard_delete_note(db: &Database, id: &str) -> Result<()> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    purge_note_with_tombstone(&tx, id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Hard-delete a note inside the caller's transaction
+pub fn purge_note_with_tombstone(conn: &Connection, id: &str) -> Result<()> {
+    let revision: Option<i64> = conn
+        .query_row("SELECT revision FROM notes WHERE id = ?", params![id], |row| row.get(0))
+        .optional()?;
+    if let Some(revision) = revision {
+        sync::purge_note(conn, id)?;
+        sync::record_deletion(conn, "note", id, revision + 1)?;
+    }
     Ok(())
 }
 
+/// Bring a note back from the trash. A notebook deleted in the meantime is
+/// dropped so the note lands outside any notebook.
+fn restore_trashed(conn: &Connection, id: &str, now: &str) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE notes SET deleted_at = NULL, status = 'active', revision = revision + 1, needs_push = 1, updated_at = ?,
+             notebook_id = (SELECT nb.id FROM notebooks nb WHERE nb.id = notes.notebook_id AND nb.deleted_at IS NULL)
+         WHERE id = ? AND (deleted_at IS NOT NULL OR status = 'trashed')",
+        params![now, id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Restore several trashed notes in one transaction; returns the restored ids
+fn restore_many(db: &Database, ids: &[String]) -> Result<Vec<String>> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let mut restored = Vec::new();
+    for id in ids {
+        if restore_trashed(&tx, id, &now)? {
+            restored.push(id.clone());
+        }
+    }
+
+    tx.commit()?;
+    Ok(restored)
+}
+
 #[tauri::command]
-pub fn restore_note(db: State<'_, Database>, id: String) -> Result<Note> {
+pub fn restore_note(app: AppHandle, db: State<'_, Database>, id: String) -> Result<Note> {
     {
         let conn = db.conn();
         let now = chrono::Utc::now().to_rfc3339();
-        conn.execute(
-            "UPDATE notes SET deleted_at = NULL, status = 'active', revision = revision + 1, updated_at = ? WHERE id = ?",
-            params![now, id],
+        restore_trashed(&conn, &id, &now)?;
+    }
+
+    let note = get_note(db, id)?;
+    events::emit(&app, ChangeEvent::Note, &note.id, ChangeKind::Restored);
+    Ok(note)
+}
+
+/// Restore the given trashed notes; ids that aren't in the trash are ignored
+#[tauri::command]
+pub fn restore_notes(app: AppHandle, db: State<'_, Database>, ids: Vec<String>) -> Result<Vec<String>> {
+    let restored = restore_many(&db, &ids)?;
+    for id in &restored {
+        events::emit(&app, ChangeEvent::Note, id, ChangeKind::Restored);
+    }
+    Ok(restored)
+}
+
+/// Empty the trash back into the notes list
+#[tauri::command]
+pub fn restore_all_trashed(app: AppHandle, db: State<'_, Database>) -> Result<Vec<String>> {
+    let ids: Vec<String> = db
+        .conn()
+        .prepare("SELECT id FROM notes WHERE deleted_at IS NOT NULL")?
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let restored = restore_many(&db, &ids)?;
+    for id in &restored {
+        events::emit(&app, ChangeEvent::Note, id, ChangeKind::Restored);
+    }
+    Ok(restored)
+}
+
+/// Apply `set` (an update of the notes columns) to a live note and read it
+/// back, both in one transaction
+fn toggle_note(db: &Database, id: &str, set: &str) -> Result<Note> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let changed = tx.execute(
+        &format!(
+            "UPDATE notes SET {}, revision = revision + 1, needs_push = 1, updated_at = ?
+             WHERE id = ? AND deleted_at IS NULL AND status != 'trashed'",
+            set
+        ),
+        params![now, id],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Note {} not found", id)));
+    }
+    let note = note_by_id(&tx, id)?;
+    tx.commit()?;
+    Ok(note)
+}
+
+pub fn toggle_pinned(db: &Database, id: &str) -> Result<Note> {
+    toggle_note(db, id, "is_pinned = 1 - is_pinned, pinned_order = NULL")
+}
+
+pub fn toggle_archived(db: &Database, id: &str) -> Result<Note> {
+    toggle_note(
+        db,
+        id,
+        "status = CASE WHEN status = 'archived' THEN 'active' ELSE 'archived' END, archived_by_notebook = NULL",
+    )
+}
+
+#[tauri::command]
+pub fn toggle_pin(app: AppHandle, db: State<'_, Database>, id: String) -> Result<Note> {
+    let note = toggle_pinned(&db, &id)?;
+    events::emit(&app, ChangeEvent::Note, &note.id, ChangeKind::Updated);
+    Ok(note)
+}
+
+/// Arrange the pinned notes: `ordered_ids` come first, in that order; pinned
+/// notes left out keep their relative order after them. The order syncs, so
+/// notes whose place changed get a new revision and are queued for push.
+pub fn reorder_pinned(db: &Database, ordered_ids: &[String]) -> Result<Vec<String>> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+
+    let pinned: Vec<String> = {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM notes WHERE is_pinned = 1 AND deleted_at IS NULL
+             ORDER BY pinned_order ASC, updated_at DESC",
+        )?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        ids.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut order: Vec<String> = Vec::with_capacity(pinned.len());
+    for id in ordered_ids {
+        if !pinned.contains(id) {
+            return Err(AppError::Validation(format!("Note {} is not pinned", id)));
+        }
+        if !order.contains(id) {
+            order.push(id.clone());
+        }
+    }
+    for id in pinned {
+        if !order.contains(&id) {
+            order.push(id);
+        }
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    for (position, id) in order.iter().enumerate() {
+        tx.execute(
+            "UPDATE notes SET pinned_order = ?1, revision = revision + 1, updated_at = ?2, needs_push = 1
+             WHERE id = ?3 AND pinned_order IS NOT ?1",
+            params![position as i64, now, id],
         )?;
     }
 
-    get_note(db, id)
+    tx.commit()?;
+    Ok(order)
+}
+
+#[tauri::command]
+pub fn reorder_pinned_notes(app: AppHandle, db: State<'_, Database>, ordered_ids: Vec<String>) -> Result<()> {
+    for id in reorder_pinned(&db, &ordered_ids)? {
+        events::emit(&app, ChangeEvent::Note, &id, ChangeKind::Updated);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn toggle_archive(app: AppHandle, db: State<'_, Database>, id: String) -> Result<Note> {
+    let note = toggle_archived(&db, &id)?;
+    events::emit(&app, ChangeEvent::Note, &note.id, ChangeKind::Updated);
+    Ok(note)
+}
+
+fn note_by_id(conn: &Connection, id: &str) -> Result<Note> {
+    let mut stmt = conn.prepare(
+        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order
+         FROM notes WHERE id = ?",
+    )?;
+    stmt.query_row(params![id], row_to_note)
+        .map_err(|_| AppError::NotFound(format!("Note {} not found", id)))
+}
+
+/// Fold the source note into the target: the source's content is appended
+/// after `separator`, tags are unioned and reminders move to the target.
+/// The source is moved to the trash. All of it happens in one transaction;
+/// returns the merged note and the reminders that moved.
+pub fn merge_note_into(
+    db: &Database,
+    source_id: &str,
+    target_id: &str,
+    separator: Option<&str>,
+) -> Result<(Note, Vec<String>)> {
+    if source_id == target_id {
+        return Err(AppError::Validation("Cannot merge a note into itself".to_string()));
+    }
+
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+
+    let source = note_by_id(&tx, source_id)?;
+    let target = note_by_id(&tx, target_id)?;
+    if target.status == NoteStatus::Trashed || target.deleted_at.is_some() {
+        return Err(AppError::Validation(format!("Cannot merge into trashed note {}", target_id)));
+    }
+
+    let separator = separator.unwrap_or("\n\n---\n\n");
+    let raw_content = format!("{}{}{}", target.content, separator, source.content);
+    validation::note_content(&tx, &raw_content)?;
+    let content = crypto::maybe_encrypt(&raw_content)?;
+    let excerpt = crypto::maybe_encrypt(&markdown::excerpt(&raw_content))?;
+
+    let mut tags = target.tags;
+    for tag in source.tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    let tags_json = serde_json::to_string(&tags).unwrap();
+    tags::ensure_tags(&tx, &tags)?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let reminder_ids: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT id FROM reminders WHERE note_id = ? AND deleted_at IS NULL")?;
+        let ids = stmt.query_map(params![source_id], |row| row.get(0))?;
+        ids.collect::<rusqlite::Result<_>>()?
+    };
+    tx.execute(
+        "UPDATE reminders SET note_id = ?, revision = revision + 1, needs_push = 1, updated_at = ?
+         WHERE note_id = ? AND deleted_at IS NULL",
+        params![target_id, now, source_id],
+    )?;
+
+    tx.execute(
+        "UPDATE notes SET content = ?, excerpt = ?, tags = ?, revision = revision + 1, needs_push = 1, updated_at = ?
+         WHERE id = ?",
+        params![content, excerpt, tags_json, now, target_id],
+    )?;
+    tasks::sync_note_tasks(&tx, target_id, &raw_content)?;
+    tx.execute(
+        "UPDATE notes SET deleted_at = ?, status = 'trashed', revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+        params![now, now, source_id],
+    )?;
+
+    let note = note_by_id(&tx, target_id)?;
+    tx.commit()?;
+    Ok((note, reminder_ids))
+}
+
+#[tauri::command]
+pub fn merge_notes(
+    app: AppHandle,
+    db: State<'_, Database>,
+    source_id: String,
+    target_id: String,
+    separator: Option<String>,
+) -> Result<Note> {
+    let (note, moved_reminders) = merge_note_into(&db, &source_id, &target_id, separator.as_deref())?;
+    events::emit(&app, ChangeEvent::Note, &source_id, ChangeKind::Deleted);
+    events::emit(&app, ChangeEvent::Note, &note.id, ChangeKind::Updated);
+    for id in &moved_reminders {
+        events::emit(&app, ChangeEvent::Reminder, id, ChangeKind::Updated);
+    }
+    Ok(note)
+}
+
+/// Move many notes between `from` and `to` status in one transaction.
+/// Notes already in `to` are left alone; trashed or missing ids are skipped.
+fn set_notes_status(
+    db: &Database,
+    ids: &[String],
+    from: NoteStatus,
+    to: NoteStatus,
+) -> Result<(ArchiveSummary, Vec<String>)> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let mut summary = ArchiveSummary::default();
+    let mut changed_ids = Vec::new();
+
+    for id in ids {
+        let status: Option<String> = tx
+            .query_row(
+                "SELECT status FROM notes WHERE id = ? AND deleted_at IS NULL",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match status.map(|s| NoteStatus::from_str(&s)) {
+            None | Some(NoteStatus::Trashed) => summary.skipped.push(id.clone()),
+            Some(status) if status == from => {
+                tx.execute(
+                    "UPDATE notes SET status = ?, archived_by_notebook = NULL, revision = revision + 1, needs_push = 1, updated_at = ?
+                     WHERE id = ?",
+                    params![to.as_str(), now, id],
+                )?;
+                summary.archived += 1;
+                changed_ids.push(id.clone());
+            }
+            Some(_) => {}
+        }
+    }
+
+    tx.commit()?;
+    Ok((summary, changed_ids))
+}
+
+#[tauri::command]
+pub fn archive_notes(app: AppHandle, db: State<'_, Database>, ids: Vec<String>) -> Result<ArchiveSummary> {
+    let (summary, changed) = set_notes_status(&db, &ids, NoteStatus::Active, NoteStatus::Archived)?;
+    for id in &changed {
+        events::emit(&app, ChangeEvent::Note, id, ChangeKind::Updated);
+    }
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn unarchive_notes(app: AppHandle, db: State<'_, Database>, ids: Vec<String>) -> Result<ArchiveSummary> {
+    let (summary, changed) = set_notes_status(&db, &ids, NoteStatus::Archived, NoteStatus::Active)?;
+    for id in &changed {
+        events::emit(&app, ChangeEvent::Note, id, ChangeKind::Updated);
+    }
+    Ok(summary)
 }
 
 #[tauri::command]
@@ -227,7 +811,7 @@ pub fn get_trashed_notes(db: State<'_, Database>) -> Result<Vec<Note>> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at
+        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order
          FROM notes WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
     )?;
 
@@ -237,3 +821,726 @@ pub fn get_trashed_notes(db: State<'_, Database>) -> Result<Vec<Note>> {
 
     Ok(notes)
 }
+
+/// Trashed notes with their notebook name and time in the trash
+pub fn list_trash(db: &Database) -> Result<Vec<TrashedNoteView>> {
+    let conn = db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.title, n.content, n.notebook_id, n.tags, n.status, n.is_pinned, n.revision,
+                n.created_at, n.updated_at, n.deleted_at, n.pinned_order,
+                nb.name, CAST(julianday('now') - julianday(n.deleted_at) AS INTEGER)
+         FROM notes n
+         LEFT JOIN notebooks nb ON nb.id = n.notebook_id AND nb.deleted_at IS NULL
+         WHERE n.deleted_at IS NOT NULL
+         ORDER BY n.deleted_at DESC",
+    )?;
+
+    let notes = stmt
+        .query_map([], |row| {
+            Ok(TrashedNoteView {
+                note: row_to_note(row)?,
+                notebook_name: row.get(12)?,
+                days_in_trash: row.get::<_, Option<i32>>(13)?.unwrap_or(0).max(0),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(notes)
+}
+
+#[tauri::command]
+pub fn get_trash(db: State<'_, Database>) -> Result<Vec<TrashedNoteView>> {
+    list_trash(&db)
+}
+
+/// Compute excerpts for notes that don't have one yet (rows written before
+/// the column existed, or by sync/import paths that only carry content)
+pub fn backfill_excerpts(conn: &Connection) -> Result<usize> {
+    fill_excerpts(conn, "SELECT id, content FROM notes WHERE excerpt IS NULL")
+}
+
+fn fill_excerpts(conn: &Connection, select_sql: &str) -> Result<usize> {
+    let rows: Vec<(String, String)> = conn
+        .prepare(select_sql)?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare("UPDATE notes SET excerpt = ? WHERE id = ?")?;
+    for (id, raw_content) in &rows {
+        let content = crypto::maybe_decrypt(raw_content).unwrap_or_else(|_| raw_content.clone());
+        let excerpt = crypto::maybe_encrypt(&markdown::excerpt(&content))?;
+        stmt.execute(params![excerpt, id])?;
+    }
+
+    Ok(rows.len())
+}
+
+/// Recompute every stored excerpt
+/// Useful after unlocking encryption or if excerpts get out of date
+#[tauri::command]
+pub fn rebuild_excerpts(db: State<'_, Database>) -> Result<usize> {
+    let conn = db.conn();
+    fill_excerpts(&conn, "SELECT id, content FROM notes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::NotebookDeleteMode;
+    use crate::test_support::{allow_writes, fail_writes};
+
+    fn insert_note(db: &Database, tags: &[&str]) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        tags::ensure_tags(&db.conn(), &tags.iter().map(|t| t.to_string()).collect::<Vec<_>>()).unwrap();
+        db.conn()
+            .execute(
+                "INSERT INTO notes (id, title, tags) VALUES (?, 'Note', ?)",
+                params![id, serde_json::to_string(tags).unwrap()],
+            )
+            .unwrap();
+        id
+    }
+
+    fn ids_with_tags(db: &Database, tags: &[&str], mode: TagMode) -> Vec<String> {
+        let filter = ListNotesFilter {
+            tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+            tag_mode: Some(mode),
+            ..Default::default()
+        };
+        let mut ids: Vec<String> = query_notes(db, filter).unwrap().into_iter().map(|n| n.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_tag_filter_ignores_suffix_matches() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let work = insert_note(&db, &["work", "urgent"]);
+        insert_note(&db, &["homework"]);
+        insert_note(&db, &["work-life"]);
+
+        assert_eq!(ids_with_tags(&db, &["work"], TagMode::All), vec![work.clone()]);
+        assert_eq!(ids_with_tags(&db, &["work"], TagMode::Any), vec![work.clone()]);
+
+        // The legacy single-tag field goes through the same matching
+        let legacy = ListNotesFilter {
+            tag: Some("work".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<String> = query_notes(&db, legacy).unwrap().into_iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![work]);
+    }
+
+    #[test]
+    fn test_notes_by_tag_exact_at_scale() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let overlapping = [
+            vec!["work"],
+            vec!["homework"],
+            vec!["work-life"],
+            vec!["Work"],
+            vec!["x", "work"],
+        ];
+        {
+            let mut conn = db.conn();
+            let tx = conn.transaction().unwrap();
+            let names = ["work", "homework", "work-life", "Work", "x"].map(str::to_string);
+            tags::ensure_tags(&tx, &names).unwrap();
+            for i in 0..10_000 {
+                tx.execute(
+                    "INSERT INTO notes (id, title, tags) VALUES (?, 'Note', ?)",
+                    params![
+                        uuid::Uuid::new_v4().to_string(),
+                        serde_json::to_string(&overlapping[i % overlapping.len()]).unwrap()
+                    ],
+                )
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+
+        // The lookup goes through note_tags and takes tens of milliseconds
+        // in a debug build; the bound leaves room for a slow machine
+        let started = std::time::Instant::now();
+        let all = notes_by_tag(&db, "work".to_string(), None, None).unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed < std::time::Duration::from_secs(2), "notes_by_tag over 10k notes took {:?}", elapsed);
+
+        assert_eq!(all.len(), 4_000);
+        assert!(all.iter().all(|n| n.tags.iter().any(|t| t == "work")));
+        assert_eq!(notes_by_tag(&db, "Work".to_string(), None, None).unwrap().len(), 2_000);
+        assert!(notes_by_tag(&db, "wor".to_string(), None, None).unwrap().is_empty());
+
+        let page = notes_by_tag(&db, "work".to_string(), Some(50), Some(3_980)).unwrap();
+        assert_eq!(page.len(), 20);
+    }
+
+    #[test]
+    fn test_tag_filter_all_and_any() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let both = insert_note(&db, &["rust", "tauri"]);
+        let rust = insert_note(&db, &["rust"]);
+        let tauri = insert_note(&db, &["tauri", "ui"]);
+        insert_note(&db, &[]);
+
+        assert_eq!(ids_with_tags(&db, &["rust", "tauri"], TagMode::All), vec![both.clone()]);
+
+        let mut expected = vec![both, rust, tauri];
+        expected.sort();
+        assert_eq!(ids_with_tags(&db, &["rust", "tauri"], TagMode::Any), expected);
+
+        // Repeating a tag doesn't make "all" impossible to satisfy
+        assert_eq!(ids_with_tags(&db, &["ui", "ui"], TagMode::All).len(), 1);
+    }
+
+    #[test]
+    fn test_reorder_pinned_notes() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let a = insert_note(&db, &[]);
+        let b = insert_note(&db, &[]);
+        let c = insert_note(&db, &[]);
+        let unpinned = insert_note(&db, &[]);
+        db.conn()
+            .execute("UPDATE notes SET is_pinned = 1 WHERE id IN (?, ?, ?)", params![a, b, c])
+            .unwrap();
+
+        let order = reorder_pinned(&db, &[c.clone(), a.clone()]).unwrap();
+        assert_eq!(order, vec![c.clone(), a.clone(), b.clone()]);
+
+        let listed: Vec<String> = query_notes(&db, ListNotesFilter::default())
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(listed, vec![c, a, b, unpinned.clone()]);
+
+        let result = reorder_pinned(&db, &[unpinned]);
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_reorder_pinned_queues_moved_notes() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let [a, b, c] = [(); 3].map(|_| insert_note(&db, &[]));
+        db.conn()
+            .execute("UPDATE notes SET is_pinned = 1 WHERE id IN (?, ?, ?)", params![a, b, c])
+            .unwrap();
+        reorder_pinned(&db, &[a.clone(), b.clone(), c.clone()]).unwrap();
+        db.conn().execute("UPDATE notes SET needs_push = 0", []).unwrap();
+        let state = |id: &str| -> (i64, bool) {
+            db.conn()
+                .query_row("SELECT revision, needs_push FROM notes WHERE id = ?", [id], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .unwrap()
+        };
+
+        // Swapping the last two leaves the first one alone
+        reorder_pinned(&db, &[a.clone(), c.clone(), b.clone()]).unwrap();
+        assert_eq!(state(&a), (2, false));
+        assert_eq!(state(&b), (3, true));
+        assert_eq!(state(&c), (3, true));
+
+        // The same order again changes nothing
+        db.conn().execute("UPDATE notes SET needs_push = 0", []).unwrap();
+        reorder_pinned(&db, &[a.clone(), c.clone(), b.clone()]).unwrap();
+        assert_eq!([state(&a), state(&b), state(&c)], [(2, false), (3, false), (3, false)]);
+    }
+
+    #[test]
+    fn test_reorder_pinned_refuses_and_dedupes() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let [a, b, c, unpinned] = [(); 4].map(|_| insert_note(&db, &[]));
+        db.conn()
+            .execute("UPDATE notes SET is_pinned = 1 WHERE id IN (?, ?, ?)", params![a, b, c])
+            .unwrap();
+        let order = || -> Vec<(String, Option<i64>)> {
+            let conn = db.conn();
+            let mut stmt =
+                conn.prepare("SELECT id, pinned_order FROM notes WHERE is_pinned = 1 ORDER BY pinned_order").unwrap();
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+            rows.map(|row| row.unwrap()).collect()
+        };
+        reorder_pinned(&db, &[b.clone(), a.clone(), c.clone()]).unwrap();
+        let arranged = order();
+
+        // One bad id refuses the whole list, wherever it comes
+        assert!(reorder_pinned(&db, &[a.clone(), unpinned.clone()]).is_err());
+        let missing = uuid::Uuid::new_v4().to_string();
+        assert!(reorder_pinned(&db, &[c.clone(), missing]).is_err());
+        assert_eq!(order(), arranged);
+
+        // A trashed note no longer counts as pinned
+        db.conn()
+            .execute("UPDATE notes SET status = 'trashed', deleted_at = '2026-01-01' WHERE id = ?", [&b])
+            .unwrap();
+        assert!(matches!(reorder_pinned(&db, std::slice::from_ref(&b)), Err(AppError::Validation(_))));
+
+        // Repeats keep their first place
+        assert_eq!(reorder_pinned(&db, &[c.clone(), a.clone(), c.clone()]).unwrap(), vec![c.clone(), a.clone()]);
+
+        // Unpinning drops the note's place
+        toggle_pinned(&db, &a).unwrap();
+        let pinned_order: Option<i64> =
+            db.conn().query_row("SELECT pinned_order FROM notes WHERE id = ?", [&a], |row| row.get(0)).unwrap();
+        assert_eq!(pinned_order, None);
+        assert_eq!(reorder_pinned(&db, &[]).unwrap(), vec![c]);
+    }
+
+    /// A source note with tag `a` and one reminder, and a target with tag `b`
+    fn merge_pair(db: &Database) -> (String, String, String) {
+        let source = insert_note(db, &["a"]);
+        let target = insert_note(db, &["b"]);
+        let reminder = uuid::Uuid::new_v4().to_string();
+        db.conn()
+            .execute(
+                "INSERT INTO reminders (id, note_id, due_date) VALUES (?, ?, '2030-01-01T00:00:00Z')",
+                params![reminder, source],
+            )
+            .unwrap();
+        (source, target, reminder)
+    }
+
+    /// Source status, target revision and tags, and the reminder's note
+    fn merge_state(
+        db: &Database,
+        source: &str,
+        target: &str,
+        reminder: &str,
+    ) -> (NoteStatus, i64, Vec<String>, String) {
+        let conn = db.conn();
+        let note = |id: &str| note_by_id(&conn, id).unwrap();
+        let owner: String =
+            conn.query_row("SELECT note_id FROM reminders WHERE id = ?", [reminder], |row| row.get(0)).unwrap();
+        (note(source).status, note(target).revision, note(target).tags, owner)
+    }
+
+    #[test]
+    fn test_merge_moves_tags_and_reminders_and_trashes_the_source() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let (source, target, reminder) = merge_pair(&db);
+        let before = merge_state(&db, &source, &target, &reminder);
+
+        let (merged, moved) = merge_note_into(&db, &source, &target, Some("\n")).unwrap();
+        assert_eq!(merged.tags, vec!["b", "a"]);
+        assert_eq!(moved, vec![reminder.clone()]);
+        let (status, revision, _, owner) = merge_state(&db, &source, &target, &reminder);
+        assert_eq!((status, revision, owner), (NoteStatus::Trashed, before.1 + 1, target.clone()));
+    }
+
+    #[test]
+    fn test_merge_refusals_write_nothing() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let (source, target, reminder) = merge_pair(&db);
+        let before = merge_state(&db, &source, &target, &reminder);
+
+        assert!(matches!(merge_note_into(&db, &source, &source, None), Err(AppError::Validation(_))));
+        let missing = uuid::Uuid::new_v4().to_string();
+        assert!(matches!(merge_note_into(&db, &source, &missing, None), Err(AppError::NotFound(_))));
+        let trashed = insert_note(&db, &[]);
+        db.conn().execute("UPDATE notes SET status = 'trashed' WHERE id = ?", [&trashed]).unwrap();
+        assert!(matches!(merge_note_into(&db, &source, &trashed, None), Err(AppError::Validation(_))));
+        assert_eq!(merge_state(&db, &source, &target, &reminder), before);
+    }
+
+    #[test]
+    fn test_merge_that_cant_trash_the_source_leaves_the_target_alone() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let (source, target, reminder) = merge_pair(&db);
+        let before = merge_state(&db, &source, &target, &reminder);
+
+        // Trashing the source is the last step
+        fail_writes(&db.conn(), &format!("UPDATE OF status ON notes WHEN NEW.id = '{}'", source));
+        assert!(merge_note_into(&db, &source, &target, None).is_err());
+        allow_writes(&db.conn());
+        assert_eq!(merge_state(&db, &source, &target, &reminder), before);
+    }
+
+    #[test]
+    fn test_notes_by_ids_keeps_order_and_duplicates() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let ids: Vec<String> = (0..1_200).map(|_| insert_note(&db, &[])).collect();
+        let trashed = ids[3].clone();
+        db.conn().execute("UPDATE notes SET status = 'trashed' WHERE id = ?", [&trashed]).unwrap();
+        db.conn().execute("UPDATE notes SET deleted_at = '2026-01-01' WHERE id = ?", [&ids[4]]).unwrap();
+
+        // Reversed, across several chunks, with a missing id and a duplicate
+        let mut wanted: Vec<String> = ids.iter().rev().cloned().collect();
+        wanted.insert(10, uuid::Uuid::new_v4().to_string());
+        wanted.push(ids[1_000].clone());
+        let notes = notes_by_ids(&db, &wanted).unwrap();
+
+        let expected: Vec<&String> =
+            wanted.iter().filter(|id| ids.contains(id) && **id != trashed && **id != ids[4]).collect();
+        assert_eq!(notes.len(), 1_199);
+        assert_eq!(notes.iter().map(|n| &n.id).collect::<Vec<_>>(), expected);
+        assert_eq!(notes.last().unwrap().id, ids[1_000]);
+        assert!(notes_by_ids(&db, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_toggles_flip_and_refuse_trashed_notes() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let id = insert_note(&db, &[]);
+
+        let pinned = toggle_pinned(&db, &id).unwrap();
+        assert!(pinned.is_pinned);
+        assert_eq!(pinned.revision, 2);
+        assert!(!toggle_pinned(&db, &id).unwrap().is_pinned);
+
+        let archived = toggle_archived(&db, &id).unwrap();
+        assert_eq!((archived.status, archived.revision), (NoteStatus::Archived, 4));
+        assert_eq!(toggle_archived(&db, &id).unwrap().status, NoteStatus::Active);
+        let needs_push: bool =
+            db.conn().query_row("SELECT needs_push FROM notes WHERE id = ?", [&id], |row| row.get(0)).unwrap();
+        assert!(needs_push);
+
+        // Trashed and missing notes are not found, and stay as they were
+        db.conn().execute("UPDATE notes SET status = 'trashed' WHERE id = ?", [&id]).unwrap();
+        assert!(matches!(toggle_pinned(&db, &id), Err(AppError::NotFound(_))));
+        assert!(matches!(toggle_archived(&db, &id), Err(AppError::NotFound(_))));
+        let revision: i64 =
+            db.conn().query_row("SELECT revision FROM notes WHERE id = ?", [&id], |row| row.get(0)).unwrap();
+        assert_eq!(revision, 5);
+        let missing = uuid::Uuid::new_v4().to_string();
+        assert!(matches!(toggle_pinned(&db, &missing), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_archive_notes_skips_trashed_and_missing() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let a = insert_note(&db, &[]);
+        let b = insert_note(&db, &[]);
+        let trashed = insert_note(&db, &[]);
+        db.conn()
+            .execute("UPDATE notes SET status = 'trashed' WHERE id = ?", params![trashed])
+            .unwrap();
+        let missing = uuid::Uuid::new_v4().to_string();
+
+        let ids = vec![a.clone(), b.clone(), trashed.clone(), missing.clone()];
+        let (summary, _) = set_notes_status(&db, &ids, NoteStatus::Active, NoteStatus::Archived).unwrap();
+        assert_eq!(summary.archived, 2);
+        assert_eq!(summary.skipped, vec![trashed, missing]);
+
+        let revision: i64 = db
+            .conn()
+            .query_row("SELECT revision FROM notes WHERE id = ? AND status = 'archived'", params![a], |row| row.get(0))
+            .unwrap();
+        assert_eq!(revision, 2);
+
+        // Already archived notes are neither changed nor skipped
+        let (summary, _) = set_notes_status(&db, std::slice::from_ref(&a), NoteStatus::Active, NoteStatus::Archived).unwrap();
+        assert_eq!(summary.archived, 0);
+        assert!(summary.skipped.is_empty());
+
+        let (summary, _) = set_notes_status(&db, &[a, b], NoteStatus::Archived, NoteStatus::Active).unwrap();
+        assert_eq!(summary.archived, 2);
+    }
+
+    fn statuses(db: &Database, ids: &[&String]) -> Vec<String> {
+        let conn = db.conn();
+        ids.iter()
+            .map(|id| conn.query_row("SELECT status FROM notes WHERE id = ?", [id], |row| row.get(0)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_archive_notes_is_all_or_nothing() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let [a, b, c] = [(); 3].map(|_| insert_note(&db, &[]));
+
+        // The third note fails; the first two go back to active
+        fail_writes(&db.conn(), &format!("UPDATE OF status ON notes WHEN NEW.id = '{}'", c));
+        let ids = vec![a.clone(), b.clone(), c.clone()];
+        assert!(set_notes_status(&db, &ids, NoteStatus::Active, NoteStatus::Archived).is_err());
+        allow_writes(&db.conn());
+        assert_eq!(statuses(&db, &[&a, &b, &c]), ["active", "active", "active"]);
+    }
+
+    #[test]
+    fn test_archive_notes_archives_a_repeated_id_once() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let a = insert_note(&db, &[]);
+        let (summary, changed) =
+            set_notes_status(&db, &[a.clone(), a.clone()], NoteStatus::Active, NoteStatus::Archived).unwrap();
+        assert_eq!((summary.archived, changed), (1, vec![a.clone()]));
+        assert_eq!(statuses(&db, &[&a]), ["archived"]);
+    }
+
+    #[test]
+    fn test_archive_notes_skips_soft_deleted_notes() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let [a, b] = [(); 2].map(|_| insert_note(&db, &[]));
+        db.conn().execute("UPDATE notes SET deleted_at = '2026-01-01' WHERE id = ?", [&b]).unwrap();
+        let ids = vec![a.clone(), b.clone()];
+        let (summary, _) = set_notes_status(&db, &ids, NoteStatus::Active, NoteStatus::Archived).unwrap();
+        assert_eq!((summary.archived, summary.skipped), (1, vec![b.clone()]));
+        assert_eq!(statuses(&db, &[&a, &b]), ["archived", "active"]);
+    }
+
+    #[test]
+    fn test_split_capture() {
+        assert_eq!(
+            split_capture("\n\n# Call the bank \n\nabout the card\nand the loan"),
+            ("Call the bank".to_string(), "about the card\nand the loan".to_string())
+        );
+        assert_eq!(split_capture("just a title"), ("just a title".to_string(), String::new()));
+
+        let long = "é".repeat(CAPTURE_TITLE_LENGTH + 20);
+        let (title, content) = split_capture(&long);
+        assert_eq!(title.chars().count(), CAPTURE_TITLE_LENGTH);
+        assert_eq!(content, long);
+    }
+
+    #[test]
+    fn test_capture_notebook_falls_back_past_deleted_ones() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let notebook = |id: &str, name: &str, parent: Option<&str>| {
+            conn.execute("INSERT INTO notebooks (id, name, parent_id) VALUES (?, ?, ?)", params![id, name, parent])
+                .unwrap();
+        };
+        assert_eq!(existing_capture_notebook(&conn).unwrap(), None);
+
+        // Only a root notebook named Inbox counts
+        notebook("nested-parent", "Projects", None);
+        notebook("nested", INBOX_NOTEBOOK, Some("nested-parent"));
+        assert_eq!(existing_capture_notebook(&conn).unwrap(), None);
+        notebook("inbox", INBOX_NOTEBOOK, None);
+        assert_eq!(existing_capture_notebook(&conn).unwrap().as_deref(), Some("inbox"));
+
+        // The capture notebook comes before the default one, while it exists
+        notebook("capture", "Capture", None);
+        notebook("default", "Default", None);
+        settings::set(&conn, settings::DEFAULT_NOTEBOOK_ID, "default").unwrap();
+        settings::set(&conn, settings::CAPTURE_NOTEBOOK_ID, "capture").unwrap();
+        assert_eq!(existing_capture_notebook(&conn).unwrap().as_deref(), Some("capture"));
+        conn.execute("UPDATE notebooks SET deleted_at = '2026-01-01' WHERE id = 'capture'", []).unwrap();
+        assert_eq!(existing_capture_notebook(&conn).unwrap().as_deref(), Some("default"));
+        conn.execute("UPDATE notebooks SET deleted_at = '2026-01-01' WHERE id IN ('default', 'inbox')", []).unwrap();
+        assert_eq!(existing_capture_notebook(&conn).unwrap(), None);
+    }
+
+    #[test]
+    fn test_capture_after_its_tag_was_deleted() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        conn.execute("INSERT INTO notebooks (id, name) VALUES ('inbox', 'Inbox')", []).unwrap();
+        let first = super::insert_note(&conn, capture_input(&conn, "first", "inbox".to_string()).unwrap()).unwrap();
+        conn.execute("DELETE FROM notes WHERE id = ?", [&first]).unwrap();
+
+        // Cleaning up unused tags deletes the capture tag; the next capture
+        // brings the same tag back
+        let tag = settings::DEFAULT_CAPTURE_TAG;
+        let (id, _) = tags::remove_unused_tags(&conn, false, true).unwrap().remove(0);
+        let note = super::insert_note(&conn, capture_input(&conn, "second", "inbox".to_string()).unwrap()).unwrap();
+        let (tag_id, deleted): (String, bool) = conn
+            .query_row("SELECT id, deleted_at IS NOT NULL FROM tags WHERE name = ?", [tag], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((tag_id, deleted), (id, false));
+        assert_eq!(tags::note_tag_names(&conn, &note).unwrap(), vec![tag]);
+    }
+
+    #[test]
+    fn test_split_capture_handles_crlf_and_indented_titles() {
+        assert_eq!(split_capture("  Title\r\n\r\nbody"), ("Title".to_string(), "body".to_string()));
+    }
+
+    #[test]
+    fn test_split_capture_title_limit_counts_characters_exactly() {
+        let exact = "x".repeat(CAPTURE_TITLE_LENGTH);
+        assert_eq!(split_capture(&format!("{}\nrest", exact)), (exact.clone(), "rest".to_string()));
+        let over = format!("{}y\nrest", exact);
+        assert_eq!(split_capture(&over), (exact, over.clone()));
+    }
+
+    #[test]
+    fn test_trash_view_and_restore_into_deleted_notebook() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let kept = insert_note(&db, &[]);
+        let orphaned = insert_note(&db, &[]);
+        {
+            let conn = db.conn();
+            conn.execute_batch(
+                "INSERT INTO notebooks (id, name) VALUES ('nb-live', 'Live'), ('nb-gone', 'Gone');",
+            )
+            .unwrap();
+            conn.execute("UPDATE notes SET notebook_id = 'nb-live' WHERE id = ?", params![kept]).unwrap();
+            conn.execute("UPDATE notes SET notebook_id = 'nb-gone' WHERE id = ?", params![orphaned]).unwrap();
+            trash_note(&conn, &kept).unwrap();
+            trash_note(&conn, &orphaned).unwrap();
+            conn.execute("UPDATE notebooks SET deleted_at = datetime('now') WHERE id = 'nb-gone'", [])
+                .unwrap();
+        }
+
+        let trash = list_trash(&db).unwrap();
+        assert_eq!(trash.len(), 2);
+        let view = trash.iter().find(|v| v.note.id == kept).unwrap();
+        assert_eq!(view.notebook_name.as_deref(), Some("Live"));
+        assert_eq!(view.days_in_trash, 0);
+
+        let restored = restore_many(&db, &[kept.clone(), orphaned.clone(), "missing".to_string()]).unwrap();
+        assert_eq!(restored, vec![kept.clone(), orphaned.clone()]);
+        assert!(list_trash(&db).unwrap().is_empty());
+
+        let conn = db.conn();
+        assert_eq!(note_by_id(&conn, &kept).unwrap().notebook_id.as_deref(), Some("nb-live"));
+        let note = note_by_id(&conn, &orphaned).unwrap();
+        assert_eq!(note.notebook_id, None);
+        assert_eq!(note.status, NoteStatus::Active);
+    }
+
+    #[test]
+    fn test_trash_ages_and_repeated_restores() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let old = insert_note(&db, &[]);
+        let skewed = insert_note(&db, &[]);
+        {
+            let conn = db.conn();
+            // The notebook was hard-deleted without detaching the note
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                 UPDATE notes SET notebook_id = 'purged' WHERE 1;
+                 PRAGMA foreign_keys = ON;",
+            )
+            .unwrap();
+            conn.execute(
+                "UPDATE notes SET status = 'trashed', deleted_at = datetime('now', '-3 days', '-1 hour') WHERE id = ?",
+                [&old],
+            )
+            .unwrap();
+            // Another device's clock ran ahead
+            conn.execute(
+                "UPDATE notes SET status = 'trashed', deleted_at = datetime('now', '+2 days') WHERE id = ?",
+                [&skewed],
+            )
+            .unwrap();
+        }
+
+        let trash = list_trash(&db).unwrap();
+        let days: Vec<(&str, i32, Option<&str>)> =
+            trash.iter().map(|v| (v.note.id.as_str(), v.days_in_trash, v.notebook_name.as_deref())).collect();
+        assert_eq!(days, vec![(skewed.as_str(), 0, None), (old.as_str(), 3, None)]);
+
+        // An id listed twice is restored once
+        assert_eq!(restore_many(&db, &[old.clone(), old.clone()]).unwrap(), vec![old.clone()]);
+        let note = note_by_id(&db.conn(), &old).unwrap();
+        assert_eq!((note.revision, note.notebook_id), (2, None));
+        // Restoring a note that isn't in the trash does nothing
+        assert!(restore_many(&db, std::slice::from_ref(&old)).unwrap().is_empty());
+        assert_eq!(note_by_id(&db.conn(), &old).unwrap().revision, 2);
+    }
+
+    fn new_notebook(conn: &Connection, name: &str, parent_id: Option<&str>) -> String {
+        notebooks::insert_notebook(
+            conn,
+            CreateNotebookInput {
+                id: None,
+                name: name.to_string(),
+                color: None,
+                icon: None,
+                parent_id: parent_id.map(String::from),
+            },
+        )
+        .unwrap()
+    }
+
+    /// Notebook a note created from the `json` input ends up in
+    fn notebook_of_new_note(conn: &Connection, json: &str) -> Option<String> {
+        let input: CreateNoteInput = serde_json::from_str(json).unwrap();
+        let id = super::insert_note(conn, input).unwrap();
+        note_by_id(conn, &id).unwrap().notebook_id
+    }
+
+    #[test]
+    fn test_default_notebook_applies_only_when_omitted() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let inbox = new_notebook(&conn, "Inbox", None);
+        settings::set(&conn, settings::DEFAULT_NOTEBOOK_ID, &inbox).unwrap();
+
+        assert_eq!(notebook_of_new_note(&conn, r#"{"title": "Omitted"}"#), Some(inbox.clone()));
+        assert_eq!(notebook_of_new_note(&conn, r#"{"title": "Explicit null", "notebook_id": null}"#), None);
+
+        // Deleting the default notebook clears the setting
+        notebooks::remove_notebook(&conn, &inbox, NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        assert_eq!(settings::get(&conn, settings::DEFAULT_NOTEBOOK_ID).unwrap(), None);
+        assert_eq!(notebook_of_new_note(&conn, r#"{"title": "After delete"}"#), None);
+    }
+
+    #[test]
+    fn test_reserialized_note_input_keeps_omitted_apart_from_null() {
+        for json in [r#"{"title": "Omitted"}"#, r#"{"title": "Null", "notebook_id": null}"#] {
+            let input: CreateNoteInput = serde_json::from_str(json).unwrap();
+            let back: CreateNoteInput = serde_json::from_str(&serde_json::to_string(&input).unwrap()).unwrap();
+            assert_eq!(back.notebook_id, input.notebook_id);
+        }
+    }
+
+    #[test]
+    fn test_a_missing_or_trashed_default_notebook_is_ignored() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        settings::set(&conn, settings::DEFAULT_NOTEBOOK_ID, "nowhere").unwrap();
+        assert_eq!(notebook_of_new_note(&conn, r#"{"title": "Missing"}"#), None);
+
+        let trashed = new_notebook(&conn, "Trashed", None);
+        conn.execute("UPDATE notebooks SET deleted_at = datetime('now') WHERE id = ?", params![trashed])
+            .unwrap();
+        settings::set(&conn, settings::DEFAULT_NOTEBOOK_ID, &trashed).unwrap();
+        assert_eq!(notebook_of_new_note(&conn, r#"{"title": "Trashed"}"#), None);
+    }
+
+    #[test]
+    fn test_an_explicit_notebook_wins_over_the_default() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let inbox = new_notebook(&conn, "Inbox", None);
+        let other = new_notebook(&conn, "Other", None);
+        settings::set(&conn, settings::DEFAULT_NOTEBOOK_ID, &inbox).unwrap();
+        assert_eq!(notebook_of_new_note(&conn, &format!(r#"{{"notebook_id": "{}"}}"#, other)), Some(other));
+    }
+
+    #[test]
+    fn test_deleting_the_default_notebooks_parent_clears_the_setting() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let projects = new_notebook(&conn, "Projects", None);
+        let inbox = new_notebook(&conn, "Inbox", Some(&projects));
+        let other = new_notebook(&conn, "Other", None);
+        settings::set(&conn, settings::DEFAULT_NOTEBOOK_ID, &inbox).unwrap();
+
+        // Deleting some other notebook keeps it
+        notebooks::remove_notebook(&conn, &other, NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        assert_eq!(settings::get(&conn, settings::DEFAULT_NOTEBOOK_ID).unwrap(), Some(inbox.clone()));
+        notebooks::remove_notebook(&conn, &projects, NotebookDeleteMode::DeleteChildren, None, false).unwrap();
+        assert_eq!(settings::get(&conn, settings::DEFAULT_NOTEBOOK_ID).unwrap(), None);
+    }
+}
@@ -1,11 +1,20 @@
-use rusqlite::params;
-use tauri::State;
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, State};
 
+use crate::crypto;
 use crate::db::Database;
 use crate::error::{AppError, Result};
-use crate::models::{CreateReminderInput, Reminder, UpdateReminderInput};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::models::{
+    AgendaReminder, CreateReminderInput, Recurrence, Reminder, ReminderAgendaGroup, ReminderStats,
+    UpdateReminderInput,
+};
+use crate::natural_date;
+use crate::recurrence;
+use crate::sync;
 
-fn row_to_reminder(row: &rusqlite::Row) -> rusqlite::Result<Reminder> {
+pub fn row_to_reminder(row: &rusqlite::Row) -> rusqlite::Result<Reminder> {
     Ok(Reminder {
         id: row.get(0)?,
         note_id: row.get(1)?,
@@ -17,22 +26,101 @@ fn row_to_reminder(row: &rusqlite::Row) -> rusqlite::Result<Reminder> {
         created_at: row.get(7)?,
         updated_at: row.get(8)?,
         deleted_at: row.get(9)?,
+        recurrence: row
+            .get::<_, Option<String>>(10)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        snoozed_from: row.get(11)?,
     })
 }
 
-/// List all reminders
+/// Due date to store for a timestamp, or for a phrase like "tomorrow 9am"
+/// read relative to now in the local timezone. Stored due dates are always
+/// UTC RFC3339, so they compare and sort as text.
+fn resolve_due_date(value: &str) -> Result<String> {
+    let due = match recurrence::parse_timestamp(value) {
+        Some(due) => due,
+        None => natural_date::parse(value, Local::now())
+            .map(|due| due.with_timezone(&Utc))
+            .ok_or_else(|| natural_date::not_understood(value))?,
+    };
+    Ok(recurrence::format_timestamp(due))
+}
+
+/// A timestamp in the stored UTC form, or unchanged if it can't be read
+pub fn normalize_timestamp(value: &str) -> String {
+    recurrence::parse_timestamp(value)
+        .map(recurrence::format_timestamp)
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Rewrite due dates stored with an offset or in SQLite's format by older
+/// versions into UTC RFC3339
+pub fn normalize_stored_dates(conn: &Connection) -> Result<usize> {
+    let rows = conn
+        .prepare(
+            "SELECT id, due_date, snoozed_from FROM reminders
+             WHERE due_date NOT GLOB ?1 OR snoozed_from NOT GLOB ?1",
+        )?
+        .query_map(params![UTC_TIMESTAMP_GLOB], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut rewritten = 0;
+    for (id, due_date, snoozed_from) in &rows {
+        let normalized = (normalize_timestamp(due_date), snoozed_from.as_deref().map(normalize_timestamp));
+        // Dates that can't be read stay as they are
+        if normalized == (due_date.clone(), snoozed_from.clone()) {
+            continue;
+        }
+        conn.execute(
+            "UPDATE reminders SET due_date = ?, snoozed_from = ? WHERE id = ?",
+            params![normalized.0, normalized.1, id],
+        )?;
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
+
+pub const UTC_TIMESTAMP_GLOB: &str = "[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]Z";
+
+/// Widest UTC offset in use, UTC+14:00
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Validate a rule and anchor it at `due_date` unless it names its own start
+fn prepare_recurrence(rule: Option<Recurrence>, due_date: &str) -> Result<Option<String>> {
+    let Some(mut rule) = rule else {
+        return Ok(None);
+    };
+    recurrence::validate(&rule)?;
+    if rule.start.is_none() {
+        rule.start = Some(due_date.to_string());
+    }
+    Ok(Some(serde_json::to_string(&rule).unwrap()))
+}
+
+/// Where a recurring reminder's series starts
+fn series_start(reminder: &Reminder, rule: &Recurrence) -> Option<DateTime<Utc>> {
+    rule.start
+        .as_deref()
+        .and_then(recurrence::parse_timestamp)
+        .or_else(|| recurrence::parse_timestamp(&reminder.due_date))
+}
+
+/// List reminders; completed ones only with `include_completed`
 #[tauri::command]
-pub fn list_reminders(db: State<'_, Database>) -> Result<Vec<Reminder>> {
+pub fn list_reminders(db: State<'_, Database>, include_completed: Option<bool>) -> Result<Vec<Reminder>> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
-         FROM reminders WHERE deleted_at IS NULL
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
+         FROM reminders WHERE deleted_at IS NULL AND (? OR completed = 0)
          ORDER BY due_date ASC",
     )?;
 
     let reminders = stmt
-        .query_map([], row_to_reminder)?
+        .query_map(params![include_completed.unwrap_or(false)], row_to_reminder)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(reminders)
@@ -44,7 +132,8 @@ pub fn get_reminders_by_note(db: State<'_, Database>, note_id: String) -> Result
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
          FROM reminders WHERE note_id = ? AND deleted_at IS NULL
          ORDER BY due_date ASC",
     )?;
@@ -56,88 +145,304 @@ pub fn get_reminders_by_note(db: State<'_, Database>, note_id: String) -> Result
     Ok(reminders)
 }
 
-/// Get upcoming reminders (not completed, due in the next N days)
+/// Reminders whose message contains `query`, case-insensitively; covers
+/// standalone reminders, which note search can't find
+#[tauri::command]
+pub fn search_reminders(db: State<'_, Database>, query: String) -> Result<Vec<Reminder>> {
+    matching_reminders(&db.conn(), &query)
+}
+
+pub fn matching_reminders(conn: &Connection, query: &str) -> Result<Vec<Reminder>> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let pattern = format!("%{}%", query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+
+    let mut stmt = conn.prepare(
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
+         FROM reminders
+         WHERE deleted_at IS NULL AND message LIKE ? ESCAPE '\\'
+         ORDER BY completed ASC, due_date ASC",
+    )?;
+
+    let reminders = stmt
+        .query_map(params![pattern], row_to_reminder)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(reminders)
+}
+
+/// Get the reminders on notes in a notebook
+#[tauri::command]
+pub fn get_reminders_by_notebook(
+    db: State<'_, Database>,
+    notebook_id: String,
+    include_completed: bool,
+) -> Result<Vec<Reminder>> {
+    reminders_by_notebook(&db.conn(), &notebook_id, include_completed)
+}
+
+pub fn reminders_by_notebook(conn: &Connection, notebook_id: &str, include_completed: bool) -> Result<Vec<Reminder>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.id, r.note_id, r.message, r.due_date, r.completed, r.notified, r.revision, r.created_at,
+                r.updated_at, r.deleted_at, r.recurrence, r.snoozed_from
+         FROM reminders r
+         JOIN notes n ON n.id = r.note_id
+         WHERE n.notebook_id = ?
+           AND r.deleted_at IS NULL
+           AND n.deleted_at IS NULL
+           AND (? OR r.completed = 0)
+         ORDER BY r.due_date ASC",
+    )?;
+
+    let reminders = stmt
+        .query_map(params![notebook_id, include_completed], row_to_reminder)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(reminders)
+}
+
+/// Days covered by the agenda
+const AGENDA_DAYS: i32 = 7;
+
+/// Reminders for the next week grouped by notebook, each with its note's
+/// title. Groups are ordered by their earliest reminder.
+#[tauri::command]
+pub fn get_reminder_agenda(db: State<'_, Database>) -> Result<Vec<ReminderAgendaGroup>> {
+    reminder_agenda(&db.conn(), Utc::now())
+}
+
+pub fn reminder_agenda(conn: &Connection, now: DateTime<Utc>) -> Result<Vec<ReminderAgendaGroup>> {
+    let mut note_stmt = conn.prepare(
+        "SELECT n.title, n.notebook_id, nb.name
+         FROM notes n
+         LEFT JOIN notebooks nb ON nb.id = n.notebook_id AND nb.deleted_at IS NULL
+         WHERE n.id = ? AND n.deleted_at IS NULL",
+    )?;
+
+    let mut groups: Vec<ReminderAgendaGroup> = Vec::new();
+    for reminder in upcoming_reminders(conn, AGENDA_DAYS, now)? {
+        let (note_title, notebook_id, notebook_name) = match &reminder.note_id {
+            Some(note_id) => {
+                let Some((raw_title, notebook_id, notebook_name)) = note_stmt
+                    .query_row(params![note_id], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, Option<String>>(1)?,
+                            row.get::<_, Option<String>>(2)?,
+                        ))
+                    })
+                    .optional()?
+                else {
+                    continue;
+                };
+                (Some(crypto::maybe_decrypt(&raw_title).unwrap_or(raw_title)), notebook_id, notebook_name)
+            }
+            None => (None, None, None),
+        };
+
+        let item = AgendaReminder { reminder, note_title };
+        // Reminders arrive sorted by due date, so groups come out in order too
+        match groups.iter_mut().find(|group| group.notebook_id == notebook_id) {
+            Some(group) => group.reminders.push(item),
+            None => groups.push(ReminderAgendaGroup {
+                notebook_id,
+                notebook_name,
+                reminders: vec![item],
+            }),
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Get upcoming reminders (not completed, due in the next N days).
+/// Recurring reminders appear once per occurrence in the window.
 #[tauri::command]
 pub fn get_upcoming_reminders(db: State<'_, Database>, days: Option<i32>) -> Result<Vec<Reminder>> {
-    let conn = db.conn();
-    let days = days.unwrap_or(30);
+    upcoming_reminders(&db.conn(), days.unwrap_or(30), Utc::now())
+}
+
+pub fn upcoming_reminders(conn: &Connection, days: i32, now: DateTime<Utc>) -> Result<Vec<Reminder>> {
+    let end = now + chrono::Duration::days(days.into());
 
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
          FROM reminders
          WHERE deleted_at IS NULL
            AND completed = 0
-           AND due_date >= datetime('now')
-           AND due_date <= datetime('now', ? || ' days')
+           AND (recurrence IS NOT NULL
+                OR (datetime(due_date) >= datetime(?1) AND datetime(due_date) <= datetime(?2)))
          ORDER BY due_date ASC",
     )?;
 
-    let reminders = stmt
-        .query_map(params![days.to_string()], row_to_reminder)?
+    let candidates = stmt
+        .query_map(
+            params![recurrence::format_timestamp(now), recurrence::format_timestamp(end)],
+            row_to_reminder,
+        )?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
+    let mut reminders = Vec::new();
+    for reminder in candidates {
+        let Some(rule) = reminder.recurrence.clone() else {
+            reminders.push(reminder);
+            continue;
+        };
+        let (Some(start), Some(due)) = (
+            series_start(&reminder, &rule),
+            recurrence::parse_timestamp(&reminder.due_date),
+        ) else {
+            continue;
+        };
+        // The pending occurrence, which a snooze moves off the series, then
+        // the series after it, inside the window
+        let pending = (due >= now).then_some(due);
+        let later = recurrence::occurrences(start, &rule).skip_while(|o| *o <= due || *o < now);
+        for occurrence in pending.into_iter().chain(later).take_while(|o| *o <= end) {
+            reminders.push(Reminder {
+                due_date: recurrence::format_timestamp(occurrence),
+                ..reminder.clone()
+            });
+        }
+    }
+
+    reminders.sort_by_key(|r| recurrence::parse_timestamp(&r.due_date));
     Ok(reminders)
 }
 
 /// Get overdue reminders (not completed, past due date)
 #[tauri::command]
 pub fn get_overdue_reminders(db: State<'_, Database>) -> Result<Vec<Reminder>> {
-    let conn = db.conn();
+    overdue_reminders(&db.conn(), Utc::now())
+}
 
+pub fn overdue_reminders(conn: &Connection, now: DateTime<Utc>) -> Result<Vec<Reminder>> {
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
          FROM reminders
          WHERE deleted_at IS NULL
            AND completed = 0
-           AND due_date < datetime('now')
+           AND datetime(due_date) < datetime(?)
          ORDER BY due_date ASC",
     )?;
 
     let reminders = stmt
-        .query_map([], row_to_reminder)?
+        .query_map(params![recurrence::format_timestamp(now)], row_to_reminder)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(reminders)
 }
 
-/// Get today's reminders
+/// Get today's reminders. "Today" is the calendar day at the client's UTC
+/// offset in minutes (e.g. 780 for +13:00), or the system's when omitted.
 #[tauri::command]
-pub fn get_today_reminders(db: State<'_, Database>) -> Result<Vec<Reminder>> {
-    let conn = db.conn();
+pub fn get_today_reminders(db: State<'_, Database>, utc_offset_minutes: Option<i32>) -> Result<Vec<Reminder>> {
+    today_reminders(&db.conn(), Utc::now(), offset_or_local(utc_offset_minutes))
+}
 
+fn offset_or_local(utc_offset_minutes: Option<i32>) -> i32 {
+    utc_offset_minutes.unwrap_or_else(|| Local::now().offset().local_minus_utc() / 60)
+}
+
+/// SQLite date modifier moving a UTC time to the given offset
+fn local_day_shift(utc_offset_minutes: i32) -> Result<String> {
+    if !(-MAX_UTC_OFFSET_MINUTES..=MAX_UTC_OFFSET_MINUTES).contains(&utc_offset_minutes) {
+        return Err(AppError::Validation(format!(
+            "UTC offset must be between -{0} and {0} minutes",
+            MAX_UTC_OFFSET_MINUTES
+        )));
+    }
+    Ok(format!("{:+} minutes", utc_offset_minutes))
+}
+
+pub fn today_reminders(conn: &Connection, now: DateTime<Utc>, utc_offset_minutes: i32) -> Result<Vec<Reminder>> {
+    let shift = local_day_shift(utc_offset_minutes)?;
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
          FROM reminders
          WHERE deleted_at IS NULL
            AND completed = 0
-           AND date(due_date) = date('now')
+           AND date(due_date, ?1) = date(?2, ?1)
          ORDER BY due_date ASC",
     )?;
 
     let reminders = stmt
-        .query_map([], row_to_reminder)?
+        .query_map(params![shift, recurrence::format_timestamp(now)], row_to_reminder)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(reminders)
 }
 
+/// Counts for the reminders dashboard widget. "Today" follows the same
+/// client offset as `get_today_reminders`.
+#[tauri::command]
+pub fn get_reminder_stats(db: State<'_, Database>, utc_offset_minutes: Option<i32>) -> Result<ReminderStats> {
+    reminder_stats(&db.conn(), Utc::now(), offset_or_local(utc_offset_minutes))
+}
+
+pub fn reminder_stats(conn: &Connection, now: DateTime<Utc>, utc_offset_minutes: i32) -> Result<ReminderStats> {
+    let shift = local_day_shift(utc_offset_minutes)?;
+    let week_end = now + chrono::Duration::days(7);
+    let month_ago = now - chrono::Duration::days(30);
+
+    let (overdue, due_today, due_this_week, upcoming, total_active, completed_last_30_days) = conn.query_row(
+        "SELECT
+             COALESCE(SUM(completed = 0 AND datetime(due_date) < datetime(?1)), 0),
+             COALESCE(SUM(completed = 0 AND date(due_date, ?4) = date(?1, ?4)), 0),
+             COALESCE(SUM(completed = 0 AND datetime(due_date) >= datetime(?1)
+                          AND datetime(due_date) <= datetime(?2)), 0),
+             COALESCE(SUM(completed = 0 AND datetime(due_date) >= datetime(?1)), 0),
+             COALESCE(SUM(completed = 0), 0),
+             COALESCE(SUM(completed = 1 AND datetime(updated_at) >= datetime(?3)), 0)
+         FROM reminders
+         WHERE deleted_at IS NULL",
+        params![
+            recurrence::format_timestamp(now),
+            recurrence::format_timestamp(week_end),
+            recurrence::format_timestamp(month_ago),
+            shift
+        ],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+    )?;
+
+    let settled = completed_last_30_days + overdue;
+    Ok(ReminderStats {
+        overdue,
+        due_today,
+        due_this_week,
+        upcoming,
+        completed_last_30_days,
+        total_active,
+        completion_rate: (settled > 0).then(|| completed_last_30_days as f64 / settled as f64),
+    })
+}
+
 /// Get reminders that need notification (due and not yet notified)
 #[tauri::command]
 pub fn get_due_reminders(db: State<'_, Database>) -> Result<Vec<Reminder>> {
-    let conn = db.conn();
+    due_reminders(&db.conn(), Utc::now())
+}
 
+pub fn due_reminders(conn: &Connection, now: DateTime<Utc>) -> Result<Vec<Reminder>> {
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
          FROM reminders
          WHERE deleted_at IS NULL
            AND completed = 0
            AND notified = 0
-           AND due_date <= datetime('now')
+           AND datetime(due_date) <= datetime(?)
          ORDER BY due_date ASC",
     )?;
 
     let reminders = stmt
-        .query_map([], row_to_reminder)?
+        .query_map(params![recurrence::format_timestamp(now)], row_to_reminder)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(reminders)
@@ -149,7 +454,8 @@ pub fn get_reminder(db: State<'_, Database>, id: String) -> Result<Reminder> {
     let conn = db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
          FROM reminders WHERE id = ?",
     )?;
 
@@ -159,26 +465,50 @@ pub fn get_reminder(db: State<'_, Database>, id: String) -> Result<Reminder> {
 
 /// Create a new reminder
 #[tauri::command]
-pub fn create_reminder(db: State<'_, Database>, input: CreateReminderInput) -> Result<Reminder> {
+pub fn create_reminder(
+    app: AppHandle,
+    db: State<'_, Database>,
+    input: CreateReminderInput,
+) -> Result<Reminder> {
     let conn = db.conn();
 
     let id = uuid::Uuid::new_v4().to_string();
     let now = chrono::Utc::now().to_rfc3339();
     let message = input.message.unwrap_or_default();
+    let due_date = resolve_due_date(&input.due_date)?;
+    let recurrence = prepare_recurrence(input.recurrence, &due_date)?;
 
     conn.execute(
-        "INSERT INTO reminders (id, note_id, message, due_date, completed, notified, revision, created_at, updated_at)
-         VALUES (?, ?, ?, ?, 0, 0, 1, ?, ?)",
-        params![id, input.note_id, message, input.due_date, now, now],
+        "INSERT INTO reminders (id, note_id, message, due_date, completed, notified, recurrence, revision, created_at, updated_at)
+         VALUES (?, ?, ?, ?, 0, 0, ?, 1, ?, ?)",
+        params![id, input.note_id, message, due_date, recurrence, now, now],
     )?;
 
     drop(conn);
-    get_reminder(db, id)
+    let reminder = get_reminder(db, id)?;
+    events::emit(&app, ChangeEvent::Reminder, &reminder.id, ChangeKind::Created);
+    Ok(reminder)
+}
+
+/// Read a phrase like "tomorrow 9am" or "in 2 hours" as an RFC3339 due
+/// date, relative to `reference` (RFC3339) or now
+#[tauri::command]
+pub fn parse_due_date(input: String, reference: Option<String>) -> Result<String> {
+    match reference {
+        Some(reference) => {
+            let reference = DateTime::parse_from_rfc3339(&reference).map_err(|_| {
+                AppError::Validation(format!("Reference time must be an RFC3339 timestamp, got '{}'", reference))
+            })?;
+            natural_date::parse_to_rfc3339(&input, reference)
+        }
+        None => natural_date::parse_to_rfc3339(&input, Local::now()),
+    }
 }
 
 /// Update a reminder
 #[tauri::command]
 pub fn update_reminder(
+    app: AppHandle,
     db: State<'_, Database>,
     id: String,
     input: UpdateReminderInput,
@@ -187,7 +517,8 @@ pub fn update_reminder(
     let existing = {
         let conn = db.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at
+            "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
              FROM reminders WHERE id = ?",
         )?;
         stmt.query_row(params![&id], row_to_reminder)
@@ -198,49 +529,208 @@ pub fn update_reminder(
     let new_revision = existing.revision + 1;
 
     let message = input.message.unwrap_or(existing.message);
-    let due_date = input.due_date.unwrap_or(existing.due_date);
     let completed = input.completed.unwrap_or(existing.completed);
     let notified = input.notified.unwrap_or(existing.notified);
+    // Moving the due date re-anchors a kept rule at the new date
+    let rule = match (input.recurrence, &input.due_date) {
+        (Some(rule), _) => rule,
+        (None, Some(_)) => existing.recurrence.map(|rule| Recurrence { start: None, ..rule }),
+        (None, None) => existing.recurrence,
+    };
+    let rescheduled = input.due_date.is_some();
+    let due_date = match input.due_date {
+        Some(due_date) => resolve_due_date(&due_date)?,
+        None => existing.due_date,
+    };
+    let recurrence = prepare_recurrence(rule, &due_date)?;
 
     {
         let conn = db.conn();
         conn.execute(
-            "UPDATE reminders SET message = ?, due_date = ?, completed = ?, notified = ?, revision = ?, updated_at = ?
+            "UPDATE reminders SET message = ?, due_date = ?, completed = ?, notified = ?, recurrence = ?, revision = ?,
+                    needs_push = 1, updated_at = ?, snoozed_from = CASE WHEN ? THEN NULL ELSE snoozed_from END
              WHERE id = ?",
             params![
                 message,
                 due_date,
                 completed as i32,
                 notified as i32,
+                recurrence,
                 new_revision,
                 now,
+                rescheduled,
                 id
             ],
         )?;
     }
 
-    get_reminder(db, id)
+    let reminder = get_reminder(db, id)?;
+    events::emit(&app, ChangeEvent::Reminder, &reminder.id, ChangeKind::Updated);
+    Ok(reminder)
 }
 
-/// Mark a reminder as completed
+/// Mark a reminder as completed. A recurring reminder moves on to its next
+/// occurrence instead, and is only completed once its series has ended.
 #[tauri::command]
-pub fn complete_reminder(db: State<'_, Database>, id: String) -> Result<Reminder> {
-    update_reminder(
-        db,
-        id,
-        UpdateReminderInput {
-            message: None,
-            due_date: None,
-            completed: Some(true),
-            notified: None,
-        },
+pub fn complete_reminder(app: AppHandle, db: State<'_, Database>, id: String) -> Result<Reminder> {
+    complete(&db.conn(), &id, Utc::now())?;
+
+    let reminder = get_reminder(db, id)?;
+    events::emit(&app, ChangeEvent::Reminder, &reminder.id, ChangeKind::Updated);
+    Ok(reminder)
+}
+
+fn load(conn: &Connection, id: &str) -> Result<Reminder> {
+    conn.query_row(
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
+         FROM reminders WHERE id = ?",
+        params![id],
+        row_to_reminder,
     )
+    .map_err(|_| AppError::NotFound(format!("Reminder {} not found", id)))
+}
+
+pub fn complete(conn: &Connection, id: &str, now: DateTime<Utc>) -> Result<()> {
+    let reminder = load(conn, id)?;
+    if reminder.deleted_at.is_some() {
+        return Err(AppError::Validation("Can't complete a deleted reminder".to_string()));
+    }
+    if reminder.completed {
+        return Ok(());
+    }
+
+    // Skip occurrences that went by while the reminder sat overdue
+    let next = reminder.recurrence.as_ref().and_then(|rule| {
+        let start = series_start(&reminder, rule)?;
+        let due = recurrence::parse_timestamp(&reminder.due_date)?;
+        recurrence::next_after(start, rule, due.max(now))
+    });
+
+    let updated_at = now.to_rfc3339();
+    match next {
+        Some(next) => conn.execute(
+            "UPDATE reminders SET due_date = ?, completed = 0, notified = 0, snoozed_from = NULL,
+                    revision = revision + 1, needs_push = 1, updated_at = ?
+             WHERE id = ?",
+            params![recurrence::format_timestamp(next), updated_at, id],
+        )?,
+        None => conn.execute(
+            "UPDATE reminders SET completed = 1, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+            params![updated_at, id],
+        )?,
+    };
+    Ok(())
+}
+
+/// Mark a completed reminder as open again. `reset_notified` (default true)
+/// lets it fire again if it's still due.
+#[tauri::command]
+pub fn uncomplete_reminder(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    reset_notified: Option<bool>,
+) -> Result<Reminder> {
+    uncomplete(&db.conn(), &id, reset_notified.unwrap_or(true), Utc::now())?;
+
+    let reminder = get_reminder(db, id)?;
+    events::emit(&app, ChangeEvent::Reminder, &reminder.id, ChangeKind::Updated);
+    Ok(reminder)
+}
+
+pub fn uncomplete(conn: &Connection, id: &str, reset_notified: bool, now: DateTime<Utc>) -> Result<()> {
+    let reminder = load(conn, id)?;
+    if reminder.deleted_at.is_some() {
+        return Err(AppError::Validation("Can't reopen a deleted reminder".to_string()));
+    }
+    if !reminder.completed {
+        return Ok(());
+    }
+
+    conn.execute(
+        "UPDATE reminders SET completed = 0, notified = CASE WHEN ? THEN 0 ELSE notified END,
+                revision = revision + 1, needs_push = 1, updated_at = ?
+         WHERE id = ?",
+        params![reset_notified, now.to_rfc3339(), id],
+    )?;
+    Ok(())
+}
+
+/// Complete an open reminder or reopen a completed one
+#[tauri::command]
+pub fn toggle_reminder_completed(app: AppHandle, db: State<'_, Database>, id: String) -> Result<Reminder> {
+    {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        toggle_completed(&tx, &id, Utc::now())?;
+        tx.commit()?;
+    }
+
+    let reminder = get_reminder(db, id)?;
+    events::emit(&app, ChangeEvent::Reminder, &reminder.id, ChangeKind::Updated);
+    Ok(reminder)
+}
+
+pub fn toggle_completed(conn: &Connection, id: &str, now: DateTime<Utc>) -> Result<()> {
+    let reminder = load(conn, id)?;
+    if reminder.deleted_at.is_some() {
+        return Err(AppError::Validation("Can't change a deleted reminder".to_string()));
+    }
+    if reminder.completed {
+        uncomplete(conn, id, true, now)
+    } else {
+        complete(conn, id, now)
+    }
+}
+
+/// Longest accepted snooze: one year
+const MAX_SNOOZE_MINUTES: i64 = 365 * 24 * 60;
+
+/// Push a reminder back by `minutes` from now, or from its due date if that's later
+#[tauri::command]
+pub fn snooze_reminder(app: AppHandle, db: State<'_, Database>, id: String, minutes: i64) -> Result<Reminder> {
+    snooze(&db.conn(), &id, minutes, Utc::now())?;
+
+    let reminder = get_reminder(db, id)?;
+    events::emit(&app, ChangeEvent::Reminder, &reminder.id, ChangeKind::Updated);
+    Ok(reminder)
+}
+
+pub fn snooze(conn: &Connection, id: &str, minutes: i64, now: DateTime<Utc>) -> Result<()> {
+    if !(1..=MAX_SNOOZE_MINUTES).contains(&minutes) {
+        return Err(AppError::Validation(format!(
+            "Snooze must be between 1 and {} minutes",
+            MAX_SNOOZE_MINUTES
+        )));
+    }
+
+    let reminder = load(conn, id)?;
+    if reminder.deleted_at.is_some() {
+        return Err(AppError::Validation("Can't snooze a deleted reminder".to_string()));
+    }
+    if reminder.completed {
+        return Err(AppError::Validation("Can't snooze a completed reminder".to_string()));
+    }
+
+    let due = recurrence::parse_timestamp(&reminder.due_date).unwrap_or(now);
+    let snoozed_until = due.max(now) + chrono::Duration::minutes(minutes);
+
+    // Repeated snoozes keep the due date from before the first one
+    conn.execute(
+        "UPDATE reminders SET snoozed_from = COALESCE(snoozed_from, due_date), due_date = ?, notified = 0,
+                revision = revision + 1, needs_push = 1, updated_at = ?
+         WHERE id = ?",
+        params![recurrence::format_timestamp(snoozed_until), now.to_rfc3339(), id],
+    )?;
+    Ok(())
 }
 
 /// Mark a reminder as notified
 #[tauri::command]
-pub fn mark_reminder_notified(db: State<'_, Database>, id: String) -> Result<Reminder> {
+pub fn mark_reminder_notified(app: AppHandle, db: State<'_, Database>, id: String) -> Result<Reminder> {
     update_reminder(
+        app,
         db,
         id,
         UpdateReminderInput {
@@ -248,38 +738,845 @@ pub fn mark_reminder_notified(db: State<'_, Database>, id: String) -> Result<Rem
             due_date: None,
             completed: None,
             notified: Some(true),
+            recurrence: None,
         },
     )
 }
 
+/// Hard-delete a reminder inside the caller's transaction, leaving a
+/// tombstone so the deletion syncs
+pub fn purge_reminder_with_tombstone(conn: &Connection, id: &str) -> Result<()> {
+    let revision: Option<i64> = conn
+        .query_row("SELECT revision FROM reminders WHERE id = ?", params![id], |row| row.get(0))
+        .optional()?;
+    if let Some(revision) = revision {
+        conn.execute("DELETE FROM reminders WHERE id = ?", params![id])?;
+        sync::record_deletion(conn, "reminder", id, revision + 1)?;
+    }
+    Ok(())
+}
+
+/// Hard-delete completed reminders last touched more than `older_than_days`
+/// ago, or every completed reminder when omitted. Returns how many went.
+#[tauri::command]
+pub fn purge_completed_reminders(
+    app: AppHandle,
+    db: State<'_, Database>,
+    older_than_days: Option<i64>,
+) -> Result<usize> {
+    let purged = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let purged = purge_completed(&tx, older_than_days, Utc::now())?;
+        tx.commit()?;
+        purged
+    };
+
+    for id in &purged {
+        events::emit(&app, ChangeEvent::Reminder, id, ChangeKind::Deleted);
+    }
+    Ok(purged.len())
+}
+
+pub fn purge_completed(conn: &Connection, older_than_days: Option<i64>, now: DateTime<Utc>) -> Result<Vec<String>> {
+    if older_than_days.is_some_and(|days| days < 0) {
+        return Err(AppError::Validation("older_than_days can't be negative".to_string()));
+    }
+
+    let ids: Vec<String> = conn
+        .prepare(
+            "SELECT id FROM reminders
+             WHERE completed = 1 AND (?2 IS NULL OR julianday(?1) - julianday(updated_at) >= ?2)",
+        )?
+        .query_map(params![recurrence::format_timestamp(now), older_than_days], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for id in &ids {
+        purge_reminder_with_tombstone(conn, id)?;
+    }
+    Ok(ids)
+}
+
 /// Delete a reminder (soft delete)
 #[tauri::command]
-pub fn delete_reminder(db: State<'_, Database>, id: String, hard: Option<bool>) -> Result<()> {
-    let conn = db.conn();
+pub fn delete_reminder(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    hard: Option<bool>,
+) -> Result<()> {
+    let mut conn = db.conn();
 
     if hard.unwrap_or(false) {
-        conn.execute("DELETE FROM reminders WHERE id = ?", params![id])?;
+        let tx = conn.transaction()?;
+        purge_reminder_with_tombstone(&tx, &id)?;
+        tx.commit()?;
     } else {
         let now = chrono::Utc::now().to_rfc3339();
         conn.execute(
-            "UPDATE reminders SET deleted_at = ?, revision = revision + 1, updated_at = ? WHERE id = ?",
+            "UPDATE reminders SET deleted_at = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
             params![now, now, id],
         )?;
     }
 
+    events::emit(&app, ChangeEvent::Reminder, &id, ChangeKind::Deleted);
     Ok(())
 }
 
 /// Delete all reminders for a note
 #[tauri::command]
-pub fn delete_note_reminders(db: State<'_, Database>, note_id: String) -> Result<()> {
+pub fn delete_note_reminders(app: AppHandle, db: State<'_, Database>, note_id: String) -> Result<()> {
     let conn = db.conn();
     let now = chrono::Utc::now().to_rfc3339();
 
+    let ids: Vec<String> = conn
+        .prepare("SELECT id FROM reminders WHERE note_id = ? AND deleted_at IS NULL")?
+        .query_map(params![&note_id], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
     conn.execute(
-        "UPDATE reminders SET deleted_at = ?, revision = revision + 1, updated_at = ? WHERE note_id = ? AND deleted_at IS NULL",
+        "UPDATE reminders SET deleted_at = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE note_id = ? AND deleted_at IS NULL",
         params![now, now, note_id],
     )?;
 
+    for id in &ids {
+        events::emit(&app, ChangeEvent::Reminder, id, ChangeKind::Deleted);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RecurrenceFrequency;
+    use crate::test_support::{allow_writes, fail_writes};
+
+    fn at(value: &str) -> DateTime<Utc> {
+        recurrence::parse_timestamp(value).unwrap()
+    }
+
+    fn reminder(conn: &Connection, due_date: &str, rule: Option<Recurrence>) -> String {
+        let note_id = uuid::Uuid::new_v4().to_string();
+        conn.execute("INSERT INTO notes (id) VALUES (?)", params![note_id]).unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO reminders (id, note_id, due_date, recurrence) VALUES (?, ?, ?, ?)",
+            params![id, note_id, due_date, prepare_recurrence(rule, due_date).unwrap()],
+        )
+        .unwrap();
+        id
+    }
+
+    fn due_and_completed(conn: &Connection, id: &str) -> (String, bool) {
+        conn.query_row("SELECT due_date, completed FROM reminders WHERE id = ?", params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap()
+    }
+
+    fn monthly() -> Option<Recurrence> {
+        Some(Recurrence {
+            frequency: RecurrenceFrequency::Monthly,
+            interval: 1,
+            until: None,
+            start: None,
+        })
+    }
+
+    #[test]
+    fn test_completing_recurring_reminder_rolls_over_month_end() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = reminder(&conn, "2025-01-31T09:00:00Z", monthly());
+
+        complete(&conn, &id, at("2025-01-31T10:00:00Z")).unwrap();
+        assert_eq!(due_and_completed(&conn, &id), ("2025-02-28T09:00:00Z".to_string(), false));
+
+        complete(&conn, &id, at("2025-02-28T10:00:00Z")).unwrap();
+        assert_eq!(due_and_completed(&conn, &id), ("2025-03-31T09:00:00Z".to_string(), false));
+
+        // Long overdue: lands on the next occurrence after now
+        complete(&conn, &id, at("2025-07-15T00:00:00Z")).unwrap();
+        assert_eq!(due_and_completed(&conn, &id).0, "2025-07-31T09:00:00Z");
+
+        let once = reminder(&conn, "2025-01-31T09:00:00Z", None);
+        complete(&conn, &once, at("2025-02-01T00:00:00Z")).unwrap();
+        assert!(due_and_completed(&conn, &once).1);
+    }
+
+    fn revision(conn: &Connection, id: &str) -> i64 {
+        conn.query_row("SELECT revision FROM reminders WHERE id = ?", params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_completing_the_last_occurrence_closes_the_series() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let until = monthly().map(|rule| Recurrence { until: Some("2025-02-28T09:00:00Z".to_string()), ..rule });
+        let id = reminder(&conn, "2025-01-31T09:00:00Z", until);
+
+        complete(&conn, &id, at("2025-01-31T10:00:00Z")).unwrap();
+        assert_eq!(due_and_completed(&conn, &id), ("2025-02-28T09:00:00Z".to_string(), false));
+        complete(&conn, &id, at("2025-02-28T10:00:00Z")).unwrap();
+        assert_eq!(due_and_completed(&conn, &id), ("2025-02-28T09:00:00Z".to_string(), true));
+        assert_eq!(revision(&conn, &id), 3);
+
+        // Completing again changes nothing
+        complete(&conn, &id, at("2025-03-01T00:00:00Z")).unwrap();
+        assert_eq!(revision(&conn, &id), 3);
+    }
+
+    #[test]
+    fn test_completing_a_snoozed_occurrence_moves_on_to_the_next() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = reminder(&conn, "2025-01-31T09:00:00Z", monthly());
+        snooze(&conn, &id, 60, at("2025-01-31T09:30:00Z")).unwrap();
+        complete(&conn, &id, at("2025-01-31T11:00:00Z")).unwrap();
+        assert_eq!(due_and_completed(&conn, &id).0, "2025-02-28T09:00:00Z");
+    }
+
+    #[test]
+    fn test_completing_deleted_or_missing_reminders_is_refused() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let deleted = reminder(&conn, "2025-01-31T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET deleted_at = 'then' WHERE id = ?", params![deleted]).unwrap();
+        assert!(matches!(complete(&conn, &deleted, Utc::now()), Err(AppError::Validation(_))));
+        assert!(!due_and_completed(&conn, &deleted).1);
+        assert!(matches!(complete(&conn, "nope", Utc::now()), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_a_zero_interval_is_refused() {
+        let zero = Recurrence { interval: 0, ..monthly().unwrap() };
+        assert!(matches!(prepare_recurrence(Some(zero), "2025-01-31T09:00:00Z"), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_upcoming_expands_occurrences_in_window() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let weekly = Recurrence {
+            frequency: RecurrenceFrequency::Weekly,
+            interval: 1,
+            until: None,
+            start: None,
+        };
+        let review = reminder(&conn, "2025-03-03T09:00:00Z", Some(weekly));
+        let once = reminder(&conn, "2025-03-05T12:00:00Z", None);
+        reminder(&conn, "2025-06-01T12:00:00Z", None);
+
+        let upcoming = upcoming_reminders(&conn, 14, at("2025-03-01T00:00:00Z")).unwrap();
+        let got: Vec<(&str, &str)> = upcoming.iter().map(|r| (r.id.as_str(), r.due_date.as_str())).collect();
+        assert_eq!(
+            got,
+            vec![
+                (review.as_str(), "2025-03-03T09:00:00Z"),
+                (once.as_str(), "2025-03-05T12:00:00Z"),
+                (review.as_str(), "2025-03-10T09:00:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upcoming_lists_a_snoozed_occurrence() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let daily = Recurrence {
+            frequency: RecurrenceFrequency::Daily,
+            interval: 1,
+            until: None,
+            start: None,
+        };
+        let id = reminder(&conn, "2025-03-03T09:00:00Z", Some(daily));
+        snooze(&conn, &id, 30, at("2025-03-03T09:00:00Z")).unwrap();
+        let due_dates = |now: &str| -> Vec<String> {
+            upcoming_reminders(&conn, 2, at(now)).unwrap().into_iter().map(|r| r.due_date).collect()
+        };
+
+        // The snoozed time comes first, up to the moment it is due, followed
+        // by the series as it was
+        for now in ["2025-03-03T09:10:00Z", "2025-03-03T09:30:00Z"] {
+            assert_eq!(
+                due_dates(now),
+                vec!["2025-03-03T09:30:00Z", "2025-03-04T09:00:00Z", "2025-03-05T09:00:00Z"]
+            );
+        }
+
+        // Once it is overdue, only the series is ahead
+        assert_eq!(due_dates("2025-03-03T09:31:00Z"), vec!["2025-03-04T09:00:00Z", "2025-03-05T09:00:00Z"]);
+    }
+
+    #[test]
+    fn test_snooze_reminder() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = reminder(&conn, "2025-03-03T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET notified = 1 WHERE id = ?", params![id]).unwrap();
+        let snoozed = |conn: &Connection| -> (String, Option<String>, bool) {
+            conn.query_row(
+                "SELECT due_date, snoozed_from, notified FROM reminders WHERE id = ?",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap()
+        };
+
+        // Fired late: counted from now
+        snooze(&conn, &id, 60, at("2025-03-03T09:30:00Z")).unwrap();
+        assert_eq!(
+            snoozed(&conn),
+            ("2025-03-03T10:30:00Z".to_string(), Some("2025-03-03T09:00:00Z".to_string()), false)
+        );
+
+        // Snoozed again before it fires: counted from the pending due date
+        snooze(&conn, &id, 15, at("2025-03-03T10:00:00Z")).unwrap();
+        assert_eq!(snoozed(&conn).0, "2025-03-03T10:45:00Z");
+        assert_eq!(snoozed(&conn).1.as_deref(), Some("2025-03-03T09:00:00Z"));
+
+        for bad in [-5, 0, MAX_SNOOZE_MINUTES + 1] {
+            assert!(matches!(snooze(&conn, &id, bad, Utc::now()), Err(AppError::Validation(_))));
+        }
+
+        complete(&conn, &id, at("2025-03-03T11:00:00Z")).unwrap();
+        assert!(matches!(snooze(&conn, &id, 10, Utc::now()), Err(AppError::Validation(_))));
+    }
+
+    fn snoozed_from(conn: &Connection, id: &str) -> Option<String> {
+        conn.query_row("SELECT snoozed_from FROM reminders WHERE id = ?", params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_snooze_accepts_both_ends_of_the_range() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let now = at("2025-03-03T09:00:00Z");
+        let id = reminder(&conn, "2025-03-03T09:00:00Z", None);
+        snooze(&conn, &id, 1, now).unwrap();
+        assert_eq!(due_and_completed(&conn, &id).0, "2025-03-03T09:01:00Z");
+        snooze(&conn, &id, MAX_SNOOZE_MINUTES, now).unwrap();
+        assert_eq!(due_and_completed(&conn, &id).0, "2026-03-03T09:01:00Z");
+    }
+
+    #[test]
+    fn test_snooze_refusals_leave_the_reminder_alone() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let now = at("2025-03-03T09:00:00Z");
+        let deleted = reminder(&conn, "2025-03-03T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET deleted_at = 'then' WHERE id = ?", params![deleted]).unwrap();
+        assert!(matches!(snooze(&conn, &deleted, 10, now), Err(AppError::Validation(_))));
+        assert_eq!(due_and_completed(&conn, &deleted).0, "2025-03-03T09:00:00Z");
+        assert_eq!((snoozed_from(&conn, &deleted), revision(&conn, &deleted)), (None, 1));
+        assert!(matches!(snooze(&conn, "nope", 10, now), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_completing_a_snoozed_recurring_reminder_clears_the_snooze() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = reminder(&conn, "2025-01-31T09:00:00Z", monthly());
+        snooze(&conn, &id, 30, at("2025-01-31T09:10:00Z")).unwrap();
+        complete(&conn, &id, at("2025-01-31T10:00:00Z")).unwrap();
+        assert_eq!(snoozed_from(&conn, &id), None);
+    }
+
+    #[test]
+    fn test_parse_due_date_reference() {
+        let parsed = parse_due_date("in 2 hours".to_string(), Some("2025-06-11T15:20:00+02:00".to_string()));
+        assert_eq!(parsed.unwrap(), "2025-06-11T17:20:00+02:00");
+
+        for reference in ["tomorrow", "2025-06-11 15:20:00", ""] {
+            match parse_due_date("in 2 hours".to_string(), Some(reference.to_string())) {
+                Err(AppError::Validation(message)) => assert!(message.contains(&format!("'{}'", reference)), "{}", message),
+                other => panic!("{:?} accepted as a reference: {:?}", reference, other),
+            }
+        }
+        // The phrase is still checked against now
+        assert!(matches!(parse_due_date("someday".to_string(), None), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_due_dates_are_stored_in_utc() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+
+        assert_eq!(resolve_due_date("2025-06-11T09:00:00+13:00").unwrap(), "2025-06-10T20:00:00Z");
+        assert_eq!(resolve_due_date("2025-06-10 20:00:00").unwrap(), "2025-06-10T20:00:00Z");
+        for value in ["Invalid Date", "", "2030-02-30T00:00:00Z", "2030-01-01T00:00:00+25:00"] {
+            match resolve_due_date(value) {
+                Err(AppError::Validation(message)) => assert!(message.contains(&format!("'{}'", value)), "{}", message),
+                other => panic!("{:?} accepted: {:?}", value, other),
+            }
+        }
+
+        // Rows written by older versions keep their offset until migrated
+        let id = reminder(&conn, "2025-06-11T09:00:00+13:00", None);
+        conn.execute("UPDATE reminders SET snoozed_from = '2025-06-11T08:00:00+13:00' WHERE id = ?", params![id])
+            .unwrap();
+        assert_eq!(normalize_stored_dates(&conn).unwrap(), 1);
+        assert_eq!(normalize_stored_dates(&conn).unwrap(), 0);
+        let snoozed_from: String =
+            conn.query_row("SELECT snoozed_from FROM reminders WHERE id = ?", params![id], |row| row.get(0)).unwrap();
+        assert_eq!(due_and_completed(&conn, &id).0, "2025-06-10T20:00:00Z");
+        assert_eq!(snoozed_from, "2025-06-10T19:00:00Z");
+
+        // 9am in +13:00 is due at 20:30 UTC the day before
+        let now = at("2025-06-10T20:30:00Z");
+        assert_eq!(due_reminders(&conn, now).unwrap().len(), 1);
+        assert_eq!(overdue_reminders(&conn, now).unwrap().len(), 1);
+        assert!(due_reminders(&conn, at("2025-06-10T19:59:00Z")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_today_near_midnight_uses_client_offset() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let late = reminder(&conn, &resolve_due_date("2025-06-12T23:30:00+13:00").unwrap(), None);
+        let early = reminder(&conn, &resolve_due_date("2025-06-12T00:10:00+13:00").unwrap(), None);
+        let yesterday = reminder(&conn, &resolve_due_date("2025-06-11T23:50:00+13:00").unwrap(), None);
+
+        // 01:00 on June 12 in +13:00, still June 11 in UTC
+        let now = at("2025-06-11T12:00:00Z");
+        let ids = |offset| -> Vec<String> {
+            today_reminders(&conn, now, offset).unwrap().into_iter().map(|r| r.id).collect()
+        };
+        assert_eq!(ids(13 * 60), vec![early.clone(), late]);
+        assert_eq!(ids(0), vec![yesterday, early]);
+        // No zone is 20 hours behind UTC
+        assert!(matches!(today_reminders(&conn, now, -20 * 60), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_old_sqlite_format_dates_migrate_and_unreadable_ones_stay() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let sqlite_format = reminder(&conn, "2025-06-10 20:00:00", None);
+        let unreadable = reminder(&conn, "next tuesday-ish", None);
+        let snoozed = reminder(&conn, "2025-06-10T20:00:00Z", None);
+        conn.execute("UPDATE reminders SET snoozed_from = '2025-06-10 19:00:00' WHERE id = ?", params![snoozed])
+            .unwrap();
+
+        // Unreadable rows aren't counted
+        assert_eq!(normalize_stored_dates(&conn).unwrap(), 2);
+        assert_eq!(normalize_stored_dates(&conn).unwrap(), 0);
+        assert_eq!(due_and_completed(&conn, &sqlite_format).0, "2025-06-10T20:00:00Z");
+        assert_eq!(due_and_completed(&conn, &unreadable).0, "next tuesday-ish");
+        assert_eq!(snoozed_from(&conn, &snoozed).as_deref(), Some("2025-06-10T19:00:00Z"));
+    }
+
+    #[test]
+    fn test_today_west_of_utc_is_still_the_day_before() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        // 00:30 on June 12 in -12:00 is June 12 in UTC, but "today" there is still June 11
+        let west = reminder(&conn, &resolve_due_date("2025-06-12T00:30:00-12:00").unwrap(), None);
+        let now = at("2025-06-12T10:00:00Z");
+        let ids = |offset| -> Vec<String> {
+            today_reminders(&conn, now, offset).unwrap().into_iter().map(|r| r.id).collect()
+        };
+        assert!(ids(-12 * 60).is_empty());
+        assert_eq!(ids(0), vec![west]);
+    }
+
+    #[test]
+    fn test_offsets_past_fourteen_hours_are_refused() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let now = at("2025-06-12T10:00:00Z");
+        for offset in [-14 * 60, 14 * 60] {
+            assert!(today_reminders(&conn, now, offset).is_ok());
+            assert!(reminder_stats(&conn, now, offset).is_ok());
+        }
+        for offset in [-14 * 60 - 1, 14 * 60 + 1] {
+            assert!(matches!(today_reminders(&conn, now, offset), Err(AppError::Validation(_))));
+            assert!(matches!(reminder_stats(&conn, now, offset), Err(AppError::Validation(_))));
+        }
+    }
+
+    #[test]
+    fn test_reminders_by_notebook_and_agenda() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        conn.execute_batch(
+            "INSERT INTO notebooks (id, name) VALUES ('work', 'Work'), ('home', 'Home');
+             INSERT INTO notes (id, title, notebook_id) VALUES
+                 ('plan', 'Plan', 'work'), ('gone', 'Gone', 'work'), ('chores', 'Chores', 'home'), ('loose', 'Loose', NULL);
+             UPDATE notes SET deleted_at = '2025-06-01T00:00:00Z' WHERE id = 'gone';
+             INSERT INTO reminders (id, note_id, due_date, completed, deleted_at) VALUES
+                 ('r1', 'plan', '2025-06-12T09:00:00Z', 0, NULL),
+                 ('r2', 'plan', '2025-06-13T09:00:00Z', 1, NULL),
+                 ('r3', 'plan', '2025-06-14T09:00:00Z', 0, '2025-06-01T00:00:00Z'),
+                 ('r4', 'gone', '2025-06-12T10:00:00Z', 0, NULL),
+                 ('r5', 'chores', '2025-06-11T18:00:00Z', 0, NULL),
+                 ('r6', 'loose', '2025-06-15T08:00:00Z', 0, NULL),
+                 ('r7', 'chores', '2025-07-30T08:00:00Z', 0, NULL);",
+        )
+        .unwrap();
+
+        let ids = |include_completed| -> Vec<String> {
+            reminders_by_notebook(&conn, "work", include_completed).unwrap().into_iter().map(|r| r.id).collect()
+        };
+        assert_eq!(ids(false), vec!["r1"]);
+        assert_eq!(ids(true), vec!["r1", "r2"]);
+
+        let agenda = reminder_agenda(&conn, at("2025-06-11T12:00:00Z")).unwrap();
+        let summary: Vec<String> = agenda
+            .iter()
+            .flat_map(|group| {
+                let notebook = group.notebook_name.clone().unwrap_or_default();
+                group
+                    .reminders
+                    .iter()
+                    .map(move |r| format!("{}/{}/{}", notebook, r.note_title.as_deref().unwrap_or_default(), r.reminder.id))
+            })
+            .collect();
+        assert_eq!(summary, vec!["Home/Chores/r5", "Work/Plan/r1", "/Loose/r6"]);
+        assert_eq!(agenda.len(), 3);
+    }
+
+    #[test]
+    fn test_reminders_of_a_missing_notebook_are_none() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        assert!(reminders_by_notebook(&conn, "nowhere", true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_agenda_covers_the_coming_week_only() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let last_moment = reminder(&conn, "2025-06-18T12:00:00Z", None);
+        reminder(&conn, "2025-06-18T12:00:01Z", None);
+        reminder(&conn, "2025-06-11T11:00:00Z", None);
+
+        let agenda = reminder_agenda(&conn, at("2025-06-11T12:00:00Z")).unwrap();
+        let ids: Vec<&str> = agenda[0].reminders.iter().map(|item| item.reminder.id.as_str()).collect();
+        assert_eq!(ids, vec![last_moment.as_str()]);
+    }
+
+    #[test]
+    fn test_agenda_shows_a_recurring_reminder_once_a_day() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let daily = Recurrence {
+            frequency: RecurrenceFrequency::Daily,
+            interval: 1,
+            until: None,
+            start: None,
+        };
+        reminder(&conn, "2025-06-10T08:00:00Z", Some(daily));
+
+        let agenda = reminder_agenda(&conn, at("2025-06-11T12:00:00Z")).unwrap();
+        assert_eq!(agenda[0].reminders.len(), 7);
+    }
+
+    #[test]
+    fn test_agenda_groups_standalone_reminders_with_notes_outside_notebooks() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let loose = reminder(&conn, "2025-06-12T12:00:00Z", None);
+        conn.execute(
+            "INSERT INTO reminders (id, note_id, due_date) VALUES ('solo', NULL, '2025-06-13T12:00:00Z')",
+            [],
+        )
+        .unwrap();
+
+        let agenda = reminder_agenda(&conn, at("2025-06-11T12:00:00Z")).unwrap();
+        assert_eq!(agenda.len(), 1);
+        assert_eq!((agenda[0].notebook_id.as_deref(), agenda[0].notebook_name.as_deref()), (None, None));
+        let ids: Vec<&str> = agenda[0].reminders.iter().map(|item| item.reminder.id.as_str()).collect();
+        assert_eq!(ids, vec![loose.as_str(), "solo"]);
+        assert_eq!(agenda[0].reminders[1].note_title, None);
+    }
+
+    #[test]
+    fn test_purge_completed_reminders() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let open = reminder(&conn, "2025-01-01T09:00:00Z", None);
+        let old = reminder(&conn, "2025-01-01T09:00:00Z", None);
+        let recent = reminder(&conn, "2025-01-01T09:00:00Z", None);
+        conn.execute(
+            "UPDATE reminders SET completed = 1, updated_at = '2025-01-01T10:00:00Z' WHERE id = ?",
+            params![old],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE reminders SET completed = 1, updated_at = '2025-03-25T10:00:00Z' WHERE id = ?",
+            params![recent],
+        )
+        .unwrap();
+
+        let now = at("2025-04-01T00:00:00Z");
+        assert!(matches!(purge_completed(&conn, Some(-1), now), Err(AppError::Validation(_))));
+        assert_eq!(purge_completed(&conn, Some(30), now).unwrap(), vec![old.clone()]);
+        assert_eq!(purge_completed(&conn, None, now).unwrap(), vec![recent]);
+
+        let remaining: Vec<String> = conn
+            .prepare("SELECT id FROM reminders")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(remaining, vec![open]);
+        let tombstoned: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM deleted_entities WHERE entity_type = 'reminder' AND entity_id = ?",
+                params![old],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tombstoned, 1);
+    }
+
+    /// A reminder completed at `updated_at`
+    fn completed_at(conn: &Connection, updated_at: &str) -> String {
+        let id = reminder(conn, "2025-01-01T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET completed = 1, updated_at = ? WHERE id = ?", params![updated_at, id])
+            .unwrap();
+        id
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_a_failed_purge_deletes_nothing_and_leaves_no_tombstones() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        let exactly_30 = completed_at(&conn, "2025-03-02T00:00:00Z");
+        completed_at(&conn, "2025-01-15T08:00:00Z");
+
+        fail_writes(&conn, &format!("DELETE ON reminders WHEN OLD.id = '{}'", exactly_30));
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(purge_completed(&tx, Some(30), at("2025-04-01T00:00:00Z")).is_err());
+        }
+        allow_writes(&conn);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM reminders"), 2);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM deleted_entities"), 0);
+    }
+
+    #[test]
+    fn test_purge_cutoff_is_inclusive_and_reads_old_dates() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let exactly_30 = completed_at(&conn, "2025-03-02T00:00:00Z");
+        completed_at(&conn, "2025-03-02T00:00:01Z");
+        // Older versions wrote SQLite's format
+        let sqlite_format = completed_at(&conn, "2025-01-15 08:00:00");
+
+        let mut purged = purge_completed(&conn, Some(30), at("2025-04-01T00:00:00Z")).unwrap();
+        purged.sort();
+        let mut expected = vec![exactly_30, sqlite_format];
+        expected.sort();
+        assert_eq!(purged, expected);
+    }
+
+    #[test]
+    fn test_purge_of_zero_days_takes_every_completed_reminder() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let now = at("2025-04-01T00:00:00Z");
+        let recent = completed_at(&conn, "2025-03-31T23:59:59Z");
+        reminder(&conn, "2025-01-01T09:00:00Z", None);
+
+        assert_eq!(purge_completed(&conn, Some(0), now).unwrap(), vec![recent]);
+        assert!(purge_completed(&conn, None, now).unwrap().is_empty());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM deleted_entities WHERE entity_type = 'reminder'"), 1);
+    }
+
+    #[test]
+    fn test_reminder_stats_agree_with_today() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        // 01:00 on June 12 in +13:00
+        let now = at("2025-06-11T12:00:00Z");
+        for due in [
+            "2025-06-10T09:00:00Z", // overdue
+            "2025-06-11T11:10:00Z", // overdue, but today in +13:00
+            "2025-06-12T10:30:00Z", // later today in +13:00
+            "2025-06-16T09:00:00Z", // this week
+            "2025-08-01T09:00:00Z", // later
+        ] {
+            reminder(&conn, due, None);
+        }
+        let done = reminder(&conn, "2025-06-01T09:00:00Z", None);
+        let long_done = reminder(&conn, "2025-03-01T09:00:00Z", None);
+        let deleted = reminder(&conn, "2025-06-10T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET completed = 1, updated_at = '2025-06-02T00:00:00Z' WHERE id = ?", params![done])
+            .unwrap();
+        conn.execute(
+            "UPDATE reminders SET completed = 1, updated_at = '2025-03-02T00:00:00Z' WHERE id = ?",
+            params![long_done],
+        )
+        .unwrap();
+        conn.execute("UPDATE reminders SET deleted_at = '2025-06-10T10:00:00Z' WHERE id = ?", params![deleted])
+            .unwrap();
+
+        let stats = reminder_stats(&conn, now, 13 * 60).unwrap();
+        assert_eq!(
+            (stats.overdue, stats.due_today, stats.due_this_week, stats.upcoming, stats.total_active),
+            (2, 2, 2, 3, 5)
+        );
+        assert_eq!(stats.due_today, today_reminders(&conn, now, 13 * 60).unwrap().len() as i64);
+        assert_eq!(stats.completed_last_30_days, 1);
+        assert_eq!(stats.completion_rate, Some(1.0 / 3.0));
+
+        assert_eq!(reminder_stats(&conn, now, 0).unwrap().due_today, 1);
+        assert_eq!(reminder_stats(&Database::in_memory().conn(), now, 0).unwrap().completion_rate, None);
+    }
+
+    #[test]
+    fn test_stats_count_due_now_as_upcoming_and_end_the_week_at_seven_days() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        for due in ["2025-06-11T12:00:00Z", "2025-06-18T12:00:00Z", "2025-06-18T12:00:01Z"] {
+            reminder(&conn, due, None);
+        }
+        let stats = reminder_stats(&conn, at("2025-06-11T12:00:00Z"), 0).unwrap();
+        assert_eq!((stats.overdue, stats.due_this_week, stats.upcoming, stats.total_active), (0, 2, 3, 3));
+        assert_eq!(stats.completion_rate, None);
+    }
+
+    #[test]
+    fn test_stats_with_only_completed_reminders() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        // One completed exactly 30 days ago, one deleted since
+        completed_at(&conn, "2025-05-12T12:00:00Z");
+        let deleted = completed_at(&conn, "2025-06-01T00:00:00Z");
+        conn.execute("UPDATE reminders SET deleted_at = '2025-06-02T00:00:00Z' WHERE id = ?", params![deleted])
+            .unwrap();
+        let stats = reminder_stats(&conn, at("2025-06-11T12:00:00Z"), 0).unwrap();
+        assert_eq!((stats.completed_last_30_days, stats.total_active), (1, 0));
+        assert_eq!(stats.completion_rate, Some(1.0));
+    }
+
+    #[test]
+    fn test_stats_with_only_overdue_reminders() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        reminder(&conn, "2025-06-01T09:00:00Z", None);
+        let stats = reminder_stats(&conn, at("2025-06-11T12:00:00Z"), 0).unwrap();
+        assert_eq!((stats.overdue, stats.due_today, stats.upcoming), (1, 0, 0));
+        assert_eq!(stats.completion_rate, Some(0.0));
+    }
+
+    #[test]
+    fn test_toggle_and_uncomplete() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = reminder(&conn, "2025-06-10T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET notified = 1 WHERE id = ?", params![id]).unwrap();
+        let now = at("2025-06-11T12:00:00Z");
+        let notified = |conn: &Connection| -> bool {
+            conn.query_row("SELECT notified FROM reminders WHERE id = ?", params![id], |row| row.get(0)).unwrap()
+        };
+
+        toggle_completed(&conn, &id, now).unwrap();
+        assert!(due_and_completed(&conn, &id).1);
+        toggle_completed(&conn, &id, now).unwrap();
+        assert!(!due_and_completed(&conn, &id).1);
+        // Reopened and still overdue, so it fires again
+        assert!(!notified(&conn));
+        assert_eq!(due_reminders(&conn, now).unwrap().len(), 1);
+
+        conn.execute("UPDATE reminders SET completed = 1, notified = 1 WHERE id = ?", params![id]).unwrap();
+        uncomplete(&conn, &id, false, now).unwrap();
+        assert!(!due_and_completed(&conn, &id).1);
+        assert!(notified(&conn));
+
+        conn.execute("UPDATE reminders SET deleted_at = ? WHERE id = ?", params!["2025-06-11T00:00:00Z", id])
+            .unwrap();
+        assert!(matches!(toggle_completed(&conn, &id, now), Err(AppError::Validation(_))));
+        assert!(matches!(uncomplete(&conn, &id, true, now), Err(AppError::Validation(_))));
+        assert!(matches!(toggle_completed(&conn, "missing", now), Err(AppError::NotFound(_))));
+    }
+
+    fn needs_push(conn: &Connection, id: &str) -> bool {
+        conn.query_row("SELECT needs_push FROM reminders WHERE id = ?", params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_reopening_an_open_reminder_changes_nothing() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = reminder(&conn, "2025-06-10T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET needs_push = 0", []).unwrap();
+        uncomplete(&conn, &id, true, at("2025-06-11T12:00:00Z")).unwrap();
+        assert_eq!((revision(&conn, &id), needs_push(&conn, &id)), (1, false));
+        assert!(matches!(uncomplete(&conn, "missing", true, Utc::now()), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_each_toggle_is_a_new_revision_to_push() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let now = at("2025-06-11T12:00:00Z");
+        let id = reminder(&conn, "2025-06-10T09:00:00Z", None);
+        conn.execute("UPDATE reminders SET needs_push = 0", []).unwrap();
+
+        toggle_completed(&conn, &id, now).unwrap();
+        assert_eq!((revision(&conn, &id), needs_push(&conn, &id)), (2, true));
+        toggle_completed(&conn, &id, now).unwrap();
+        assert_eq!(revision(&conn, &id), 3);
+    }
+
+    #[test]
+    fn test_toggling_a_recurring_reminder_moves_it_on_instead_of_completing_it() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let now = at("2025-06-11T12:00:00Z");
+        let id = reminder(&conn, "2025-06-10T09:00:00Z", monthly());
+        toggle_completed(&conn, &id, now).unwrap();
+        assert_eq!(due_and_completed(&conn, &id), ("2025-07-10T09:00:00Z".to_string(), false));
+        toggle_completed(&conn, &id, now).unwrap();
+        assert_eq!(due_and_completed(&conn, &id), ("2025-08-10T09:00:00Z".to_string(), false));
+    }
+
+    #[test]
+    fn test_standalone_reminders() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let attached = reminder(&conn, "2025-06-12T09:00:00Z", None);
+        conn.execute(
+            "INSERT INTO reminders (id, note_id, message, due_date) VALUES ('passport', NULL, 'Renew 100% passport', ?)",
+            params!["2025-06-13T09:00:00Z"],
+        )
+        .unwrap();
+        let now = at("2025-06-11T12:00:00Z");
+
+        let upcoming: Vec<String> = upcoming_reminders(&conn, 7, now).unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(upcoming, vec![attached.clone(), "passport".to_string()]);
+        assert_eq!(reminder_stats(&conn, now, 0).unwrap().total_active, 2);
+        let found = matching_reminders(&conn, "100% PASS").unwrap();
+        assert_eq!((found.len(), found[0].note_id.clone()), (1, None));
+        assert!(matching_reminders(&conn, "0_%").unwrap().is_empty());
+
+        let agenda = reminder_agenda(&conn, now).unwrap();
+        let standalone = agenda.iter().find(|group| group.notebook_id.is_none()).unwrap();
+        assert_eq!(standalone.reminders.len(), 2);
+        assert_eq!(standalone.reminders[1].note_title, None);
+
+        // Deleting a note only takes its own reminders with it
+        conn.execute("DELETE FROM notes", []).unwrap();
+        let left: Vec<String> = conn
+            .prepare("SELECT id FROM reminders")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(left, vec!["passport"]);
+    }
+}
@@ -1,11 +1,19 @@
-use rusqlite::params;
-use tauri::State;
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, State};
 
 use crate::db::Database;
 use crate::error::{AppError, Result};
-use crate::models::{CreateTagInput, Tag, UpdateTagInput};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::models::{
+    CreateTagInput, CreateTagsResult, SkippedTag, Tag, TagSuggestions, TagWithCount,
+    UpdateTagInput,
+};
+use crate::sync;
+use crate::validation;
 
-fn row_to_tag(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+pub fn row_to_tag(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
     Ok(Tag {
         id: row.get(0)?,
         name: row.get(1)?,
@@ -33,6 +41,67 @@ pub fn list_tags(db: State<'_, Database>) -> Result<Vec<Tag>> {
     Ok(tags)
 }
 
+/// Every live tag with its usage count; unused tags come back with zero
+pub fn tags_with_counts(conn: &Connection) -> Result<Vec<TagWithCount>> {
+    query_tag_counts(conn, "t.name")
+}
+
+/// Live tags with the number of non-trashed notes using them, in `order_by`
+fn query_tag_counts(conn: &Connection, order_by: &str) -> Result<Vec<TagWithCount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT t.id, t.name, t.color, t.revision, t.created_at, t.updated_at, t.deleted_at,
+                COALESCE(usage.note_count, 0) AS note_count
+         FROM tags t
+         LEFT JOIN (
+             SELECT nt.tag_id, COUNT(*) AS note_count
+             FROM note_tags nt JOIN notes n ON n.id = nt.note_id
+             WHERE n.deleted_at IS NULL AND n.status != 'trashed'
+             GROUP BY nt.tag_id
+         ) usage ON usage.tag_id = t.id
+         WHERE t.deleted_at IS NULL
+         ORDER BY {}",
+        order_by
+    ))?;
+
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(TagWithCount {
+                tag: row_to_tag(row)?,
+                note_count: row.get(7)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(tags)
+}
+
+#[tauri::command]
+pub fn list_tags_with_counts(db: State<'_, Database>) -> Result<Vec<TagWithCount>> {
+    tags_with_counts(&db.conn())
+}
+
+const DEFAULT_SUGGESTION_LIMIT: i64 = 10;
+
+#[tauri::command]
+pub fn suggest_tags(db: State<'_, Database>, prefix: String, limit: Option<i64>) -> Result<TagSuggestions> {
+    tag_suggestions(&db.conn(), &prefix, limit.unwrap_or(DEFAULT_SUGGESTION_LIMIT))
+}
+
+/// Tags starting with `prefix` (ignoring case), most used first
+pub fn tag_suggestions(conn: &Connection, prefix: &str, limit: i64) -> Result<TagSuggestions> {
+    // Matched here rather than with LIKE, which only folds ASCII case
+    let prefix = prefix.trim().to_lowercase();
+    let mut tags: Vec<TagWithCount> = query_tag_counts(conn, "note_count DESC, t.name")?
+        .into_iter()
+        .filter(|t| t.tag.name.to_lowercase().starts_with(&prefix))
+        .collect();
+
+    let exact_match = !prefix.is_empty() && tags.iter().any(|t| t.tag.name.to_lowercase() == prefix);
+    tags.truncate(limit.max(0) as usize);
+
+    Ok(TagSuggestions { tags, exact_match })
+}
+
 #[tauri::command]
 pub fn get_tag(db: State<'_, Database>, id: String) -> Result<Tag> {
     let conn = db.conn();
@@ -63,55 +132,184 @@ pub fn get_tag_by_name(db: State<'_, Database>, name: String) -> Result<Option<T
 }
 
 #[tauri::command]
-pub fn create_tag(db: State<'_, Database>, input: CreateTagInput) -> Result<Tag> {
-    // Check if tag with same name exists
-    {
-        let conn = db.conn();
-        let mut stmt = conn.prepare(
-            "SELECT id FROM tags WHERE name = ? AND deleted_at IS NULL",
-        )?;
-        if stmt.exists(params![&input.name])? {
-            return Err(AppError::Conflict(format!(
-                "Tag '{}' already exists",
-                input.name
-            )));
-        }
+pub fn create_tag(app: AppHandle, db: State<'_, Database>, input: CreateTagInput) -> Result<Tag> {
+    let id = insert_tag(&db.conn(), input)?;
+
+    let tag = get_tag(db, id)?;
+    events::emit(&app, ChangeEvent::Tag, &tag.id, ChangeKind::Created);
+    Ok(tag)
+}
+
+/// Trimmed tag name, rejecting empty and malformed names
+fn clean_tag_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Tag name can't be empty".to_string()));
     }
+    validation::tag_name(name)?;
+    Ok(name.to_string())
+}
 
-    let id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
+/// Validate and insert a new tag, returning its id
+pub fn insert_tag(conn: &Connection, input: CreateTagInput) -> Result<String> {
+    let name = clean_tag_name(&input.name)?;
 
-    {
-        let conn = db.conn();
-        conn.execute(
-            "INSERT INTO tags (id, name, color, revision, created_at, updated_at)
-             VALUES (?, ?, ?, 1, ?, ?)",
-            params![id, input.name, input.color, now, now],
-        )?;
+    check_name_free(conn, &name, None)?;
+    write_tag(conn, input.id, &name, input.color)
+}
+
+/// Fail unless `name` is free for a tag other than `except`. Soft-deleted
+/// tags still hold their name under the UNIQUE constraint.
+fn check_name_free(conn: &Connection, name: &str, except: Option<&str>) -> Result<()> {
+    let holder: Option<bool> = conn
+        .query_row(
+            "SELECT deleted_at IS NOT NULL FROM tags WHERE name = ? AND id IS NOT ?",
+            params![name, except],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match holder {
+        Some(false) => Err(AppError::Conflict(format!("Tag '{}' already exists", name))),
+        Some(true) => Err(AppError::Validation(format!("A deleted tag is still named '{}'", name))),
+        None => Ok(()),
+    }
+}
+
+/// Insert a tag row; tags created without a color get one from the palette
+fn write_tag(conn: &Connection, id: Option<String>, name: &str, color: Option<String>) -> Result<String> {
+    if let Some(color) = &color {
+        validation::color(color)?;
     }
+    let color = color.unwrap_or_else(|| validation::palette_color_for(name).to_string());
 
-    get_tag(db, id)
+    let now = chrono::Utc::now().to_rfc3339();
+    let id = validation::new_entity_id(conn, "tags", id)?;
+    conn.execute(
+        "INSERT INTO tags (id, name, color, revision, created_at, updated_at)
+         VALUES (?, ?, ?, 1, ?, ?)",
+        params![id, name, color, now, now],
+    )?;
+
+    Ok(id)
 }
 
+/// Named colors accepted for tags and notebooks, for the color pickers
 #[tauri::command]
-pub fn find_or_create_tag(db: State<'_, Database>, name: String, color: Option<String>) -> Result<Tag> {
-    // Check if exists
+pub fn get_color_palette() -> Vec<String> {
+    validation::COLOR_PALETTE.iter().map(|c| c.to_string()).collect()
+}
+
+#[tauri::command]
+pub fn create_tags(
+    app: AppHandle,
+    db: State<'_, Database>,
+    inputs: Vec<CreateTagInput>,
+) -> Result<CreateTagsResult> {
+    let result = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let result = insert_tags(&tx, inputs)?;
+        tx.commit()?;
+        result
+    };
+
+    for tag in &result.created {
+        events::emit(&app, ChangeEvent::Tag, &tag.id, ChangeKind::Created);
+    }
+    Ok(result)
+}
+
+/// Insert every input whose name is valid and not taken, either by an
+/// existing tag or by an earlier input. Everything else is reported as
+/// skipped rather than failing the whole call.
+pub fn insert_tags(conn: &Connection, inputs: Vec<CreateTagInput>) -> Result<CreateTagsResult> {
+    // Soft-deleted tags still hold their name under the UNIQUE constraint
+    let mut live: HashSet<String> = HashSet::new();
+    let mut deleted: HashSet<String> = HashSet::new();
     {
-        let conn = db.conn();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, color, revision, created_at, updated_at, deleted_at
-             FROM tags WHERE name = ? AND deleted_at IS NULL",
-        )?;
-        if let Ok(tag) = stmt.query_row(params![&name], row_to_tag) {
-            return Ok(tag);
+        let mut stmt = conn.prepare("SELECT name, deleted_at IS NOT NULL FROM tags")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)))?;
+        for row in rows {
+            let (name, is_deleted) = row?;
+            if is_deleted {
+                deleted.insert(name);
+            } else {
+                live.insert(name);
+            }
+        }
+    }
+
+    let mut result = CreateTagsResult::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut ids = Vec::new();
+    for input in inputs {
+        let skip = |reason: String| SkippedTag { name: input.name.clone(), reason };
+        let name = match clean_tag_name(&input.name) {
+            Ok(name) => name,
+            Err(e) => {
+                result.skipped.push(skip(e.to_string()));
+                continue;
+            }
+        };
+        if live.contains(&name) {
+            result.skipped.push(skip(format!("Tag '{}' already exists", name)));
+            continue;
+        }
+        if deleted.contains(&name) {
+            result.skipped.push(skip(format!("A deleted tag is still named '{}'", name)));
+            continue;
         }
+        if !seen.insert(name.clone()) {
+            result.skipped.push(skip(format!("Tag '{}' appears more than once", name)));
+            continue;
+        }
+
+        match write_tag(conn, input.id.clone(), &name, input.color.clone()) {
+            Ok(id) => ids.push(id),
+            Err(e @ (AppError::Validation(_) | AppError::Conflict(_))) => {
+                result.skipped.push(skip(e.to_string()));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, name, color, revision, created_at, updated_at, deleted_at
+         FROM tags WHERE id = ?",
+    )?;
+    for id in ids {
+        result.created.push(stmt.query_row(params![id], row_to_tag)?);
     }
 
-    create_tag(db, CreateTagInput { name, color })
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn find_or_create_tag(
+    app: AppHandle,
+    db: State<'_, Database>,
+    name: String,
+    color: Option<String>,
+) -> Result<Tag> {
+    let found = find_tag_reviving(&db.conn(), &name)?;
+    let Some((id, revived)) = found else {
+        return create_tag(app, db, CreateTagInput { id: None, name, color });
+    };
+
+    let tag = get_tag(db, id)?;
+    if revived {
+        events::emit(&app, ChangeEvent::Tag, &tag.id, ChangeKind::Restored);
+    }
+    Ok(tag)
 }
 
 #[tauri::command]
-pub fn update_tag(db: State<'_, Database>, id: String, input: UpdateTagInput) -> Result<Tag> {
+pub fn update_tag(
+    app: AppHandle,
+    db: State<'_, Database>,
+    id: String,
+    input: UpdateTagInput,
+) -> Result<Tag> {
     let existing = {
         let conn = db.conn();
         let mut stmt = conn.prepare(
@@ -122,41 +320,57 @@ pub fn update_tag(db: State<'_, Database>, id: String, input: UpdateTagInput) ->
             .map_err(|_| AppError::NotFound(format!("Tag {} not found", id)))?
     };
 
+    let changed_notes = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let changed_notes = write_tag_update(&tx, existing, input)?;
+        tx.commit()?;
+        changed_notes
+    };
+
+    let tag = get_tag(db, id)?;
+    for note_id in &changed_notes {
+        events::emit(&app, ChangeEvent::Note, note_id, ChangeKind::Updated);
+    }
+    events::emit(&app, ChangeEvent::Tag, &tag.id, ChangeKind::Updated);
+    Ok(tag)
+}
+
+/// Apply `input` to an existing tag. A rename is carried over to every note
+/// using the old name; returns the ids of the notes that changed.
+pub fn write_tag_update(conn: &Connection, existing: Tag, input: UpdateTagInput) -> Result<Vec<String>> {
+    if let Some(Some(color)) = &input.color {
+        validation::color(color)?;
+    }
+
     // Check name uniqueness if changing name
-    if let Some(ref new_name) = input.name {
+    let new_name = input.name.as_deref().map(clean_tag_name).transpose()?;
+    if let Some(ref new_name) = new_name {
         if new_name != &existing.name {
-            let conn = db.conn();
-            let mut stmt = conn.prepare(
-                "SELECT id FROM tags WHERE name = ? AND deleted_at IS NULL AND id != ?",
-            )?;
-            if stmt.exists(params![new_name, &id])? {
-                return Err(AppError::Conflict(format!(
-                    "Tag '{}' already exists",
-                    new_name
-                )));
-            }
+            check_name_free(conn, new_name, Some(&existing.id))?;
         }
     }
 
     let now = chrono::Utc::now().to_rfc3339();
     let new_revision = existing.revision + 1;
 
-    let name = input.name.unwrap_or(existing.name);
-    let color = input.color.or(existing.color);
+    let name = new_name.unwrap_or_else(|| existing.name.clone());
+    let color = input.color.unwrap_or(existing.color);
 
-    {
-        let conn = db.conn();
-        conn.execute(
-            "UPDATE tags SET name = ?, color = ?, revision = ?, updated_at = ? WHERE id = ?",
-            params![name, color, new_revision, now, id],
-        )?;
-    }
+    conn.execute(
+        "UPDATE tags SET name = ?, color = ?, revision = ?, needs_push = 1, updated_at = ? WHERE id = ?",
+        params![name, color, new_revision, now, existing.id],
+    )?;
 
-    get_tag(db, id)
+    if name == existing.name {
+        return Ok(Vec::new());
+    }
+    // note_tags already points at the renamed row; only the JSON lags behind
+    rewrite_note_tags(conn, &existing.id, |_| {})
 }
 
 #[tauri::command]
-pub fn delete_tag(db: State<'_, Database>, id: String, hard: Option<bool>) -> Result<()> {
+pub fn delete_tag(app: AppHandle, db: State<'_, Database>, id: String, hard: Option<bool>) -> Result<()> {
     let existing = {
         let conn = db.conn();
         let mut stmt = conn.prepare(
@@ -167,66 +381,985 @@ pub fn delete_tag(db: State<'_, Database>, id: String, hard: Option<bool>) -> Re
             .map_err(|_| AppError::NotFound(format!("Tag {} not found", id)))?
     };
 
-    let conn = db.conn();
+    let changed_notes = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let changed_notes = remove_tag(&tx, &existing, hard.unwrap_or(false))?;
+        tx.commit()?;
+        changed_notes
+    };
 
-    // Remove tag from all notes
-    let tag_pattern = format!("\"{}\"", existing.name);
-    conn.execute(
-        "UPDATE notes SET tags = REPLACE(tags, ?, ''), revision = revision + 1, updated_at = datetime('now')
-         WHERE tags LIKE ?",
-        params![tag_pattern, format!("%{}%", tag_pattern)],
-    )?;
+    for note_id in &changed_notes {
+        events::emit(&app, ChangeEvent::Note, note_id, ChangeKind::Updated);
+    }
+    events::emit(&app, ChangeEvent::Tag, &id, ChangeKind::Deleted);
+    Ok(())
+}
+
+/// Take a tag off every note, then soft-delete it, or with `hard` remove it
+/// and leave a tombstone so other devices drop it too. Returns the ids of
+/// the notes that changed.
+pub fn remove_tag(conn: &Connection, existing: &Tag, hard: bool) -> Result<Vec<String>> {
+    let changed_notes = rewrite_note_tags(conn, &existing.id, |tags| {
+        tags.retain(|tag| *tag != existing.name);
+    })?;
+
+    if hard {
+        conn.execute("DELETE FROM tags WHERE id = ?", params![existing.id])?;
+        sync::record_deletion(conn, "tag", &existing.id, existing.revision + 1)?;
+    } else {
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "UPDATE tags SET deleted_at = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+            params![now, now, existing.id],
+        )?;
+    }
+    Ok(changed_notes)
+}
 
-    if hard.unwrap_or(false) {
-        conn.execute("DELETE FROM tags WHERE id = ?", params![id])?;
+/// Soft-delete (or with `dry_run`, just report) tags no live note uses.
+/// Notes in the trash count as users unless `include_trash_references` is
+/// false. Returns the names of the affected tags.
+#[tauri::command]
+pub fn delete_unused_tags(
+    app: AppHandle,
+    db: State<'_, Database>,
+    dry_run: bool,
+    include_trash_references: Option<bool>,
+) -> Result<Vec<String>> {
+    let unused = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let unused = remove_unused_tags(&tx, dry_run, include_trash_references.unwrap_or(true))?;
+        tx.commit()?;
+        unused
+    };
+
+    if !dry_run {
+        for (id, _) in &unused {
+            events::emit(&app, ChangeEvent::Tag, id, ChangeKind::Deleted);
+        }
+    }
+    Ok(unused.into_iter().map(|(_, name)| name).collect())
+}
+
+/// Ids and names of the unused tags, soft-deleted unless `dry_run`
+pub fn remove_unused_tags(
+    conn: &Connection,
+    dry_run: bool,
+    include_trash_references: bool,
+) -> Result<Vec<(String, String)>> {
+    let users = if include_trash_references {
+        "n.deleted_at IS NULL"
     } else {
+        "n.deleted_at IS NULL AND n.status != 'trashed'"
+    };
+    let unused: Vec<(String, String)> = conn
+        .prepare(&format!(
+            "SELECT t.id, t.name FROM tags t
+             WHERE t.deleted_at IS NULL AND NOT EXISTS (
+                 SELECT 1 FROM note_tags nt JOIN notes n ON n.id = nt.note_id
+                 WHERE nt.tag_id = t.id AND {}
+             )
+             ORDER BY t.name",
+            users
+        ))?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if !dry_run {
         let now = chrono::Utc::now().to_rfc3339();
+        for (id, _) in &unused {
+            conn.execute(
+                "UPDATE tags SET deleted_at = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+                params![now, now, id],
+            )?;
+        }
+    }
+    Ok(unused)
+}
+
+/// Swap `from` for `to` in a note's tag list, keeping the first occurrence
+/// when the note already carries `to`
+fn replace_tag(tags: &mut Vec<String>, from: &str, to: &str) {
+    let mut replaced: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags.drain(..) {
+        let tag = if tag == from { to.to_string() } else { tag };
+        if !replaced.contains(&tag) {
+            replaced.push(tag);
+        }
+    }
+    *tags = replaced;
+}
+
+/// Apply `edit` to the tag list (as recorded in note_tags) of every note
+/// linked to `tag_id`, writing the result to the notes' JSON and bumping the
+/// revision only where it differs from what was stored. The note triggers
+/// then bring note_tags in line with the new JSON.
+/// Returns the ids of the notes that changed.
+pub fn rewrite_note_tags(
+    conn: &Connection,
+    tag_id: &str,
+    edit: impl Fn(&mut Vec<String>),
+) -> Result<Vec<String>> {
+    let rows: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT n.id, n.tags FROM notes n
+             JOIN note_tags nt ON nt.note_id = n.id
+             WHERE nt.tag_id = ?",
+        )?
+        .query_map(params![tag_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut changed = Vec::new();
+    for (id, tags_json) in rows {
+        let stored: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+        let mut tags = note_tag_names(conn, &id)?;
+        edit(&mut tags);
+        if tags == stored {
+            continue;
+        }
         conn.execute(
-            "UPDATE tags SET deleted_at = ?, revision = revision + 1, updated_at = ? WHERE id = ?",
-            params![now, now, id],
+            "UPDATE notes SET tags = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+            params![serde_json::to_string(&tags).unwrap(), now, id],
         )?;
+        changed.push(id);
     }
+    Ok(changed)
+}
 
+/// Make sure every name in `names` has a live tag before a note naming them
+/// is written here, creating missing tags and bringing deleted ones back.
+/// The note triggers only link notes to tags that exist. Pulled notes don't
+/// go through this: their tags come with the same pull.
+pub fn ensure_tags(conn: &Connection, names: &[String]) -> Result<()> {
+    for name in names {
+        if find_tag_reviving(conn, name)?.is_none() {
+            write_tag(conn, None, name, None)?;
+        }
+    }
     Ok(())
 }
 
-#[tauri::command]
-pub fn merge_tags(db: State<'_, Database>, source_id: String, target_id: String) -> Result<Tag> {
-    let source = {
-        let conn = db.conn();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, color, revision, created_at, updated_at, deleted_at
-             FROM tags WHERE id = ?",
+/// Id of the tag named `name` and whether it had to be revived, or None when
+/// no tag has the name. A deleted tag keeps its name, so it is brought back
+/// rather than refused.
+pub fn find_tag_reviving(conn: &Connection, name: &str) -> Result<Option<(String, bool)>> {
+    let existing: Option<(String, bool)> = conn
+        .query_row(
+            "SELECT id, deleted_at IS NOT NULL FROM tags WHERE name = ?",
+            params![name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    if let Some((id, true)) = &existing {
+        conn.execute(
+            "UPDATE tags SET deleted_at = NULL, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+            params![chrono::Utc::now().to_rfc3339(), id],
         )?;
-        stmt.query_row(params![&source_id], row_to_tag)
-            .map_err(|_| AppError::NotFound(format!("Tag {} not found", source_id)))?
+    }
+    Ok(existing)
+}
+
+/// A note's tag names from note_tags, in the order the note lists them
+pub fn note_tag_names(conn: &Connection, note_id: &str) -> Result<Vec<String>> {
+    let names = conn
+        .prepare(
+            "SELECT t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id
+             WHERE nt.note_id = ? ORDER BY nt.position",
+        )?
+        .query_map(params![note_id], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(names)
+}
+
+/// Rebuild note_tags from every note's JSON tag list, the way the note
+/// triggers do on write. Used once for databases created before the table.
+pub fn rebuild_note_tags_index(conn: &Connection) -> Result<usize> {
+    conn.execute("DELETE FROM note_tags", [])?;
+
+    let rows: Vec<(String, String)> = conn
+        .prepare("SELECT id, tags FROM notes")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for (note_id, tags_json) in rows {
+        let values: Vec<serde_json::Value> = serde_json::from_str(&tags_json).unwrap_or_default();
+        let names: Vec<String> = values.iter().filter_map(|value| value.as_str()).map(str::to_string).collect();
+        ensure_tags(conn, &names)?;
+        for (position, name) in names.iter().enumerate() {
+            let tag_id: String =
+                conn.query_row("SELECT id FROM tags WHERE name = ?", params![name], |row| row.get(0))?;
+            conn.execute(
+                "INSERT OR IGNORE INTO note_tags (note_id, tag_id, position) VALUES (?, ?, ?)",
+                params![note_id, tag_id, position as i64],
+            )?;
+        }
+    }
+
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM note_tags", [], |row| row.get(0))?;
+    Ok(count as usize)
+}
+
+#[tauri::command]
+pub fn merge_tags(
+    app: AppHandle,
+    db: State<'_, Database>,
+    source_id: String,
+    target_id: String,
+) -> Result<Tag> {
+    let changed_notes = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let changed_notes = merge_tag_into(&tx, &source_id, &target_id)?;
+        tx.commit()?;
+        changed_notes
     };
 
-    let _target = {
-        let conn = db.conn();
-        let mut stmt = conn.prepare(
+    let tag = get_tag(db, target_id)?;
+    for note_id in &changed_notes {
+        events::emit(&app, ChangeEvent::Note, note_id, ChangeKind::Updated);
+    }
+    events::emit(&app, ChangeEvent::Tag, &source_id, ChangeKind::Deleted);
+    events::emit(&app, ChangeEvent::Tag, &tag.id, ChangeKind::Updated);
+    Ok(tag)
+}
+
+/// Replace the source tag with the target on every note (without
+/// duplicating the target) and soft-delete the source so the merge syncs.
+/// Returns the ids of the notes that changed.
+pub fn merge_tag_into(conn: &Connection, source_id: &str, target_id: &str) -> Result<Vec<String>> {
+    if source_id == target_id {
+        return Err(AppError::Validation("Can't merge a tag into itself".to_string()));
+    }
+    let tag_by_id = |id: &str| {
+        conn.query_row(
             "SELECT id, name, color, revision, created_at, updated_at, deleted_at
-             FROM tags WHERE id = ?",
-        )?;
-        stmt.query_row(params![&target_id], row_to_tag)
-            .map_err(|_| AppError::NotFound(format!("Tag {} not found", target_id)))?
+             FROM tags WHERE id = ? AND deleted_at IS NULL",
+            params![id],
+            row_to_tag,
+        )
+        .map_err(|_| AppError::NotFound(format!("Tag {} not found", id)))
     };
+    let source = tag_by_id(source_id)?;
+    let target = tag_by_id(target_id)?;
 
-    // Replace source tag with target tag in all notes
-    let source_pattern = format!("\"{}\"", source.name);
-    let target_pattern = format!("\"{}\"", _target.name);
+    let changed_notes =
+        rewrite_note_tags(conn, &source.id, |tags| replace_tag(tags, &source.name, &target.name))?;
 
-    {
-        let conn = db.conn();
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE tags SET deleted_at = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+        params![now, now, source_id],
+    )?;
+
+    Ok(changed_notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{allow_writes, fail_writes, tag_by_id, tag_by_name};
+
+    /// A note written the way the app writes one, creating its tags
+    fn note_with_tags(conn: &Connection, tags: &[&str]) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        ensure_tags(conn, &tags.iter().map(|t| t.to_string()).collect::<Vec<_>>()).unwrap();
         conn.execute(
-            "UPDATE notes SET tags = REPLACE(tags, ?, ?), revision = revision + 1, updated_at = datetime('now')
-             WHERE tags LIKE ?",
-            params![source_pattern, target_pattern, format!("%{}%", source_pattern)],
-        )?;
+            "INSERT INTO notes (id, tags) VALUES (?, ?)",
+            params![id, serde_json::to_string(tags).unwrap()],
+        )
+        .unwrap();
+        id
+    }
+
+    fn tags_of(conn: &Connection, id: &str) -> (Vec<String>, i64) {
+        let (json, revision): (String, i64) = conn
+            .query_row("SELECT tags, revision FROM notes WHERE id = ?", params![id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        (serde_json::from_str(&json).expect("tags column must stay valid JSON"), revision)
+    }
+
+    #[test]
+    fn test_removing_a_tag_keeps_valid_json() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let start = note_with_tags(&conn, &["work", "a", "b"]);
+        let middle = note_with_tags(&conn, &["a", "work", "b"]);
+        let end = note_with_tags(&conn, &["a", "b", "work"]);
+        let only = note_with_tags(&conn, &["work"]);
+        let similar = note_with_tags(&conn, &["homework", "work-life", "workout"]);
+
+        let work = tag_id(&conn, "work");
+        let changed = rewrite_note_tags(&conn, &work, |tags| tags.retain(|t| t != "work")).unwrap();
+        assert_eq!(changed.len(), 4);
+
+        for id in [&start, &middle, &end] {
+            assert_eq!(tags_of(&conn, id), (vec!["a".to_string(), "b".to_string()], 2));
+        }
+        assert_eq!(tags_of(&conn, &only), (Vec::<String>::new(), 2));
+
+        // Tags that merely contain the name are untouched and keep their revision
+        assert_eq!(
+            tags_of(&conn, &similar),
+            (vec!["homework".to_string(), "work-life".to_string(), "workout".to_string()], 1)
+        );
+    }
+
+    #[test]
+    fn test_removing_a_tag_that_cant_be_deleted_keeps_it_on_notes() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        let note = note_with_tags(&conn, &["work", "a", "work"]);
+
+        fail_writes(&conn, "UPDATE OF deleted_at ON tags");
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(remove_tag(&tx, &tag_by_name(&tx, "work"), false).is_err());
+        }
+        allow_writes(&conn);
+        assert_eq!(tags_of(&conn, &note), (vec!["work".to_string(), "a".to_string(), "work".to_string()], 1));
+    }
+
+    #[test]
+    fn test_removing_a_tag_takes_every_copy_from_trashed_notes_too() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let doubled = note_with_tags(&conn, &["work", "a", "work"]);
+        let trashed = note_with_tags(&conn, &["work"]);
+        conn.execute("UPDATE notes SET status = 'trashed' WHERE id = ?", params![trashed]).unwrap();
+
+        let mut changed = remove_tag(&conn, &tag_by_name(&conn, "work"), false).unwrap();
+        changed.sort();
+        let mut expected = vec![doubled.clone(), trashed.clone()];
+        expected.sort();
+        assert_eq!(changed, expected);
+        assert_eq!(tags_of(&conn, &doubled), (vec!["a".to_string()], 2));
+        assert_eq!(tags_of(&conn, &trashed), (Vec::<String>::new(), 2));
+
+        // Removing it again finds no notes
+        assert!(remove_tag(&conn, &tag_by_name(&conn, "work"), false).unwrap().is_empty());
+    }
+
+    fn tag_id(conn: &Connection, name: &str) -> String {
+        conn.query_row("SELECT id FROM tags WHERE name = ?", params![name], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn rename(name: &str) -> UpdateTagInput {
+        UpdateTagInput { name: Some(name.to_string()), color: None }
+    }
+
+    fn tag(conn: &Connection, name: &str) -> String {
+        insert_tag(
+            conn,
+            CreateTagInput {
+                id: None,
+                name: name.to_string(),
+                color: None,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_tags_dedupes_and_soft_deletes_source() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let work = tag(&conn, "work");
+        let job = tag(&conn, "job");
+        tag(&conn, "workout");
+
+        let both = note_with_tags(&conn, &["work", "x", "job"]);
+        let source_only = note_with_tags(&conn, &["a", "work"]);
+        let overlapping = note_with_tags(&conn, &["workout", "homework"]);
+
+        let changed = merge_tag_into(&conn, &work, &job).unwrap();
+        assert_eq!(changed.len(), 2);
+
+        assert_eq!(tags_of(&conn, &both).0, vec!["job", "x"]);
+        assert_eq!(tags_of(&conn, &source_only).0, vec!["a", "job"]);
+        assert_eq!(tags_of(&conn, &overlapping), (vec!["workout".to_string(), "homework".to_string()], 1));
+
+        let deleted_at: Option<String> = conn
+            .query_row("SELECT deleted_at FROM tags WHERE id = ?", params![work], |row| row.get(0))
+            .unwrap();
+        assert!(deleted_at.is_some());
+
+        assert!(matches!(merge_tag_into(&conn, &job, &job), Err(AppError::Validation(_))));
+        assert!(matches!(merge_tag_into(&conn, &work, &job), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_merge_tags_refuses_deleted_and_missing_tags() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let work = tag(&conn, "work");
+        let job = tag(&conn, "job");
+        let gone = tag(&conn, "gone");
+        conn.execute("UPDATE tags SET deleted_at = 'then' WHERE id = ?", params![gone]).unwrap();
+
+        assert!(matches!(merge_tag_into(&conn, &work, &gone), Err(AppError::NotFound(_))));
+        assert!(matches!(merge_tag_into(&conn, &gone, &job), Err(AppError::NotFound(_))));
+        assert!(matches!(merge_tag_into(&conn, "nope", &job), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_merge_tags_rolls_back_when_the_source_cant_be_deleted() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        let work = tag(&conn, "work");
+        let job = tag(&conn, "job");
+        let note = note_with_tags(&conn, &["job", "work"]);
+
+        fail_writes(&conn, &format!("UPDATE OF deleted_at ON tags WHEN NEW.id = '{}'", work));
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(merge_tag_into(&tx, &work, &job).is_err());
+        }
+        allow_writes(&conn);
+        assert_eq!(tags_of(&conn, &note), (vec!["job".to_string(), "work".to_string()], 1));
+    }
+
+    #[test]
+    fn test_merge_into_a_tag_the_note_has_keeps_its_place() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let work = tag(&conn, "work");
+        let job = tag(&conn, "job");
+        let note = note_with_tags(&conn, &["job", "work"]);
+
+        merge_tag_into(&conn, &work, &job).unwrap();
+        assert_eq!(tags_of(&conn, &note), (vec!["job".to_string()], 2));
+        assert_eq!(counts(&conn), vec![("job".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_tag_counts_include_unused_and_skip_trashed() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "work");
+        tag(&conn, "idle");
+        note_with_tags(&conn, &["work"]);
+        note_with_tags(&conn, &["work", "untracked"]);
+        let trashed = note_with_tags(&conn, &["work"]);
+        conn.execute("UPDATE notes SET status = 'trashed' WHERE id = ?", params![trashed]).unwrap();
+
+        // Writing a note creates the tags it names
+        assert_eq!(
+            counts(&conn),
+            vec![("idle".to_string(), 0), ("untracked".to_string(), 1), ("work".to_string(), 2)]
+        );
+    }
+
+    /// Name and note count of every live tag
+    fn counts(conn: &Connection) -> Vec<(String, i64)> {
+        tags_with_counts(conn).unwrap().into_iter().map(|t| (t.tag.name, t.note_count)).collect()
+    }
+
+    #[test]
+    fn test_tag_counts_count_a_doubled_name_once() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        note_with_tags(&conn, &["work", "work"]);
+        assert_eq!(counts(&conn), vec![("work".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_tag_counts_include_archived_notes_but_not_deleted_ones() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let archived = note_with_tags(&conn, &["work"]);
+        conn.execute("UPDATE notes SET status = 'archived' WHERE id = ?", params![archived]).unwrap();
+        let deleted = note_with_tags(&conn, &["work"]);
+        conn.execute("UPDATE notes SET deleted_at = datetime('now') WHERE id = ?", params![deleted]).unwrap();
+        assert_eq!(counts(&conn), vec![("work".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_tag_counts_skip_deleted_tags_and_malformed_tag_lists() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        note_with_tags(&conn, &["gone"]);
+        conn.execute("UPDATE tags SET deleted_at = 'then' WHERE name = 'gone'", []).unwrap();
+        let malformed = note_with_tags(&conn, &["work"]);
+        conn.execute("UPDATE notes SET tags = 'oops' WHERE id = ?", params![malformed]).unwrap();
+        assert_eq!(counts(&conn), vec![("work".to_string(), 0)]);
+    }
+
+    #[test]
+    fn test_rename_tag_carries_over_to_notes() {
+        let _guard = crate::crypto::test_guard();
+        let db = Database::in_memory();
+        let (plain, already_renamed) = {
+            let mut conn = db.conn();
+            let id = tag(&conn, "wrk");
+            let plain = note_with_tags(&conn, &["a", "wrk"]);
+            // A stray duplicate in the stored JSON collapses with the rename
+            let already_renamed = note_with_tags(&conn, &["wrk", "b", "wrk"]);
+            note_with_tags(&conn, &["taken"]);
+
+            // Names used on notes are tags too, so renaming onto one is a conflict
+            let result = write_tag_update(&conn, tag_by_id(&conn, &id), rename("taken"));
+            assert!(matches!(result, Err(AppError::Conflict(_))));
+
+            let tx = conn.transaction().unwrap();
+            assert_eq!(write_tag_update(&tx, tag_by_id(&tx, &id), rename("work")).unwrap().len(), 2);
+            tx.commit().unwrap();
+
+            assert_eq!(tags_of(&conn, &plain), (vec!["a".to_string(), "work".to_string()], 2));
+            assert_eq!(tags_of(&conn, &already_renamed), (vec!["work".to_string(), "b".to_string()], 2));
+            assert_eq!(note_tag_names(&conn, &plain).unwrap(), vec!["a", "work"]);
+            (plain, already_renamed)
+        };
+
+        let by_tag = |name: &str| {
+            let filter = crate::models::ListNotesFilter {
+                tag: Some(name.to_string()),
+                ..Default::default()
+            };
+            let mut ids: Vec<String> = crate::commands::notes::query_notes(&db, filter)
+                .unwrap()
+                .into_iter()
+                .map(|n| n.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let mut expected = vec![plain, already_renamed];
+        expected.sort();
+        assert_eq!(by_tag("work"), expected);
+        assert!(by_tag("wrk").is_empty());
+    }
+
+    #[test]
+    fn test_rename_tag_trims_and_refuses_blank_names() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = tag(&conn, "wrk");
+        let note = note_with_tags(&conn, &["wrk"]);
+
+        for blank in ["", "   "] {
+            let refused = write_tag_update(&conn, tag_by_id(&conn, &id), rename(blank));
+            assert!(matches!(refused, Err(AppError::Validation(_))));
+        }
+        assert_eq!(tag_by_id(&conn, &id).name, "wrk");
+
+        // The padding never reaches the tag or the notes using it
+        write_tag_update(&conn, tag_by_id(&conn, &id), rename(" work ")).unwrap();
+        assert_eq!(tag_by_id(&conn, &id).name, "work");
+        assert_eq!(tags_of(&conn, &note), (vec!["work".to_string()], 2));
+
+        // A padded copy of its own name is no rename at all
+        write_tag_update(&conn, tag_by_id(&conn, &id), rename("work  ")).unwrap();
+        assert_eq!(tags_of(&conn, &note).1, 2);
+    }
+
+    #[test]
+    fn test_create_tags_skips_conflicts_up_front() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "existing");
+        let gone = tag(&conn, "gone");
+        conn.execute("UPDATE tags SET deleted_at = 'now' WHERE id = ?", params![gone]).unwrap();
+
+        let input = |name: &str| CreateTagInput { id: None, name: name.to_string(), color: None };
+        let result = insert_tags(
+            &conn,
+            vec![
+                input("  fresh "),
+                input("existing"),
+                input("fresh"),
+                input("   "),
+                input("gone"),
+                input("bad,name"),
+                input("other"),
+            ],
+        )
+        .unwrap();
+
+        let created: Vec<&str> = result.created.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(created, vec!["fresh", "other"]);
+        let skipped: Vec<&str> = result.skipped.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(skipped, vec!["existing", "fresh", "   ", "gone", "bad,name"]);
+        assert!(result.skipped.iter().all(|s| !s.reason.is_empty()));
+    }
+
+    fn tag_input(name: &str, id: Option<&str>, color: Option<&str>) -> CreateTagInput {
+        CreateTagInput {
+            id: id.map(str::to_string),
+            name: name.to_string(),
+            color: color.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_create_tags_skips_bad_colors_and_reused_ids() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let result = insert_tags(
+            &conn,
+            vec![
+                tag_input("first", Some(&id), None),
+                tag_input("second", Some(&id), None),
+                tag_input("third", None, Some("blueish")),
+                tag_input("fourth", Some("not-a-uuid"), None),
+                tag_input("fifth", None, Some("#00ff88")),
+            ],
+        )
+        .unwrap();
+        let created: Vec<&str> = result.created.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(created, vec!["first", "fifth"]);
+        let skipped: Vec<&str> = result.skipped.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(skipped, vec!["second", "third", "fourth"]);
+    }
+
+    #[test]
+    fn test_create_tags_undoes_earlier_tags_on_a_failed_write() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+
+        fail_writes(&conn, "INSERT ON tags WHEN NEW.name = 'broken'");
+        {
+            let tx = conn.transaction().unwrap();
+            let inputs = vec![tag_input("kept", None, None), tag_input("broken", None, None)];
+            assert!(insert_tags(&tx, inputs).is_err());
+        }
+        allow_writes(&conn);
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM tags", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_suggest_tags_by_usage_then_name() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        for name in ["Rust", "rustacean", "ruby", "rusty", "python"] {
+            tag(&conn, name);
+        }
+        note_with_tags(&conn, &["rusty"]);
+        note_with_tags(&conn, &["rusty", "rustacean"]);
+
+        let found = tag_suggestions(&conn, "RUS", 10).unwrap();
+        assert!(!found.exact_match);
+        assert_eq!(suggested_names(found), vec!["rusty", "rustacean", "Rust"]);
+
+        let found = tag_suggestions(&conn, "rust", 1).unwrap();
+        assert!(found.exact_match);
+        assert_eq!(suggested_names(found), vec!["rusty"]);
+
+        assert!(tag_suggestions(&conn, "go", 10).unwrap().tags.is_empty());
+    }
+
+    fn suggested_names(found: TagSuggestions) -> Vec<String> {
+        found.tags.into_iter().map(|t| t.tag.name).collect()
+    }
+
+    #[test]
+    fn test_suggest_tags_folds_case_beyond_ascii_and_trims_the_prefix() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        for name in ["école", "Ecology", "eager"] {
+            tag(&conn, name);
+        }
+
+        let found = tag_suggestions(&conn, "  ÉCO ", 10).unwrap();
+        assert!(!found.exact_match);
+        assert_eq!(suggested_names(found), vec!["école"]);
+        assert!(tag_suggestions(&conn, "École", 10).unwrap().exact_match);
+    }
+
+    #[test]
+    fn test_suggest_tags_for_an_empty_prefix_lists_live_tags_without_an_exact_match() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        for name in ["école", "Ecology", "eager", "gone"] {
+            tag(&conn, name);
+        }
+        conn.execute("UPDATE tags SET deleted_at = 'then' WHERE name = 'gone'", []).unwrap();
+
+        let all = tag_suggestions(&conn, "", 10).unwrap();
+        assert!(!all.exact_match);
+        assert_eq!(suggested_names(all), vec!["Ecology", "eager", "école"]);
+        assert!(tag_suggestions(&conn, "go", 10).unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn test_suggest_tags_with_no_room_suggests_nothing() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "eager");
+        for limit in [0, -5] {
+            assert!(tag_suggestions(&conn, "e", limit).unwrap().tags.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_note_tags_follow_note_writes() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let gone = tag(&conn, "gone");
+        conn.execute("UPDATE tags SET deleted_at = 'then' WHERE id = ?", params![gone]).unwrap();
+        let tag_state = || -> (Option<String>, i64) {
+            conn.query_row("SELECT deleted_at, revision FROM tags WHERE id = ?", params![gone], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap()
+        };
+
+        let id = note_with_tags(&conn, &["b", "a", "b"]);
+        assert_eq!(note_tag_names(&conn, &id).unwrap(), vec!["b", "a"]);
+
+        // The triggers link existing tags only, deleted ones included, and
+        // never create or revive one
+        conn.execute("UPDATE notes SET tags = '[\"gone\",\"c\"]' WHERE id = ?", params![id]).unwrap();
+        assert_eq!(note_tag_names(&conn, &id).unwrap(), vec!["gone"]);
+        assert_eq!(tag_state(), (Some("then".to_string()), 1));
+        assert!(!conn.prepare("SELECT 1 FROM tags WHERE name = 'c'").unwrap().exists([]).unwrap());
+
+        // A tag showing up later links the notes already naming it
+        tag(&conn, "c");
+        assert_eq!(note_tag_names(&conn, &id).unwrap(), vec!["gone", "c"]);
+
+        ensure_tags(&conn, &["gone".to_string()]).unwrap();
+        let (deleted_at, revision) = tag_state();
+        assert_eq!((deleted_at, revision), (None, 2));
+
+        // Search sees the tag list as stored in note_tags
+        let fts_tags: String = conn
+            .query_row("SELECT tags FROM notes_fts WHERE id = ?", params![id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(fts_tags, r#"["gone","c"]"#);
+
+        // Malformed JSON counts as no tags instead of failing the write
+        conn.execute("UPDATE notes SET tags = 'oops' WHERE id = ?", params![id]).unwrap();
+        assert!(note_tag_names(&conn, &id).unwrap().is_empty());
+
+        conn.execute("DELETE FROM notes WHERE id = ?", params![id]).unwrap();
+        let left: i64 = conn.query_row("SELECT COUNT(*) FROM note_tags", [], |row| row.get(0)).unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[test]
+    fn test_rebuild_note_tags_index_backfills() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let first = note_with_tags(&conn, &["x", "y"]);
+        let second = note_with_tags(&conn, &["y"]);
+        conn.execute("DELETE FROM note_tags", []).unwrap();
+        conn.execute("DELETE FROM tags WHERE name = 'x'", []).unwrap();
+
+        assert_eq!(rebuild_note_tags_index(&conn).unwrap(), 3);
+        assert_eq!(note_tag_names(&conn, &first).unwrap(), vec!["x", "y"]);
+        assert_eq!(note_tag_names(&conn, &second).unwrap(), vec!["y"]);
+    }
+
+    #[test]
+    fn test_hard_delete_leaves_a_tombstone() {
+        let db = Database::in_memory();
+        let (soft, hard, note) = {
+            let conn = db.conn();
+            let soft = tag(&conn, "soft");
+            let hard = tag(&conn, "hard");
+            let note = note_with_tags(&conn, &["hard", "soft"]);
+
+            assert_eq!(remove_tag(&conn, &tag_by_id(&conn, &soft), false).unwrap(), vec![note.clone()]);
+            assert_eq!(remove_tag(&conn, &tag_by_id(&conn, &hard), true).unwrap(), vec![note.clone()]);
+            assert_eq!(tags_of(&conn, &note).0, Vec::<String>::new());
+            (soft, hard, note)
+        };
+
+        let changes = sync::get_changes_since(&db, 0).unwrap();
+        let tombstones: Vec<(&str, &str, i64)> = changes
+            .deleted
+            .iter()
+            .map(|d| (d.entity_type.as_str(), d.entity_id.as_str(), d.revision))
+            .collect();
+        assert_eq!(tombstones, vec![("tag", hard.as_str(), 2)]);
+        // The soft delete syncs as the tag row itself
+        assert!(changes.tags.iter().any(|t| t.id == soft && t.deleted_at.is_some()));
+        assert!(changes.notes.iter().any(|n| n.id == note));
+    }
 
-        // Delete source tag (hard delete since we're merging)
-        conn.execute("DELETE FROM tags WHERE id = ?", params![source_id])?;
+    #[test]
+    fn test_delete_unused_tags() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "idle");
+        note_with_tags(&conn, &["used"]);
+        let trashed = note_with_tags(&conn, &["binned"]);
+        conn.execute("UPDATE notes SET status = 'trashed' WHERE id = ?", params![trashed]).unwrap();
+        let names = |tags: Vec<(String, String)>| tags.into_iter().map(|(_, name)| name).collect::<Vec<_>>();
+
+        assert_eq!(names(remove_unused_tags(&conn, true, false).unwrap()), vec!["binned", "idle"]);
+        assert_eq!(names(remove_unused_tags(&conn, false, true).unwrap()), vec!["idle"]);
+
+        let live: Vec<String> = tags_with_counts(&conn).unwrap().into_iter().map(|t| t.tag.name).collect();
+        assert_eq!(live, vec!["binned", "used"]);
+        assert!(remove_unused_tags(&conn, true, true).unwrap().is_empty());
+    }
+
+    fn tag_states(conn: &Connection) -> Vec<(String, bool, i64)> {
+        conn.prepare("SELECT name, deleted_at IS NOT NULL, revision FROM tags ORDER BY name")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap()
+    }
+
+    fn unused_names(tags: Vec<(String, String)>) -> Vec<String> {
+        tags.into_iter().map(|(_, name)| name).collect()
+    }
+
+    #[test]
+    fn test_a_near_miss_name_doesnt_use_a_tag() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "work");
+        note_with_tags(&conn, &["workout"]);
+        assert_eq!(unused_names(remove_unused_tags(&conn, true, true).unwrap()), vec!["work"]);
+    }
+
+    #[test]
+    fn test_deleted_notes_dont_use_their_tags() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "stale");
+        let deleted = note_with_tags(&conn, &["stale"]);
+        conn.execute("UPDATE notes SET deleted_at = datetime('now') WHERE id = ?", params![deleted]).unwrap();
+        assert_eq!(unused_names(remove_unused_tags(&conn, true, true).unwrap()), vec!["stale"]);
+    }
+
+    #[test]
+    fn test_a_dry_run_of_delete_unused_tags_changes_nothing() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "work");
+        tag(&conn, "stale");
+        let before = tag_states(&conn);
+        assert_eq!(unused_names(remove_unused_tags(&conn, true, true).unwrap()), vec!["stale", "work"]);
+        assert_eq!(tag_states(&conn), before);
+    }
+
+    #[test]
+    fn test_a_failed_delete_unused_tags_rolls_every_tag_back() {
+        let db = Database::in_memory();
+        let mut conn = db.conn();
+        tag(&conn, "stale");
+        tag(&conn, "work");
+        let before = tag_states(&conn);
+
+        // The second tag can't be written
+        fail_writes(&conn, "UPDATE ON tags WHEN NEW.name = 'work'");
+        {
+            let tx = conn.transaction().unwrap();
+            assert!(remove_unused_tags(&tx, false, true).is_err());
+        }
+        allow_writes(&conn);
+        assert_eq!(tag_states(&conn), before);
+    }
+
+    #[test]
+    fn test_delete_unused_tags_soft_deletes_with_a_new_revision() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        tag(&conn, "work");
+        tag(&conn, "stale");
+        note_with_tags(&conn, &["workout"]);
+        remove_unused_tags(&conn, false, true).unwrap();
+        assert_eq!(
+            tag_states(&conn),
+            vec![("stale".to_string(), true, 2), ("work".to_string(), true, 2), ("workout".to_string(), false, 1)]
+        );
+    }
+
+    #[test]
+    fn test_names_of_deleted_tags_are_refused() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let gone = tag(&conn, "gone");
+        let live = tag(&conn, "live");
+        conn.execute("UPDATE tags SET deleted_at = 'then' WHERE id = ?", params![gone]).unwrap();
+        let input = |name: &str| CreateTagInput { id: None, name: name.to_string(), color: None };
+
+        assert!(matches!(insert_tag(&conn, input("gone")), Err(AppError::Validation(_))));
+        assert!(matches!(insert_tag(&conn, input("live")), Err(AppError::Conflict(_))));
+        let refused = write_tag_update(&conn, tag_by_id(&conn, &live), rename("gone"));
+        assert!(matches!(refused, Err(AppError::Validation(_))));
+
+        // Keeping its own name isn't a clash, even once deleted
+        write_tag_update(&conn, tag_by_id(&conn, &gone), rename("gone")).unwrap();
+        write_tag_update(&conn, tag_by_id(&conn, &live), rename("fresh")).unwrap();
+        assert_eq!(tag_by_id(&conn, &live).name, "fresh");
+    }
+
+    #[test]
+    fn test_tag_colors_validated_or_assigned() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let input = |name: &str, color: Option<&str>| CreateTagInput {
+            id: None,
+            name: name.to_string(),
+            color: color.map(str::to_string),
+        };
+
+        assert!(matches!(insert_tag(&conn, input("a", Some("blueish"))), Err(AppError::Validation(_))));
+
+        let id = insert_tag(&conn, input("b", Some("#00ff88"))).unwrap();
+        let color_of = |id: &str| -> Option<String> {
+            conn.query_row("SELECT color FROM tags WHERE id = ?", params![id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(color_of(&id).as_deref(), Some("#00ff88"));
+
+        let id = insert_tag(&conn, input("c", None)).unwrap();
+        assert_eq!(color_of(&id).as_deref(), Some(validation::palette_color_for("c")));
+    }
+
+    #[test]
+    fn test_a_bad_color_refuses_the_whole_tag_update() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = tag(&conn, "work");
+        let before = tag_by_id(&conn, &id);
+        let update = UpdateTagInput {
+            name: Some("renamed".to_string()),
+            color: Some(Some("#fff".to_string())),
+        };
+        assert!(matches!(write_tag_update(&conn, tag_by_id(&conn, &id), update), Err(AppError::Validation(_))));
+        let after = tag_by_id(&conn, &id);
+        assert_eq!((after.name, after.color, after.revision), (before.name, before.color, before.revision));
     }
 
-    get_tag(db, target_id)
+    #[test]
+    fn test_tags_made_by_note_writes_get_a_palette_color() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        note_with_tags(&conn, &["implicit"]);
+        let color = tag_by_name(&conn, "implicit").color;
+        assert_eq!(color.as_deref(), Some(validation::palette_color_for("implicit")));
+    }
+
+    #[test]
+    fn test_update_tag_clears_color() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = tag(&conn, "work");
+        let update = |color: Option<Option<&str>>| UpdateTagInput {
+            name: None,
+            color: color.map(|c| c.map(str::to_string)),
+        };
+
+        write_tag_update(&conn, tag_by_id(&conn, &id), update(Some(Some("#123456")))).unwrap();
+        write_tag_update(&conn, tag_by_id(&conn, &id), update(None)).unwrap();
+        assert_eq!(tag_by_id(&conn, &id).color.as_deref(), Some("#123456"));
+
+        write_tag_update(&conn, tag_by_id(&conn, &id), update(Some(None))).unwrap();
+        assert_eq!(tag_by_id(&conn, &id).color, None);
+    }
 }
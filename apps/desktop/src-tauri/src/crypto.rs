@@ -155,12 +155,21 @@ pub fn maybe_decrypt(content: &str) -> Result<String> {
     }
 }
 
+/// Serializes tests that depend on the global encryption state: the crypto
+/// tests switch it on, which would leak into concurrently running DB tests
+#[cfg(test)]
+pub fn test_guard() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_encrypt_decrypt() {
+        let _guard = test_guard();
         let password = "test_password_123";
         let salt = init_encryption(password, None).unwrap();
 
@@ -185,6 +194,7 @@ mod tests {
 
     #[test]
     fn test_maybe_encrypt_decrypt() {
+        let _guard = test_guard();
         // Without encryption enabled, should pass through
         let text = "Not encrypted";
         assert_eq!(maybe_encrypt(text).unwrap(), text);
@@ -1,9 +1,12 @@
-use rusqlite::Connection;
+use rusqlite::{params, Connection};
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
 
+use crate::commands::{notes, reminders, tags};
 use crate::error::Result;
+use crate::sync;
+use crate::tasks;
 
 pub struct Database {
     conn: Mutex<Connection>,
@@ -20,7 +23,24 @@ impl Database {
 
     pub fn init_schema(&self) -> Result<()> {
         let conn = self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let had_tasks = table_exists(&conn, "note_tasks")?;
+        let had_note_tags = table_exists(&conn, "note_tags")?;
+        // schema.sql won't replace a trigger that already exists, and older
+        // versions of these created and revived tags
+        drop_note_triggers(&conn)?;
         conn.execute_batch(include_str!("schema.sql"))?;
+        migrate(&conn)?;
+        // Due dates written with an offset before they were stored in UTC
+        reminders::normalize_stored_dates(&conn)?;
+
+        // Notes written before tasks were extracted on save
+        if !had_tasks {
+            tasks::rebuild_tasks_index(&conn)?;
+        }
+        // Notes written before tags were normalized
+        if !had_note_tags {
+            tags::rebuild_note_tags_index(&conn)?;
+        }
         Ok(())
     }
 
@@ -29,6 +49,171 @@ impl Database {
     }
 }
 
+#[cfg(test)]
+impl Database {
+    /// Fresh in-memory database with the full schema, for unit tests
+    pub fn in_memory() -> Self {
+        let db = Self::new(PathBuf::from(":memory:")).unwrap();
+        db.init_schema().unwrap();
+        db
+    }
+}
+
+/// Bring databases created by older versions up to date.
+/// schema.sql only creates missing tables, so columns added to existing
+/// tables later on are added here.
+fn migrate(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "notes", "excerpt", "TEXT")?;
+    add_column_if_missing(conn, "notes", "pinned_order", "INTEGER")?;
+    add_column_if_missing(conn, "notes", "archived_by_notebook", "TEXT")?;
+    add_column_if_missing(conn, "notebooks", "is_archived", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "notebooks", "sync_excluded", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "reminders", "recurrence", "TEXT")?;
+    add_column_if_missing(conn, "reminders", "snoozed_from", "TEXT")?;
+    allow_standalone_reminders(conn)?;
+    per_type_sync_cursors(conn)?;
+    push_flags(conn)?;
+    Ok(())
+}
+
+/// Older databases have a single pull/push cursor pair; every entity type
+/// starts from it
+fn per_type_sync_cursors(conn: &Connection) -> Result<()> {
+    let migrated = conn
+        .prepare("SELECT 1 FROM pragma_table_info('sync_state') WHERE name = 'notes_pull_revision'")?
+        .exists([])?;
+    if migrated {
+        return Ok(());
+    }
+
+    for table in sync::CURSOR_TABLES {
+        for direction in ["pull", "push"] {
+            let column = format!("{}_{}_revision", table, direction);
+            add_column_if_missing(conn, "sync_state", &column, "INTEGER NOT NULL DEFAULT 0")?;
+            conn.execute(
+                &format!("UPDATE sync_state SET {} = last_{}_revision", column, direction),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Older databases tell unpushed rows by comparing revisions to the push
+/// cursors; the `needs_push` flags start out from that comparison
+fn push_flags(conn: &Connection) -> Result<()> {
+    let mut missing = Vec::new();
+    for table in sync::CURSOR_TABLES.iter().chain(&["deleted_entities"]) {
+        if !column_exists(conn, table, "needs_push")? {
+            missing.push(*table);
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    // Filling in the flags would rewrite every note's search row through the
+    // note triggers. Drop them meanwhile, then recreate them from schema.sql.
+    drop_note_triggers(conn)?;
+
+    for table in missing {
+        add_column_if_missing(conn, table, "needs_push", "INTEGER NOT NULL DEFAULT 1")?;
+        let cursor = match table {
+            "deleted_entities" => "CASE deleted_entities.entity_type
+                 WHEN 'note' THEN notes_push_revision WHEN 'notebook' THEN notebooks_push_revision
+                 WHEN 'tag' THEN tags_push_revision WHEN 'reminder' THEN reminders_push_revision
+                 ELSE last_push_revision END"
+                .to_string(),
+            _ => format!("{}_push_revision", table),
+        };
+        conn.execute(
+            &format!(
+                "UPDATE {} SET needs_push = revision > (SELECT {} FROM sync_state WHERE id = 1)
+                 WHERE EXISTS (SELECT 1 FROM sync_state WHERE id = 1)",
+                table, cursor
+            ),
+            [],
+        )?;
+    }
+    conn.execute_batch(include_str!("schema.sql"))?;
+    Ok(())
+}
+
+/// Older databases require every reminder to belong to a note. SQLite
+/// can't drop a NOT NULL constraint, so the table is rebuilt.
+fn allow_standalone_reminders(conn: &Connection) -> Result<()> {
+    let note_required: bool = conn.query_row(
+        "SELECT \"notnull\" FROM pragma_table_info('reminders') WHERE name = 'note_id'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !note_required {
+        return Ok(());
+    }
+
+    // Rolled back when dropped, so a failed copy leaves the old table as it was
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "CREATE TABLE reminders_new (
+             id TEXT PRIMARY KEY,
+             note_id TEXT REFERENCES notes(id) ON DELETE CASCADE,
+             message TEXT NOT NULL DEFAULT '',
+             due_date TEXT NOT NULL,
+             completed INTEGER NOT NULL DEFAULT 0,
+             notified INTEGER NOT NULL DEFAULT 0,
+             recurrence TEXT,
+             snoozed_from TEXT,
+             revision INTEGER NOT NULL DEFAULT 1,
+             created_at TEXT NOT NULL DEFAULT (datetime('now')),
+             updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+             deleted_at TEXT
+         );
+         INSERT INTO reminders_new (id, note_id, message, due_date, completed, notified, recurrence, snoozed_from,
+                                    revision, created_at, updated_at, deleted_at)
+             SELECT id, note_id, message, due_date, completed, notified, recurrence, snoozed_from,
+                    revision, created_at, updated_at, deleted_at
+             FROM reminders;
+         DROP TABLE reminders;
+         ALTER TABLE reminders_new RENAME TO reminders;
+         CREATE INDEX idx_reminders_note ON reminders(note_id) WHERE deleted_at IS NULL;
+         CREATE INDEX idx_reminders_due_date ON reminders(due_date) WHERE completed = 0 AND deleted_at IS NULL;
+         CREATE INDEX idx_reminders_revision ON reminders(revision);",
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn drop_note_triggers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DROP TRIGGER IF EXISTS notes_fts_insert;
+         DROP TRIGGER IF EXISTS notes_fts_update;
+         DROP TRIGGER IF EXISTS tags_link_notes;
+         DROP TRIGGER IF EXISTS tags_link_renamed;",
+    )?;
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    let exists = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?")?
+        .exists(params![table])?;
+    Ok(exists)
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?", table))?
+        .exists(params![column])?;
+    Ok(exists)
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub fn get_db(app: &AppHandle) -> tauri::State<'_, Database> {
     app.state::<Database>()
@@ -41,7 +226,160 @@ pub fn init_database(app: &AppHandle) -> std::result::Result<(), Box<dyn std::er
     let db_path = app_dir.join("viny.db");
     let db = Database::new(db_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
     db.init_schema().map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    notes::backfill_excerpts(&db.conn()).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 
     app.manage(db);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migration_allows_standalone_reminders() {
+        // A database from before standalone reminders
+        let db = Database::in_memory();
+        db.conn()
+            .execute_batch(
+                "DROP TABLE reminders;
+                 CREATE TABLE reminders (
+                     id TEXT PRIMARY KEY,
+                     note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+                     message TEXT NOT NULL DEFAULT '',
+                     due_date TEXT NOT NULL,
+                     completed INTEGER NOT NULL DEFAULT 0,
+                     notified INTEGER NOT NULL DEFAULT 0,
+                     revision INTEGER NOT NULL DEFAULT 1,
+                     created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                     updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                     deleted_at TEXT
+                 );
+                 INSERT INTO notes (id) VALUES ('n1');
+                 INSERT INTO reminders (id, note_id, message, due_date) VALUES ('r1', 'n1', 'Kept', '2030-01-01T00:00:00Z');",
+            )
+            .unwrap();
+
+        db.init_schema().unwrap();
+        let conn = db.conn();
+        conn.execute("INSERT INTO reminders (id, note_id, due_date) VALUES ('r2', NULL, '2030-01-01T00:00:00Z')", [])
+            .unwrap();
+        let message: String = conn.query_row("SELECT message FROM reminders WHERE id = 'r1'", [], |row| row.get(0)).unwrap();
+        assert_eq!(message, "Kept");
+
+        // Attached reminders still go with their note
+        conn.execute("DELETE FROM notes WHERE id = 'n1'", []).unwrap();
+        let left: i64 = conn.query_row("SELECT COUNT(*) FROM reminders", [], |row| row.get(0)).unwrap();
+        assert_eq!(left, 1);
+    }
+
+    /// The reminders table from before standalone reminders, holding a row the new one won't take
+    fn reminders_needing_a_note(db: &Database) {
+        db.conn()
+            .execute_batch(
+                "DROP TABLE reminders;
+                 CREATE TABLE reminders (
+                     id TEXT PRIMARY KEY,
+                     note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+                     message TEXT NOT NULL DEFAULT '',
+                     due_date TEXT,
+                     completed INTEGER NOT NULL DEFAULT 0,
+                     notified INTEGER NOT NULL DEFAULT 0,
+                     revision INTEGER NOT NULL DEFAULT 1,
+                     created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                     updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                     deleted_at TEXT
+                 );
+                 INSERT INTO notes (id) VALUES ('n1');
+                 INSERT INTO reminders (id, note_id, due_date) VALUES ('r1', 'n1', '2030-01-01T00:00:00Z');
+                 INSERT INTO reminders (id, note_id, due_date) VALUES ('r2', 'n1', NULL);",
+            )
+            .unwrap();
+    }
+
+    fn reminder_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM reminders", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_standalone_reminder_migration_stopped_halfway_leaves_the_old_table() {
+        let db = Database::in_memory();
+        reminders_needing_a_note(&db);
+        assert!(db.init_schema().is_err());
+
+        let conn = db.conn();
+        assert!(conn.is_autocommit());
+        assert!(!table_exists(&conn, "reminders_new").unwrap());
+        assert_eq!(reminder_count(&conn), 2);
+        let note_required: bool = conn
+            .query_row("SELECT \"notnull\" FROM pragma_table_info('reminders') WHERE name = 'note_id'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(note_required);
+    }
+
+    #[test]
+    fn test_standalone_reminder_migration_goes_through_once_the_row_is_fixed() {
+        let db = Database::in_memory();
+        reminders_needing_a_note(&db);
+        assert!(db.init_schema().is_err());
+        db.conn().execute("UPDATE reminders SET due_date = '2030-01-02T00:00:00Z' WHERE id = 'r2'", []).unwrap();
+        db.init_schema().unwrap();
+        // Running again changes nothing
+        db.init_schema().unwrap();
+
+        let conn = db.conn();
+        assert_eq!(reminder_count(&conn), 2);
+        // Attached reminders still need a note that exists
+        assert!(conn
+            .execute("INSERT INTO reminders (id, note_id, due_date) VALUES ('r3', 'nowhere', '2030-01-01T00:00:00Z')", [])
+            .is_err());
+    }
+
+    #[test]
+    fn test_migration_splits_sync_cursors() {
+        let db = Database::in_memory();
+        db.conn()
+            .execute_batch(
+                "DROP TABLE sync_state;
+                 CREATE TABLE sync_state (
+                     id INTEGER PRIMARY KEY CHECK (id = 1),
+                     last_pull_revision INTEGER NOT NULL DEFAULT 0,
+                     last_push_revision INTEGER NOT NULL DEFAULT 0,
+                     last_synced_at TEXT
+                 );
+                 INSERT INTO sync_state (id, last_pull_revision, last_push_revision) VALUES (1, 12, 8);",
+            )
+            .unwrap();
+        db.init_schema().unwrap();
+
+        let state = sync::get_sync_state(&db).unwrap();
+        assert_eq!(state.pull_cursors, sync::SyncCursors::all(12));
+        assert_eq!(state.push_cursors, sync::SyncCursors::all(8));
+    }
+
+    #[test]
+    fn test_migration_flags_rows_past_push_cursor() {
+        // A database from before push flags, last pushed at revision 8
+        let db = Database::in_memory();
+        let mut batch = String::from("DROP TRIGGER notes_fts_insert; DROP TRIGGER notes_fts_update;");
+        for table in sync::CURSOR_TABLES.iter().chain(&["deleted_entities"]) {
+            batch += &format!("ALTER TABLE {} DROP COLUMN needs_push;", table);
+        }
+        batch += "UPDATE sync_state SET notes_push_revision = 8, tags_push_revision = 8;
+                  INSERT INTO notes (id, revision) VALUES ('pushed', 8), ('edited', 9);
+                  INSERT INTO tags (id, name, revision, deleted_at) VALUES ('t1', 'old', 2, datetime('now'));";
+        db.conn().execute_batch(&batch).unwrap();
+        db.init_schema().unwrap();
+
+        let changes = sync::unpushed_changes(&db).unwrap();
+        let ids: Vec<_> = changes.notes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!((ids, changes.tags.len()), (vec!["edited"], 0));
+
+        // The recreated triggers link a deleted tag without reviving it
+        db.conn().execute("INSERT INTO notes (id, tags) VALUES ('new', '[\"old\"]')", []).unwrap();
+        assert_eq!(tags::note_tag_names(&db.conn(), "new").unwrap(), vec!["old"]);
+        assert_eq!(sync::get_sync_state(&db).unwrap().pending_changes, 2);
+    }
+}
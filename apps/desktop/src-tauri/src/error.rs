@@ -22,8 +22,20 @@ pub enum AppError {
     #[error("Sync error: {0}")]
     Sync(String),
 
+    /// The server refused a push over these entities; nothing was stored
+    #[error("Sync error: server rejected {} pushed entities", .0.len())]
+    PushRejected(Vec<crate::sync::RejectedEntity>),
+
+    /// The server is limiting this device's requests; sync again after the wait
+    #[error("Sync error: the server asked to wait {retry_after_secs}s before syncing again")]
+    RateLimited { retry_after_secs: u64 },
+
     #[error("Encryption error: {0}")]
     Encryption(String),
+
+    /// An op inside `batch_execute` failed; the whole batch was rolled back
+    #[error("Batch operation {index} failed: {source}")]
+    Batch { index: usize, source: Box<AppError> },
 }
 
 impl Serialize for AppError {
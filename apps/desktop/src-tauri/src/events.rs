@@ -0,0 +1,119 @@
+//! Change events
+//!
+//! Every successful write emits an event to all windows so that the main
+//! window, quick capture, etc. stay in sync without a manual refresh:
+//! - `note:changed`, `notebook:changed`, `tag:changed`, `reminder:changed`
+//! - Payload: `EntityChanged { id, kind }`
+//!
+//! `sync:push_queue_drained` (no payload) fires when a sync pushes the last
+//! of the changes that were waiting, and `sync:pulled` (payload `SyncStats`)
+//! when a live update pulled something in the background. A background
+//! sync or live pull that fails emits `sync:failed` (payload `SyncFailed`),
+//! so the UI can show why nothing is syncing.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use ts_rs::TS;
+
+// =============================================================================
+// Types
+// =============================================================================
+
+/// Event names, one per entity type
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub enum ChangeEvent {
+    #[serde(rename = "note:changed")]
+    Note,
+    #[serde(rename = "notebook:changed")]
+    Notebook,
+    #[serde(rename = "tag:changed")]
+    Tag,
+    #[serde(rename = "reminder:changed")]
+    Reminder,
+}
+
+impl ChangeEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Note => "note:changed",
+            Self::Notebook => "notebook:changed",
+            Self::Tag => "tag:changed",
+            Self::Reminder => "reminder:changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+/// Payload of every change event
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct EntityChanged {
+    pub id: String,
+    pub kind: ChangeKind,
+}
+
+/// Payload of `sync:failed`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct SyncFailed {
+    pub error: String,
+    /// Background syncs failed in a row, including this one; 0 for a live
+    /// pull or a rate-limited sync, which don't back off
+    pub failures: u32,
+    /// How long the server asked to wait, when it rate-limited the sync
+    pub retry_after_secs: Option<u64>,
+}
+
+// =============================================================================
+// Emit
+// =============================================================================
+
+/// Every queued local change has reached the server
+pub const PUSH_QUEUE_DRAINED: &str = "sync:push_queue_drained";
+
+/// Changes from another device were pulled without the UI asking
+pub const SYNC_PULLED: &str = "sync:pulled";
+
+/// A background sync or live pull failed
+pub const SYNC_FAILED: &str = "sync:failed";
+
+/// Notify all windows that an entity changed.
+/// The write has already succeeded, so a failed emit is not an error.
+pub fn emit(app: &AppHandle, event: ChangeEvent, id: &str, kind: ChangeKind) {
+    let payload = EntityChanged {
+        id: id.to_string(),
+        kind,
+    };
+    let _ = app.emit(event.as_str(), payload);
+}
+
+pub fn emit_push_queue_drained(app: &AppHandle) {
+    let _ = app.emit(PUSH_QUEUE_DRAINED, ());
+}
+
+pub fn emit_sync_failed(app: &AppHandle, error: &crate::error::AppError, failures: u32) {
+    let retry_after_secs = match error {
+        crate::error::AppError::RateLimited { retry_after_secs } => Some(*retry_after_secs),
+        _ => None,
+    };
+    let payload = SyncFailed {
+        error: error.to_string(),
+        failures,
+        retry_after_secs,
+    };
+    let _ = app.emit(SYNC_FAILED, payload);
+}
+
+pub fn emit_pulled(app: &AppHandle, stats: &crate::sync::SyncStats) {
+    let _ = app.emit(SYNC_PULLED, stats);
+}
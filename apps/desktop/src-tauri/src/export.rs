@@ -1,10 +1,10 @@
 //! Export/Import module
 //!
 //! Provides ZIP-based backup and restore functionality:
-//! - Export: Creates a ZIP with all notes, notebooks, tags as JSON
+//! - Export: Creates a ZIP with all notes, notebooks, tags and reminders as JSON
 //! - Import: Restores data from a ZIP backup
 
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{Read, Write};
@@ -14,9 +14,12 @@ use ts_rs::TS;
 use zip::write::SimpleFileOptions;
 use zip::{ZipArchive, ZipWriter};
 
+use crate::commands::{notebooks, notes, reminders, tags};
 use crate::db::Database;
-use crate::error::Result;
-use crate::models::{Note, NoteStatus, Notebook, Tag};
+use crate::error::{AppError, Result};
+use crate::maintenance::{self, OrphanReport};
+use crate::models::{Note, NoteStatus, Notebook, Reminder, Tag};
+use crate::tasks;
 
 // =============================================================================
 // Types
@@ -30,6 +33,9 @@ pub struct ExportData {
     pub notes: Vec<Note>,
     pub notebooks: Vec<Notebook>,
     pub tags: Vec<Tag>,
+    /// Missing from archives written before version 1.1
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -38,6 +44,8 @@ pub struct ExportStats {
     pub notes: i32,
     pub notebooks: i32,
     pub tags: i32,
+    #[serde(default)]
+    pub reminders: i32,
     pub file_path: String,
 }
 
@@ -50,6 +58,14 @@ pub struct ImportStats {
     pub notes_skipped: i32,
     pub notebooks_skipped: i32,
     pub tags_skipped: i32,
+    pub reminders_imported: i32,
+    /// Already present (without overwrite), or their note is in neither the
+    /// archive nor this database
+    pub reminders_skipped: i32,
+    /// The archive was a notebook export rather than a full backup
+    pub partial: bool,
+    /// Orphaned rows cleaned up after the import
+    pub orphans: OrphanReport,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -63,93 +79,195 @@ pub struct ImportOptions {
 // Export Functions
 // =============================================================================
 
-/// Get all data for export
-fn get_export_data(db: &Database) -> Result<ExportData> {
-    let conn = db.conn();
+const NOTE_COLUMNS: &str =
+    "id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order";
+const NOTEBOOK_COLUMNS: &str =
+    "id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived, sync_excluded";
+const TAG_COLUMNS: &str = "id, name, color, revision, created_at, updated_at, deleted_at";
+const REMINDER_COLUMNS: &str =
+    "id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at, recurrence, snoozed_from";
+
+/// Version written by full exports; 1.1 added reminders
+const EXPORT_VERSION: &str = "1.1";
+/// Suffix marking an export that holds only part of the database
+const PARTIAL_SUFFIX: &str = "+partial";
+
+/// Whether an archive came from `export_notebook` rather than a full export
+fn is_partial(version: &str) -> bool {
+    version.ends_with(PARTIAL_SUFFIX)
+}
 
-    // Get all notes (including soft-deleted for full backup)
-    let mut notes_stmt = conn.prepare(
-        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at
-         FROM notes"
-    )?;
+// Notes are exported as stored (encrypted content stays encrypted)
+fn row_to_export_note(row: &rusqlite::Row) -> rusqlite::Result<Note> {
+    let tags_json: String = row.get(4)?;
+    let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+    let status_str: String = row.get(5)?;
+
+    Ok(Note {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        content: row.get(2)?,
+        notebook_id: row.get(3)?,
+        tags,
+        status: NoteStatus::from_str(&status_str),
+        is_pinned: row.get::<_, i32>(6)? != 0,
+        revision: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        deleted_at: row.get(10)?,
+        pinned_order: row.get(11)?,
+    })
+}
 
-    let notes: Vec<Note> = notes_stmt
-        .query_map([], |row| {
-            let tags_json: String = row.get(4)?;
-            let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            let status_str: String = row.get(5)?;
-
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                content: row.get(2)?,
-                notebook_id: row.get(3)?,
-                tags,
-                status: NoteStatus::from_str(&status_str),
-                is_pinned: row.get::<_, i32>(6)? != 0,
-                revision: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-                deleted_at: row.get(10)?,
-            })
-        })?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
+fn row_to_export_notebook(row: &rusqlite::Row) -> rusqlite::Result<Notebook> {
+    Ok(Notebook {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        icon: row.get(3)?,
+        parent_id: row.get(4)?,
+        revision: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        deleted_at: row.get(8)?,
+        is_archived: row.get::<_, i32>(9)? != 0,
+        sync_excluded: row.get::<_, i32>(10)? != 0,
+    })
+}
 
-    // Get all notebooks
-    let mut notebooks_stmt = conn.prepare(
-        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
-         FROM notebooks"
-    )?;
+fn row_to_export_tag(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        revision: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        deleted_at: row.get(6)?,
+    })
+}
 
-    let notebooks: Vec<Notebook> = notebooks_stmt
-        .query_map([], |row| {
-            Ok(Notebook {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                color: row.get(2)?,
-                icon: row.get(3)?,
-                parent_id: row.get(4)?,
-                revision: row.get(5)?,
-                created_at: row.get(6)?,
-                updated_at: row.get(7)?,
-                deleted_at: row.get(8)?,
-            })
-        })?
+/// Run `SELECT {columns} FROM {table} {tail}` and map every row
+fn query_all<T, P: rusqlite::Params>(
+    conn: &Connection,
+    table: &str,
+    columns: &str,
+    tail: &str,
+    params: P,
+    map: fn(&rusqlite::Row) -> rusqlite::Result<T>,
+) -> Result<Vec<T>> {
+    let sql = format!("SELECT {} FROM {} {}", columns, table, tail);
+    let rows = conn
+        .prepare(&sql)?
+        .query_map(params, map)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
 
-    // Get all tags
-    let mut tags_stmt = conn.prepare(
-        "SELECT id, name, color, revision, created_at, updated_at, deleted_at
-         FROM tags"
-    )?;
+/// Get all data for export
+fn get_export_data(db: &Database) -> Result<ExportData> {
+    let conn = db.conn();
 
-    let tags: Vec<Tag> = tags_stmt
-        .query_map([], |row| {
-            Ok(Tag {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                color: row.get(2)?,
-                revision: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                deleted_at: row.get(6)?,
-            })
-        })?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
+    // Soft-deleted rows are included for a full backup
+    Ok(ExportData {
+        version: EXPORT_VERSION.to_string(),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        notes: query_all(&conn, "notes", NOTE_COLUMNS, "", [], row_to_export_note)?,
+        notebooks: query_all(&conn, "notebooks", NOTEBOOK_COLUMNS, "", [], row_to_export_notebook)?,
+        tags: query_all(&conn, "tags", TAG_COLUMNS, "", [], row_to_export_tag)?,
+        reminders: query_all(&conn, "reminders", REMINDER_COLUMNS, "", [], reminders::row_to_reminder)?,
+    })
+}
+
+/// Data for one notebook (and its descendants when `recursive`): its notes,
+/// the tags those notes use, and their reminders
+fn get_notebook_export_data(db: &Database, notebook_id: &str, recursive: bool) -> Result<ExportData> {
+    let conn = db.conn();
+    let exists = conn
+        .prepare("SELECT 1 FROM notebooks WHERE id = ? AND deleted_at IS NULL")?
+        .exists(params![notebook_id])?;
+    if !exists {
+        return Err(AppError::NotFound(format!("Notebook {} not found", notebook_id)));
+    }
+
+    let scope = notebooks::notebook_scope(&conn, notebook_id, recursive)?;
+    let scope_json = serde_json::to_string(&scope).unwrap();
+    let in_scope = "notebook_id IN (SELECT value FROM json_each(?1))";
+
+    // Parents before children, so the importer can insert them in order
+    let mut notebooks = query_all(
+        &conn,
+        "notebooks",
+        NOTEBOOK_COLUMNS,
+        "WHERE id IN (SELECT value FROM json_each(?1))",
+        params![scope_json],
+        row_to_export_notebook,
+    )?;
+    notebooks.sort_by_key(|nb| scope.iter().position(|id| *id == nb.id));
 
     Ok(ExportData {
-        version: "1.0".to_string(),
+        version: format!("{}{}", EXPORT_VERSION, PARTIAL_SUFFIX),
         exported_at: chrono::Utc::now().to_rfc3339(),
-        notes,
+        notes: query_all(
+            &conn,
+            "notes",
+            NOTE_COLUMNS,
+            &format!("WHERE {}", in_scope),
+            params![scope_json],
+            row_to_export_note,
+        )?,
         notebooks,
-        tags,
+        tags: query_all(
+            &conn,
+            "tags",
+            TAG_COLUMNS,
+            &format!(
+                "WHERE deleted_at IS NULL AND name IN
+                     (SELECT t.value FROM notes, json_each(notes.tags) t WHERE {})",
+                in_scope
+            ),
+            params![scope_json],
+            row_to_export_tag,
+        )?,
+        reminders: query_all(
+            &conn,
+            "reminders",
+            REMINDER_COLUMNS,
+            &format!("WHERE note_id IN (SELECT id FROM notes WHERE {})", in_scope),
+            params![scope_json],
+            reminders::row_to_reminder,
+        )?,
     })
 }
 
+fn export_stats(data: &ExportData, file_path: String) -> ExportStats {
+    ExportStats {
+        notes: data.notes.len() as i32,
+        notebooks: data.notebooks.len() as i32,
+        tags: data.tags.len() as i32,
+        reminders: data.reminders.len() as i32,
+        file_path,
+    }
+}
+
 /// Export all data to a ZIP file
 pub fn export_to_zip(db: &Database, path: PathBuf) -> Result<ExportStats> {
     let data = get_export_data(db)?;
+    write_zip(&data, path)
+}
+
+/// Export one notebook subtree to a ZIP file
+pub fn export_notebook_to_zip(
+    db: &Database,
+    notebook_id: &str,
+    path: PathBuf,
+    recursive: bool,
+) -> Result<ExportStats> {
+    let data = get_notebook_export_data(db, notebook_id, recursive)?;
+    write_zip(&data, path)
+}
 
+fn write_zip(data: &ExportData, path: PathBuf) -> Result<ExportStats> {
     let file = File::create(&path).map_err(|e| crate::error::AppError::Io(e.to_string()))?;
     let mut zip = ZipWriter::new(file);
 
@@ -161,7 +279,7 @@ pub fn export_to_zip(db: &Database, path: PathBuf) -> Result<ExportStats> {
     zip.start_file("data.json", options)
         .map_err(|e| crate::error::AppError::Io(e.to_string()))?;
 
-    let json = serde_json::to_string_pretty(&data)
+    let json = serde_json::to_string_pretty(data)
         .map_err(|e| crate::error::AppError::Io(e.to_string()))?;
 
     zip.write_all(json.as_bytes())
@@ -170,12 +288,7 @@ pub fn export_to_zip(db: &Database, path: PathBuf) -> Result<ExportStats> {
     zip.finish()
         .map_err(|e| crate::error::AppError::Io(e.to_string()))?;
 
-    Ok(ExportStats {
-        notes: data.notes.len() as i32,
-        notebooks: data.notebooks.len() as i32,
-        tags: data.tags.len() as i32,
-        file_path: path.to_string_lossy().to_string(),
-    })
+    Ok(export_stats(data, path.to_string_lossy().to_string()))
 }
 
 // =============================================================================
@@ -201,7 +314,9 @@ pub fn import_from_zip(db: &Database, path: PathBuf, overwrite: bool) -> Result<
     let data: ExportData = serde_json::from_str(&contents)
         .map_err(|e| crate::error::AppError::Io(e.to_string()))?;
 
-    let conn = db.conn();
+    // All or nothing, so a failure halfway doesn't leave half a restore
+    let mut guard = db.conn();
+    let conn = guard.transaction()?;
     let mut stats = ImportStats {
         notes_imported: 0,
         notebooks_imported: 0,
@@ -209,6 +324,10 @@ pub fn import_from_zip(db: &Database, path: PathBuf, overwrite: bool) -> Result<
         notes_skipped: 0,
         notebooks_skipped: 0,
         tags_skipped: 0,
+        reminders_imported: 0,
+        reminders_skipped: 0,
+        partial: is_partial(&data.version),
+        orphans: OrphanReport::default(),
     };
 
     // Import notebooks first (notes reference them)
@@ -226,19 +345,48 @@ pub fn import_from_zip(db: &Database, path: PathBuf, overwrite: bool) -> Result<
             continue;
         }
 
+        // A partial export's top notebook may have a parent this database
+        // doesn't know; it lands at the root instead
+        let mut parent_id = notebook.parent_id.clone();
+        if stats.partial {
+            if let Some(parent) = &parent_id {
+                let known = data.notebooks.iter().any(|nb| nb.id == *parent)
+                    || conn.prepare("SELECT 1 FROM notebooks WHERE id = ?")?.exists(params![parent])?;
+                if !known {
+                    parent_id = None;
+                }
+            }
+        }
+
+        // Upsert rather than replace, which would take local notes and
+        // sub-notebooks out of the old row
         conn.execute(
-            "INSERT OR REPLACE INTO notebooks (id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO notebooks (id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived, sync_excluded)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 color = excluded.color,
+                 icon = excluded.icon,
+                 parent_id = excluded.parent_id,
+                 revision = excluded.revision,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 deleted_at = excluded.deleted_at,
+                 is_archived = excluded.is_archived,
+                 sync_excluded = excluded.sync_excluded,
+                 needs_push = 1",
             params![
                 notebook.id,
                 notebook.name,
                 notebook.color,
                 notebook.icon,
-                notebook.parent_id,
+                parent_id,
                 notebook.revision,
                 notebook.created_at,
                 notebook.updated_at,
                 notebook.deleted_at,
+                notebook.is_archived as i32,
+                notebook.sync_excluded as i32,
             ],
         )?;
         stats.notebooks_imported += 1;
@@ -259,9 +407,21 @@ pub fn import_from_zip(db: &Database, path: PathBuf, overwrite: bool) -> Result<
             continue;
         }
 
+        // Another tag here with the same name gives way, as on a pull; the
+        // triggers link its notes to the imported tag by name
+        conn.execute("DELETE FROM tags WHERE name = ? AND id != ?", params![tag.name, tag.id])?;
+        // Upsert rather than replace, so the tag keeps its note links
         conn.execute(
-            "INSERT OR REPLACE INTO tags (id, name, color, revision, created_at, updated_at, deleted_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO tags (id, name, color, revision, created_at, updated_at, deleted_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 color = excluded.color,
+                 revision = excluded.revision,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 deleted_at = excluded.deleted_at,
+                 needs_push = 1",
             params![
                 tag.id,
                 tag.name,
@@ -291,9 +451,27 @@ pub fn import_from_zip(db: &Database, path: PathBuf, overwrite: bool) -> Result<
         }
 
         let tags_json = serde_json::to_string(&note.tags).unwrap();
+        tags::ensure_tags(&conn, &note.tags)?;
+        // Upsert rather than replace, which would take the note's tag links
+        // and everything else hanging off it along with the old row
         conn.execute(
-            "INSERT OR REPLACE INTO notes (id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO notes (id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 title = excluded.title,
+                 content = excluded.content,
+                 excerpt = NULL,
+                 notebook_id = excluded.notebook_id,
+                 tags = excluded.tags,
+                 status = excluded.status,
+                 is_pinned = excluded.is_pinned,
+                 archived_by_notebook = NULL,
+                 revision = excluded.revision,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 deleted_at = excluded.deleted_at,
+                 pinned_order = excluded.pinned_order,
+                 needs_push = 1",
             params![
                 note.id,
                 note.title,
@@ -306,11 +484,65 @@ pub fn import_from_zip(db: &Database, path: PathBuf, overwrite: bool) -> Result<
                 note.created_at,
                 note.updated_at,
                 note.deleted_at,
+                note.pinned_order,
             ],
         )?;
+        tasks::refresh_note_tasks(&conn, &note.id)?;
         stats.notes_imported += 1;
     }
 
+    // Import reminders last, once the notes they belong to are in place
+    for reminder in &data.reminders {
+        let exists = conn.prepare("SELECT 1 FROM reminders WHERE id = ?")?.exists(params![&reminder.id])?;
+        let note_known = match &reminder.note_id {
+            Some(note_id) => conn.prepare("SELECT 1 FROM notes WHERE id = ?")?.exists(params![note_id])?,
+            None => true,
+        };
+        if (exists && !overwrite) || !note_known {
+            stats.reminders_skipped += 1;
+            continue;
+        }
+
+        let recurrence = reminder.recurrence.as_ref().map(|rule| serde_json::to_string(rule).unwrap());
+        conn.execute(
+            "INSERT INTO reminders (id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at, recurrence, snoozed_from)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 note_id = excluded.note_id,
+                 message = excluded.message,
+                 due_date = excluded.due_date,
+                 completed = excluded.completed,
+                 notified = excluded.notified,
+                 revision = excluded.revision,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 deleted_at = excluded.deleted_at,
+                 recurrence = excluded.recurrence,
+                 snoozed_from = excluded.snoozed_from,
+                 needs_push = 1",
+            params![
+                reminder.id,
+                reminder.note_id,
+                reminder.message,
+                reminders::normalize_timestamp(&reminder.due_date),
+                reminder.completed as i32,
+                reminder.notified as i32,
+                reminder.revision,
+                reminder.created_at,
+                reminder.updated_at,
+                reminder.deleted_at,
+                recurrence,
+                reminder.snoozed_from.as_deref().map(reminders::normalize_timestamp),
+            ],
+        )?;
+        stats.reminders_imported += 1;
+    }
+
+    // Imported rows only carry content; compute their excerpts
+    notes::backfill_excerpts(&conn)?;
+    stats.orphans = maintenance::cleanup_orphan_rows(&conn)?;
+    conn.commit()?;
+
     Ok(stats)
 }
 
@@ -334,10 +566,349 @@ pub fn import_data(db: State<'_, Database>, options: ImportOptions) -> Result<Im
 #[tauri::command]
 pub fn get_export_preview(db: State<'_, Database>) -> Result<ExportStats> {
     let data = get_export_data(&db)?;
-    Ok(ExportStats {
-        notes: data.notes.len() as i32,
-        notebooks: data.notebooks.len() as i32,
-        tags: data.tags.len() as i32,
-        file_path: String::new(),
-    })
+    Ok(export_stats(&data, String::new()))
+}
+
+/// Export a single notebook (and its subtree when `recursive`) to a ZIP file
+#[tauri::command]
+pub fn export_notebook(
+    db: State<'_, Database>,
+    notebook_id: String,
+    path: String,
+    recursive: bool,
+) -> Result<ExportStats> {
+    export_notebook_to_zip(&db, &notebook_id, PathBuf::from(path), recursive)
+}
+
+/// Preview of what `export_notebook` would write
+#[tauri::command]
+pub fn get_notebook_export_preview(
+    db: State<'_, Database>,
+    notebook_id: String,
+    recursive: bool,
+) -> Result<ExportStats> {
+    let data = get_notebook_export_data(&db, &notebook_id, recursive)?;
+    Ok(export_stats(&data, String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{allow_writes, fail_writes};
+    use rusqlite::OptionalExtension;
+
+    #[test]
+    fn test_notebook_export_round_trip() {
+        let source = Database::in_memory();
+        {
+            let conn = source.conn();
+            conn.execute_batch(
+                "INSERT INTO notebooks (id, name) VALUES ('outer', 'Outer');
+                 INSERT INTO notebooks (id, name, parent_id) VALUES ('project', 'Project', 'outer');
+                 INSERT INTO notebooks (id, name, parent_id) VALUES ('sub', 'Sub', 'project');
+                 INSERT INTO notebooks (id, name) VALUES ('other', 'Other');
+                 INSERT INTO tags (id, name) VALUES ('t1', 'work'), ('t2', 'home');
+                 INSERT INTO notes (id, title, notebook_id, tags) VALUES
+                     ('n1', 'Plan', 'project', '[\"work\"]'),
+                     ('n2', 'Detail', 'sub', '[]'),
+                     ('n3', 'Groceries', 'other', '[\"home\"]');
+                 INSERT INTO reminders (id, note_id, due_date) VALUES
+                     ('r1', 'n2', '2030-01-01T00:00:00Z'), ('r2', 'n3', '2030-01-01T00:00:00Z');",
+            )
+            .unwrap();
+        }
+
+        let shallow = get_notebook_export_data(&source, "project", false).unwrap();
+        assert_eq!((shallow.notebooks.len(), shallow.notes.len(), shallow.reminders.len()), (1, 1, 0));
+
+        let data = get_notebook_export_data(&source, "project", true).unwrap();
+        assert!(is_partial(&data.version));
+        assert_eq!(data.notebooks.iter().map(|nb| nb.id.as_str()).collect::<Vec<_>>(), vec!["project", "sub"]);
+        assert_eq!(data.notes.len(), 2);
+        assert_eq!(data.tags.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), vec!["work"]);
+        assert_eq!(data.reminders.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["r1"]);
+
+        // The subtree imports into an empty database, its top at the root
+        let path = std::env::temp_dir().join(format!("viny-notebook-{}.zip", uuid::Uuid::new_v4()));
+        let stats = write_zip(&data, path.clone()).unwrap();
+        assert_eq!((stats.notebooks, stats.notes, stats.tags, stats.reminders), (2, 2, 1, 1));
+
+        let target = Database::in_memory();
+        let imported = import_from_zip(&target, path.clone(), false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(imported.partial);
+        assert_eq!(imported.notebooks_imported, 2);
+        let parent: Option<String> = target
+            .conn()
+            .query_row("SELECT parent_id FROM notebooks WHERE id = 'project'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(parent, None);
+    }
+
+    /// A project notebook inside `outer`, with a trashed sub-notebook and a note on a deleted tag
+    fn project_with_trash() -> Database {
+        let source = Database::in_memory();
+        source
+            .conn()
+            .execute_batch(
+                "INSERT INTO notebooks (id, name) VALUES ('outer', 'Outer');
+                 INSERT INTO notebooks (id, name, parent_id) VALUES ('project', 'Project', 'outer');
+                 INSERT INTO notebooks (id, name, parent_id, deleted_at) VALUES
+                     ('gone', 'Gone', 'project', '2020-01-01T00:00:00Z');
+                 INSERT INTO tags (id, name, deleted_at) VALUES ('t1', 'old', '2020-01-01T00:00:00Z');
+                 INSERT INTO notes (id, title, notebook_id, tags) VALUES ('n1', 'Plan', 'project', '[\"old\"]');",
+            )
+            .unwrap();
+        source
+    }
+
+    fn zipped(data: &ExportData, name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("viny-{}-{}.zip", name, uuid::Uuid::new_v4()));
+        write_zip(data, path.clone()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_exporting_a_missing_or_trashed_notebook_is_refused() {
+        let source = project_with_trash();
+        for missing in ["nowhere", "gone"] {
+            assert!(matches!(get_notebook_export_data(&source, missing, true), Err(AppError::NotFound(_))));
+        }
+    }
+
+    #[test]
+    fn test_notebook_export_leaves_out_trashed_sub_notebooks_and_deleted_tags() {
+        let source = project_with_trash();
+        let data = get_notebook_export_data(&source, "project", true).unwrap();
+        assert_eq!(data.notebooks.iter().map(|nb| nb.id.as_str()).collect::<Vec<_>>(), vec!["project"]);
+        assert!(data.tags.is_empty());
+    }
+
+    #[test]
+    fn test_notebook_export_back_into_its_database_keeps_the_known_parent() {
+        let source = project_with_trash();
+        let path = zipped(&get_notebook_export_data(&source, "project", true).unwrap(), "notebook");
+        let skipped = import_from_zip(&source, path.clone(), false).unwrap();
+        assert_eq!((skipped.notebooks_imported, skipped.notebooks_skipped), (0, 1));
+        let replaced = import_from_zip(&source, path.clone(), true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replaced.notebooks_imported, 1);
+        let parent: Option<String> = source
+            .conn()
+            .query_row("SELECT parent_id FROM notebooks WHERE id = 'project'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(parent.as_deref(), Some("outer"));
+    }
+
+    /// An archive with a reminder on a note and a standalone one
+    fn reminders_archive() -> PathBuf {
+        let source = Database::in_memory();
+        source
+            .conn()
+            .execute_batch(
+                "INSERT INTO notes (id, title) VALUES ('n1', 'Plan');
+                 INSERT INTO reminders (id, note_id, message, due_date) VALUES
+                     ('attached', 'n1', 'Archived', '2030-01-01T00:00:00Z'),
+                     ('standalone', NULL, 'Alone', '2030-01-01T00:00:00Z');",
+            )
+            .unwrap();
+        zipped(&get_export_data(&source).unwrap(), "reminders")
+    }
+
+    fn reminder_message(db: &Database, id: &str) -> Option<String> {
+        db.conn()
+            .query_row("SELECT message FROM reminders WHERE id = ?", params![id], |row| row.get(0))
+            .optional()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_failed_reminder_import_undoes_the_notes_before_it() {
+        let path = reminders_archive();
+        let target = Database::in_memory();
+        fail_writes(&target.conn(), "INSERT ON reminders WHEN NEW.id = 'standalone'");
+        assert!(import_from_zip(&target, path.clone(), false).is_err());
+        allow_writes(&target.conn());
+        std::fs::remove_file(&path).unwrap();
+        let notes: i64 = target.conn().query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0)).unwrap();
+        assert_eq!((notes, reminder_message(&target, "attached")), (0, None));
+    }
+
+    #[test]
+    fn test_standalone_reminders_import_and_are_kept_unless_overwriting() {
+        let path = reminders_archive();
+        let target = Database::in_memory();
+        target
+            .conn()
+            .execute(
+                "INSERT INTO reminders (id, message, due_date) VALUES ('standalone', 'Local', '2031-01-01T00:00:00Z')",
+                [],
+            )
+            .unwrap();
+        let stats = import_from_zip(&target, path.clone(), false).unwrap();
+        assert_eq!((stats.reminders_imported, stats.reminders_skipped), (1, 1));
+        assert_eq!(reminder_message(&target, "standalone").as_deref(), Some("Local"));
+        let stats = import_from_zip(&target, path.clone(), true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stats.reminders_imported, 2);
+        assert_eq!(reminder_message(&target, "standalone").as_deref(), Some("Alone"));
+    }
+
+    #[test]
+    fn test_overwrite_import_keeps_local_notes_on_the_tag() {
+        let _guard = crate::crypto::test_guard();
+        let source = Database::in_memory();
+        source
+            .conn()
+            .execute_batch(
+                "INSERT INTO tags (id, name, color) VALUES ('t1', 'work', '#00ff88');
+                 INSERT INTO notes (id, title, tags) VALUES ('n1', 'Plan', '[\"work\"]');",
+            )
+            .unwrap();
+        let data = get_export_data(&source).unwrap();
+        let path = std::env::temp_dir().join(format!("viny-overwrite-{}.zip", uuid::Uuid::new_v4()));
+        write_zip(&data, path.clone()).unwrap();
+
+        // The same tag here, on a note the archive doesn't have
+        let target = Database::in_memory();
+        target
+            .conn()
+            .execute_batch(
+                "INSERT INTO tags (id, name) VALUES ('t1', 'work');
+                 INSERT INTO notes (id, title, tags) VALUES ('local', 'Mine', '[\"work\"]');",
+            )
+            .unwrap();
+        let stats = import_from_zip(&target, path.clone(), true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stats.tags_imported, 1);
+
+        let filter = crate::models::ListNotesFilter {
+            tag: Some("work".to_string()),
+            ..Default::default()
+        };
+        let mut tagged: Vec<String> =
+            notes::query_notes(&target, filter).unwrap().into_iter().map(|n| n.id).collect();
+        tagged.sort();
+        assert_eq!(tagged, vec!["local", "n1"]);
+        let color: Option<String> =
+            target.conn().query_row("SELECT color FROM tags WHERE id = 't1'", [], |row| row.get(0)).unwrap();
+        assert_eq!(color.as_deref(), Some("#00ff88"));
+    }
+
+    #[test]
+    fn test_overwrite_import_keeps_what_the_archive_lacks() {
+        let _guard = crate::crypto::test_guard();
+        let source = Database::in_memory();
+        source
+            .conn()
+            .execute_batch(
+                "INSERT INTO notebooks (id, name) VALUES ('nb', 'Project');
+                 INSERT INTO notes (id, title, content, notebook_id) VALUES ('n1', 'Plan', '- [ ] call', 'nb');
+                 INSERT INTO reminders (id, note_id, message, due_date) VALUES
+                     ('r1', 'n1', 'From the archive', '2030-01-01T00:00:00Z');",
+            )
+            .unwrap();
+        let data = get_export_data(&source).unwrap();
+        let path = std::env::temp_dir().join(format!("viny-overwrite-{}.zip", uuid::Uuid::new_v4()));
+        write_zip(&data, path.clone()).unwrap();
+
+        // Here the note has a reminder of its own, and the notebook a note
+        let target = Database::in_memory();
+        {
+            let conn = target.conn();
+            conn.execute_batch(
+                "INSERT INTO notebooks (id, name) VALUES ('nb', 'Project');
+                 INSERT INTO notes (id, title, content, notebook_id) VALUES
+                     ('n1', 'Plan', '- [ ] call', 'nb'), ('local', 'Mine', '', 'nb');
+                 INSERT INTO reminders (id, note_id, message, due_date) VALUES
+                     ('r1', 'n1', 'Local copy', '2031-01-01T00:00:00Z'),
+                     ('mine', 'n1', 'Only here', '2031-01-01T00:00:00Z');",
+            )
+            .unwrap();
+            tasks::refresh_note_tasks(&conn, "n1").unwrap();
+        }
+        let task_id = |db: &Database| -> String {
+            db.conn().query_row("SELECT id FROM note_tasks WHERE note_id = 'n1'", [], |row| row.get(0)).unwrap()
+        };
+        let task = task_id(&target);
+
+        let stats = import_from_zip(&target, path.clone(), true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((stats.notebooks_imported, stats.notes_imported, stats.reminders_imported), (1, 1, 1));
+
+        let conn = target.conn();
+        let reminders: Vec<(String, String)> = conn
+            .prepare("SELECT id, message FROM reminders ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            reminders,
+            vec![
+                ("mine".to_string(), "Only here".to_string()),
+                ("r1".to_string(), "From the archive".to_string())
+            ]
+        );
+        let notebook: Option<String> =
+            conn.query_row("SELECT notebook_id FROM notes WHERE id = 'local'", [], |row| row.get(0)).unwrap();
+        assert_eq!(notebook.as_deref(), Some("nb"));
+        drop(conn);
+        assert_eq!(task_id(&target), task);
+    }
+
+    #[test]
+    fn test_full_export_round_trips_reminders() {
+        let source = Database::in_memory();
+        source
+            .conn()
+            .execute_batch(
+                "INSERT INTO notes (id, title, tags) VALUES ('n1', 'Plan', '[\"work\"]');
+                 INSERT INTO reminders (id, note_id, due_date, recurrence) VALUES
+                     ('r1', 'n1', '2030-01-01T00:00:00Z', '{\"frequency\":\"weekly\",\"interval\":1}');",
+            )
+            .unwrap();
+        let mut data = get_export_data(&source).unwrap();
+        assert_eq!(data.version, EXPORT_VERSION);
+        assert_eq!(data.reminders.len(), 1);
+
+        // One reminder whose note isn't anywhere
+        let mut orphan = data.reminders[0].clone();
+        orphan.id = "r2".to_string();
+        orphan.note_id = Some("missing".to_string());
+        data.reminders.push(orphan);
+
+        let path = std::env::temp_dir().join(format!("viny-full-{}.zip", uuid::Uuid::new_v4()));
+        write_zip(&data, path.clone()).unwrap();
+
+        // A local note already using the tag keeps its link when the archive's tag row replaces the local one
+        let target = Database::in_memory();
+        target
+            .conn()
+            .execute("INSERT INTO notes (id, title, tags) VALUES ('local', 'Mine', '[\"work\"]')", [])
+            .unwrap();
+        let stats = import_from_zip(&target, path.clone(), false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((stats.reminders_imported, stats.reminders_skipped), (1, 1));
+
+        let conn = target.conn();
+        let recurrence: Option<String> = conn
+            .query_row("SELECT recurrence FROM reminders WHERE id = 'r1'", [], |row| row.get(0))
+            .unwrap();
+        assert!(recurrence.is_some_and(|r| r.contains("weekly")));
+        let linked: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM note_tags nt JOIN tags t ON t.id = nt.tag_id WHERE t.name = 'work'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(linked, 2);
+
+        // Archives from before reminders were exported still load
+        let json = serde_json::to_string(&data).unwrap().replace("\"reminders\"", "\"ignored\"");
+        let old: ExportData = serde_json::from_str(&json.replace(EXPORT_VERSION, "1.0")).unwrap();
+        assert!(old.reminders.is_empty());
+    }
 }
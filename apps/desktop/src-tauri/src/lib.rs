@@ -1,39 +1,71 @@
+mod auto_sync;
+mod batch;
+mod checksum;
 mod commands;
 mod crypto;
 mod db;
 mod error;
+mod events;
 mod export;
+mod maintenance;
+mod markdown;
 mod models;
+mod natural_date;
+mod recurrence;
 mod search;
+mod settings;
 mod sync;
+mod tasks;
+#[cfg(test)]
+mod test_support;
+mod validation;
 
 use commands::{
     // Notes
-    create_note, delete_note, get_note, get_trashed_notes, list_notes, restore_note, update_note,
+    archive_notes, create_note, delete_note, get_note, get_notes_by_ids, get_notes_by_tag, get_trash,
+    get_trashed_notes, list_notes, merge_notes, quick_capture, rebuild_excerpts, reorder_pinned_notes,
+    restore_all_trashed, restore_note, restore_notes, toggle_archive, toggle_pin, unarchive_notes,
+    update_note,
     // Notebooks
-    create_notebook, delete_notebook, get_child_notebooks, get_notebook, get_root_notebooks,
-    list_notebooks, update_notebook,
+    archive_notebook, create_notebook, delete_notebook, empty_notebook_trash, get_child_notebooks,
+    get_notebook, get_notebook_counts, get_notebook_stats, get_root_notebooks, get_trashed_notebooks,
+    list_notebooks, purge_notebook, set_notebook_sync_excluded, unarchive_notebook, update_notebook,
     // Tags
-    create_tag, delete_tag, find_or_create_tag, get_tag, get_tag_by_name, list_tags, merge_tags,
-    update_tag,
+    create_tag, create_tags, delete_tag, delete_unused_tags, find_or_create_tag, get_color_palette,
+    get_tag, get_tag_by_name, list_tags, list_tags_with_counts, merge_tags, suggest_tags, update_tag,
     // Reminders
     complete_reminder, create_reminder, delete_note_reminders, delete_reminder, get_due_reminders,
-    get_overdue_reminders, get_reminder, get_reminders_by_note, get_today_reminders,
-    get_upcoming_reminders, list_reminders, mark_reminder_notified, update_reminder,
+    get_overdue_reminders, get_reminder, get_reminder_agenda, get_reminder_stats,
+    get_reminders_by_note, get_reminders_by_notebook, get_today_reminders, get_upcoming_reminders,
+    list_reminders, mark_reminder_notified, parse_due_date, purge_completed_reminders,
+    search_reminders, snooze_reminder, toggle_reminder_completed, uncomplete_reminder,
+    update_reminder,
     // Encryption
     change_encryption_password, disable_encryption, has_encryption_configured, is_encryption_enabled,
     lock_encryption, setup_encryption, unlock_encryption,
 };
 
-use export::{export_data, get_export_preview, import_data};
+use auto_sync::{disable_auto_sync, enable_auto_sync};
+
+use batch::batch_execute;
+
+use export::{export_data, export_notebook, get_export_preview, get_notebook_export_preview, import_data};
+
+use maintenance::{cleanup_orphans, get_deep_notebooks, get_invalid_reminder_dates};
 
 use search::{rebuild_search_index, search};
 
+use settings::{get_setting, set_setting};
+
 use sync::{
-    apply_remote_changes, check_server_connection, get_local_sync_state, get_pending_changes,
-    mark_changes_pushed, prepare_sync, sync_with_server,
+    apply_remote_changes, check_server_connection, flush_push_queue, get_device_info, get_local_sync_state,
+    get_pending_changes, get_push_queue_summary, get_sync_status, list_conflict_archive, list_server_devices,
+    list_unresolved_conflicts, mark_changes_pushed, prepare_sync, preview_sync, purge_synced_tombstones,
+    reset_sync_cursor, resolve_conflict, restore_conflict_version, sync_with_server,
 };
 
+use tasks::{get_open_tasks, get_tasks_for_note, rebuild_tasks, toggle_task};
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -44,18 +76,32 @@ pub fn run() {
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .setup(|app| {
-            db::init_database(&app.handle())?;
+            db::init_database(app.handle())?;
+            auto_sync::start(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // Notes
             list_notes,
             get_note,
+            get_notes_by_ids,
+            get_notes_by_tag,
             create_note,
             update_note,
             delete_note,
             restore_note,
             get_trashed_notes,
+            get_trash,
+            restore_notes,
+            restore_all_trashed,
+            toggle_pin,
+            toggle_archive,
+            archive_notes,
+            unarchive_notes,
+            reorder_pinned_notes,
+            quick_capture,
+            merge_notes,
+            rebuild_excerpts,
             // Notebooks
             list_notebooks,
             get_notebook,
@@ -64,34 +110,78 @@ pub fn run() {
             delete_notebook,
             get_root_notebooks,
             get_child_notebooks,
+            get_notebook_counts,
+            get_notebook_stats,
+            get_trashed_notebooks,
+            purge_notebook,
+            empty_notebook_trash,
+            archive_notebook,
+            unarchive_notebook,
+            set_notebook_sync_excluded,
             // Tags
             list_tags,
+            list_tags_with_counts,
+            suggest_tags,
             get_tag,
             get_tag_by_name,
             create_tag,
+            create_tags,
             find_or_create_tag,
             update_tag,
             delete_tag,
+            delete_unused_tags,
             merge_tags,
+            get_color_palette,
             // Sync
             get_local_sync_state,
+            get_device_info,
             get_pending_changes,
             apply_remote_changes,
             mark_changes_pushed,
+            reset_sync_cursor,
             prepare_sync,
             sync_with_server,
+            preview_sync,
             check_server_connection,
+            get_sync_status,
+            list_server_devices,
+            get_push_queue_summary,
+            flush_push_queue,
+            list_unresolved_conflicts,
+            resolve_conflict,
+            list_conflict_archive,
+            restore_conflict_version,
+            purge_synced_tombstones,
+            enable_auto_sync,
+            disable_auto_sync,
+            // Batch
+            batch_execute,
             // Search
             search,
             rebuild_search_index,
+            // Tasks
+            get_open_tasks,
+            get_tasks_for_note,
+            toggle_task,
+            rebuild_tasks,
             // Export/Import
             export_data,
             import_data,
             get_export_preview,
+            export_notebook,
+            get_notebook_export_preview,
+            // Maintenance
+            cleanup_orphans,
+            get_deep_notebooks,
+            get_invalid_reminder_dates,
             // Reminders
             list_reminders,
             get_reminder,
             get_reminders_by_note,
+            get_reminders_by_notebook,
+            search_reminders,
+            get_reminder_agenda,
+            get_reminder_stats,
             get_upcoming_reminders,
             get_overdue_reminders,
             get_today_reminders,
@@ -99,9 +189,17 @@ pub fn run() {
             create_reminder,
             update_reminder,
             complete_reminder,
+            uncomplete_reminder,
+            toggle_reminder_completed,
             mark_reminder_notified,
+            snooze_reminder,
+            parse_due_date,
             delete_reminder,
             delete_note_reminders,
+            purge_completed_reminders,
+            // Settings
+            get_setting,
+            set_setting,
             // Encryption
             is_encryption_enabled,
             has_encryption_configured,
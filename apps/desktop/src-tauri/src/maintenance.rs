@@ -0,0 +1,298 @@
+//! Database maintenance
+//!
+//! Repairs rows left behind by paths that bypass foreign keys or the FTS
+//! triggers (older imports, direct hard deletes), and values written before
+//! validation existed.
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use ts_rs::TS;
+
+use crate::commands::reminders;
+use crate::db::Database;
+use crate::error::Result;
+use crate::models::Reminder;
+use crate::recurrence;
+use crate::settings;
+use crate::validation;
+
+// =============================================================================
+// Types
+// =============================================================================
+
+/// Rows fixed by `cleanup_orphans`, per category
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct OrphanReport {
+    /// FTS rows whose note no longer exists
+    pub fts_rows: i32,
+    /// Reminders pointing at a missing note
+    pub reminders: i32,
+    /// Notes whose notebook was hard-deleted (moved to no notebook)
+    pub notebook_refs: i32,
+    /// Notebook icons that failed validation and were cleared
+    pub invalid_icons: i32,
+}
+
+/// A notebook nested deeper than the configured limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct DeepNotebook {
+    pub notebook_id: String,
+    pub name: String,
+    /// Root notebooks are depth 1
+    pub depth: i32,
+}
+
+// =============================================================================
+// Cleanup
+// =============================================================================
+
+/// Remove or detach rows that reference missing notes or notebooks
+pub fn cleanup_orphan_rows(conn: &Connection) -> Result<OrphanReport> {
+    let fts_rows = conn.execute(
+        "DELETE FROM notes_fts WHERE id NOT IN (SELECT id FROM notes)",
+        [],
+    )?;
+    let reminders = conn.execute(
+        "DELETE FROM reminders WHERE note_id NOT IN (SELECT id FROM notes)",
+        [],
+    )?;
+    // No revision bump: the notebook is gone on every device already
+    let notebook_refs = conn.execute(
+        "UPDATE notes SET notebook_id = NULL
+         WHERE notebook_id IS NOT NULL AND notebook_id NOT IN (SELECT id FROM notebooks)",
+        [],
+    )?;
+
+    Ok(OrphanReport {
+        fts_rows: fts_rows as i32,
+        reminders: reminders as i32,
+        notebook_refs: notebook_refs as i32,
+        invalid_icons: clear_invalid_icons(conn)? as i32,
+    })
+}
+
+/// Clear icons written before validation existed (e.g. inline SVG). The
+/// revision is bumped so the smaller row replaces the bloated one on sync.
+fn clear_invalid_icons(conn: &Connection) -> Result<usize> {
+    let icons: Vec<(String, String)> = conn
+        .prepare("SELECT id, icon FROM notebooks WHERE icon IS NOT NULL")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut cleared = 0;
+    for (id, icon) in icons {
+        if !validation::is_valid_icon(&icon) {
+            conn.execute(
+                "UPDATE notebooks SET icon = NULL, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+                params![now, id],
+            )?;
+            cleared += 1;
+        }
+    }
+    Ok(cleared)
+}
+
+// =============================================================================
+// Reports
+// =============================================================================
+
+/// Live notebooks nested deeper than `max_depth`, deepest first. Notebooks
+/// under a deleted parent count as roots.
+pub fn notebooks_over_depth(conn: &Connection, max_depth: i64) -> Result<Vec<DeepNotebook>> {
+    let notebooks = conn
+        .prepare(
+            "WITH RECURSIVE tree(id, name, depth) AS (
+                 SELECT id, name, 1 FROM notebooks
+                 WHERE deleted_at IS NULL
+                   AND (parent_id IS NULL
+                        OR parent_id NOT IN (SELECT id FROM notebooks WHERE deleted_at IS NULL))
+                 UNION ALL
+                 SELECT nb.id, nb.name, tree.depth + 1
+                 FROM notebooks nb JOIN tree ON nb.parent_id = tree.id
+                 WHERE nb.deleted_at IS NULL AND tree.depth < 1000
+             )
+             SELECT id, name, depth FROM tree WHERE depth > ? ORDER BY depth DESC, name",
+        )?
+        .query_map(params![max_depth], |row| {
+            Ok(DeepNotebook {
+                notebook_id: row.get(0)?,
+                name: row.get(1)?,
+                depth: row.get(2)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(notebooks)
+}
+
+/// Reminders whose due date or snooze origin can't be read as a timestamp,
+/// written before due dates were validated. They match no date query, so
+/// they only turn up here.
+pub fn reminders_with_invalid_dates(conn: &Connection) -> Result<Vec<Reminder>> {
+    // Readable dates were normalized at startup, so only ones SQLite doesn't
+    // read back unchanged can be bad. That includes impossible days like
+    // Feb 30, which have the right shape.
+    let candidates = conn
+        .prepare(
+            "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                    recurrence, snoozed_from
+             FROM reminders
+             WHERE strftime(?1, due_date) IS NOT due_date OR strftime(?1, snoozed_from) IS NOT snoozed_from
+             ORDER BY created_at",
+        )?
+        .query_map(params!["%Y-%m-%dT%H:%M:%SZ"], reminders::row_to_reminder)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(candidates
+        .into_iter()
+        .filter(|reminder| {
+            recurrence::parse_timestamp(&reminder.due_date).is_none()
+                || reminder.snoozed_from.as_deref().is_some_and(|value| recurrence::parse_timestamp(value).is_none())
+        })
+        .collect())
+}
+
+// =============================================================================
+// Tauri Commands
+// =============================================================================
+
+/// Clean up orphaned FTS rows, reminders, notebook references and invalid icons
+#[tauri::command]
+pub fn cleanup_orphans(db: State<'_, Database>) -> Result<OrphanReport> {
+    cleanup_orphan_rows(&db.conn())
+}
+
+/// Reminders with unreadable due dates, for the user to fix or delete
+#[tauri::command]
+pub fn get_invalid_reminder_dates(db: State<'_, Database>) -> Result<Vec<Reminder>> {
+    reminders_with_invalid_dates(&db.conn())
+}
+
+/// Notebooks nested deeper than the `max_notebook_depth` setting
+#[tauri::command]
+pub fn get_deep_notebooks(db: State<'_, Database>) -> Result<Vec<DeepNotebook>> {
+    let conn = db.conn();
+    let max_depth =
+        settings::get_i64(&conn, settings::MAX_NOTEBOOK_DEPTH, settings::DEFAULT_MAX_NOTEBOOK_DEPTH)?;
+    notebooks_over_depth(&conn, max_depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_orphans_reports_each_category() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+
+        // Simulate writes that bypassed foreign keys and triggers
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             INSERT INTO notes (id, title, notebook_id) VALUES ('n1', 'Kept', 'gone-notebook');
+             INSERT INTO notebooks (id, name, icon) VALUES ('nb1', 'Bloated', '<svg>...</svg>'), ('nb2', 'Fine', 'folder');
+             INSERT INTO notes_fts (id, title, content, tags) VALUES ('ghost', 'Ghost', '', '[]');
+             INSERT INTO reminders (id, note_id, due_date) VALUES ('r1', 'ghost', '2030-01-01T00:00:00Z');
+             INSERT INTO reminders (id, note_id, due_date) VALUES ('r2', 'n1', '2030-01-01T00:00:00Z');
+             PRAGMA foreign_keys = ON;",
+        )
+        .unwrap();
+
+        let report = cleanup_orphan_rows(&conn).unwrap();
+        assert_eq!(report, OrphanReport { fts_rows: 1, reminders: 1, notebook_refs: 1, invalid_icons: 1 });
+        let icons: Vec<Option<String>> = conn
+            .prepare("SELECT icon FROM notebooks ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(icons, vec![None, Some("folder".to_string())]);
+
+        let notebook_id: Option<String> = conn
+            .query_row("SELECT notebook_id FROM notes WHERE id = ?", params!["n1"], |row| row.get(0))
+            .unwrap();
+        assert_eq!(notebook_id, None);
+
+        // Second run has nothing left to do
+        assert_eq!(cleanup_orphan_rows(&conn).unwrap(), OrphanReport::default());
+    }
+
+    #[test]
+    fn test_cleanup_orphans_keeps_what_is_still_referenced() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        // Trashed rows still exist, and a standalone reminder has no note at all
+        conn.execute_batch(
+            "INSERT INTO notebooks (id, name, icon, deleted_at) VALUES ('nb', 'Trashed', '📁', '2026-01-01');
+             INSERT INTO notes (id, title, notebook_id, status, deleted_at)
+                 VALUES ('n', 'Trashed too', 'nb', 'trashed', '2026-01-01');
+             INSERT INTO reminders (id, note_id, due_date) VALUES ('standalone', NULL, '2030-01-01T00:00:00Z');
+             INSERT INTO reminders (id, note_id, due_date) VALUES ('attached', 'n', '2030-01-01T00:00:00Z');",
+        )
+        .unwrap();
+
+        assert_eq!(cleanup_orphan_rows(&conn).unwrap(), OrphanReport::default());
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM reminders"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM notes_fts WHERE id = 'n'"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM notes WHERE notebook_id = 'nb'"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM notebooks WHERE icon IS NOT NULL"), 1);
+    }
+
+    #[test]
+    fn test_notebooks_over_depth() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        // a > b > c > d, imported without going through the depth check
+        conn.execute_batch(
+            "INSERT INTO notebooks (id, name) VALUES ('a', 'A');
+             INSERT INTO notebooks (id, name, parent_id) VALUES ('b', 'B', 'a'), ('c', 'C', 'b'), ('d', 'D', 'c');",
+        )
+        .unwrap();
+
+        let deep = notebooks_over_depth(&conn, 2).unwrap();
+        let found: Vec<(&str, i32)> = deep.iter().map(|n| (n.notebook_id.as_str(), n.depth)).collect();
+        assert_eq!(found, vec![("d", 4), ("c", 3)]);
+        assert!(notebooks_over_depth(&conn, 4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reports_reminders_with_invalid_dates() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        conn.execute_batch(
+            "INSERT INTO reminders (id, due_date) VALUES ('ok', '2030-01-01T00:00:00Z'), ('bad', 'Invalid Date');
+             INSERT INTO reminders (id, due_date, snoozed_from) VALUES ('bad-snooze', '2030-01-01T00:00:00Z', 'NaN');",
+        )
+        .unwrap();
+        // Left alone by the normalization pass
+        reminders::normalize_stored_dates(&conn).unwrap();
+
+        let ids: Vec<String> = reminders_with_invalid_dates(&conn).unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&"bad".to_string()) && ids.contains(&"bad-snooze".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_date_report_skips_dates_readable_in_other_formats() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        conn.execute_batch(
+            "INSERT INTO reminders (id, due_date) VALUES
+                 ('empty', ''), ('feb-30', '2030-02-30T00:00:00Z'), ('bad-offset', '2030-01-01T00:00:00+25:00'),
+                 ('sqlite-format', '2030-01-01 09:00:00'), ('fractional', '2030-01-01T09:00:00.250+02:00');",
+        )
+        .unwrap();
+
+        // Even before they're normalized
+        let mut ids: Vec<String> = reminders_with_invalid_dates(&conn).unwrap().into_iter().map(|r| r.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["bad-offset", "empty", "feb-30"]);
+        reminders::normalize_stored_dates(&conn).unwrap();
+        assert_eq!(reminders_with_invalid_dates(&conn).unwrap().len(), 3);
+    }
+}
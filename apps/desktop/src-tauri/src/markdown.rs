@@ -0,0 +1,313 @@
+//! Markdown helpers
+//!
+//! Builds the plain-text excerpt stored alongside each note, so the note
+//! list doesn't have to strip markdown on the JS side for every row, and
+//! finds the checkbox items that feed the tasks view.
+
+/// Maximum length of a stored excerpt, in characters
+pub const EXCERPT_LENGTH: usize = 300;
+
+/// Build a plain-text excerpt from the start of some markdown content.
+///
+/// Fenced code blocks and horizontal rules are dropped; headers, quote and
+/// list markers, emphasis, inline code and link syntax are stripped, keeping
+/// only the visible text. Whitespace is collapsed to single spaces.
+pub fn excerpt(content: &str) -> String {
+    let mut out = String::new();
+    let mut in_fence = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence || is_rule(trimmed) {
+            continue;
+        }
+
+        let text = strip_inline(strip_block_prefix(trimmed));
+        for word in text.split_whitespace() {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(word);
+        }
+
+        if out.chars().count() >= EXCERPT_LENGTH {
+            break;
+        }
+    }
+
+    match out.char_indices().nth(EXCERPT_LENGTH) {
+        Some((idx, _)) => out[..idx].trim_end().to_string(),
+        None => out,
+    }
+}
+
+/// `---`, `***`, `___` (optionally spaced) on a line of their own
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && (compact.chars().all(|c| c == '-')
+            || compact.chars().all(|c| c == '*')
+            || compact.chars().all(|c| c == '_'))
+}
+
+/// Strip header, blockquote, list and task markers (which may be nested,
+/// e.g. `> - [ ] item`)
+fn strip_block_prefix(mut line: &str) -> &str {
+    loop {
+        let before = line;
+
+        if line.starts_with('#') {
+            let rest = line.trim_start_matches('#');
+            if rest.is_empty() || rest.starts_with(' ') {
+                line = rest;
+            }
+        } else if let Some(rest) = line.strip_prefix('>') {
+            line = rest;
+        } else if let Some(rest) = ["- ", "* ", "+ "].iter().find_map(|m| line.strip_prefix(m)) {
+            line = rest;
+        } else if let Some(rest) = ["[ ] ", "[x] ", "[X] "].iter().find_map(|m| line.strip_prefix(m)) {
+            line = rest;
+        } else {
+            let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+            if digits > 0 {
+                let rest = &line[digits..];
+                if let Some(rest) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+                    line = rest;
+                }
+            }
+        }
+
+        line = line.trim_start();
+        if line == before {
+            return line;
+        }
+    }
+}
+
+/// Strip inline syntax: emphasis, inline code, links, images and wiki links
+fn strip_inline(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            // Image: drop the `!`, the rest is handled like a link
+            '!' if chars.get(i + 1) == Some(&'[') => {}
+            // Wiki link: [[Target]] or [[Target|Alias]]
+            '[' if chars.get(i + 1) == Some(&'[') => {
+                if let Some(end) = find_seq(&chars, i + 2, &[']', ']']) {
+                    let inner: String = chars[i + 2..end].iter().collect();
+                    let label = inner.rsplit('|').next().unwrap_or_default();
+                    out.push_str(label);
+                    i = end + 2;
+                    continue;
+                }
+                out.push(c);
+            }
+            // Link: [text](url)
+            '[' => {
+                if let Some(close) = find_seq(&chars, i + 1, &[']']) {
+                    if chars.get(close + 1) == Some(&'(') {
+                        if let Some(paren) = find_seq(&chars, close + 2, &[')']) {
+                            let text: String = chars[i + 1..close].iter().collect();
+                            out.push_str(&strip_inline(&text));
+                            i = paren + 1;
+                            continue;
+                        }
+                    }
+                }
+                out.push(c);
+            }
+            '*' | '~' | '`' => {}
+            // Underscores only count as emphasis at word boundaries, so
+            // snake_case identifiers survive
+            '_' => {
+                let prev_word = i > 0 && chars[i - 1].is_alphanumeric();
+                let next_word = chars.get(i + 1).is_some_and(|n| n.is_alphanumeric());
+                if prev_word && next_word {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+
+    out
+}
+
+fn find_seq(chars: &[char], from: usize, seq: &[char]) -> Option<usize> {
+    (from..chars.len()).find(|&i| chars[i..].starts_with(seq))
+}
+
+/// A `- [ ] text` / `- [x] text` checkbox item
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskItem {
+    /// 1-based line number within the content
+    pub line_no: i64,
+    pub text: String,
+    pub checked: bool,
+    /// Byte offset of the character between the brackets
+    pub mark_offset: usize,
+}
+
+/// Find the checkbox items in some markdown content, skipping fenced code
+pub fn task_items(content: &str) -> Vec<TaskItem> {
+    let mut items = Vec::new();
+    let mut in_fence = false;
+    let mut offset = 0;
+
+    for (idx, raw_line) in content.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += raw_line.len();
+
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let Some(rest) = strip_list_marker(trimmed) else {
+            continue;
+        };
+        let checked = match rest.get(..3) {
+            Some("[ ]") => false,
+            Some("[x]") | Some("[X]") => true,
+            _ => continue,
+        };
+        let after = &rest[3..];
+        if !after.is_empty() && !after.starts_with(' ') {
+            continue;
+        }
+        let text = after.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        items.push(TaskItem {
+            line_no: idx as i64 + 1,
+            text: text.to_string(),
+            checked,
+            mark_offset: line_start + (line.len() - rest.len()) + 1,
+        });
+    }
+
+    items
+}
+
+/// Rewrite the checkbox of `item` (as found by [`task_items`]) in `content`
+pub fn set_task_checked(content: &mut String, item: &TaskItem, checked: bool) {
+    let mark = if checked { "x" } else { " " };
+    content.replace_range(item.mark_offset..item.mark_offset + 1, mark);
+}
+
+/// `- `, `* `, `+ `, `1. ` or `1) ` at the start of a line
+fn strip_list_marker(line: &str) -> Option<&str> {
+    if let Some(rest) = ["- ", "* ", "+ "].iter().find_map(|m| line.strip_prefix(m)) {
+        return Some(rest);
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest = &line[digits..];
+    rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt_strips_headers_and_emphasis() {
+        let md = "# Title\n\nSome **bold** and _italic_ and ~~gone~~ text with `code`.";
+        assert_eq!(excerpt(md), "Title Some bold and italic and gone text with code.");
+    }
+
+    #[test]
+    fn test_excerpt_drops_fenced_code_blocks() {
+        let md = "Before\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\n~~~\nmore code\n~~~\nAfter";
+        assert_eq!(excerpt(md), "Before After");
+    }
+
+    #[test]
+    fn test_excerpt_unclosed_fence_drops_rest() {
+        let md = "Intro\n```\nnever closed\nstill code";
+        assert_eq!(excerpt(md), "Intro");
+    }
+
+    #[test]
+    fn test_excerpt_keeps_link_text_only() {
+        let md = "See [the docs](https://example.com/a_b) and ![diagram](img.png) or [[Other Note]] / [[target|alias]].";
+        assert_eq!(excerpt(md), "See the docs and diagram or Other Note / alias.");
+    }
+
+    #[test]
+    fn test_excerpt_leaves_unmatched_brackets() {
+        assert_eq!(excerpt("array[0] and [not a link"), "array[0] and [not a link");
+    }
+
+    #[test]
+    fn test_excerpt_strips_lists_quotes_and_tasks() {
+        let md = "> quoted\n- item one\n* item two\n1. first\n- [ ] open task\n- [x] done task\n---";
+        assert_eq!(excerpt(md), "quoted item one item two first open task done task");
+    }
+
+    #[test]
+    fn test_excerpt_keeps_snake_case() {
+        assert_eq!(excerpt("call my_function now"), "call my_function now");
+    }
+
+    #[test]
+    fn test_excerpt_truncates_on_char_boundary() {
+        let md = "é".repeat(EXCERPT_LENGTH * 2);
+        let result = excerpt(&md);
+        assert_eq!(result.chars().count(), EXCERPT_LENGTH);
+    }
+
+    #[test]
+    fn test_excerpt_empty() {
+        assert_eq!(excerpt(""), "");
+        assert_eq!(excerpt("```\ncode only\n```"), "");
+    }
+
+    #[test]
+    fn test_task_items_finds_checkboxes() {
+        let md = "# Todo\n- [ ] buy milk\n  * [x] call mom\n1. [X] numbered\nplain [ ] text\n- [ ]\n- [ ]no space";
+        let items = task_items(md);
+        let found: Vec<(i64, &str, bool)> = items.iter().map(|t| (t.line_no, t.text.as_str(), t.checked)).collect();
+        assert_eq!(
+            found,
+            vec![(2, "buy milk", false), (3, "call mom", true), (4, "numbered", true)]
+        );
+    }
+
+    #[test]
+    fn test_task_items_skips_fenced_code() {
+        let md = "```\n- [ ] not a task\n```\n- [ ] real task";
+        let items = task_items(md);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].line_no, 4);
+    }
+
+    #[test]
+    fn test_set_task_checked_rewrites_only_the_mark() {
+        let mut md = "intro\r\n  - [ ] é first\r\n- [x] second\r\n".to_string();
+        let items = task_items(&md);
+
+        set_task_checked(&mut md, &items[0], true);
+        set_task_checked(&mut md, &items[1], false);
+        assert_eq!(md, "intro\r\n  - [x] é first\r\n- [ ] second\r\n");
+    }
+}
@@ -1,6 +1,17 @@
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+/// Deserialize a field where an explicit `null` differs from an omitted
+/// one: omitted stays `None` (via `#[serde(default)]`), `null` becomes
+/// `Some(None)`
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct Note {
@@ -15,23 +26,21 @@ pub struct Note {
     pub created_at: String,
     pub updated_at: String,
     pub deleted_at: Option<String>,
+    /// Position among pinned notes (set by `reorder_pinned_notes`)
+    #[serde(default)]
+    pub pinned_order: Option<i64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Default)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 #[serde(rename_all = "snake_case")]
 pub enum NoteStatus {
+    #[default]
     Active,
     Archived,
     Trashed,
 }
 
-impl Default for NoteStatus {
-    fn default() -> Self {
-        Self::Active
-    }
-}
-
 impl NoteStatus {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -53,9 +62,14 @@ impl NoteStatus {
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct CreateNoteInput {
+    /// Caller-supplied UUID (e.g. from an importer); minted when omitted
+    pub id: Option<String>,
     pub title: Option<String>,
     pub content: Option<String>,
-    pub notebook_id: Option<String>,
+    /// Omitted: the `default_notebook_id` setting applies. `null`: no notebook
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub notebook_id: Option<Option<String>>,
     pub tags: Option<Vec<String>>,
 }
 
@@ -70,6 +84,28 @@ pub struct UpdateNoteInput {
     pub is_pinned: Option<bool>,
 }
 
+/// Trashed note with the context the trash view shows
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct TrashedNoteView {
+    #[serde(flatten)]
+    pub note: Note,
+    /// Name of the notebook the note was in, if it still exists
+    pub notebook_name: Option<String>,
+    /// Whole days since the note was trashed
+    pub days_in_trash: i32,
+}
+
+/// Outcome of `archive_notes` / `unarchive_notes`
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct ArchiveSummary {
+    /// Notes whose status changed
+    pub archived: i32,
+    /// Ids that are trashed or don't exist
+    pub skipped: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct Notebook {
@@ -82,11 +118,88 @@ pub struct Notebook {
     pub created_at: String,
     pub updated_at: String,
     pub deleted_at: Option<String>,
+    /// Set by `archive_notebook`; archived notebooks are hidden from `list_notebooks`
+    #[serde(default)]
+    pub is_archived: bool,
+    /// Set by `set_notebook_sync_excluded`; the notebook and its notes never
+    /// leave this device
+    #[serde(default)]
+    pub sync_excluded: bool,
+}
+
+/// Outcome of `archive_notebook` / `unarchive_notebook`
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct NotebookArchiveSummary {
+    /// Notebooks whose archived flag changed
+    pub notebook_ids: Vec<String>,
+    /// Notes whose status changed
+    pub note_ids: Vec<String>,
+}
+
+/// Dashboard figures for a notebook (and its subtree when recursive)
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct NotebookStats {
+    pub notebook_id: String,
+    pub active_count: i32,
+    pub archived_count: i32,
+    pub trashed_count: i32,
+    /// Most recent `updated_at` among the notes
+    pub last_updated_at: Option<String>,
+    /// Total size of note content as stored, in bytes
+    pub total_content_bytes: i64,
+    /// Up to 10 most used tags, most frequent first
+    pub top_tags: Vec<TagUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct TagUsage {
+    pub name: String,
+    pub count: i32,
+}
+
+/// Note counts for one sidebar entry; `notebook_id` is `None` for unfiled notes
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct NotebookCount {
+    pub notebook_id: Option<String>,
+    pub active_count: i32,
+    pub archived_count: i32,
+}
+
+/// What `delete_notebook` does with the notebook's children and notes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum NotebookDeleteMode {
+    /// Children move up to the deleted notebook's parent; notes become unfiled
+    #[default]
+    PromoteChildren,
+    /// The whole subtree is deleted; all of its notes become unfiled
+    DeleteChildren,
+    /// Children and notes both move up to the deleted notebook's parent
+    MoveNotesToParent,
+}
+
+/// Outcome of `delete_notebook`
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct NotebookDeleteSummary {
+    /// Ids of every notebook deleted, the requested one first
+    pub notebooks_deleted: Vec<String>,
+    /// Child notebooks reparented to the deleted notebook's parent
+    pub children_promoted: Vec<String>,
+    /// Notes moved out of deleted notebooks
+    pub notes_moved: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct CreateNotebookInput {
+    /// Caller-supplied UUID (e.g. from an importer); minted when omitted
+    pub id: Option<String>,
     pub name: String,
     pub color: Option<String>,
     pub icon: Option<String>,
@@ -97,9 +210,17 @@ pub struct CreateNotebookInput {
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct UpdateNotebookInput {
     pub name: Option<String>,
-    pub color: Option<String>,
-    pub icon: Option<String>,
-    pub parent_id: Option<String>,
+    /// Omitted keeps the current value; `null` clears it
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub color: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub icon: Option<Option<String>>,
+    /// Omitted keeps the current parent; `null` moves the notebook to the root
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub parent_id: Option<Option<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -114,29 +235,89 @@ pub struct Tag {
     pub deleted_at: Option<String>,
 }
 
+/// A tag with the number of non-trashed notes using it
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct TagWithCount {
+    pub tag: Tag,
+    pub note_count: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct CreateTagInput {
+    /// Caller-supplied UUID (e.g. from an importer); minted when omitted
+    pub id: Option<String>,
     pub name: String,
     pub color: Option<String>,
 }
 
+/// Result of `suggest_tags`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct TagSuggestions {
+    pub tags: Vec<TagWithCount>,
+    /// A tag named exactly like the prefix (ignoring case) exists
+    pub exact_match: bool,
+}
+
+/// A name `create_tags` didn't insert, and why
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct SkippedTag {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Outcome of `create_tags`
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct CreateTagsResult {
+    pub created: Vec<Tag>,
+    pub skipped: Vec<SkippedTag>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct UpdateTagInput {
     pub name: Option<String>,
-    pub color: Option<String>,
+    /// Omitted keeps the current value; `null` clears it
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub color: Option<Option<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct ListNotesFilter {
     pub notebook_id: Option<String>,
     pub status: Option<NoteStatus>,
+    /// Single tag; kept for older callers, combined with `tags`
     pub tag: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// How `tags` combine; defaults to `all`
+    pub tag_mode: Option<TagMode>,
     pub search: Option<String>,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    /// Return the stored plain-text excerpt in `content` instead of the full body
+    pub summary: Option<bool>,
+    /// RFC3339 bounds; `*_after` is inclusive, `*_before` exclusive
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum TagMode {
+    /// Notes carrying every listed tag
+    #[default]
+    All,
+    /// Notes carrying at least one listed tag
+    Any,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -155,7 +336,8 @@ pub struct SyncState {
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct Reminder {
     pub id: String,
-    pub note_id: String,
+    /// `None` for a standalone reminder
+    pub note_id: Option<String>,
     pub message: String,
     pub due_date: String,
     pub completed: bool,
@@ -164,14 +346,56 @@ pub struct Reminder {
     pub created_at: String,
     pub updated_at: String,
     pub deleted_at: Option<String>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Due date before the reminder was first snoozed; cleared when it's
+    /// rescheduled or moves to its next occurrence
+    #[serde(default)]
+    pub snoozed_from: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "lowercase")]
+pub enum RecurrenceFrequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Repeat rule for a reminder; the FREQ / INTERVAL / UNTIL subset of RRULE
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct Recurrence {
+    pub frequency: RecurrenceFrequency,
+    /// Repeat every `interval` periods
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    /// No occurrences after this RFC3339 timestamp
+    #[serde(default)]
+    pub until: Option<String>,
+    /// First occurrence, which later ones are counted from; taken from the
+    /// reminder's due date when the rule is saved without one
+    #[serde(default)]
+    pub start: Option<String>,
+}
+
+fn default_interval() -> u32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct CreateReminderInput {
-    pub note_id: String,
+    /// Omit for a standalone reminder
+    pub note_id: Option<String>,
     pub message: Option<String>,
+    /// RFC3339 timestamp, or a phrase like "tomorrow 9am"
     pub due_date: String,
+    #[serde(default)]
+    #[ts(optional)]
+    pub recurrence: Option<Recurrence>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -181,4 +405,102 @@ pub struct UpdateReminderInput {
     pub due_date: Option<String>,
     pub completed: Option<bool>,
     pub notified: Option<bool>,
+    /// Omitted keeps the current rule; `null` makes the reminder one-shot
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub recurrence: Option<Option<Recurrence>>,
+}
+
+/// Counts behind the reminders dashboard widget. Deleted reminders are
+/// never counted; "active" means not completed.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct ReminderStats {
+    pub overdue: i64,
+    /// Active reminders due on the client's current calendar day
+    pub due_today: i64,
+    /// Active reminders due in the next 7 days
+    pub due_this_week: i64,
+    /// Active reminders not yet due
+    pub upcoming: i64,
+    pub completed_last_30_days: i64,
+    pub total_active: i64,
+    /// Share of completed-or-overdue reminders that were completed in the
+    /// last 30 days; `None` when there are neither
+    pub completion_rate: Option<f64>,
+}
+
+/// A reminder with the title of the note it belongs to, `None` when standalone
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct AgendaReminder {
+    pub reminder: Reminder,
+    pub note_title: Option<String>,
+}
+
+/// Upcoming reminders of one notebook; `notebook_id` is `None` for notes
+/// outside any notebook and for standalone reminders
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct ReminderAgendaGroup {
+    pub notebook_id: Option<String>,
+    pub notebook_name: Option<String>,
+    pub reminders: Vec<AgendaReminder>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_color_omitted_null_or_set() {
+        let parse = |json: &str| serde_json::from_str::<UpdateTagInput>(json).unwrap().color;
+        assert_eq!(parse(r#"{"name": null}"#), None);
+        assert_eq!(parse(r#"{"name": null, "color": null}"#), Some(None));
+        assert_eq!(parse(r##"{"name": null, "color": "#112233"}"##), Some(Some("#112233".to_string())));
+    }
+
+    #[test]
+    fn test_tag_update_rejects_and_defaults() {
+        let empty = serde_json::from_str::<UpdateTagInput>("{}").unwrap();
+        assert_eq!((empty.name, empty.color), (None, None));
+        for bad in [r#"{"color": 5}"#, r#"{"color": ["blue"]}"#, r#"{"name": false}"#] {
+            assert!(serde_json::from_str::<UpdateTagInput>(bad).is_err(), "accepted {}", bad);
+        }
+        // Omitted and null survive a round trip as themselves
+        for color in [None, Some(None), Some(Some("blue".to_string()))] {
+            let json = serde_json::to_string(&UpdateTagInput { name: None, color: color.clone() }).unwrap();
+            assert_eq!(serde_json::from_str::<UpdateTagInput>(&json).unwrap().color, color, "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_reminder_recurrence_omitted_null_or_set() {
+        let parse = |json: &str| serde_json::from_str::<UpdateReminderInput>(json).unwrap().recurrence;
+        assert_eq!(parse("{}"), None);
+        assert_eq!(parse(r#"{"recurrence": null}"#), Some(None));
+        let weekly = parse(r#"{"recurrence": {"frequency": "weekly"}}"#).unwrap().unwrap();
+        assert_eq!((weekly.interval, weekly.until, weekly.start), (1, None, None));
+        assert!(serde_json::from_str::<UpdateReminderInput>(r#"{"recurrence": {"frequency": "hourly"}}"#).is_err());
+
+        for json in ["{}", r#"{"recurrence": null}"#] {
+            let input: UpdateReminderInput = serde_json::from_str(json).unwrap();
+            let back: UpdateReminderInput = serde_json::from_str(&serde_json::to_string(&input).unwrap()).unwrap();
+            assert_eq!(back.recurrence, input.recurrence);
+        }
+    }
+
+    #[test]
+    fn test_notebook_color_and_icon_omitted_null_or_set() {
+        let parse = |json: &str| {
+            let input = serde_json::from_str::<UpdateNotebookInput>(json).unwrap();
+            (input.color, input.icon)
+        };
+        assert_eq!(parse(r#"{"name": "Work"}"#), (None, None));
+        assert_eq!(parse(r#"{"name": null, "color": null, "icon": null}"#), (Some(None), Some(None)));
+        assert_eq!(
+            parse(r#"{"name": null, "color": "blue", "icon": "folder"}"#),
+            (Some(Some("blue".to_string())), Some(Some("folder".to_string())))
+        );
+    }
 }
@@ -0,0 +1,292 @@
+//! Natural-language due dates
+//!
+//! Understands the short English phrases people type into a reminder field:
+//! "now", "today", "tonight", "tomorrow", weekday names ("friday",
+//! "next friday"), "next week", "next month", "in 2 hours" / "in a day", each
+//! optionally followed (or preceded) by a time such as "9am", "at 5:30pm",
+//! "17:00", "noon" or "midnight". A bare time means its next occurrence.
+//!
+//! Calendar phrases keep the wall-clock time in the reference's timezone, so
+//! "tomorrow 9am" is 9am local even across a DST change; "in N minutes/hours"
+//! is an exact duration.
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Weekday};
+
+use crate::error::{AppError, Result};
+
+/// Time used when a day is given without one
+const DEFAULT_TIME: (u32, u32) = (9, 0);
+/// Time used for "tonight" without an explicit time
+const TONIGHT_TIME: (u32, u32) = (20, 0);
+
+/// Parse `input` relative to `reference` into an RFC3339 timestamp in the
+/// reference's timezone
+pub fn parse_to_rfc3339<Tz: TimeZone>(input: &str, reference: DateTime<Tz>) -> Result<String>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    parse(input, reference)
+        .map(|due| due.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .ok_or_else(|| not_understood(input))
+}
+
+pub fn not_understood(input: &str) -> AppError {
+    AppError::Validation(format!("Couldn't understand due date '{}'", input))
+}
+
+pub fn parse<Tz: TimeZone>(input: &str, reference: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let lowered = input.trim().to_lowercase();
+    let tokens: Vec<&str> = lowered.split_whitespace().collect();
+
+    match tokens.as_slice() {
+        [] => None,
+        ["now"] => Some(reference),
+        ["in", amount, unit] => parse_offset(amount, unit, reference),
+        _ => parse_day_and_time(&tokens, reference),
+    }
+}
+
+/// "in 2 hours", "in a week"
+fn parse_offset<Tz: TimeZone>(amount: &str, unit: &str, reference: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let amount: u32 = match amount {
+        "a" | "an" => 1,
+        n => n.parse().ok()?,
+    };
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+
+    match unit {
+        "min" | "minute" => reference.checked_add_signed(Duration::minutes(amount.into())),
+        "hr" | "hour" => reference.checked_add_signed(Duration::hours(amount.into())),
+        "day" => shift_days(reference, amount.into()),
+        "week" => shift_days(reference, i64::from(amount) * 7),
+        "month" => {
+            let date = reference.date_naive().checked_add_months(Months::new(amount))?;
+            at_local(&reference, date, reference.time())
+        }
+        "year" => {
+            let date = reference.date_naive().checked_add_months(Months::new(amount.checked_mul(12)?))?;
+            at_local(&reference, date, reference.time())
+        }
+        _ => None,
+    }
+}
+
+/// Move by whole days, keeping the local time of day
+fn shift_days<Tz: TimeZone>(reference: DateTime<Tz>, days: i64) -> Option<DateTime<Tz>> {
+    let date = reference.date_naive().checked_add_signed(Duration::days(days))?;
+    at_local(&reference, date, reference.time())
+}
+
+/// A day phrase and a time phrase in either order; either may be missing
+fn parse_day_and_time<Tz: TimeZone>(tokens: &[&str], reference: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let today = reference.date_naive();
+
+    for split in 0..=tokens.len() {
+        let (head, tail) = tokens.split_at(split);
+        let candidates = [(head, tail), (tail, head)];
+        for (day_tokens, time_tokens) in candidates {
+            let Some((date, default_time)) = parse_day(day_tokens, today) else {
+                continue;
+            };
+            let time = match time_tokens {
+                [] => default_time,
+                _ => match parse_time(time_tokens) {
+                    Some(time) => Some(time),
+                    None => continue,
+                },
+            };
+
+            return match (date, time) {
+                (Some(date), Some(time)) => at_local(&reference, date, time),
+                (Some(date), None) => at_local(&reference, date, hm(DEFAULT_TIME)),
+                // A bare time is its next occurrence
+                (None, Some(time)) => {
+                    let due = at_local(&reference, today, time)?;
+                    if due > reference {
+                        Some(due)
+                    } else {
+                        at_local(&reference, today.succ_opt()?, time)
+                    }
+                }
+                (None, None) => None,
+            };
+        }
+    }
+    None
+}
+
+/// The date a day phrase names, plus its default time if it has one.
+/// An empty phrase parses as "no date".
+fn parse_day(tokens: &[&str], today: NaiveDate) -> Option<(Option<NaiveDate>, Option<NaiveTime>)> {
+    let date = match tokens {
+        [] => return Some((None, None)),
+        ["today"] => today,
+        ["tonight"] => return Some((Some(today), Some(hm(TONIGHT_TIME)))),
+        ["tomorrow"] => today.succ_opt()?,
+        ["next", "week"] => today.checked_add_signed(Duration::days(7))?,
+        ["next", "month"] => today.checked_add_months(Months::new(1))?,
+        [day] | ["next" | "on", day] => next_weekday(today, parse_weekday(day)?),
+        _ => return None,
+    };
+    Some((Some(date), None))
+}
+
+/// First `weekday` strictly after `today`
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(if ahead == 0 { 7 } else { ahead.into() })
+}
+
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    let weekday = match token {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+/// "9am", "9 am", "at 5:30pm", "17:00", "noon", "midnight"
+fn parse_time(tokens: &[&str]) -> Option<NaiveTime> {
+    let tokens = match tokens {
+        ["at", rest @ ..] => rest,
+        _ => tokens,
+    };
+    let text = tokens.concat();
+
+    match text.as_str() {
+        "" => return None,
+        "noon" => return Some(hm((12, 0))),
+        "midnight" => return Some(hm((0, 0))),
+        _ => {}
+    }
+
+    let (clock, meridiem) = match (text.strip_suffix("am"), text.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(0)),
+        (_, Some(clock)) => (clock, Some(12)),
+        _ => (text.as_str(), None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        // A plain number is only a time with am/pm ("9am", not "9")
+        None if meridiem.is_some() => (clock.parse::<u32>().ok()?, 0),
+        _ => return None,
+    };
+
+    let hour = match meridiem {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn hm((hour, minute): (u32, u32)) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+/// `date` at local `time` in the reference's timezone. A time skipped by a
+/// DST change moves forward an hour; a repeated one takes the earlier instant.
+fn at_local<Tz: TimeZone>(reference: &DateTime<Tz>, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Tz>> {
+    let local = NaiveDateTime::new(date, time);
+    let zone = reference.timezone();
+    zone.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| zone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn at(value: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(value).unwrap()
+    }
+
+    fn parsed(input: &str, reference: &str) -> String {
+        parse_to_rfc3339(input, at(reference)).unwrap()
+    }
+
+    #[test]
+    fn test_relative_phrases() {
+        // Wednesday afternoon
+        let reference = "2025-06-11T15:20:00+02:00";
+        assert_eq!(parsed("now", reference), "2025-06-11T15:20:00+02:00");
+        assert_eq!(parsed("Tomorrow 9am", reference), "2025-06-12T09:00:00+02:00");
+        assert_eq!(parsed("9:30 pm today", reference), "2025-06-11T21:30:00+02:00");
+        assert_eq!(parsed("tonight", reference), "2025-06-11T20:00:00+02:00");
+        assert_eq!(parsed("in 2 hours", reference), "2025-06-11T17:20:00+02:00");
+        assert_eq!(parsed("in an hour", reference), "2025-06-11T16:20:00+02:00");
+        assert_eq!(parsed("friday at noon", reference), "2025-06-13T12:00:00+02:00");
+        assert_eq!(parsed("next wednesday", reference), "2025-06-18T09:00:00+02:00");
+        assert_eq!(parsed("next week", reference), "2025-06-18T09:00:00+02:00");
+        // A time already past today means tomorrow
+        assert_eq!(parsed("9am", reference), "2025-06-12T09:00:00+02:00");
+        assert_eq!(parsed("17:00", reference), "2025-06-11T17:00:00+02:00");
+    }
+
+    #[test]
+    fn test_end_of_month_and_dst_boundaries() {
+        let month_end = "2025-01-31T18:00:00+13:00";
+        assert_eq!(parsed("tomorrow 9am", month_end), "2025-02-01T09:00:00+13:00");
+        assert_eq!(parsed("in 1 month", month_end), "2025-02-28T18:00:00+13:00");
+        assert_eq!(parsed("next month", month_end), "2025-02-28T09:00:00+13:00");
+        assert_eq!(parsed("in 2 days", "2024-02-28T08:00:00Z"), "2024-03-01T08:00:00Z");
+
+        // The evening before US clocks spring forward (2025-03-09 02:00 EST):
+        // durations are exact, calendar phrases keep the wall-clock time
+        let before_dst = "2025-03-08T23:30:00-05:00";
+        assert_eq!(parsed("in 3 hours", before_dst), "2025-03-09T02:30:00-05:00");
+        assert_eq!(parsed("tomorrow 9am", before_dst), "2025-03-09T09:00:00-05:00");
+        assert_eq!(parsed("in 1 day", before_dst), "2025-03-09T23:30:00-05:00");
+    }
+
+    /// Wednesday afternoon
+    const WEDNESDAY: &str = "2025-06-11T15:20:00+02:00";
+
+    #[test]
+    fn test_todays_weekday_or_the_current_time_means_the_next_one() {
+        assert_eq!(parsed("wednesday", WEDNESDAY), "2025-06-18T09:00:00+02:00");
+        assert_eq!(parsed("15:20", WEDNESDAY), "2025-06-12T15:20:00+02:00");
+        assert_eq!(parsed("midnight", WEDNESDAY), "2025-06-12T00:00:00+02:00");
+        assert_eq!(parsed("in 0 minutes", WEDNESDAY), WEDNESDAY);
+    }
+
+    #[test]
+    fn test_phrases_read_in_any_case_spacing_or_order() {
+        assert_eq!(parsed("on Monday", WEDNESDAY), "2025-06-16T09:00:00+02:00");
+        assert_eq!(parsed("  TOMORROW   at  5:30PM ", WEDNESDAY), "2025-06-12T17:30:00+02:00");
+        assert_eq!(parsed("9am tomorrow", WEDNESDAY), "2025-06-12T09:00:00+02:00");
+        assert_eq!(parsed("12am tomorrow", WEDNESDAY), "2025-06-12T00:00:00+02:00");
+        assert_eq!(parsed("12pm fri", WEDNESDAY), "2025-06-13T12:00:00+02:00");
+    }
+
+    #[test]
+    fn test_phrases_across_year_ends_and_leap_days() {
+        assert_eq!(parsed("tomorrow", "2025-12-31T22:00:00-03:00"), "2026-01-01T09:00:00-03:00");
+        assert_eq!(parsed("in 1 year", "2024-02-29T10:00:00Z"), "2025-02-28T10:00:00Z");
+    }
+
+    #[test]
+    fn test_repeated_or_impossible_phrases_are_not_dates() {
+        for input in ["tomorrow tomorrow", "12:5pm", "0am", "24:00", "in -1 hours", "in 2 fortnights", "at", "next"] {
+            assert!(parse(input, at(WEDNESDAY)).is_none(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn test_unparseable_input_is_a_validation_error() {
+        for input in ["", "someday", "in many hours", "tomorrow 25:00", "13pm", "next blursday", "9"] {
+            match parse_to_rfc3339(input, at("2025-06-11T15:20:00Z")) {
+                Err(AppError::Validation(message)) => assert!(message.contains(input), "{}", message),
+                other => panic!("{:?} parsed as {:?}", input, other),
+            }
+        }
+    }
+}
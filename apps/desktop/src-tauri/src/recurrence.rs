@@ -0,0 +1,116 @@
+//! Recurring reminder rules
+//!
+//! A rule repeats every `interval` days, weeks, months or years counted from
+//! the series start. Occurrences are always computed from the start rather
+//! than from the previous occurrence, so a monthly reminder on the 31st lands
+//! on Feb 28 and comes back to Mar 31 instead of drifting to the 28th.
+
+use chrono::{DateTime, Duration, Months, NaiveDateTime, SecondsFormat, Utc};
+
+use crate::error::{AppError, Result};
+use crate::models::{Recurrence, RecurrenceFrequency};
+
+/// Parse a stored timestamp, either RFC3339 or SQLite's `datetime()` form (UTC)
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|dt| dt.and_utc()))
+        .ok()
+}
+
+pub fn format_timestamp(value: DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn validate(rule: &Recurrence) -> Result<()> {
+    if rule.interval == 0 {
+        return Err(AppError::Validation("Recurrence interval must be at least 1".to_string()));
+    }
+    for (field, value) in [("until", &rule.until), ("start", &rule.start)] {
+        if let Some(value) = value {
+            if parse_timestamp(value).is_none() {
+                return Err(AppError::Validation(format!(
+                    "Recurrence {} must be an RFC3339 timestamp, got '{}'",
+                    field, value
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `n`th occurrence after `start` (`n = 0` is the start itself)
+fn nth(start: DateTime<Utc>, rule: &Recurrence, n: u32) -> Option<DateTime<Utc>> {
+    let steps = n.checked_mul(rule.interval)?;
+    match rule.frequency {
+        RecurrenceFrequency::Daily => start.checked_add_signed(Duration::days(steps.into())),
+        RecurrenceFrequency::Weekly => start.checked_add_signed(Duration::weeks(steps.into())),
+        RecurrenceFrequency::Monthly => start.checked_add_months(Months::new(steps)),
+        RecurrenceFrequency::Yearly => start.checked_add_months(Months::new(steps.checked_mul(12)?)),
+    }
+}
+
+/// Occurrences of the series in order, ending at `until` when set
+pub fn occurrences(start: DateTime<Utc>, rule: &Recurrence) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+    let until = rule.until.as_deref().and_then(parse_timestamp);
+    (0..)
+        .map_while(move |n| nth(start, rule, n))
+        .take_while(move |occurrence| until.is_none_or(|until| *occurrence <= until))
+}
+
+/// First occurrence strictly after `after`, or `None` once the series is over
+pub fn next_after(start: DateTime<Utc>, rule: &Recurrence, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    occurrences(start, rule).find(|occurrence| *occurrence > after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(frequency: RecurrenceFrequency, interval: u32) -> Recurrence {
+        Recurrence {
+            frequency,
+            interval,
+            until: None,
+            start: None,
+        }
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        parse_timestamp(value).unwrap()
+    }
+
+    #[test]
+    fn test_monthly_clamps_to_month_end_without_drifting() {
+        let start = at("2025-01-31T09:00:00Z");
+        let monthly = rule(RecurrenceFrequency::Monthly, 1);
+        let dates: Vec<String> = occurrences(start, &monthly).take(4).map(format_timestamp).collect();
+        assert_eq!(
+            dates,
+            vec![
+                "2025-01-31T09:00:00Z",
+                "2025-02-28T09:00:00Z",
+                "2025-03-31T09:00:00Z",
+                "2025-04-30T09:00:00Z",
+            ]
+        );
+
+        // Leap day yearly series falls back to Feb 28 in common years
+        let yearly = rule(RecurrenceFrequency::Yearly, 1);
+        let next = next_after(at("2024-02-29T00:00:00Z"), &yearly, at("2024-03-01T00:00:00Z")).unwrap();
+        assert_eq!(format_timestamp(next), "2025-02-28T00:00:00Z");
+    }
+
+    #[test]
+    fn test_next_after_respects_interval_and_until() {
+        let start = at("2025-06-02T08:00:00Z");
+        let mut fortnightly = rule(RecurrenceFrequency::Weekly, 2);
+        let next = next_after(start, &fortnightly, at("2025-06-20T00:00:00Z")).unwrap();
+        assert_eq!(format_timestamp(next), "2025-06-30T08:00:00Z");
+
+        fortnightly.until = Some("2025-06-29T00:00:00Z".to_string());
+        assert_eq!(next_after(start, &fortnightly, at("2025-06-20T00:00:00Z")), None);
+
+        assert!(validate(&rule(RecurrenceFrequency::Daily, 0)).is_err());
+    }
+}
@@ -6,11 +6,15 @@ CREATE TABLE IF NOT EXISTS notes (
     id TEXT PRIMARY KEY,
     title TEXT NOT NULL DEFAULT '',
     content TEXT NOT NULL DEFAULT '',
+    excerpt TEXT,
     notebook_id TEXT REFERENCES notebooks(id) ON DELETE SET NULL,
     tags TEXT NOT NULL DEFAULT '[]',
     status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'trashed')),
     is_pinned INTEGER NOT NULL DEFAULT 0,
+    pinned_order INTEGER,
+    archived_by_notebook TEXT,
     revision INTEGER NOT NULL DEFAULT 1,
+    needs_push INTEGER NOT NULL DEFAULT 1, -- changed here since the last push
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now')),
     deleted_at TEXT
@@ -23,7 +27,10 @@ CREATE TABLE IF NOT EXISTS notebooks (
     color TEXT,
     icon TEXT,
     parent_id TEXT REFERENCES notebooks(id) ON DELETE SET NULL,
+    is_archived INTEGER NOT NULL DEFAULT 0,
+    sync_excluded INTEGER NOT NULL DEFAULT 0, -- kept on this device only
     revision INTEGER NOT NULL DEFAULT 1,
+    needs_push INTEGER NOT NULL DEFAULT 1, -- changed here since the last push
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now')),
     deleted_at TEXT
@@ -35,6 +42,7 @@ CREATE TABLE IF NOT EXISTS tags (
     name TEXT NOT NULL UNIQUE,
     color TEXT,
     revision INTEGER NOT NULL DEFAULT 1,
+    needs_push INTEGER NOT NULL DEFAULT 1, -- changed here since the last push
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now')),
     deleted_at TEXT
@@ -45,17 +53,109 @@ CREATE TABLE IF NOT EXISTS sync_state (
     id INTEGER PRIMARY KEY CHECK (id = 1),
     last_pull_revision INTEGER NOT NULL DEFAULT 0,
     last_push_revision INTEGER NOT NULL DEFAULT 0,
-    last_synced_at TEXT
+    last_synced_at TEXT,
+    -- Per-type cursors; last_*_revision hold the lowest of each set
+    notes_pull_revision INTEGER NOT NULL DEFAULT 0,
+    notebooks_pull_revision INTEGER NOT NULL DEFAULT 0,
+    tags_pull_revision INTEGER NOT NULL DEFAULT 0,
+    reminders_pull_revision INTEGER NOT NULL DEFAULT 0,
+    notes_push_revision INTEGER NOT NULL DEFAULT 0,
+    notebooks_push_revision INTEGER NOT NULL DEFAULT 0,
+    tags_push_revision INTEGER NOT NULL DEFAULT 0,
+    reminders_push_revision INTEGER NOT NULL DEFAULT 0
 );
 
 -- Initialize sync state
 INSERT OR IGNORE INTO sync_state (id, last_pull_revision, last_push_revision) VALUES (1, 0, 0);
 
+-- Tombstones for hard-deleted rows, so deletions propagate through sync
+CREATE TABLE IF NOT EXISTS deleted_entities (
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    revision INTEGER NOT NULL,
+    deleted_at TEXT NOT NULL,
+    needs_push INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (entity_type, entity_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deleted_entities_revision ON deleted_entities(revision);
+
+-- Pulled reminders whose note hasn't arrived yet, retried on the next merge
+CREATE TABLE IF NOT EXISTS deferred_reminders (
+    id TEXT PRIMARY KEY,
+    payload TEXT NOT NULL,
+    received_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Conflicts a sync resolved by discarding one side, kept until the user
+-- confirms or changes the outcome. Titles and contents are note snapshots,
+-- stored as they were in the notes table (possibly encrypted).
+CREATE TABLE IF NOT EXISTS sync_conflicts (
+    id TEXT PRIMARY KEY,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    local_revision INTEGER NOT NULL,
+    remote_revision INTEGER NOT NULL,
+    local_title TEXT,
+    local_content TEXT,
+    remote_title TEXT,
+    remote_content TEXT,
+    resolution TEXT NOT NULL,
+    resolved_at TEXT, -- NULL until resolve_conflict
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_conflicts_unresolved ON sync_conflicts(resolved_at);
+
+-- Note versions that lost an automatic conflict resolution. Not tied to the
+-- note, so a version can still be restored after the note is deleted.
+CREATE TABLE IF NOT EXISTS conflict_archive (
+    id TEXT PRIMARY KEY,
+    note_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '[]',
+    revision INTEGER NOT NULL,
+    source TEXT NOT NULL, -- 'local' or 'remote': the side that lost
+    resolution TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_conflict_archive_note ON conflict_archive(note_id, created_at);
+
+-- Each note as it was when last pulled or pushed, the common ancestor for
+-- field-level merges. Title and content are SHA-256 hashes of the plaintext.
+CREATE TABLE IF NOT EXISTS note_sync_base (
+    note_id TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+    title_hash TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    notebook_id TEXT,
+    tags TEXT NOT NULL DEFAULT '[]',
+    status TEXT NOT NULL,
+    is_pinned INTEGER NOT NULL DEFAULT 0,
+    pinned_order INTEGER,
+    deleted_at TEXT
+);
+
+-- Tags carried by each note. The notes.tags JSON stays for sync and export;
+-- the triggers below derive this table from it on every write, linking the
+-- tags that exist. Writers create or revive the tag rows first.
+CREATE TABLE IF NOT EXISTS note_tags (
+    note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    tag_id TEXT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (note_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_note_tags_tag ON note_tags(tag_id);
+
 -- Indexes for performance
 CREATE INDEX IF NOT EXISTS idx_notes_notebook ON notes(notebook_id) WHERE deleted_at IS NULL;
 CREATE INDEX IF NOT EXISTS idx_notes_status ON notes(status) WHERE deleted_at IS NULL;
 CREATE INDEX IF NOT EXISTS idx_notes_updated ON notes(updated_at);
 CREATE INDEX IF NOT EXISTS idx_notes_revision ON notes(revision);
+CREATE INDEX IF NOT EXISTS idx_notes_created_dt ON notes(datetime(created_at)) WHERE deleted_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_notes_updated_dt ON notes(datetime(updated_at)) WHERE deleted_at IS NULL;
 CREATE INDEX IF NOT EXISTS idx_notebooks_parent ON notebooks(parent_id) WHERE deleted_at IS NULL;
 CREATE INDEX IF NOT EXISTS idx_notebooks_revision ON notebooks(revision);
 CREATE INDEX IF NOT EXISTS idx_tags_name ON tags(name) WHERE deleted_at IS NULL;
@@ -70,34 +170,119 @@ CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(
     tokenize='porter unicode61'
 );
 
--- Triggers to keep FTS in sync with notes table
+-- Triggers to keep note_tags and FTS in sync with notes table.
+-- note_tags links a note to the tags already named in its JSON list; the
+-- tag rows themselves are created by the code writing the note, never here.
+-- note_tags is refreshed first so the FTS tags column can be read back from it.
+-- Malformed tag JSON counts as no tags rather than failing the write.
 -- Insert trigger
 CREATE TRIGGER IF NOT EXISTS notes_fts_insert AFTER INSERT ON notes BEGIN
+    DELETE FROM note_tags WHERE note_id = NEW.id;
+    INSERT OR IGNORE INTO note_tags (note_id, tag_id, position)
+    SELECT NEW.id, t.id, MIN(j.key)
+    FROM json_each(CASE WHEN json_valid(NEW.tags) THEN NEW.tags ELSE '[]' END) j
+    JOIN tags t ON t.name = j.value
+    WHERE j.type = 'text' AND j.key IS NOT NULL
+    GROUP BY t.id;
     INSERT INTO notes_fts(id, title, content, tags)
-    VALUES (NEW.id, NEW.title, NEW.content, NEW.tags);
+    VALUES (NEW.id, NEW.title, NEW.content, (
+        SELECT json_group_array(name) FROM (
+            SELECT t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id
+            WHERE nt.note_id = NEW.id ORDER BY nt.position
+        )
+    ));
 END;
 
--- Update trigger
+-- Update trigger (note_tags is only touched when the tag list changed)
 CREATE TRIGGER IF NOT EXISTS notes_fts_update AFTER UPDATE ON notes BEGIN
+    DELETE FROM note_tags WHERE note_id = NEW.id AND NEW.tags IS NOT OLD.tags;
+    INSERT OR IGNORE INTO note_tags (note_id, tag_id, position)
+    SELECT NEW.id, t.id, MIN(j.key)
+    FROM json_each(CASE WHEN json_valid(NEW.tags) THEN NEW.tags ELSE '[]' END) j
+    JOIN tags t ON t.name = j.value
+    WHERE j.type = 'text' AND j.key IS NOT NULL AND NEW.tags IS NOT OLD.tags
+    GROUP BY t.id;
     DELETE FROM notes_fts WHERE id = OLD.id;
     INSERT INTO notes_fts(id, title, content, tags)
-    VALUES (NEW.id, NEW.title, NEW.content, NEW.tags);
+    VALUES (NEW.id, NEW.title, NEW.content, (
+        SELECT json_group_array(name) FROM (
+            SELECT t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id
+            WHERE nt.note_id = NEW.id ORDER BY nt.position
+        )
+    ));
+END;
+
+-- A tag arriving after the notes naming it (a pull or an import), or taking
+-- over a name, links those notes by name and refreshes their search rows
+CREATE TRIGGER IF NOT EXISTS tags_link_notes AFTER INSERT ON tags BEGIN
+    INSERT OR IGNORE INTO note_tags (note_id, tag_id, position)
+    SELECT n.id, NEW.id, MIN(j.key)
+    FROM notes n, json_each(CASE WHEN json_valid(n.tags) THEN n.tags ELSE '[]' END) j
+    WHERE j.value = NEW.name AND j.type = 'text' AND j.key IS NOT NULL
+    GROUP BY n.id;
+    UPDATE notes_fts SET tags = (
+        SELECT json_group_array(name) FROM (
+            SELECT t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id
+            WHERE nt.note_id = notes_fts.id ORDER BY nt.position
+        )
+    )
+    WHERE id IN (SELECT note_id FROM note_tags WHERE tag_id = NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS tags_link_renamed AFTER UPDATE OF name ON tags WHEN NEW.name IS NOT OLD.name BEGIN
+    INSERT OR IGNORE INTO note_tags (note_id, tag_id, position)
+    SELECT n.id, NEW.id, MIN(j.key)
+    FROM notes n, json_each(CASE WHEN json_valid(n.tags) THEN n.tags ELSE '[]' END) j
+    WHERE j.value = NEW.name AND j.type = 'text' AND j.key IS NOT NULL
+    GROUP BY n.id;
+    UPDATE notes_fts SET tags = (
+        SELECT json_group_array(name) FROM (
+            SELECT t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id
+            WHERE nt.note_id = notes_fts.id ORDER BY nt.position
+        )
+    )
+    WHERE id IN (SELECT note_id FROM note_tags WHERE tag_id = NEW.id);
 END;
 
 -- Delete trigger
 CREATE TRIGGER IF NOT EXISTS notes_fts_delete AFTER DELETE ON notes BEGIN
+    DELETE FROM note_tags WHERE note_id = OLD.id;
     DELETE FROM notes_fts WHERE id = OLD.id;
 END;
 
+-- App settings (key/value)
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Checkbox items parsed from note content
+CREATE TABLE IF NOT EXISTS note_tasks (
+    id TEXT PRIMARY KEY,
+    note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    line_no INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    checked INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_note_tasks_note ON note_tasks(note_id, line_no);
+CREATE INDEX IF NOT EXISTS idx_note_tasks_open ON note_tasks(note_id) WHERE checked = 0;
+
 -- Reminders table
 CREATE TABLE IF NOT EXISTS reminders (
     id TEXT PRIMARY KEY,
-    note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    note_id TEXT REFERENCES notes(id) ON DELETE CASCADE, -- NULL for standalone reminders
     message TEXT NOT NULL DEFAULT '',
     due_date TEXT NOT NULL,
     completed INTEGER NOT NULL DEFAULT 0,
     notified INTEGER NOT NULL DEFAULT 0,
+    recurrence TEXT,
+    snoozed_from TEXT,
     revision INTEGER NOT NULL DEFAULT 1,
+    needs_push INTEGER NOT NULL DEFAULT 1, -- changed here since the last push
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at TEXT NOT NULL DEFAULT (datetime('now')),
     deleted_at TEXT
@@ -48,7 +48,7 @@ pub fn search_notes(db: &Database, options: SearchOptions) -> Result<Vec<SearchR
     let mut sql = String::from(
         "SELECT
             n.id, n.title, n.content, n.notebook_id, n.tags, n.status,
-            n.is_pinned, n.revision, n.created_at, n.updated_at, n.deleted_at,
+            n.is_pinned, n.revision, n.created_at, n.updated_at, n.deleted_at, n.pinned_order,
             bm25(notes_fts) as rank,
             snippet(notes_fts, 2, '<mark>', '</mark>', '...', 32) as snippet
          FROM notes_fts fts
@@ -143,9 +143,10 @@ fn map_search_result(row: &rusqlite::Row) -> rusqlite::Result<SearchResult> {
             created_at: row.get(8)?,
             updated_at: row.get(9)?,
             deleted_at: row.get(10)?,
+            pinned_order: row.get(11)?,
         },
-        rank: row.get(11)?,
-        snippet: row.get(12)?,
+        rank: row.get(12)?,
+        snippet: row.get(13)?,
     })
 }
 
@@ -157,10 +158,16 @@ pub fn rebuild_fts_index(db: &Database) -> Result<()> {
     // Clear existing FTS data
     conn.execute("DELETE FROM notes_fts", [])?;
 
-    // Repopulate from notes table
+    // Repopulate from notes table, with tags read from note_tags like the triggers do
     conn.execute(
         "INSERT INTO notes_fts(id, title, content, tags)
-         SELECT id, title, content, tags FROM notes",
+         SELECT id, title, content, (
+             SELECT json_group_array(name) FROM (
+                 SELECT t.name FROM note_tags nt JOIN tags t ON t.id = nt.tag_id
+                 WHERE nt.note_id = notes.id ORDER BY nt.position
+             )
+         )
+         FROM notes",
         [],
     )?;
 
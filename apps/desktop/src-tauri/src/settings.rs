@@ -0,0 +1,214 @@
+//! App settings
+//!
+//! Simple key/value store in the `settings` table. Known keys are validated
+//! on write; reads of unset keys fall back to the defaults below.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::State;
+
+use crate::db::Database;
+use crate::error::{AppError, Result};
+use crate::validation;
+
+// =============================================================================
+// Keys
+// =============================================================================
+
+/// Largest note content accepted by note writes, in bytes
+pub const MAX_CONTENT_BYTES: &str = "max_content_bytes";
+pub const DEFAULT_MAX_CONTENT_BYTES: i64 = 5 * 1024 * 1024;
+
+/// Tag applied to every quick-capture note
+pub const CAPTURE_TAG: &str = "capture_tag";
+pub const DEFAULT_CAPTURE_TAG: &str = "inbox";
+
+/// Notebook quick-capture notes go to; the "Inbox" notebook when unset
+pub const CAPTURE_NOTEBOOK_ID: &str = "capture_notebook_id";
+
+/// Notebook new notes go to when the caller doesn't pick one
+pub const DEFAULT_NOTEBOOK_ID: &str = "default_notebook_id";
+
+/// Days a deleted notebook stays in the trash before `empty_notebook_trash` purges it
+pub const TRASH_RETENTION_DAYS: &str = "trash_retention_days";
+pub const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+/// Deepest allowed notebook nesting; root notebooks are depth 1
+pub const MAX_NOTEBOOK_DEPTH: &str = "max_notebook_depth";
+pub const DEFAULT_MAX_NOTEBOOK_DEPTH: i64 = 6;
+
+/// This install's sync identity; generated on first sync
+pub const DEVICE_ID: &str = "device_id";
+
+/// Bearer token sent to the sync server; unauthenticated when unset
+pub const SYNC_AUTH_TOKEN: &str = "sync_auth_token";
+
+/// How pulled notes that conflict with local edits are resolved; LWW when unset
+pub const SYNC_CONFLICT_STRATEGY: &str = "sync_conflict_strategy";
+
+/// Whether the background task syncs on its own ("true" / "false")
+pub const SYNC_ENABLED: &str = "sync_enabled";
+
+/// Minutes between background syncs
+pub const SYNC_INTERVAL_MINUTES: &str = "sync_interval_minutes";
+pub const DEFAULT_SYNC_INTERVAL_MINUTES: i64 = 15;
+
+/// Tries per sync request before a network error or 5xx is reported
+pub const SYNC_RETRY_ATTEMPTS: &str = "sync_retry_attempts";
+pub const DEFAULT_SYNC_RETRY_ATTEMPTS: i64 = 3;
+
+/// Most entities of each type per pull request; a sync pulls pages until done
+pub const SYNC_PULL_PAGE_SIZE: &str = "sync_pull_page_size";
+pub const DEFAULT_SYNC_PULL_PAGE_SIZE: i64 = 500;
+
+/// Sync server the background task talks to
+pub const SERVER_URL: &str = "server_url";
+
+/// Days a pushed soft deletion is kept before its row is purged
+pub const TOMBSTONE_RETENTION_DAYS: &str = "tombstone_retention_days";
+pub const DEFAULT_TOMBSTONE_RETENTION_DAYS: i64 = 90;
+
+/// Whether a successful sync purges expired soft deletions ("true" / "false")
+pub const PURGE_TOMBSTONES_AFTER_SYNC: &str = "purge_tombstones_after_sync";
+
+/// Accept any TLS certificate from the sync server, e.g. a self-signed one
+/// on a home server ("true" / "false"). Anyone on the network can then read
+/// and alter the sync traffic.
+pub const SYNC_ALLOW_SELF_SIGNED: &str = "sync_allow_self_signed";
+
+/// Check a value before storing it under a known key
+fn validate(key: &str, value: &str) -> Result<()> {
+    match key {
+        MAX_CONTENT_BYTES
+        | MAX_NOTEBOOK_DEPTH
+        | TRASH_RETENTION_DAYS
+        | SYNC_INTERVAL_MINUTES
+        | SYNC_RETRY_ATTEMPTS
+        | SYNC_PULL_PAGE_SIZE
+        | TOMBSTONE_RETENTION_DAYS => match value.parse::<i64>() {
+            Ok(n) if n > 0 => Ok(()),
+            _ => Err(AppError::Validation(format!("{} must be a positive integer", key))),
+        },
+        CAPTURE_TAG if value.trim().is_empty() => {
+            Err(AppError::Validation(format!("{} can't be empty", key)))
+        }
+        CAPTURE_TAG => validation::tag_name(value),
+        DEVICE_ID if uuid::Uuid::parse_str(value).is_err() => {
+            Err(AppError::Validation(format!("{} must be a UUID", key)))
+        }
+        SYNC_ENABLED | PURGE_TOMBSTONES_AFTER_SYNC | SYNC_ALLOW_SELF_SIGNED if value != "true" && value != "false" => {
+            Err(AppError::Validation(format!("{} must be true or false", key)))
+        }
+        SYNC_CONFLICT_STRATEGY => crate::sync::ConflictStrategy::parse(value).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+// =============================================================================
+// Read / Write
+// =============================================================================
+
+pub fn get(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let value = conn
+        .query_row("SELECT value FROM settings WHERE key = ?", params![key], |row| row.get(0))
+        .optional()?;
+    Ok(value)
+}
+
+/// Integer setting, or `default` when unset or unparsable
+pub fn get_i64(conn: &Connection, key: &str, default: i64) -> Result<i64> {
+    Ok(get(conn, key)?.and_then(|v| v.parse().ok()).unwrap_or(default))
+}
+
+pub fn set(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    validate(key, value)?;
+    conn.execute(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+/// Remove a setting so it falls back to its default
+pub fn reset(conn: &Connection, key: &str) -> Result<()> {
+    conn.execute("DELETE FROM settings WHERE key = ?", params![key])?;
+    Ok(())
+}
+
+/// Reset a setting only if it currently holds `value`
+pub fn reset_if(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute("DELETE FROM settings WHERE key = ? AND value = ?", params![key, value])?;
+    Ok(())
+}
+
+// =============================================================================
+// Tauri Commands
+// =============================================================================
+
+#[tauri::command]
+pub fn get_setting(db: State<'_, Database>, key: String) -> Result<Option<String>> {
+    get(&db.conn(), &key)
+}
+
+/// Store a setting; `None` resets it to the default
+#[tauri::command]
+pub fn set_setting(db: State<'_, Database>, key: String, value: Option<String>) -> Result<()> {
+    let conn = db.conn();
+    match value {
+        Some(value) => set(&conn, &key, &value),
+        None => reset(&conn, &key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_roundtrip_and_reset() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+
+        assert_eq!(get_i64(&conn, MAX_CONTENT_BYTES, DEFAULT_MAX_CONTENT_BYTES).unwrap(), DEFAULT_MAX_CONTENT_BYTES);
+
+        set(&conn, MAX_CONTENT_BYTES, "1024").unwrap();
+        set(&conn, MAX_CONTENT_BYTES, "2048").unwrap();
+        assert_eq!(get_i64(&conn, MAX_CONTENT_BYTES, DEFAULT_MAX_CONTENT_BYTES).unwrap(), 2048);
+
+        reset(&conn, MAX_CONTENT_BYTES).unwrap();
+        assert_eq!(get(&conn, MAX_CONTENT_BYTES).unwrap(), None);
+    }
+
+    #[test]
+    fn test_settings_validate_known_keys() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+
+        for bad in ["", "0", "-5", "lots"] {
+            let result = set(&conn, MAX_CONTENT_BYTES, bad);
+            assert!(matches!(result, Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+
+        // Unknown keys are free-form
+        set(&conn, "ui.theme", "dark").unwrap();
+        assert_eq!(get(&conn, "ui.theme").unwrap().as_deref(), Some("dark"));
+    }
+
+    #[test]
+    fn test_self_signed_setting_is_strictly_true_or_false() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+
+        for bad in ["yes", "1", "TRUE", ""] {
+            let result = set(&conn, SYNC_ALLOW_SELF_SIGNED, bad);
+            assert!(matches!(result, Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+        assert_eq!(get(&conn, SYNC_ALLOW_SELF_SIGNED).unwrap(), None);
+
+        // The sync client builds either way
+        for allowed in ["true", "false"] {
+            set(&conn, SYNC_ALLOW_SELF_SIGNED, allowed).unwrap();
+            assert!(crate::sync::http_client(&conn).is_ok());
+        }
+    }
+}
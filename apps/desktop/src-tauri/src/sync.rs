@@ -6,14 +6,22 @@
 //! - Push: send local changes to server
 //! - Conflict resolution: higher revision wins; if equal, newer updated_at wins
 
-use rusqlite::params;
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, State};
 use ts_rs::TS;
 
+use crate::auto_sync::AutoSync;
+use crate::checksum;
+use crate::commands::{notes, reminders, tags};
+use crate::crypto;
 use crate::db::Database;
-use crate::error::Result;
-use crate::models::{Note, NoteStatus, Notebook, Tag};
+use crate::error::{AppError, Result};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::models::{CreateNoteInput, Note, NoteStatus, Notebook, Reminder, Tag, UpdateNoteInput};
+use crate::settings;
+use crate::tasks;
+use crate::validation;
 
 // =============================================================================
 // Types
@@ -34,14 +42,75 @@ pub struct SyncResult {
     pub pushed: SyncStats,
     pub conflicts: Vec<SyncConflict>,
     pub last_synced_at: String,
+    /// Set when the pull was applied but the push failed; the push cursor
+    /// didn't move, so retrying pushes the same changes
+    #[serde(default)]
+    pub push_error: Option<String>,
+    /// Entities the server refused to store, when that's why the push failed
+    #[serde(default)]
+    pub rejected: Vec<RejectedEntity>,
+    /// Seconds the server asked to wait, when it rate-limited the push
+    #[serde(default)]
+    pub retry_after_secs: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS, Default)]
+/// How many rows of one type a pull would create, update or conflict on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct PreviewCounts {
+    pub created: i32,
+    pub updated: i32,
+    pub conflicted: i32,
+}
+
+/// What a sync would do, from `preview_sync`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct SyncPreview {
+    pub notes: PreviewCounts,
+    pub notebooks: PreviewCounts,
+    pub tags: PreviewCounts,
+    pub reminders: PreviewCounts,
+    /// Hard deletions the pull would apply
+    pub deleted: i32,
+    /// Rows and tombstones the push would send
+    pub to_push: i32,
+    pub server_revision: i64,
+}
+
+impl SyncPreview {
+    fn counts_mut(&mut self, entity_type: &str) -> Option<&mut PreviewCounts> {
+        match entity_type {
+            "note" => Some(&mut self.notes),
+            "notebook" => Some(&mut self.notebooks),
+            "tag" => Some(&mut self.tags),
+            "reminder" => Some(&mut self.reminders),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default, PartialEq)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct SyncStats {
     pub notes: i32,
     pub notebooks: i32,
     pub tags: i32,
+    #[serde(default)]
+    pub reminders: i32,
+    /// Hard deletions applied
+    #[serde(default)]
+    pub deleted: i32,
+}
+
+impl std::ops::AddAssign for SyncStats {
+    fn add_assign(&mut self, other: SyncStats) {
+        self.notes += other.notes;
+        self.notebooks += other.notebooks;
+        self.tags += other.tags;
+        self.reminders += other.reminders;
+        self.deleted += other.deleted;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -51,116 +120,464 @@ pub struct SyncConflict {
     pub entity_id: String,
     pub local_revision: i64,
     pub remote_revision: i64,
-    pub resolution: String, // "local_wins" | "remote_wins"
+    pub resolution: String, // "local_wins" | "remote_wins" | "keep_both" | "merged"
+}
+
+/// A pushed entity that failed the server's validation, e.g. content above
+/// its size limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct RejectedEntity {
+    pub entity_type: String,
+    pub id: String,
+    pub field: String,
+    pub message: String,
+}
+
+/// A conflict kept in `sync_conflicts` until the user resolves it. The
+/// snapshots are only set for notes.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct StoredConflict {
+    pub id: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub local_revision: i64,
+    pub remote_revision: i64,
+    pub local_title: Option<String>,
+    pub local_content: Option<String>,
+    pub remote_title: Option<String>,
+    pub remote_content: Option<String>,
+    /// How the sync resolved it
+    pub resolution: String,
+    pub created_at: String,
 }
 
+/// A note version that lost an automatic conflict resolution, kept in
+/// `conflict_archive` so `restore_conflict_version` can bring it back
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct ArchivedNoteVersion {
+    pub id: String,
+    pub note_id: String,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub revision: i64,
+    /// Which side lost: "local" or "remote"
+    pub source: String,
+    /// How the sync resolved the conflict
+    pub resolution: String,
+    pub created_at: String,
+}
+
+/// Outcome picked by the user in `resolve_conflict`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictChoice {
+    Local,
+    Remote,
+    /// Keep the note's title with the caller's merged content
+    Merged,
+}
+
+impl ConflictChoice {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConflictChoice::Local => "local",
+            ConflictChoice::Remote => "remote",
+            ConflictChoice::Merged => "merged",
+        }
+    }
+}
+
+/// How a pulled note that conflicts with a local edit is resolved.
+/// Notebooks, tags and reminders always use LWW.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Default)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Higher revision wins, then newer `updated_at`
+    #[default]
+    Lww,
+    LocalWins,
+    RemoteWins,
+    /// LWW, but the losing side is kept as a "conflicted copy" note
+    KeepBoth,
+}
+
+impl ConflictStrategy {
+    pub fn parse(value: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(|_| {
+            AppError::Validation(format!(
+                "Unknown conflict strategy '{}' (expected lww, local_wins, remote_wins or keep_both)",
+                value
+            ))
+        })
+    }
+
+    /// `strategy` when given, else the stored setting
+    pub fn resolve(conn: &Connection, strategy: Option<ConflictStrategy>) -> Result<Self> {
+        match strategy {
+            Some(strategy) => Ok(strategy),
+            None => match settings::get(conn, settings::SYNC_CONFLICT_STRATEGY)? {
+                Some(value) => Self::parse(&value),
+                None => Ok(Self::default()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct SyncPayload {
     pub notes: Vec<Note>,
     pub notebooks: Vec<Notebook>,
     pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
+    /// Tombstones of hard-deleted entities
+    #[serde(default)]
+    pub deleted: Vec<DeletedEntity>,
     pub since_revision: i64,
 }
 
+/// Tombstone left behind by a hard delete
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct DeletedEntity {
+    pub entity_type: String, // "note" | "notebook" | "tag" | "reminder"
+    pub entity_id: String,
+    pub revision: i64,
+    pub deleted_at: String,
+}
+
+/// Where this device stands against the server, for the status bar
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatusKind {
+    UpToDate,
+    /// Local changes are waiting to be pushed
+    LocalChanges,
+    /// The server has changes this device hasn't pulled
+    ServerNewer,
+    /// Both of the above
+    Diverged,
+    /// The server was asked but didn't answer
+    Offline,
+}
+
+impl SyncStatusKind {
+    fn of(pending_changes: i32, local_revision: i64, server_revision: Option<i64>) -> Self {
+        let server_newer = server_revision.is_some_and(|revision| revision > local_revision);
+        match (pending_changes > 0, server_newer) {
+            (false, false) => SyncStatusKind::UpToDate,
+            (true, false) => SyncStatusKind::LocalChanges,
+            (false, true) => SyncStatusKind::ServerNewer,
+            (true, true) => SyncStatusKind::Diverged,
+        }
+    }
+}
+
+/// Outcome of `get_sync_status`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct SyncStatus {
+    pub status: SyncStatusKind,
+    pub pending_changes: i32,
+    pub last_synced_at: Option<String>,
+    /// Newest server revision this device has pulled or pushed up to
+    pub local_revision: i64,
+    /// The server's global revision, when it was asked and answered
+    pub server_revision: Option<i64>,
+}
+
+/// Outcome of `check_server_connection`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct ServerConnection {
+    pub reachable: bool,
+    /// The URL that was checked, in the canonical form to store
+    pub server_url: String,
+}
+
+/// This device as the sync server sees it
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct DeviceInfo {
+    pub device_id: String,
+    pub last_synced_at: Option<String>,
+    pub last_pull_revision: i64,
+    pub last_push_revision: i64,
+}
+
+/// A revision per entity type. Deletions follow the cursor of the type
+/// they delete.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct SyncCursors {
+    pub notes: i64,
+    pub notebooks: i64,
+    pub tags: i64,
+    pub reminders: i64,
+}
+
+/// Tables with their own cursor, in `SyncCursors` field order
+pub const CURSOR_TABLES: [&str; 4] = ["notes", "notebooks", "tags", "reminders"];
+
+impl SyncCursors {
+    pub fn all(revision: i64) -> Self {
+        SyncCursors {
+            notes: revision,
+            notebooks: revision,
+            tags: revision,
+            reminders: revision,
+        }
+    }
+
+    fn values(&self) -> [i64; 4] {
+        [self.notes, self.notebooks, self.tags, self.reminders]
+    }
+
+    /// The oldest cursor; everything up to it is synced for every type
+    pub fn min(&self) -> i64 {
+        self.values().into_iter().min().unwrap_or(0)
+    }
+
+    /// Every cursor moved up to at least `revision`
+    pub fn advanced_to(&self, revision: i64) -> Self {
+        SyncCursors {
+            notes: self.notes.max(revision),
+            notebooks: self.notebooks.max(revision),
+            tags: self.tags.max(revision),
+            reminders: self.reminders.max(revision),
+        }
+    }
+
+    /// Cursor for an entity type ("note", "notebook", ...); the oldest one
+    /// for unknown types
+    #[cfg(test)]
+    pub fn of(&self, entity_type: &str) -> i64 {
+        match entity_type {
+            "note" => self.notes,
+            "notebook" => self.notebooks,
+            "tag" => self.tags,
+            "reminder" => self.reminders,
+            _ => self.min(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../src/lib/bindings/")]
 pub struct LocalSyncState {
+    /// Lowest of `pull_cursors`
     pub last_pull_revision: i64,
+    /// Lowest of `push_cursors`
     pub last_push_revision: i64,
     pub last_synced_at: Option<String>,
+    /// Rows and tombstones flagged with `needs_push`
     pub pending_changes: i32,
+    pub pull_cursors: SyncCursors,
+    pub push_cursors: SyncCursors,
+}
+
+/// Local changes waiting to be pushed, e.g. while the server is unreachable
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct PushQueueSummary {
+    pub notes: i32,
+    pub notebooks: i32,
+    pub tags: i32,
+    pub reminders: i32,
+    /// Hard deletions
+    pub deleted: i32,
+    /// When the longest-waiting change was made (UTC), None when empty
+    pub oldest_change_at: Option<String>,
+}
+
+impl PushQueueSummary {
+    pub fn total(&self) -> i32 {
+        self.notes + self.notebooks + self.tags + self.reminders + self.deleted
+    }
 }
 
 // =============================================================================
 // Sync State Management
 // =============================================================================
 
-pub fn get_sync_state(db: &Database) -> Result<LocalSyncState> {
-    let conn = db.conn();
+fn read_cursors(conn: &Connection, direction: &str) -> Result<SyncCursors> {
+    let cursors = conn
+        .query_row(
+            &format!(
+                "SELECT notes_{0}_revision, notebooks_{0}_revision, tags_{0}_revision, reminders_{0}_revision
+                 FROM sync_state WHERE id = 1",
+                direction
+            ),
+            [],
+            |row| {
+                Ok(SyncCursors {
+                    notes: row.get(0)?,
+                    notebooks: row.get(1)?,
+                    tags: row.get(2)?,
+                    reminders: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(cursors.unwrap_or_default())
+}
 
-    let mut stmt = conn.prepare(
-        "SELECT last_pull_revision, last_push_revision, last_synced_at FROM sync_state WHERE id = 1",
+/// Store a pull or push cursor set, keeping `last_<direction>_revision` at its minimum
+fn write_cursors(conn: &Connection, direction: &str, cursors: &SyncCursors) -> Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        &format!(
+            "UPDATE sync_state SET notes_{0}_revision = ?, notebooks_{0}_revision = ?, tags_{0}_revision = ?,
+                 reminders_{0}_revision = ?, last_{0}_revision = ?, last_synced_at = ?
+             WHERE id = 1",
+            direction
+        ),
+        params![cursors.notes, cursors.notebooks, cursors.tags, cursors.reminders, cursors.min(), now],
     )?;
+    Ok(())
+}
 
-    let state = stmt
-        .query_row([], |row| {
-            Ok(LocalSyncState {
-                last_pull_revision: row.get(0)?,
-                last_push_revision: row.get(1)?,
-                last_synced_at: row.get(2)?,
-                pending_changes: 0, // Will be calculated below
-            })
-        })
-        .unwrap_or(LocalSyncState {
-            last_pull_revision: 0,
-            last_push_revision: 0,
-            last_synced_at: None,
-            pending_changes: 0,
-        });
+pub fn get_sync_state(db: &Database) -> Result<LocalSyncState> {
+    let conn = db.conn();
 
-    // Count pending changes (entities with revision > last_push_revision)
-    let pending: i32 = conn
-        .query_row(
-            "SELECT
-                (SELECT COUNT(*) FROM notes WHERE revision > ? AND deleted_at IS NULL) +
-                (SELECT COUNT(*) FROM notebooks WHERE revision > ? AND deleted_at IS NULL) +
-                (SELECT COUNT(*) FROM tags WHERE revision > ? AND deleted_at IS NULL)",
-            params![
-                state.last_push_revision,
-                state.last_push_revision,
-                state.last_push_revision
-            ],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
+    let last_synced_at: Option<String> = conn
+        .query_row("SELECT last_synced_at FROM sync_state WHERE id = 1", [], |row| row.get(0))
+        .optional()?
+        .flatten();
+    let pull_cursors = read_cursors(&conn, "pull")?;
+    let push_cursors = read_cursors(&conn, "push")?;
+
+    let pending = push_queue(&conn)?;
 
     Ok(LocalSyncState {
-        pending_changes: pending,
-        ..state
+        last_pull_revision: pull_cursors.min(),
+        last_push_revision: push_cursors.min(),
+        last_synced_at,
+        pending_changes: pending.total(),
+        pull_cursors,
+        push_cursors,
     })
 }
 
+/// Everything the next push will send, trashed rows and tombstones included
+pub fn push_queue(conn: &Connection) -> Result<PushQueueSummary> {
+    let mut queue = PushQueueSummary::default();
+    let mut oldest: Option<String> = None;
+    for (count, table, entity_type, changed_at) in [
+        (&mut queue.notes, "notes", "note", "updated_at"),
+        (&mut queue.notebooks, "notebooks", "notebook", "updated_at"),
+        (&mut queue.tags, "tags", "tag", "updated_at"),
+        (&mut queue.reminders, "reminders", "reminder", "updated_at"),
+        (&mut queue.deleted, "deleted_entities", "", "deleted_at"),
+    ] {
+        // strftime normalizes offsets and SQLite's own datetime format alike
+        let (queued, first): (i32, Option<String>) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), MIN(strftime('%Y-%m-%dT%H:%M:%SZ', {})) FROM {} WHERE needs_push = 1 AND {}",
+                changed_at,
+                table,
+                synced_rows(entity_type)
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        *count = queued;
+        oldest = match (oldest, first) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    queue.oldest_change_at = oldest;
+    Ok(queue)
+}
+
+/// Move every pull and/or push cursor to one revision
 fn update_sync_state(
     db: &Database,
     pull_revision: Option<i64>,
     push_revision: Option<i64>,
 ) -> Result<()> {
     let conn = db.conn();
-    let now = chrono::Utc::now().to_rfc3339();
 
     if let Some(rev) = pull_revision {
-        conn.execute(
-            "UPDATE sync_state SET last_pull_revision = ?, last_synced_at = ? WHERE id = 1",
-            params![rev, now],
-        )?;
+        write_cursors(&conn, "pull", &SyncCursors::all(rev))?;
     }
 
     if let Some(rev) = push_revision {
-        conn.execute(
-            "UPDATE sync_state SET last_push_revision = ?, last_synced_at = ? WHERE id = 1",
-            params![rev, now],
-        )?;
+        write_cursors(&conn, "push", &SyncCursors::all(rev))?;
     }
 
     Ok(())
 }
 
+/// Rewind one entity type's cursors and flag all of it for push, so the next
+/// sync pulls and pushes all of it
+pub fn reset_cursor(conn: &Connection, entity_type: &str) -> Result<()> {
+    let table = entity_table(entity_type)
+        .ok_or_else(|| AppError::Validation(format!("Unknown entity type '{}'", entity_type)))?;
+    for direction in ["pull", "push"] {
+        let mut cursors = read_cursors(conn, direction)?;
+        match entity_type {
+            "note" => cursors.notes = 0,
+            "notebook" => cursors.notebooks = 0,
+            "tag" => cursors.tags = 0,
+            _ => cursors.reminders = 0,
+        }
+        write_cursors(conn, direction, &cursors)?;
+    }
+    conn.execute(&format!("UPDATE {} SET needs_push = 1", table), [])?;
+    conn.execute("UPDATE deleted_entities SET needs_push = 1 WHERE entity_type = ?", params![entity_type])?;
+    Ok(())
+}
+
 // =============================================================================
 // Get Changes for Push
 // =============================================================================
 
-pub fn get_changes_since(db: &Database, since_revision: i64) -> Result<SyncPayload> {
+/// What still has to be pushed: every row changed on this device since its
+/// last successful push
+pub fn unpushed_changes(db: &Database) -> Result<SyncPayload> {
     let conn = db.conn();
+    let since_revision = read_cursors(&conn, "push")?.min();
+    collect_changes(&conn, |_| "needs_push = 1".to_string(), since_revision)
+}
 
-    // Get notes changed since revision
-    let mut notes_stmt = conn.prepare(
-        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at
-         FROM notes WHERE revision > ?",
-    )?;
+#[cfg(test)]
+pub fn get_changes_since(db: &Database, since_revision: i64) -> Result<SyncPayload> {
+    changes_since_cursors(db, &SyncCursors::all(since_revision))
+}
+
+/// Rows changed past their type's cursor, with tombstones following the
+/// cursor of the type they delete
+#[cfg(test)]
+pub fn changes_since_cursors(db: &Database, since: &SyncCursors) -> Result<SyncPayload> {
+    collect_changes(&db.conn(), |entity_type| format!("revision > {}", since.of(entity_type)), since.min())
+}
+
+/// Rows of each type matching `condition(entity_type)`
+fn collect_changes(
+    conn: &Connection,
+    condition: impl Fn(&str) -> String,
+    since_revision: i64,
+) -> Result<SyncPayload> {
+    let rows = |entity_type: &str| format!("({}) AND {}", condition(entity_type), synced_rows(entity_type));
+
+    let mut notes_stmt = conn.prepare(&format!(
+        "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order
+         FROM notes WHERE {}",
+        rows("note")
+    ))?;
 
     let notes: Vec<Note> = notes_stmt
-        .query_map(params![since_revision], |row| {
+        .query_map([], |row| {
             let tags_json: String = row.get(4)?;
             let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
             let status_str: String = row.get(5)?;
@@ -177,18 +594,19 @@ pub fn get_changes_since(db: &Database, since_revision: i64) -> Result<SyncPaylo
                 created_at: row.get(8)?,
                 updated_at: row.get(9)?,
                 deleted_at: row.get(10)?,
+                pinned_order: row.get(11)?,
             })
         })?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
-    // Get notebooks changed since revision
-    let mut notebooks_stmt = conn.prepare(
-        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at
-         FROM notebooks WHERE revision > ?",
-    )?;
+    let mut notebooks_stmt = conn.prepare(&format!(
+        "SELECT id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived
+         FROM notebooks WHERE {}",
+        rows("notebook")
+    ))?;
 
     let notebooks: Vec<Notebook> = notebooks_stmt
-        .query_map(params![since_revision], |row| {
+        .query_map([], |row| {
             Ok(Notebook {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -199,18 +617,20 @@ pub fn get_changes_since(db: &Database, since_revision: i64) -> Result<SyncPaylo
                 created_at: row.get(6)?,
                 updated_at: row.get(7)?,
                 deleted_at: row.get(8)?,
+                is_archived: row.get::<_, i32>(9)? != 0,
+                sync_excluded: false,
             })
         })?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
-    // Get tags changed since revision
-    let mut tags_stmt = conn.prepare(
+    let mut tags_stmt = conn.prepare(&format!(
         "SELECT id, name, color, revision, created_at, updated_at, deleted_at
-         FROM tags WHERE revision > ?",
-    )?;
+         FROM tags WHERE {}",
+        rows("tag")
+    ))?;
 
     let tags: Vec<Tag> = tags_stmt
-        .query_map(params![since_revision], |row| {
+        .query_map([], |row| {
             Ok(Tag {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -223,149 +643,969 @@ pub fn get_changes_since(db: &Database, since_revision: i64) -> Result<SyncPaylo
         })?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
+    let mut reminders_stmt = conn.prepare(&format!(
+        "SELECT id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at,
+                recurrence, snoozed_from
+         FROM reminders WHERE {}",
+        rows("reminder")
+    ))?;
+
+    let reminders: Vec<Reminder> = reminders_stmt
+        .query_map([], reminders::row_to_reminder)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // Tombstones go by the condition of the type they delete
+    let mut deleted_stmt = conn.prepare(&format!(
+        "SELECT entity_type, entity_id, revision, deleted_at
+         FROM deleted_entities
+         WHERE CASE entity_type WHEN 'note' THEN {} WHEN 'notebook' THEN {} WHEN 'tag' THEN {}
+             WHEN 'reminder' THEN {} ELSE {} END",
+        condition("note"),
+        condition("notebook"),
+        condition("tag"),
+        condition("reminder"),
+        condition("")
+    ))?;
+
+    let deleted: Vec<DeletedEntity> = deleted_stmt
+        .query_map([], row_to_deleted_entity)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
     Ok(SyncPayload {
         notes,
         notebooks,
         tags,
+        reminders,
+        deleted,
         since_revision,
     })
 }
 
+fn mark_all_pushed(db: &Database) -> Result<()> {
+    let conn = db.conn();
+    for table in CURSOR_TABLES {
+        conn.execute(&format!("UPDATE {} SET needs_push = 0 WHERE needs_push = 1", table), [])?;
+    }
+    conn.execute("UPDATE deleted_entities SET needs_push = 0 WHERE needs_push = 1", [])?;
+    Ok(())
+}
+
+/// Clear the push flags of what a push delivered. Rows edited again since
+/// the payload was read have a newer revision and stay flagged.
+fn clear_pushed(conn: &Connection, pushed: &SyncPayload) -> Result<()> {
+    let rows = pushed
+        .notes
+        .iter()
+        .map(|n| ("notes", &n.id, n.revision))
+        .chain(pushed.notebooks.iter().map(|nb| ("notebooks", &nb.id, nb.revision)))
+        .chain(pushed.tags.iter().map(|t| ("tags", &t.id, t.revision)))
+        .chain(pushed.reminders.iter().map(|r| ("reminders", &r.id, r.revision)));
+    for (table, id, revision) in rows {
+        conn.execute(
+            &format!("UPDATE {} SET needs_push = 0 WHERE id = ? AND revision = ?", table),
+            params![id, revision],
+        )?;
+    }
+    for note in &pushed.notes {
+        write_sync_base(conn, note)?;
+    }
+    for deleted in &pushed.deleted {
+        conn.execute(
+            "UPDATE deleted_entities SET needs_push = 0 WHERE entity_type = ? AND entity_id = ? AND revision = ?",
+            params![&deleted.entity_type, &deleted.entity_id, deleted.revision],
+        )?;
+    }
+    Ok(())
+}
+
 // =============================================================================
-// Merge Remote Changes (LWW)
+// Selective Sync
 // =============================================================================
 
-pub fn merge_remote_changes(
-    db: &Database,
-    remote: SyncPayload,
-) -> Result<(SyncStats, Vec<SyncConflict>)> {
-    let conn = db.conn();
-    let mut stats = SyncStats::default();
-    let mut conflicts = Vec::new();
+/// SQL condition on `entity_type`'s table for the rows allowed to leave this
+/// device: all but sync-excluded notebooks, their notes and those notes'
+/// reminders
+fn synced_rows(entity_type: &str) -> &'static str {
+    match entity_type {
+        "note" => "(notebook_id IS NULL OR notebook_id NOT IN (SELECT id FROM notebooks WHERE sync_excluded = 1))",
+        "notebook" => "sync_excluded = 0",
+        "reminder" => {
+            "(note_id IS NULL OR note_id NOT IN (
+                SELECT notes.id FROM notes JOIN notebooks ON notebooks.id = notes.notebook_id
+                WHERE notebooks.sync_excluded = 1))"
+        }
+        _ => "1",
+    }
+}
 
-    // Merge notes
-    for remote_note in remote.notes {
-        let local_revision: Option<i64> = conn
-            .query_row(
-                "SELECT revision FROM notes WHERE id = ?",
-                params![&remote_note.id],
-                |row| row.get(0),
-            )
-            .ok();
+/// Whether a local row is kept on this device only, so nothing pulled may
+/// overwrite or delete it
+fn stays_local(conn: &Connection, entity_type: &str, entity_id: &str) -> Result<bool> {
+    let sql = match entity_type {
+        "note" => {
+            "SELECT 1 FROM notes JOIN notebooks ON notebooks.id = notes.notebook_id
+             WHERE notes.id = ? AND notebooks.sync_excluded = 1"
+        }
+        "notebook" => "SELECT 1 FROM notebooks WHERE id = ? AND sync_excluded = 1",
+        "reminder" => {
+            "SELECT 1 FROM reminders
+             JOIN notes ON notes.id = reminders.note_id
+             JOIN notebooks ON notebooks.id = notes.notebook_id
+             WHERE reminders.id = ? AND notebooks.sync_excluded = 1"
+        }
+        _ => return Ok(false),
+    };
+    Ok(conn.prepare(sql)?.exists(params![entity_id])?)
+}
 
-        let should_apply = match local_revision {
-            None => true, // New note, always apply
-            Some(local_rev) => {
-                if remote_note.revision > local_rev {
-                    true // Remote is newer
-                } else if remote_note.revision == local_rev {
-                    // Same revision, compare updated_at
-                    let local_updated: String = conn
-                        .query_row(
-                            "SELECT updated_at FROM notes WHERE id = ?",
-                            params![&remote_note.id],
-                            |row| row.get(0),
-                        )
-                        .unwrap_or_default();
+/// Follow a note into or out of a sync-excluded notebook: moving in pushes a
+/// tombstone so other devices drop their copy, moving out takes it back.
+/// Call after the note's notebook changed.
+pub fn note_moved(conn: &Connection, note_id: &str) -> Result<()> {
+    if stays_local(conn, "note", note_id)? {
+        let revision: i64 = conn.query_row("SELECT revision FROM notes WHERE id = ?", params![note_id], |row| {
+            row.get(0)
+        })?;
+        record_deletion(conn, "note", note_id, revision + 1)
+    } else {
+        forget_deletion(conn, "note", note_id)
+    }
+}
 
-                    remote_note.updated_at > local_updated
-                } else {
-                    // Local is newer, record conflict
-                    conflicts.push(SyncConflict {
-                        entity_type: "note".to_string(),
-                        entity_id: remote_note.id.clone(),
-                        local_revision: local_rev,
-                        remote_revision: remote_note.revision,
-                        resolution: "local_wins".to_string(),
-                    });
-                    false
+// =============================================================================
+// Tombstones
+// =============================================================================
+
+fn row_to_deleted_entity(row: &rusqlite::Row) -> rusqlite::Result<DeletedEntity> {
+    Ok(DeletedEntity {
+        entity_type: row.get(0)?,
+        entity_id: row.get(1)?,
+        revision: row.get(2)?,
+        deleted_at: row.get(3)?,
+    })
+}
+
+/// Record that an entity was hard-deleted so the deletion is pushed on the
+/// next sync. Call inside the transaction that removes the row.
+pub fn record_deletion(conn: &Connection, entity_type: &str, entity_id: &str, revision: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO deleted_entities (entity_type, entity_id, revision, deleted_at)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT(entity_type, entity_id) DO UPDATE SET
+             revision = excluded.revision,
+             deleted_at = excluded.deleted_at,
+             needs_push = 1",
+        params![entity_type, entity_id, revision, chrono::Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Drop the tombstone of an entity that exists again, pushed or not
+pub fn forget_deletion(conn: &Connection, entity_type: &str, entity_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM deleted_entities WHERE entity_type = ? AND entity_id = ?",
+        params![entity_type, entity_id],
+    )?;
+    Ok(())
+}
+
+/// Hard-delete a note and everything hanging off it
+pub fn purge_note(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute("DELETE FROM reminders WHERE note_id = ?", params![id])?;
+    conn.execute("DELETE FROM note_tasks WHERE note_id = ?", params![id])?;
+    conn.execute("DELETE FROM notes WHERE id = ?", params![id])?;
+    Ok(())
+}
+
+/// Hard-delete soft-deleted notes, notebooks and tags deleted at least
+/// `older_than_days` ago, once the deletion has been pushed. No tombstone is
+/// written: the deletion already went out. Returns (entity_type, id) pairs.
+pub fn purge_synced(conn: &Connection, older_than_days: i64) -> Result<Vec<(&'static str, String)>> {
+    if older_than_days < 0 {
+        return Err(AppError::Validation("older_than_days can't be negative".to_string()));
+    }
+
+    let mut purged = Vec::new();
+    for (entity_type, table) in [("note", "notes"), ("notebook", "notebooks"), ("tag", "tags")] {
+        let ids: Vec<String> = conn
+            .prepare(&format!(
+                "SELECT id FROM {}
+                 WHERE deleted_at IS NOT NULL AND needs_push = 0
+                   AND julianday('now') - julianday(deleted_at) >= ?",
+                table
+            ))?
+            .query_map(params![older_than_days], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        for id in ids {
+            match table {
+                "notes" => purge_note(conn, &id)?,
+                _ => {
+                    conn.execute(&format!("DELETE FROM {} WHERE id = ?", table), params![id])?;
                 }
             }
-        };
-
-        if should_apply {
-            let tags_json = serde_json::to_string(&remote_note.tags).unwrap();
-            conn.execute(
-                "INSERT OR REPLACE INTO notes (id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-                params![
-                    remote_note.id,
-                    remote_note.title,
-                    remote_note.content,
-                    remote_note.notebook_id,
-                    tags_json,
-                    remote_note.status.as_str(),
-                    remote_note.is_pinned as i32,
-                    remote_note.revision,
-                    remote_note.created_at,
-                    remote_note.updated_at,
-                    remote_note.deleted_at,
-                ],
-            )?;
-            stats.notes += 1;
+            purged.push((entity_type, id));
         }
     }
+    Ok(purged)
+}
 
-    // Merge notebooks
-    for remote_notebook in remote.notebooks {
-        let local_revision: Option<i64> = conn
-            .query_row(
-                "SELECT revision FROM notebooks WHERE id = ?",
-                params![&remote_notebook.id],
-                |row| row.get(0),
-            )
-            .ok();
+fn retention_days(conn: &Connection) -> Result<i64> {
+    settings::get_i64(conn, settings::TOMBSTONE_RETENTION_DAYS, settings::DEFAULT_TOMBSTONE_RETENTION_DAYS)
+}
 
-        let should_apply = match local_revision {
-            None => true,
-            Some(local_rev) => {
-                if remote_notebook.revision > local_rev {
-                    true
-                } else if remote_notebook.revision == local_rev {
-                    let local_updated: String = conn
-                        .query_row(
-                            "SELECT updated_at FROM notebooks WHERE id = ?",
-                            params![&remote_notebook.id],
-                            |row| row.get(0),
-                        )
-                        .unwrap_or_default();
+/// Both sides of a conflicting note, as (title, content)
+struct NoteSnapshots<'a> {
+    local: (&'a str, &'a str),
+    remote: (&'a str, &'a str),
+}
 
-                    remote_notebook.updated_at > local_updated
-                } else {
-                    conflicts.push(SyncConflict {
-                        entity_type: "notebook".to_string(),
-                        entity_id: remote_notebook.id.clone(),
-                        local_revision: local_rev,
-                        remote_revision: remote_notebook.revision,
-                        resolution: "local_wins".to_string(),
-                    });
-                    false
-                }
-            }
+fn record_conflict(conn: &Connection, conflict: &SyncConflict, notes: Option<NoteSnapshots>) -> Result<()> {
+    let (local, remote) = match notes {
+        Some(notes) => (Some(notes.local), Some(notes.remote)),
+        None => (None, None),
+    };
+    conn.execute(
+        "INSERT INTO sync_conflicts
+             (id, entity_type, entity_id, local_revision, remote_revision,
+              local_title, local_content, remote_title, remote_content, resolution)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            conflict.entity_type,
+            conflict.entity_id,
+            conflict.local_revision,
+            conflict.remote_revision,
+            local.map(|(title, _)| title),
+            local.map(|(_, content)| content),
+            remote.map(|(title, _)| title),
+            remote.map(|(_, content)| content),
+            conflict.resolution,
+        ],
+    )?;
+    Ok(())
+}
+
+fn decrypt_snapshot(value: Option<String>) -> Result<Option<String>> {
+    value.map(|value| crypto::maybe_decrypt(&value)).transpose()
+}
+
+/// Conflicts still waiting for the user, oldest first
+pub fn unresolved_conflicts(conn: &Connection) -> Result<Vec<StoredConflict>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, entity_type, entity_id, local_revision, remote_revision,
+                local_title, local_content, remote_title, remote_content, resolution, created_at
+         FROM sync_conflicts WHERE resolved_at IS NULL ORDER BY created_at, rowid",
+    )?;
+    let conflicts = stmt
+        .query_map([], |row| {
+            Ok(StoredConflict {
+                id: row.get(0)?,
+                entity_type: row.get(1)?,
+                entity_id: row.get(2)?,
+                local_revision: row.get(3)?,
+                remote_revision: row.get(4)?,
+                local_title: row.get(5)?,
+                local_content: row.get(6)?,
+                remote_title: row.get(7)?,
+                remote_content: row.get(8)?,
+                resolution: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    conflicts
+        .into_iter()
+        .map(|conflict| {
+            Ok(StoredConflict {
+                local_title: decrypt_snapshot(conflict.local_title)?,
+                local_content: decrypt_snapshot(conflict.local_content)?,
+                remote_title: decrypt_snapshot(conflict.remote_title)?,
+                remote_content: decrypt_snapshot(conflict.remote_content)?,
+                ..conflict
+            })
+        })
+        .collect()
+}
+
+/// Apply the user's choice for a stored conflict. The entity lands on a
+/// revision above both sides so the outcome wins on every device. Returns
+/// the entity type and id.
+pub fn resolve(
+    conn: &Connection,
+    conflict_id: &str,
+    choice: ConflictChoice,
+    merged_content: Option<String>,
+) -> Result<(String, String)> {
+    let conflict = unresolved_conflicts(conn)?
+        .into_iter()
+        .find(|conflict| conflict.id == conflict_id)
+        .ok_or_else(|| AppError::NotFound(format!("Unresolved conflict {}", conflict_id)))?;
+    let table = entity_table(&conflict.entity_type)
+        .ok_or_else(|| AppError::Validation(format!("Unknown entity type '{}'", conflict.entity_type)))?;
+    let revision = conflict.local_revision.max(conflict.remote_revision) + 1;
+
+    if conflict.entity_type == "note" {
+        let (title, content) = match choice {
+            ConflictChoice::Local => (conflict.local_title, conflict.local_content),
+            ConflictChoice::Remote => (conflict.remote_title, conflict.remote_content),
+            ConflictChoice::Merged => match merged_content {
+                Some(content) => (None, Some(content)),
+                None => return Err(AppError::Validation("Merged resolution needs merged_content".to_string())),
+            },
         };
+        let input = UpdateNoteInput {
+            title,
+            content,
+            notebook_id: None,
+            tags: None,
+            status: None,
+            is_pinned: None,
+        };
+        notes::write_note_update(conn, &conflict.entity_id, input)?;
+        conn.execute(
+            "UPDATE notes SET revision = MAX(revision, ?) WHERE id = ?",
+            params![revision, conflict.entity_id],
+        )?;
+    } else {
+        // Only notes keep both versions; anything else keeps what's on this device
+        if choice != ConflictChoice::Local {
+            return Err(AppError::Validation(format!(
+                "{} conflicts can only be resolved as local",
+                conflict.entity_type
+            )));
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        let updated = conn.execute(
+            &format!(
+                "UPDATE {} SET revision = MAX(revision + 1, ?), needs_push = 1, updated_at = ? WHERE id = ?",
+                table
+            ),
+            params![revision, now, conflict.entity_id],
+        )?;
+        if updated == 0 {
+            return Err(AppError::NotFound(format!("{} {}", conflict.entity_type, conflict.entity_id)));
+        }
+    }
 
-        if should_apply {
-            conn.execute(
-                "INSERT OR REPLACE INTO notebooks (id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-                params![
-                    remote_notebook.id,
-                    remote_notebook.name,
-                    remote_notebook.color,
-                    remote_notebook.icon,
-                    remote_notebook.parent_id,
-                    remote_notebook.revision,
-                    remote_notebook.created_at,
-                    remote_notebook.updated_at,
-                    remote_notebook.deleted_at,
-                ],
-            )?;
-            stats.notebooks += 1;
+    // Later conflicts on the same entity are settled by this outcome too
+    conn.execute(
+        "UPDATE sync_conflicts SET resolution = ?, resolved_at = datetime('now')
+         WHERE entity_type = ? AND entity_id = ? AND resolved_at IS NULL",
+        params![choice.as_str(), conflict.entity_type, conflict.entity_id],
+    )?;
+    Ok((conflict.entity_type, conflict.entity_id))
+}
+
+/// Losing versions kept per note; older ones are pruned
+const ARCHIVED_VERSIONS_PER_NOTE: i64 = 20;
+
+/// Days a losing version is kept at most
+const ARCHIVE_RETENTION_DAYS: i64 = 90;
+
+/// Keep the full losing version of a note (as stored) from an automatic
+/// conflict resolution, pruning what's past the per-note cap or too old
+fn archive_note_version(conn: &Connection, loser: &Note, source: &str, resolution: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO conflict_archive (id, note_id, title, content, tags, revision, source, resolution)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            loser.id,
+            loser.title,
+            loser.content,
+            serde_json::to_string(&loser.tags).unwrap(),
+            loser.revision,
+            source,
+            resolution,
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM conflict_archive
+         WHERE note_id = ? AND id NOT IN (
+             SELECT id FROM conflict_archive WHERE note_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ?)",
+        params![loser.id, loser.id, ARCHIVED_VERSIONS_PER_NOTE],
+    )?;
+    conn.execute(
+        "DELETE FROM conflict_archive WHERE julianday('now') - julianday(created_at) >= ?",
+        params![ARCHIVE_RETENTION_DAYS],
+    )?;
+    Ok(())
+}
+
+/// Archived losing versions of a note, newest first, decrypted
+pub fn conflict_archive(conn: &Connection, note_id: &str) -> Result<Vec<ArchivedNoteVersion>> {
+    let versions = conn
+        .prepare(
+            "SELECT id, note_id, title, content, tags, revision, source, resolution, created_at
+             FROM conflict_archive WHERE note_id = ? ORDER BY created_at DESC, rowid DESC",
+        )?
+        .query_map(params![note_id], |row| {
+            let tags: String = row.get(4)?;
+            Ok(ArchivedNoteVersion {
+                id: row.get(0)?,
+                note_id: row.get(1)?,
+                title: row.get(2)?,
+                content: row.get(3)?,
+                tags: serde_json::from_str(&tags).unwrap_or_default(),
+                revision: row.get(5)?,
+                source: row.get(6)?,
+                resolution: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    versions
+        .into_iter()
+        .map(|version| {
+            Ok(ArchivedNoteVersion {
+                title: crypto::maybe_decrypt(&version.title)?,
+                content: crypto::maybe_decrypt(&version.content)?,
+                ..version
+            })
+        })
+        .collect()
+}
+
+/// Bring an archived version back, as a new revision of its note or, when
+/// `as_new_note` is set or the note is gone, as a new note. Returns the id
+/// of the note written and whether it was created.
+pub fn restore_archived_version(conn: &Connection, archive_id: &str, as_new_note: bool) -> Result<(String, bool)> {
+    let note_id: Option<String> = conn
+        .query_row("SELECT note_id FROM conflict_archive WHERE id = ?", params![archive_id], |row| row.get(0))
+        .optional()?;
+    let Some(note_id) = note_id else {
+        return Err(AppError::NotFound(format!("Archived version {} not found", archive_id)));
+    };
+    let version = conflict_archive(conn, &note_id)?
+        .into_iter()
+        .find(|version| version.id == archive_id)
+        .ok_or_else(|| AppError::NotFound(format!("Archived version {} not found", archive_id)))?;
+    let current = stored_note(conn, &note_id)?.map(|(note, _)| note);
+
+    match current {
+        Some(_) if !as_new_note => {
+            let input = UpdateNoteInput {
+                title: Some(version.title),
+                content: Some(version.content),
+                notebook_id: None,
+                tags: Some(version.tags),
+                status: None,
+                is_pinned: None,
+            };
+            notes::write_note_update(conn, &note_id, input)?;
+            Ok((note_id, false))
+        }
+        current => {
+            let input = CreateNoteInput {
+                id: None,
+                title: Some(version.title),
+                content: Some(version.content),
+                notebook_id: current.map(|note| note.notebook_id),
+                tags: Some(version.tags),
+            };
+            Ok((notes::insert_note(conn, input)?, true))
+        }
+    }
+}
+
+/// Name of the tag put on conflicted copies
+pub const CONFLICT_TAG: &str = "conflict";
+
+/// A note as stored, title and content possibly encrypted, with its push flag
+fn stored_note(conn: &Connection, id: &str) -> Result<Option<(Note, bool)>> {
+    let note = conn
+        .query_row(
+            "SELECT id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at,
+                    pinned_order, needs_push
+             FROM notes WHERE id = ?",
+            params![id],
+            |row| {
+                let tags: String = row.get(4)?;
+                let note = Note {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    content: row.get(2)?,
+                    notebook_id: row.get(3)?,
+                    tags: serde_json::from_str(&tags).unwrap_or_default(),
+                    status: NoteStatus::from_str(&row.get::<_, String>(5)?),
+                    is_pinned: row.get(6)?,
+                    revision: row.get(7)?,
+                    created_at: row.get(8)?,
+                    updated_at: row.get(9)?,
+                    deleted_at: row.get(10)?,
+                    pinned_order: row.get(11)?,
+                };
+                Ok((note, row.get(12)?))
+            },
+        )
+        .optional()?;
+    Ok(note)
+}
+
+/// Keep the local version of a note about to be overwritten as a new note
+fn copy_conflicted_note(conn: &Connection, id: &str) -> Result<()> {
+    let (local, _) = stored_note(conn, id)?.ok_or_else(|| AppError::NotFound(format!("Note {}", id)))?;
+    insert_conflicted_copy(conn, &local)
+}
+
+/// Insert `loser` as a new note titled "Title (conflicted copy from <date>)"
+/// and tagged `conflict`, creating the tag if needed. Title and content stay
+/// as stored (possibly encrypted).
+fn insert_conflicted_copy(conn: &Connection, loser: &Note) -> Result<()> {
+    let date = loser.updated_at.get(..10).unwrap_or(&loser.updated_at);
+    let title = format!("{} (conflicted copy from {})", crypto::maybe_decrypt(&loser.title)?, date);
+    let title = crypto::maybe_encrypt(&title)?;
+
+    let mut tags = loser.tags.clone();
+    if !tags.iter().any(|tag| tag == CONFLICT_TAG) {
+        tags.push(CONFLICT_TAG.to_string());
+    }
+    tags::ensure_tags(conn, &[CONFLICT_TAG.to_string()])?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO notes (id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, 0, 1, ?, ?)",
+        params![
+            id,
+            title,
+            loser.content,
+            loser.notebook_id,
+            serde_json::to_string(&tags).unwrap(),
+            loser.status.as_str(),
+            now,
+            now
+        ],
+    )?;
+    tasks::refresh_note_tasks(conn, &id)?;
+    Ok(())
+}
+
+/// A note as of its last sync, the common ancestor of a three-way merge.
+/// Title and content are kept as hashes of their plaintext: only whether
+/// they changed matters, and the table can't leak encrypted notes.
+struct SyncBase {
+    title: String,
+    content: String,
+    notebook_id: Option<String>,
+    tags: Vec<String>,
+    status: NoteStatus,
+    pin: (bool, Option<i64>),
+    deleted_at: Option<String>,
+}
+
+impl SyncBase {
+    fn of(note: &Note) -> Result<Self> {
+        Ok(SyncBase {
+            title: text_hash(&note.title)?,
+            content: text_hash(&note.content)?,
+            notebook_id: note.notebook_id.clone(),
+            tags: note.tags.clone(),
+            status: note.status.clone(),
+            pin: (note.is_pinned, note.pinned_order),
+            deleted_at: note.deleted_at.clone(),
+        })
+    }
+}
+
+fn text_hash(stored: &str) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    Ok(format!("{:x}", Sha256::digest(crypto::maybe_decrypt(stored)?.as_bytes())))
+}
+
+fn read_sync_base(conn: &Connection, note_id: &str) -> Result<Option<SyncBase>> {
+    let base = conn
+        .query_row(
+            "SELECT title_hash, content_hash, notebook_id, tags, status, is_pinned, pinned_order, deleted_at
+             FROM note_sync_base WHERE note_id = ?",
+            params![note_id],
+            |row| {
+                let tags: String = row.get(3)?;
+                Ok(SyncBase {
+                    title: row.get(0)?,
+                    content: row.get(1)?,
+                    notebook_id: row.get(2)?,
+                    tags: serde_json::from_str(&tags).unwrap_or_default(),
+                    status: NoteStatus::from_str(&row.get::<_, String>(4)?),
+                    pin: (row.get(5)?, row.get(6)?),
+                    deleted_at: row.get(7)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(base)
+}
+
+/// Remember `note` as the version both sides now share
+fn write_sync_base(conn: &Connection, note: &Note) -> Result<()> {
+    let base = SyncBase::of(note)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO note_sync_base
+             (note_id, title_hash, content_hash, notebook_id, tags, status, is_pinned, pinned_order, deleted_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            note.id,
+            base.title,
+            base.content,
+            base.notebook_id,
+            serde_json::to_string(&base.tags).unwrap(),
+            base.status.as_str(),
+            base.pin.0,
+            base.pin.1,
+            base.deleted_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Outcome of merging a pulled note field by field
+enum FieldMerge {
+    /// No unpushed local edits, or no base to tell who changed what
+    NotApplicable,
+    /// Nothing changed remotely since the base; the local note stays
+    KeptLocal,
+    /// The merged note was written. The conflict is set when both sides
+    /// changed the title or content and the older edit was dropped.
+    Merged(Option<SyncConflict>),
+}
+
+/// Merge a note edited on both sides since it was last synced. A field only
+/// one side changed comes from that side. Title and content changed on both
+/// come from the side edited last; tags keep what either side added and drop
+/// what either removed; a notebook set on one side beats one cleared on the
+/// other. The note keeps its push flag, so the merge goes back to the server.
+fn merge_note_fields(conn: &Connection, remote: &Note) -> Result<FieldMerge> {
+    let Some((local, true)) = stored_note(conn, &remote.id)? else {
+        return Ok(FieldMerge::NotApplicable);
+    };
+    let Some(base) = read_sync_base(conn, &remote.id)? else {
+        return Ok(FieldMerge::NotApplicable);
+    };
+
+    let (ours, theirs) = (SyncBase::of(&local)?, SyncBase::of(remote)?);
+    // Past both sides, so the server takes the push as a newer edit
+    let revision = local.revision.max(remote.revision) + 1;
+    write_sync_base(conn, remote)?;
+    if !changed_since(&theirs, &base) {
+        // The server copy is what was last synced (often our own push coming
+        // back); keep the local edits, at a revision the server will accept
+        conn.execute("UPDATE notes SET revision = ? WHERE id = ?", params![revision, local.id])?;
+        return Ok(FieldMerge::KeptLocal);
+    }
+
+    let remote_newer = remote.updated_at > local.updated_at;
+    // Whether a field comes from the remote side
+    let take = |local_changed: bool, remote_changed: bool| remote_changed && (!local_changed || remote_newer);
+    let take_title = take(ours.title != base.title, theirs.title != base.title);
+    let take_content = take(ours.content != base.content, theirs.content != base.content);
+    let take_pin = take(ours.pin != base.pin, theirs.pin != base.pin);
+    let take_status = take(ours.status != base.status, theirs.status != base.status);
+    let take_deleted = take(ours.deleted_at != base.deleted_at, theirs.deleted_at != base.deleted_at);
+    let notebook_id = match (ours.notebook_id != base.notebook_id, theirs.notebook_id != base.notebook_id) {
+        (true, true) if local.notebook_id.is_none() || remote.notebook_id.is_none() => {
+            local.notebook_id.clone().or_else(|| remote.notebook_id.clone())
+        }
+        (local_changed, remote_changed) if take(local_changed, remote_changed) => remote.notebook_id.clone(),
+        _ => local.notebook_id.clone(),
+    };
+    let mut tags: Vec<String> = local
+        .tags
+        .iter()
+        .filter(|tag| remote.tags.contains(tag) || !base.tags.contains(tag))
+        .cloned()
+        .collect();
+    for tag in &remote.tags {
+        if !base.tags.contains(tag) && !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+
+    fn pick<T: Clone>(from_remote: bool, remote: &T, local: &T) -> T {
+        if from_remote { remote } else { local }.clone()
+    }
+    let merged = Note {
+        title: pick(take_title, &remote.title, &local.title),
+        content: pick(take_content, &remote.content, &local.content),
+        notebook_id,
+        tags,
+        status: pick(take_status, &remote.status, &local.status),
+        is_pinned: pick(take_pin, &remote.is_pinned, &local.is_pinned),
+        pinned_order: pick(take_pin, &remote.pinned_order, &local.pinned_order),
+        deleted_at: pick(take_deleted, &remote.deleted_at, &local.deleted_at),
+        revision,
+        updated_at: local.updated_at.clone().max(remote.updated_at.clone()),
+        ..local.clone()
+    };
+    conn.execute(
+        "UPDATE notes SET title = ?, content = ?, notebook_id = ?, tags = ?, status = ?, is_pinned = ?,
+                          pinned_order = ?, deleted_at = ?, revision = ?, updated_at = ?, needs_push = 1
+         WHERE id = ?",
+        params![
+            merged.title,
+            merged.content,
+            merged.notebook_id,
+            serde_json::to_string(&merged.tags).unwrap(),
+            merged.status.as_str(),
+            merged.is_pinned,
+            merged.pinned_order,
+            merged.deleted_at,
+            merged.revision,
+            merged.updated_at,
+            merged.id,
+        ],
+    )?;
+    tasks::refresh_note_tasks(conn, &merged.id)?;
+
+    let both_rewrote = (ours.title != base.title && theirs.title != base.title)
+        || (ours.content != base.content && theirs.content != base.content);
+    if !both_rewrote || (ours.title, ours.content) == (theirs.title, theirs.content) {
+        return Ok(FieldMerge::Merged(None));
+    }
+    let conflict = SyncConflict {
+        entity_type: "note".to_string(),
+        entity_id: remote.id.clone(),
+        local_revision: local.revision,
+        remote_revision: remote.revision,
+        resolution: "merged".to_string(),
+    };
+    let snapshots = NoteSnapshots {
+        local: (&local.title, &local.content),
+        remote: (&remote.title, &remote.content),
+    };
+    record_conflict(conn, &conflict, Some(snapshots))?;
+    // Whichever rewrite didn't make it into the merge
+    for (side, source) in [(&local, "local"), (remote, "remote")] {
+        if (&side.title, &side.content) != (&merged.title, &merged.content) {
+            archive_note_version(conn, side, source, &conflict.resolution)?;
+        }
+    }
+    Ok(FieldMerge::Merged(Some(conflict)))
+}
+
+fn changed_since(note: &SyncBase, base: &SyncBase) -> bool {
+    (&note.title, &note.content, &note.notebook_id, &note.tags, &note.status, &note.pin, &note.deleted_at)
+        != (&base.title, &base.content, &base.notebook_id, &base.tags, &base.status, &base.pin, &base.deleted_at)
+}
+
+/// Whether a row was hard-deleted here after the given remote copy was last
+/// edited, in which case the remote copy must not bring it back
+fn is_tombstoned(conn: &Connection, entity_type: &str, entity_id: &str, updated_at: &str) -> Result<bool> {
+    let exists = conn
+        .prepare(
+            "SELECT 1 FROM deleted_entities
+             WHERE entity_type = ? AND entity_id = ? AND datetime(deleted_at) >= datetime(?)",
+        )?
+        .exists(params![entity_type, entity_id, updated_at])?;
+    Ok(exists)
+}
+
+fn entity_table(entity_type: &str) -> Option<&'static str> {
+    match entity_type {
+        "note" => Some("notes"),
+        "notebook" => Some("notebooks"),
+        "tag" => Some("tags"),
+        "reminder" => Some("reminders"),
+        _ => None,
+    }
+}
+
+/// Apply a remote tombstone. A local row edited after the deletion
+/// survives, and is reported as a conflict.
+fn apply_deletion(conn: &Connection, deleted: &DeletedEntity) -> Result<Option<SyncConflict>> {
+    let Some(table) = entity_table(&deleted.entity_type) else {
+        return Ok(None);
+    };
+
+    let local: Option<(i64, bool)> = conn
+        .query_row(
+            &format!(
+                "SELECT revision, datetime(updated_at) > datetime(?) FROM {} WHERE id = ?",
+                table
+            ),
+            params![&deleted.deleted_at, &deleted.entity_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    if let Some((local_revision, edited_after)) = local {
+        if edited_after {
+            return Ok(Some(SyncConflict {
+                entity_type: deleted.entity_type.clone(),
+                entity_id: deleted.entity_id.clone(),
+                local_revision,
+                remote_revision: deleted.revision,
+                resolution: "local_wins".to_string(),
+            }));
+        }
+
+        match table {
+            "notes" => purge_note(conn, &deleted.entity_id)?,
+            _ => {
+                conn.execute(&format!("DELETE FROM {} WHERE id = ?", table), params![&deleted.entity_id])?;
+            }
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO deleted_entities (entity_type, entity_id, revision, deleted_at, needs_push)
+         VALUES (?, ?, ?, ?, 0)
+         ON CONFLICT(entity_type, entity_id) DO UPDATE SET
+             revision = MAX(revision, excluded.revision),
+             deleted_at = excluded.deleted_at,
+             needs_push = 0",
+        params![&deleted.entity_type, &deleted.entity_id, deleted.revision, &deleted.deleted_at],
+    )?;
+
+    Ok(None)
+}
+
+/// Park a remote reminder whose note hasn't been pulled yet
+fn defer_reminder(conn: &Connection, reminder: &Reminder) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO deferred_reminders (id, payload) VALUES (?, ?)",
+        params![&reminder.id, serde_json::to_string(reminder).unwrap()],
+    )?;
+    Ok(())
+}
+
+/// Remove and return every deferred reminder; the ones still missing their
+/// note are deferred again by the merge
+fn take_deferred_reminders(conn: &Connection) -> Result<Vec<Reminder>> {
+    let payloads = conn
+        .prepare("SELECT payload FROM deferred_reminders ORDER BY received_at")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    conn.execute("DELETE FROM deferred_reminders", [])?;
+    Ok(payloads.iter().filter_map(|json| serde_json::from_str(json).ok()).collect())
+}
+
+// =============================================================================
+// Merge Remote Changes (LWW)
+// =============================================================================
+
+/// Merge without moving the pull cursors
+#[cfg(test)]
+fn merge_remote_changes(
+    db: &Database,
+    remote: SyncPayload,
+    strategy: ConflictStrategy,
+) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    merge_and_advance(db, remote, strategy, None)
+}
+
+/// Merge in one transaction, rolled back on any error. The pull cursors move
+/// to `pull_revision` inside it, so a merge is never kept without its cursor
+/// (or the reverse). One commit instead of one per row is also what makes
+/// large pulls fast.
+fn merge_and_advance(
+    db: &Database,
+    remote: SyncPayload,
+    strategy: ConflictStrategy,
+    pull_revision: Option<i64>,
+) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    // Rows arrive grouped by type, so a note can land before its notebook;
+    // references are checked once everything is in, at commit
+    tx.pragma_update(None, "defer_foreign_keys", true)?;
+    let result = merge_into(&tx, remote, strategy)?;
+    if let Some(revision) = pull_revision {
+        let cursors = read_cursors(&tx, "pull")?;
+        let advanced = cursors.advanced_to(revision);
+        if advanced != cursors {
+            write_cursors(&tx, "pull", &advanced)?;
+        }
+    }
+    tx.commit()?;
+    Ok(result)
+}
+
+/// Run the merge in a transaction that is rolled back, comparing each pulled
+/// row before and after, so the preview follows the real merge exactly
+fn preview_merge(db: &Database, remote: SyncPayload, strategy: ConflictStrategy) -> Result<SyncPreview> {
+    let rows: Vec<(&str, String)> = remote
+        .notes
+        .iter()
+        .map(|n| ("note", n.id.clone()))
+        .chain(remote.notebooks.iter().map(|nb| ("notebook", nb.id.clone())))
+        .chain(remote.tags.iter().map(|t| ("tag", t.id.clone())))
+        .chain(remote.reminders.iter().map(|r| ("reminder", r.id.clone())))
+        .collect();
+
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+    tx.pragma_update(None, "defer_foreign_keys", true)?;
+    let before = row_versions(&tx, &rows)?;
+    let (stats, conflicts) = merge_into(&tx, remote, strategy)?;
+    let after = row_versions(&tx, &rows)?;
+    let pushed = collect_changes(&tx, |_| "needs_push = 1".to_string(), 0)?;
+    // Dropping the transaction rolls the merge back
+
+    let mut preview = SyncPreview {
+        deleted: stats.deleted,
+        to_push: (pushed.notes.len()
+            + pushed.notebooks.len()
+            + pushed.tags.len()
+            + pushed.reminders.len()
+            + pushed.deleted.len()) as i32,
+        ..SyncPreview::default()
+    };
+    for (((entity_type, _), before), after) in rows.iter().zip(before).zip(after) {
+        let Some(counts) = preview.counts_mut(entity_type) else {
+            continue;
+        };
+        match (before, after) {
+            (None, Some(_)) => counts.created += 1,
+            (Some(before), Some(after)) if before != after => counts.updated += 1,
+            _ => {}
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    for conflict in &conflicts {
+        if seen.insert((conflict.entity_type.as_str(), conflict.entity_id.as_str())) {
+            if let Some(counts) = preview.counts_mut(&conflict.entity_type) {
+                counts.conflicted += 1;
+            }
         }
     }
+    Ok(preview)
+}
+
+/// (revision, updated_at) of each row, `None` where it doesn't exist
+fn row_versions(conn: &Connection, rows: &[(&str, String)]) -> Result<Vec<Option<(i64, String)>>> {
+    rows.iter()
+        .map(|(entity_type, id)| {
+            let table = entity_table(entity_type).unwrap_or("notes");
+            let version = conn
+                .query_row(
+                    &format!("SELECT revision, updated_at FROM {} WHERE id = ?", table),
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            Ok(version)
+        })
+        .collect()
+}
 
-    // Merge tags
+fn merge_into(
+    conn: &Connection,
+    remote: SyncPayload,
+    strategy: ConflictStrategy,
+) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    let mut stats = SyncStats::default();
+    let mut conflicts = Vec::new();
+
+    // Merge tags first, so the pulled notes link to them by name
     for remote_tag in remote.tags {
+        if is_tombstoned(conn, "tag", &remote_tag.id, &remote_tag.updated_at)? {
+            continue;
+        }
+
         let local_revision: Option<i64> = conn
             .query_row(
                 "SELECT revision FROM tags WHERE id = ?",
@@ -390,22 +1630,40 @@ pub fn merge_remote_changes(
 
                     remote_tag.updated_at > local_updated
                 } else {
-                    conflicts.push(SyncConflict {
+                    let conflict = SyncConflict {
                         entity_type: "tag".to_string(),
                         entity_id: remote_tag.id.clone(),
                         local_revision: local_rev,
                         remote_revision: remote_tag.revision,
                         resolution: "local_wins".to_string(),
-                    });
+                    };
+                    record_conflict(conn, &conflict, None)?;
+                    conflicts.push(conflict);
                     false
                 }
             }
         };
 
         if should_apply {
+            // Another tag here with the same name (created on both sides, or
+            // named by a note before this pull) gives way to the pulled one;
+            // the triggers link its notes to the pulled tag by name
             conn.execute(
-                "INSERT OR REPLACE INTO tags (id, name, color, revision, created_at, updated_at, deleted_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                "DELETE FROM tags WHERE name = ? AND id != ?",
+                params![remote_tag.name, remote_tag.id],
+            )?;
+            // Upsert rather than replace, so the tag keeps its note links
+            conn.execute(
+                "INSERT INTO tags (id, name, color, revision, created_at, updated_at, deleted_at, needs_push)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, 0)
+                 ON CONFLICT(id) DO UPDATE SET
+                     name = excluded.name,
+                     color = excluded.color,
+                     revision = excluded.revision,
+                     created_at = excluded.created_at,
+                     updated_at = excluded.updated_at,
+                     deleted_at = excluded.deleted_at,
+                     needs_push = 0",
                 params![
                     remote_tag.id,
                     remote_tag.name,
@@ -420,52 +1678,514 @@ pub fn merge_remote_changes(
         }
     }
 
-    Ok((stats, conflicts))
-}
-
-// =============================================================================
-// Tauri Commands
-// =============================================================================
+    // Merge notes
+    for remote_note in remote.notes {
+        if is_tombstoned(conn, "note", &remote_note.id, &remote_note.updated_at)? {
+            continue;
+        }
+        // Private notes stay as they are here, and nothing is filed into a private notebook
+        let notebook_id = remote_note.notebook_id.as_deref().unwrap_or_default();
+        if stays_local(conn, "note", &remote_note.id)? || stays_local(conn, "notebook", notebook_id)? {
+            continue;
+        }
 
-/// Get current sync state
-#[tauri::command]
-pub fn get_local_sync_state(db: State<'_, Database>) -> Result<LocalSyncState> {
-    get_sync_state(&db)
-}
+        // Edited on both sides since the last sync: merge rather than pick one
+        if strategy == ConflictStrategy::Lww {
+            match merge_note_fields(conn, &remote_note)? {
+                FieldMerge::NotApplicable => {}
+                FieldMerge::KeptLocal => continue,
+                FieldMerge::Merged(conflict) => {
+                    conflicts.extend(conflict);
+                    stats.notes += 1;
+                    continue;
+                }
+            }
+        }
 
-/// Get changes that need to be pushed
-#[tauri::command]
-pub fn get_pending_changes(db: State<'_, Database>) -> Result<SyncPayload> {
-    let state = get_sync_state(&db)?;
-    get_changes_since(&db, state.last_push_revision)
-}
+        let local = conn
+            .query_row(
+                "SELECT revision, updated_at, title, content FROM notes WHERE id = ?",
+                params![&remote_note.id],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
 
-/// Apply remote changes (pull)
-#[tauri::command]
-pub fn apply_remote_changes(
-    db: State<'_, Database>,
-    payload: SyncPayload,
-) -> Result<(SyncStats, Vec<SyncConflict>)> {
-    let result = merge_remote_changes(&db, payload)?;
+        let should_apply = match local {
+            None => true, // New note, always apply
+            Some((local_rev, local_updated, local_title, local_content)) => {
+                // Higher revision wins; same revision, newer updated_at wins
+                let remote_newer = remote_note.revision > local_rev
+                    || (remote_note.revision == local_rev && remote_note.updated_at > local_updated);
+                // An older remote revision, or the same revision with different
+                // text, means both sides changed the note
+                let conflicted = remote_note.revision < local_rev
+                    || (remote_note.revision == local_rev
+                        && (remote_note.title != local_title || remote_note.content != local_content));
 
-    // Update last pull revision to the max revision we received
-    let max_revision = result
-        .0
-        .notes
-        .max(result.0.notebooks)
-        .max(result.0.tags) as i64;
+                let resolution = match strategy {
+                    _ if !conflicted => None,
+                    // LWW only reports the case where the local copy is newer
+                    ConflictStrategy::Lww if remote_newer || remote_note.revision == local_rev => None,
+                    ConflictStrategy::Lww | ConflictStrategy::LocalWins => Some((false, "local_wins")),
+                    ConflictStrategy::RemoteWins => Some((true, "remote_wins")),
+                    ConflictStrategy::KeepBoth => {
+                        if remote_newer {
+                            copy_conflicted_note(conn, &remote_note.id)?;
+                        } else {
+                            insert_conflicted_copy(conn, &remote_note)?;
+                        }
+                        Some((remote_newer, "keep_both"))
+                    }
+                };
 
-    if max_revision > 0 {
-        let state = get_sync_state(&db)?;
-        update_sync_state(&db, Some(state.last_pull_revision.max(max_revision)), None)?;
-    }
+                match resolution {
+                    Some((apply, resolution)) => {
+                        let conflict = SyncConflict {
+                            entity_type: "note".to_string(),
+                            entity_id: remote_note.id.clone(),
+                            local_revision: local_rev,
+                            remote_revision: remote_note.revision,
+                            resolution: resolution.to_string(),
+                        };
+                        // Keep-both loses nothing, so there's nothing to review
+                        if strategy != ConflictStrategy::KeepBoth {
+                            let snapshots = NoteSnapshots {
+                                local: (&local_title, &local_content),
+                                remote: (&remote_note.title, &remote_note.content),
+                            };
+                            record_conflict(conn, &conflict, Some(snapshots))?;
+                            match stored_note(conn, &remote_note.id)? {
+                                Some((local, _)) if apply => archive_note_version(conn, &local, "local", resolution)?,
+                                _ => archive_note_version(conn, &remote_note, "remote", resolution)?,
+                            }
+                        }
+                        conflicts.push(conflict);
+                        apply
+                    }
+                    None => remote_newer,
+                }
+            }
+        };
 
-    Ok(result)
+        if should_apply {
+            let tags_json = serde_json::to_string(&remote_note.tags).unwrap();
+            // Upsert rather than replace, so the note's reminders and tasks
+            // aren't cascade-deleted
+            conn.execute(
+                "INSERT INTO notes (id, title, content, notebook_id, tags, status, is_pinned, revision, created_at, updated_at, deleted_at, pinned_order, needs_push)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0)
+                 ON CONFLICT(id) DO UPDATE SET
+                     title = excluded.title,
+                     content = excluded.content,
+                     notebook_id = excluded.notebook_id,
+                     tags = excluded.tags,
+                     status = excluded.status,
+                     is_pinned = excluded.is_pinned,
+                     revision = excluded.revision,
+                     created_at = excluded.created_at,
+                     updated_at = excluded.updated_at,
+                     deleted_at = excluded.deleted_at,
+                     pinned_order = excluded.pinned_order,
+                     needs_push = 0",
+                params![
+                    remote_note.id,
+                    remote_note.title,
+                    remote_note.content,
+                    remote_note.notebook_id,
+                    tags_json,
+                    remote_note.status.as_str(),
+                    remote_note.is_pinned as i32,
+                    remote_note.revision,
+                    remote_note.created_at,
+                    remote_note.updated_at,
+                    remote_note.deleted_at,
+                    remote_note.pinned_order,
+                ],
+            )?;
+            tasks::refresh_note_tasks(conn, &remote_note.id)?;
+            write_sync_base(conn, &remote_note)?;
+            stats.notes += 1;
+        }
+    }
+
+    // Merge notebooks
+    for remote_notebook in remote.notebooks {
+        if is_tombstoned(conn, "notebook", &remote_notebook.id, &remote_notebook.updated_at)? {
+            continue;
+        }
+        if stays_local(conn, "notebook", &remote_notebook.id)? {
+            continue;
+        }
+
+        let local_revision: Option<i64> = conn
+            .query_row(
+                "SELECT revision FROM notebooks WHERE id = ?",
+                params![&remote_notebook.id],
+                |row| row.get(0),
+            )
+            .ok();
+
+        let should_apply = match local_revision {
+            None => true,
+            Some(local_rev) => {
+                if remote_notebook.revision > local_rev {
+                    true
+                } else if remote_notebook.revision == local_rev {
+                    let local_updated: String = conn
+                        .query_row(
+                            "SELECT updated_at FROM notebooks WHERE id = ?",
+                            params![&remote_notebook.id],
+                            |row| row.get(0),
+                        )
+                        .unwrap_or_default();
+
+                    remote_notebook.updated_at > local_updated
+                } else {
+                    let conflict = SyncConflict {
+                        entity_type: "notebook".to_string(),
+                        entity_id: remote_notebook.id.clone(),
+                        local_revision: local_rev,
+                        remote_revision: remote_notebook.revision,
+                        resolution: "local_wins".to_string(),
+                    };
+                    record_conflict(conn, &conflict, None)?;
+                    conflicts.push(conflict);
+                    false
+                }
+            }
+        };
+
+        if should_apply {
+            conn.execute(
+                // Upsert: replacing the row would unfile its notes and children
+                "INSERT INTO notebooks (id, name, color, icon, parent_id, revision, created_at, updated_at, deleted_at, is_archived, needs_push)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0)
+                 ON CONFLICT(id) DO UPDATE SET
+                     name = excluded.name,
+                     color = excluded.color,
+                     icon = excluded.icon,
+                     parent_id = excluded.parent_id,
+                     revision = excluded.revision,
+                     created_at = excluded.created_at,
+                     updated_at = excluded.updated_at,
+                     deleted_at = excluded.deleted_at,
+                     is_archived = excluded.is_archived,
+                     needs_push = 0",
+                params![
+                    remote_notebook.id,
+                    remote_notebook.name,
+                    remote_notebook.color,
+                    // Devices from before icons were validated may still send inline SVG
+                    remote_notebook.icon.filter(|icon| validation::is_valid_icon(icon)),
+                    remote_notebook.parent_id,
+                    remote_notebook.revision,
+                    remote_notebook.created_at,
+                    remote_notebook.updated_at,
+                    remote_notebook.deleted_at,
+                    remote_notebook.is_archived as i32,
+                ],
+            )?;
+            stats.notebooks += 1;
+        }
+    }
+
+    // Merge reminders once their notes are in place. Reminders deferred by an
+    // earlier pull are retried first.
+    let mut pending = take_deferred_reminders(conn)?;
+    pending.extend(remote.reminders);
+    for remote_reminder in pending {
+        if is_tombstoned(conn, "reminder", &remote_reminder.id, &remote_reminder.updated_at)? {
+            continue;
+        }
+        let note_id = remote_reminder.note_id.as_deref().unwrap_or_default();
+        if stays_local(conn, "reminder", &remote_reminder.id)? || stays_local(conn, "note", note_id)? {
+            continue;
+        }
+
+        if let Some(note_id) = &remote_reminder.note_id {
+            let note_known = conn.prepare("SELECT 1 FROM notes WHERE id = ?")?.exists(params![note_id])?;
+            if !note_known {
+                // A reminder for a note deleted here goes with it; otherwise
+                // the note hasn't arrived yet, so hold on to it
+                if !conn
+                    .prepare("SELECT 1 FROM deleted_entities WHERE entity_type = 'note' AND entity_id = ?")?
+                    .exists(params![note_id])?
+                {
+                    defer_reminder(conn, &remote_reminder)?;
+                }
+                continue;
+            }
+        }
+
+        let local: Option<(i64, String)> = conn
+            .query_row(
+                "SELECT revision, updated_at FROM reminders WHERE id = ?",
+                params![&remote_reminder.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let should_apply = match local {
+            None => true,
+            Some((local_rev, local_updated)) => {
+                if remote_reminder.revision > local_rev {
+                    true
+                } else if remote_reminder.revision == local_rev {
+                    remote_reminder.updated_at > local_updated
+                } else {
+                    let conflict = SyncConflict {
+                        entity_type: "reminder".to_string(),
+                        entity_id: remote_reminder.id.clone(),
+                        local_revision: local_rev,
+                        remote_revision: remote_reminder.revision,
+                        resolution: "local_wins".to_string(),
+                    };
+                    record_conflict(conn, &conflict, None)?;
+                    conflicts.push(conflict);
+                    false
+                }
+            }
+        };
+
+        if should_apply {
+            let recurrence = remote_reminder.recurrence.as_ref().map(|rule| serde_json::to_string(rule).unwrap());
+            conn.execute(
+                "INSERT INTO reminders (id, note_id, message, due_date, completed, notified, revision, created_at, updated_at, deleted_at, recurrence, snoozed_from, needs_push)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0)
+                 ON CONFLICT(id) DO UPDATE SET
+                     note_id = excluded.note_id,
+                     message = excluded.message,
+                     due_date = excluded.due_date,
+                     completed = excluded.completed,
+                     notified = excluded.notified,
+                     revision = excluded.revision,
+                     created_at = excluded.created_at,
+                     updated_at = excluded.updated_at,
+                     deleted_at = excluded.deleted_at,
+                     recurrence = excluded.recurrence,
+                     snoozed_from = excluded.snoozed_from,
+                     needs_push = 0",
+                params![
+                    remote_reminder.id,
+                    remote_reminder.note_id,
+                    remote_reminder.message,
+                    reminders::normalize_timestamp(&remote_reminder.due_date),
+                    remote_reminder.completed as i32,
+                    remote_reminder.notified as i32,
+                    remote_reminder.revision,
+                    remote_reminder.created_at,
+                    remote_reminder.updated_at,
+                    remote_reminder.deleted_at,
+                    recurrence,
+                    remote_reminder.snoozed_from.as_deref().map(reminders::normalize_timestamp),
+                ],
+            )?;
+            stats.reminders += 1;
+        }
+    }
+
+    // Apply hard deletions last, so a tombstone beats an older copy of the
+    // same row in this payload
+    for deleted in &remote.deleted {
+        // Including the echo of the tombstones that made private rows leave
+        if stays_local(conn, &deleted.entity_type, &deleted.entity_id)? {
+            continue;
+        }
+        match apply_deletion(conn, deleted)? {
+            Some(conflict) => {
+                record_conflict(conn, &conflict, None)?;
+                conflicts.push(conflict);
+            }
+            None => stats.deleted += 1,
+        }
+    }
+
+    // Merged rows only carry content; compute their excerpts
+    notes::backfill_excerpts(conn)?;
+
+    Ok((stats, conflicts))
+}
+
+// =============================================================================
+// Tauri Commands
+// =============================================================================
+
+/// Get current sync state
+#[tauri::command]
+pub fn get_local_sync_state(db: State<'_, Database>) -> Result<LocalSyncState> {
+    get_sync_state(&db)
+}
+
+/// This device's sync id and when it last synced
+#[tauri::command]
+pub fn get_device_info(db: State<'_, Database>) -> Result<DeviceInfo> {
+    let device_id = device_id(&db.conn())?;
+    let state = get_sync_state(&db)?;
+    Ok(DeviceInfo {
+        device_id,
+        last_synced_at: state.last_synced_at,
+        last_pull_revision: state.last_pull_revision,
+        last_push_revision: state.last_push_revision,
+    })
+}
+
+/// Get changes that need to be pushed
+#[tauri::command]
+pub fn get_pending_changes(db: State<'_, Database>) -> Result<SyncPayload> {
+    unpushed_changes(&db)
+}
+
+/// Highest revision of anything in the payload, 0 when it's empty
+fn payload_revision(payload: &SyncPayload) -> i64 {
+    let revisions = payload
+        .notes
+        .iter()
+        .map(|n| n.revision)
+        .chain(payload.notebooks.iter().map(|nb| nb.revision))
+        .chain(payload.tags.iter().map(|t| t.revision))
+        .chain(payload.reminders.iter().map(|r| r.revision))
+        .chain(payload.deleted.iter().map(|d| d.revision));
+    revisions.max().unwrap_or(0)
+}
+
+/// Merge a pulled payload and advance the pull cursor to `server_revision`,
+/// or to the newest revision in the payload when the server didn't say
+pub fn apply_pull(
+    db: &Database,
+    payload: SyncPayload,
+    server_revision: Option<i64>,
+    strategy: ConflictStrategy,
+) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    let revision = server_revision.unwrap_or_else(|| payload_revision(&payload));
+    merge_and_advance(db, payload, strategy, Some(revision))
+}
+
+/// Apply remote changes (pull)
+#[tauri::command]
+pub fn apply_remote_changes(
+    db: State<'_, Database>,
+    payload: SyncPayload,
+    server_revision: Option<i64>,
+    conflict_strategy: Option<ConflictStrategy>,
+) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    let strategy = ConflictStrategy::resolve(&db.conn(), conflict_strategy)?;
+    apply_pull(&db, payload, server_revision, strategy)
+}
+
+/// Resync one entity type ("note", "notebook", "tag" or "reminder") from
+/// scratch on the next sync
+#[tauri::command]
+pub fn reset_sync_cursor(db: State<'_, Database>, entity_type: String) -> Result<LocalSyncState> {
+    reset_cursor(&db.conn(), &entity_type)?;
+    get_sync_state(&db)
+}
+
+/// Conflicts a sync resolved by discarding one side, for the user to review
+#[tauri::command]
+pub fn list_unresolved_conflicts(db: State<'_, Database>) -> Result<Vec<StoredConflict>> {
+    unresolved_conflicts(&db.conn())
+}
+
+#[tauri::command]
+pub fn list_conflict_archive(db: State<'_, Database>, note_id: String) -> Result<Vec<ArchivedNoteVersion>> {
+    conflict_archive(&db.conn(), &note_id)
 }
 
-/// Mark changes as pushed
+/// Bring back a version lost to an automatic conflict resolution
+#[tauri::command]
+pub fn restore_conflict_version(
+    app: AppHandle,
+    db: State<'_, Database>,
+    archive_id: String,
+    as_new_note: Option<bool>,
+) -> Result<Note> {
+    let (id, created) = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let restored = restore_archived_version(&tx, &archive_id, as_new_note.unwrap_or(false))?;
+        tx.commit()?;
+        restored
+    };
+
+    let note = notes::get_note(db, id)?;
+    let kind = if created { ChangeKind::Created } else { ChangeKind::Updated };
+    events::emit(&app, ChangeEvent::Note, &note.id, kind);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn resolve_conflict(
+    app: AppHandle,
+    db: State<'_, Database>,
+    conflict_id: String,
+    choice: ConflictChoice,
+    merged_content: Option<String>,
+) -> Result<()> {
+    let (entity_type, entity_id) = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let resolved = resolve(&tx, &conflict_id, choice, merged_content)?;
+        tx.commit()?;
+        resolved
+    };
+
+    events::emit(&app, change_event(&entity_type), &entity_id, ChangeKind::Updated);
+    Ok(())
+}
+
+fn change_event(entity_type: &str) -> ChangeEvent {
+    match entity_type {
+        "note" => ChangeEvent::Note,
+        "notebook" => ChangeEvent::Notebook,
+        "tag" => ChangeEvent::Tag,
+        _ => ChangeEvent::Reminder,
+    }
+}
+
+/// Hard-delete soft-deleted rows whose deletion has been pushed, once older
+/// than `older_than_days` (the `tombstone_retention_days` setting when omitted)
+#[tauri::command]
+pub fn purge_synced_tombstones(
+    app: AppHandle,
+    db: State<'_, Database>,
+    older_than_days: Option<i64>,
+) -> Result<SyncStats> {
+    let purged = {
+        let mut conn = db.conn();
+        let tx = conn.transaction()?;
+        let days = match older_than_days {
+            Some(days) => days,
+            None => retention_days(&tx)?,
+        };
+        let purged = purge_synced(&tx, days)?;
+        tx.commit()?;
+        purged
+    };
+
+    let mut stats = SyncStats::default();
+    for (entity_type, id) in &purged {
+        match *entity_type {
+            "note" => stats.notes += 1,
+            "notebook" => stats.notebooks += 1,
+            _ => stats.tags += 1,
+        }
+        events::emit(&app, change_event(entity_type), id, ChangeKind::Deleted);
+    }
+    Ok(stats)
+}
+
+/// Mark changes as pushed: clears every push flag and moves the push
+/// cursors to the server's revision
 #[tauri::command]
 pub fn mark_changes_pushed(db: State<'_, Database>, up_to_revision: i64) -> Result<()> {
+    mark_all_pushed(&db)?;
     update_sync_state(&db, None, Some(up_to_revision))
 }
 
@@ -474,7 +2194,7 @@ pub fn mark_changes_pushed(db: State<'_, Database>, up_to_revision: i64) -> Resu
 #[tauri::command]
 pub fn prepare_sync(db: State<'_, Database>) -> Result<(LocalSyncState, SyncPayload)> {
     let state = get_sync_state(&db)?;
-    let changes = get_changes_since(&db, state.last_push_revision)?;
+    let changes = unpushed_changes(&db)?;
     Ok((state, changes))
 }
 
@@ -485,7 +2205,27 @@ pub fn prepare_sync(db: State<'_, Database>) -> Result<(LocalSyncState, SyncPayl
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PullRequest {
     device_id: String,
+    /// Lowest of `since`, for servers without per-type cursors
     last_sync_revision: i64,
+    since: SyncCursors,
+    /// Most entities of each type to send back
+    limit: Option<i64>,
+    /// Only looking: the server doesn't record the pull against a device
+    dry_run: bool,
+}
+
+impl PullRequest {
+    /// A pull of up to `limit` changes past `since`, as `device_id` or, for a
+    /// preview, as no device at all
+    fn new(device_id: Option<&str>, since: SyncCursors, limit: i64) -> Self {
+        PullRequest {
+            device_id: device_id.unwrap_or_default().to_string(),
+            last_sync_revision: since.min(),
+            since,
+            limit: Some(limit),
+            dry_run: device_id.is_none(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -493,7 +2233,39 @@ struct PullResponse {
     notes: Vec<ServerNote>,
     notebooks: Vec<ServerNotebook>,
     tags: Vec<ServerTag>,
+    #[serde(default)]
+    reminders: Vec<ServerReminder>,
+    #[serde(default)]
+    deleted: Vec<DeletedEntity>,
     server_revision: i64,
+    /// Servers without paging send everything and leave these out
+    #[serde(default)]
+    has_more: bool,
+    #[serde(default)]
+    page_revision: Option<i64>,
+}
+
+/// One page of a pull, converted to local models
+struct RemotePage {
+    payload: SyncPayload,
+    /// Where the pull cursors move once the page is merged
+    revision: i64,
+    has_more: bool,
+}
+
+impl RemotePage {
+    /// Cursors to ask for the next page with, or None after the last page
+    fn next(&self, since: SyncCursors) -> Result<Option<SyncCursors>> {
+        if !self.has_more {
+            return Ok(None);
+        }
+        let next = since.advanced_to(self.revision);
+        if next == since {
+            // Asking again would return the same page forever
+            return Err(AppError::Sync(format!("server sent a page that ends at revision {}", self.revision)));
+        }
+        Ok(Some(next))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -502,12 +2274,56 @@ struct PushRequest {
     notes: Vec<ServerNote>,
     notebooks: Vec<ServerNotebook>,
     tags: Vec<ServerTag>,
+    reminders: Vec<ServerReminder>,
+    deleted: Vec<DeletedEntity>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PushResponse {
     accepted: usize,
+    /// Accepted per type; older servers only send the total
+    #[serde(default)]
+    accepted_counts: Option<SyncStats>,
     conflicts: Vec<ServerConflict>,
+    /// Per pushed entity; older servers leave it out
+    #[serde(default)]
+    results: Vec<PushResult>,
+    server_revision: i64,
+}
+
+impl PushResponse {
+    fn pushed_stats(&self) -> SyncStats {
+        if let Some(counts) = &self.accepted_counts {
+            return counts.clone();
+        }
+        if self.results.is_empty() {
+            return SyncStats {
+                notes: self.accepted as i32,
+                ..SyncStats::default()
+            };
+        }
+        let mut stats = SyncStats::default();
+        for result in &self.results {
+            match (result.status.as_str(), result.entity_type.as_str()) {
+                ("deleted", _) => stats.deleted += 1,
+                ("accepted", "note") => stats.notes += 1,
+                ("accepted", "notebook") => stats.notebooks += 1,
+                ("accepted", "tag") => stats.tags += 1,
+                ("accepted", "reminder") => stats.reminders += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+/// What the server did with one pushed entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PushResult {
+    id: String,
+    entity_type: String,
+    /// "accepted" or "conflict"; "deleted" or "skipped" for deletions
+    status: String,
     server_revision: i64,
 }
 
@@ -518,6 +2334,33 @@ struct ServerConflict {
     local_revision: i64,
     server_revision: i64,
     resolution: String,
+    /// What the server kept; older servers leave it out
+    #[serde(default)]
+    server_copy: Option<serde_json::Value>,
+}
+
+/// The copies the server kept over conflicting pushed entities, as local models
+fn server_copies(conflicts: &[ServerConflict]) -> Result<SyncPayload> {
+    fn parse<T: serde::de::DeserializeOwned>(conflict: &ServerConflict, copy: &serde_json::Value) -> Result<T> {
+        serde_json::from_value(copy.clone()).map_err(|e| {
+            AppError::Sync(format!("invalid server copy of {} {}: {}", conflict.entity_type, conflict.entity_id, e))
+        })
+    }
+
+    let mut copies = SyncPayload::default();
+    for conflict in conflicts {
+        let Some(copy) = &conflict.server_copy else {
+            continue;
+        };
+        match conflict.entity_type.as_str() {
+            "note" => copies.notes.push(server_to_note(parse(conflict, copy)?)?),
+            "notebook" => copies.notebooks.push(server_to_notebook(parse(conflict, copy)?)),
+            "tag" => copies.tags.push(server_to_tag(parse(conflict, copy)?)),
+            "reminder" => copies.reminders.push(server_to_reminder(parse(conflict, copy)?)),
+            _ => {}
+        }
+    }
+    Ok(copies)
 }
 
 // Server-side models (slightly different format)
@@ -529,10 +2372,20 @@ struct ServerNote {
     notebook_id: Option<String>,
     tags: String, // JSON string on server
     status: String,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    pinned_order: Option<i64>,
     created_at: String,
     updated_at: String,
     revision: i64,
     is_deleted: bool,
+    /// Older servers only send `is_deleted`
+    #[serde(default)]
+    deleted_at: Option<String>,
+    /// Title and content are ciphertext only a device with the key can read
+    #[serde(default)]
+    encrypted: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -540,11 +2393,17 @@ struct ServerNotebook {
     id: String,
     name: String,
     color: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
     parent_id: Option<String>,
+    #[serde(default)]
+    is_archived: bool,
     created_at: String,
     updated_at: String,
     revision: i64,
     is_deleted: bool,
+    #[serde(default)]
+    deleted_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -556,66 +2415,114 @@ struct ServerTag {
     updated_at: String,
     revision: i64,
     is_deleted: bool,
+    #[serde(default)]
+    deleted_at: Option<String>,
 }
 
-fn get_device_id() -> String {
-    use std::fs;
-    use std::path::PathBuf;
-
-    // Try to get app data dir, fallback to temp dir
-    let device_id_path: PathBuf = dirs::data_dir()
-        .unwrap_or_else(|| std::env::temp_dir())
-        .join("viny")
-        .join(".device_id");
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ServerReminder {
+    id: String,
+    note_id: Option<String>,
+    message: String,
+    due_date: String,
+    completed: bool,
+    notified: bool,
+    recurrence: Option<String>, // JSON string on server
+    snoozed_from: Option<String>,
+    created_at: String,
+    updated_at: String,
+    revision: i64,
+    is_deleted: bool,
+    #[serde(default)]
+    deleted_at: Option<String>,
+}
 
-    // Try to read existing device ID
-    if let Ok(id) = fs::read_to_string(&device_id_path) {
-        let id = id.trim().to_string();
-        if !id.is_empty() {
-            return id;
-        }
+/// The id this install syncs as, stored in settings. Generated on first
+/// use, or carried over from the `.device_id` file older versions wrote.
+pub fn device_id(conn: &Connection) -> Result<String> {
+    if let Some(id) = settings::get(conn, settings::DEVICE_ID)? {
+        return Ok(id);
     }
 
-    // Generate new device ID and persist it
-    let new_id = uuid::Uuid::new_v4().to_string();
-    if let Some(parent) = device_id_path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-    let _ = fs::write(&device_id_path, &new_id);
+    let id = legacy_device_id().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    settings::set(conn, settings::DEVICE_ID, &id)?;
+    Ok(id)
+}
 
-    new_id
+fn legacy_device_id() -> Option<String> {
+    let path = dirs::data_dir()?.join("viny").join(".device_id");
+    let id = std::fs::read_to_string(path).ok()?;
+    let id = id.trim();
+    uuid::Uuid::parse_str(id).is_ok().then(|| id.to_string())
 }
 
-fn note_to_server(note: &Note) -> ServerNote {
-    ServerNote {
+/// With encryption on, title and content leave the device encrypted; notes
+/// written before it was switched on are still plaintext locally
+fn note_to_server(note: &Note) -> Result<ServerNote> {
+    let encrypted = crypto::is_encryption_enabled();
+    let (title, content) = if encrypted {
+        (
+            crypto::encrypt(&crypto::maybe_decrypt(&note.title)?)?,
+            crypto::encrypt(&crypto::maybe_decrypt(&note.content)?)?,
+        )
+    } else {
+        (note.title.clone(), note.content.clone())
+    };
+
+    Ok(ServerNote {
         id: note.id.clone(),
-        title: note.title.clone(),
-        content: note.content.clone(),
+        title,
+        content,
         notebook_id: note.notebook_id.clone(),
         tags: serde_json::to_string(&note.tags).unwrap_or_default(),
         status: note.status.as_str().to_string(),
+        is_pinned: note.is_pinned,
+        pinned_order: note.pinned_order,
         created_at: note.created_at.clone(),
         updated_at: note.updated_at.clone(),
         revision: note.revision,
         is_deleted: note.deleted_at.is_some(),
-    }
+        deleted_at: note.deleted_at.clone(),
+        encrypted,
+    })
 }
 
-fn server_to_note(s: ServerNote) -> Note {
+/// Decrypts an encrypted note, which fails with `LOCKED` while the key isn't
+/// loaded, and stores it the way local notes are stored
+fn server_to_note(s: ServerNote) -> Result<Note> {
+    let (title, content) = if s.encrypted {
+        if !crypto::is_encryption_enabled() {
+            return Err(AppError::Sync(LOCKED.to_string()));
+        }
+        (
+            crypto::maybe_encrypt(&crypto::decrypt(&s.title)?)?,
+            crypto::maybe_encrypt(&crypto::decrypt(&s.content)?)?,
+        )
+    } else {
+        (s.title, s.content)
+    };
+
     let tags: Vec<String> = serde_json::from_str(&s.tags).unwrap_or_default();
-    Note {
+    Ok(Note {
         id: s.id,
-        title: s.title,
-        content: s.content,
+        title,
+        content,
         notebook_id: s.notebook_id,
         tags,
         status: NoteStatus::from_str(&s.status),
-        is_pinned: false,
+        is_pinned: s.is_pinned,
         revision: s.revision,
         created_at: s.created_at,
         updated_at: s.updated_at.clone(),
-        deleted_at: if s.is_deleted { Some(s.updated_at) } else { None },
-    }
+        deleted_at: remote_deleted_at(s.is_deleted, s.deleted_at, &s.updated_at),
+        pinned_order: s.pinned_order,
+    })
+}
+
+/// When a pulled row was deleted; older servers don't say, and their
+/// `updated_at` is the closest thing
+fn remote_deleted_at(is_deleted: bool, deleted_at: Option<String>, updated_at: &str) -> Option<String> {
+    is_deleted.then(|| deleted_at.unwrap_or_else(|| updated_at.to_string()))
 }
 
 fn notebook_to_server(nb: &Notebook) -> ServerNotebook {
@@ -623,11 +2530,14 @@ fn notebook_to_server(nb: &Notebook) -> ServerNotebook {
         id: nb.id.clone(),
         name: nb.name.clone(),
         color: nb.color.clone(),
+        icon: nb.icon.clone(),
         parent_id: nb.parent_id.clone(),
+        is_archived: nb.is_archived,
         created_at: nb.created_at.clone(),
         updated_at: nb.updated_at.clone(),
         revision: nb.revision,
         is_deleted: nb.deleted_at.is_some(),
+        deleted_at: nb.deleted_at.clone(),
     }
 }
 
@@ -636,12 +2546,14 @@ fn server_to_notebook(s: ServerNotebook) -> Notebook {
         id: s.id,
         name: s.name,
         color: s.color,
-        icon: None,
+        icon: s.icon,
         parent_id: s.parent_id,
         revision: s.revision,
         created_at: s.created_at,
         updated_at: s.updated_at.clone(),
-        deleted_at: if s.is_deleted { Some(s.updated_at) } else { None },
+        deleted_at: remote_deleted_at(s.is_deleted, s.deleted_at, &s.updated_at),
+        is_archived: s.is_archived,
+        sync_excluded: false,
     }
 }
 
@@ -654,6 +2566,7 @@ fn tag_to_server(tag: &Tag) -> ServerTag {
         updated_at: tag.updated_at.clone(),
         revision: tag.revision,
         is_deleted: tag.deleted_at.is_some(),
+        deleted_at: tag.deleted_at.clone(),
     }
 }
 
@@ -665,112 +2578,3178 @@ fn server_to_tag(s: ServerTag) -> Tag {
         revision: s.revision,
         created_at: s.created_at,
         updated_at: s.updated_at.clone(),
-        deleted_at: if s.is_deleted { Some(s.updated_at) } else { None },
+        deleted_at: remote_deleted_at(s.is_deleted, s.deleted_at, &s.updated_at),
     }
 }
 
-/// Sync with remote server
-#[tauri::command]
-pub async fn sync_with_server(
-    db: State<'_, Database>,
-    server_url: String,
-) -> Result<SyncResult> {
-    let client = reqwest::Client::new();
-    let device_id = get_device_id();
-
-    // Get current state
-    let local_state = get_sync_state(&db)?;
+fn reminder_to_server(reminder: &Reminder) -> ServerReminder {
+    ServerReminder {
+        id: reminder.id.clone(),
+        note_id: reminder.note_id.clone(),
+        message: reminder.message.clone(),
+        due_date: reminder.due_date.clone(),
+        completed: reminder.completed,
+        notified: reminder.notified,
+        recurrence: reminder.recurrence.as_ref().map(|rule| serde_json::to_string(rule).unwrap_or_default()),
+        snoozed_from: reminder.snoozed_from.clone(),
+        created_at: reminder.created_at.clone(),
+        updated_at: reminder.updated_at.clone(),
+        revision: reminder.revision,
+        is_deleted: reminder.deleted_at.is_some(),
+        deleted_at: reminder.deleted_at.clone(),
+    }
+}
 
-    // 1. Pull remote changes
-    let pull_req = PullRequest {
-        device_id: device_id.clone(),
-        last_sync_revision: local_state.last_pull_revision,
+fn server_to_reminder(s: ServerReminder) -> Reminder {
+    Reminder {
+        id: s.id,
+        note_id: s.note_id,
+        message: s.message,
+        due_date: s.due_date,
+        completed: s.completed,
+        notified: s.notified,
+        revision: s.revision,
+        created_at: s.created_at,
+        updated_at: s.updated_at.clone(),
+        deleted_at: remote_deleted_at(s.is_deleted, s.deleted_at, &s.updated_at),
+        recurrence: s.recurrence.and_then(|json| serde_json::from_str(&json).ok()),
+        snoozed_from: s.snoozed_from,
+    }
+}
+
+/// Error returned when the server rejects our credentials, so the UI can
+/// prompt for a new token
+pub const UNAUTHORIZED: &str = "unauthorized";
+
+/// Error returned when pulled notes are encrypted and encryption is locked,
+/// so the UI can ask for the password before syncing again
+pub const LOCKED: &str = "locked";
+
+/// The token to sync with: `auth_token` when given, else the stored one
+pub fn auth_token(conn: &Connection, auth_token: Option<String>) -> Result<Option<String>> {
+    let token = match auth_token {
+        Some(token) => Some(token),
+        None => settings::get(conn, settings::SYNC_AUTH_TOKEN)?,
     };
+    Ok(token.filter(|token| !token.trim().is_empty()))
+}
 
-    let pull_response: PullResponse = client
-        .post(format!("{}/api/sync/pull", server_url))
-        .json(&pull_req)
-        .send()
-        .await
-        .map_err(|e| crate::error::AppError::Sync(e.to_string()))?
+fn check_status(status: reqwest::StatusCode) -> Result<()> {
+    match status {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            Err(AppError::Sync(UNAUTHORIZED.to_string()))
+        }
+        status if !status.is_success() => Err(AppError::Sync(format!("Server responded with {}", status))),
+        _ => Ok(()),
+    }
+}
+
+/// How often a sync request is retried after a network error or 5xx
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay: std::time::Duration,
+}
+
+impl RetryPolicy {
+    pub const NONE: RetryPolicy = RetryPolicy {
+        attempts: 1,
+        base_delay: std::time::Duration::ZERO,
+    };
+
+    /// `sync_retry_attempts` tries, half a second apart and doubling
+    pub fn from_settings(conn: &Connection) -> Result<Self> {
+        let attempts =
+            settings::get_i64(conn, settings::SYNC_RETRY_ATTEMPTS, settings::DEFAULT_SYNC_RETRY_ATTEMPTS)?;
+        Ok(RetryPolicy {
+            attempts: attempts.clamp(1, 10) as u32,
+            base_delay: std::time::Duration::from_millis(500),
+        })
+    }
+
+    /// Wait before retry number `retry` (0-based): the base delay doubled
+    /// per retry, plus up to one base delay of jitter
+    fn delay(&self, retry: u32) -> std::time::Duration {
+        use rand::Rng;
+        let jitter = rand::thread_rng().gen_range(0.0..1.0);
+        self.base_delay.mul_f64(f64::from(1u32 << retry.min(16)) + jitter)
+    }
+}
+
+/// The client sync requests go through. With `sync_allow_self_signed` on it
+/// accepts any certificate, which the log says loudly.
+pub fn http_client(conn: &Connection) -> Result<reqwest::Client> {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    let allow_self_signed = settings::get(conn, settings::SYNC_ALLOW_SELF_SIGNED)?.as_deref() == Some("true");
+    if allow_self_signed {
+        WARNED.call_once(|| {
+            eprintln!(
+                "WARNING: sync_allow_self_signed is on; the sync server's TLS certificate is not verified \
+                 and anyone on the network can read or alter sync traffic"
+            )
+        });
+    }
+    reqwest::Client::builder()
+        .danger_accept_invalid_certs(allow_self_signed)
+        .build()
+        .map_err(|e| AppError::Sync(e.to_string()))
+}
+
+/// Longest `Retry-After` a request waits out itself; longer ones fail with
+/// `RateLimited` so the caller can schedule the retry
+const MAX_INLINE_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The wait a 429 response asks for; a minute when it doesn't say in seconds
+fn retry_after(response: &reqwest::Response) -> std::time::Duration {
+    let seconds = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(60);
+    std::time::Duration::from_secs(seconds)
+}
+
+/// Send a request with the bearer token attached, if any, retrying network
+/// errors and 5xx responses, and waiting out short rate limits. Other errors
+/// fail on the first attempt.
+async fn send(
+    request: reqwest::RequestBuilder,
+    token: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<reqwest::Response> {
+    let request = match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+
+    let mut attempt = 1;
+    loop {
+        let this_try = request
+            .try_clone()
+            .ok_or_else(|| AppError::Sync("Request body can't be retried".to_string()))?;
+        let (transient, error) = match this_try.send().await {
+            Ok(response) if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY => {
+                return Err(rejection(response).await);
+            }
+            Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let wait = retry_after(&response);
+                if wait > MAX_INLINE_RETRY_AFTER || attempt >= retry.attempts {
+                    return Err(AppError::RateLimited {
+                        retry_after_secs: wait.as_secs(),
+                    });
+                }
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+                continue;
+            }
+            Ok(response) => match check_status(response.status()) {
+                Ok(()) => return Ok(response),
+                Err(e) => (response.status().is_server_error(), e),
+            },
+            Err(e) => (true, AppError::Sync(e.to_string())),
+        };
+        if !transient || attempt >= retry.attempts {
+            return Err(error);
+        }
+        tokio::time::sleep(retry.delay(attempt - 1)).await;
+        attempt += 1;
+    }
+}
+
+/// The entities listed in a 422 answer to a push
+async fn rejection(response: reqwest::Response) -> AppError {
+    #[derive(Deserialize)]
+    struct Body {
+        rejected: Vec<RejectedEntity>,
+    }
+    match response.json::<Body>().await {
+        Ok(body) => AppError::PushRejected(body.rejected),
+        Err(_) => AppError::Sync(format!("Server responded with {}", reqwest::StatusCode::UNPROCESSABLE_ENTITY)),
+    }
+}
+
+async fn send_json<T: serde::de::DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    token: Option<&str>,
+    retry: RetryPolicy,
+) -> Result<T> {
+    send(request, token, retry)
+        .await?
         .json()
         .await
-        .map_err(|e| crate::error::AppError::Sync(e.to_string()))?;
+        .map_err(|e| AppError::Sync(e.to_string()))
+}
 
-    // Convert and merge remote changes
-    let remote_payload = SyncPayload {
-        notes: pull_response.notes.into_iter().map(server_to_note).collect(),
-        notebooks: pull_response.notebooks.into_iter().map(server_to_notebook).collect(),
-        tags: pull_response.tags.into_iter().map(server_to_tag).collect(),
-        since_revision: local_state.last_pull_revision,
-    };
+/// Sync with remote server
+#[tauri::command]
+pub async fn sync_with_server(
+    app: AppHandle,
+    db: State<'_, Database>,
+    auto_sync: State<'_, AutoSync>,
+    server_url: String,
+    auth_token: Option<String>,
+    conflict_strategy: Option<ConflictStrategy>,
+) -> Result<SyncResult> {
+    let server_url = validation::normalize_server_url(&server_url)?;
+    let _running = auto_sync.begin()?;
+    run_sync_notifying(&app, &db, &server_url, auth_token, conflict_strategy).await
+}
+
+/// `run_sync`, emitting `PUSH_QUEUE_DRAINED` when it empties a push queue
+/// that had changes waiting
+pub async fn run_sync_notifying(
+    app: &AppHandle,
+    db: &Database,
+    server_url: &str,
+    auth_token: Option<String>,
+    conflict_strategy: Option<ConflictStrategy>,
+) -> Result<SyncResult> {
+    let queued = push_queue(&db.conn())?.total();
+    let result = run_sync(db, server_url, auth_token, conflict_strategy).await?;
+    if queued > 0 && push_queue(&db.conn())?.total() == 0 {
+        events::emit_push_queue_drained(app);
+    }
+    Ok(result)
+}
+
+/// Pull without pushing, e.g. when the server announces a new revision.
+/// Callers hold the `AutoSync` lock.
+pub async fn run_pull(db: &Database, server_url: &str) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    let device_id = device_id(&db.conn())?;
+    let token = self::auth_token(&db.conn(), None)?;
+    let strategy = ConflictStrategy::resolve(&db.conn(), None)?;
+    let retry = RetryPolicy::from_settings(&db.conn())?;
+    let client = http_client(&db.conn())?;
+    pull_pages(db, &client, server_url, &device_id, token.as_deref(), strategy, retry).await
+}
+
+/// Pull remote changes a page at a time, each merged along with its cursor
+/// so an interrupted sync resumes after the last merged page
+async fn pull_pages(
+    db: &Database,
+    client: &reqwest::Client,
+    server_url: &str,
+    device_id: &str,
+    token: Option<&str>,
+    strategy: ConflictStrategy,
+    retry: RetryPolicy,
+) -> Result<(SyncStats, Vec<SyncConflict>)> {
+    let page_size =
+        settings::get_i64(&db.conn(), settings::SYNC_PULL_PAGE_SIZE, settings::DEFAULT_SYNC_PULL_PAGE_SIZE)?;
+
+    let mut pulled_stats = SyncStats::default();
+    let mut pull_conflicts = Vec::new();
+    let mut since = Some(get_sync_state(db)?.pull_cursors);
+    while let Some(cursors) = since {
+        let page = fetch_page(client, server_url, Some(device_id), token, retry, cursors, page_size).await?;
+        since = page.next(cursors)?;
+        let (stats, conflicts) = apply_pull(db, page.payload, Some(page.revision), strategy)?;
+        pulled_stats += stats;
+        pull_conflicts.extend(conflicts);
+    }
+    Ok((pulled_stats, pull_conflicts))
+}
 
-    let (pulled_stats, pull_conflicts) = merge_remote_changes(&db, remote_payload)?;
+/// Pull then push against `server_url`. Callers hold the `AutoSync` lock.
+pub async fn run_sync(
+    db: &Database,
+    server_url: &str,
+    auth_token: Option<String>,
+    conflict_strategy: Option<ConflictStrategy>,
+) -> Result<SyncResult> {
+    let client = http_client(&db.conn())?;
+    let device_id = device_id(&db.conn())?;
+    let token = self::auth_token(&db.conn(), auth_token)?;
+    let strategy = ConflictStrategy::resolve(&db.conn(), conflict_strategy)?;
+    let retry = RetryPolicy::from_settings(&db.conn())?;
 
-    // Update pull revision
-    update_sync_state(&db, Some(pull_response.server_revision), None)?;
+    // 1. Pull remote changes
+    let (mut pulled_stats, pull_conflicts) =
+        pull_pages(db, &client, server_url, &device_id, token.as_deref(), strategy, retry).await?;
 
     // 2. Push local changes
-    let changes = get_changes_since(&db, local_state.last_push_revision)?;
+    let changes = unpushed_changes(db)?;
 
     let push_req = PushRequest {
         device_id,
-        notes: changes.notes.iter().map(note_to_server).collect(),
+        notes: changes.notes.iter().map(note_to_server).collect::<Result<_>>()?,
         notebooks: changes.notebooks.iter().map(notebook_to_server).collect(),
         tags: changes.tags.iter().map(tag_to_server).collect(),
+        reminders: changes.reminders.iter().map(reminder_to_server).collect(),
+        deleted: changes.deleted.clone(),
     };
 
-    let push_response: PushResponse = client
+    let push_request = client
         .post(format!("{}/api/sync/push", server_url))
-        .json(&push_req)
-        .send()
-        .await
-        .map_err(|e| crate::error::AppError::Sync(e.to_string()))?
-        .json()
-        .await
-        .map_err(|e| crate::error::AppError::Sync(e.to_string()))?;
+        .json(&checksum::sign(&push_req)?);
+    let push_response = send_json(push_request, token.as_deref(), retry).await;
+    let push_response: PushResponse = match push_response.and_then(checksum::verified) {
+        Ok(response) => response,
+        // The pull is already merged, so report it along with the failure
+        Err(e) => {
+            let rejected = match &e {
+                AppError::PushRejected(rejected) => rejected.clone(),
+                _ => Vec::new(),
+            };
+            let retry_after_secs = match e {
+                AppError::RateLimited { retry_after_secs } => Some(retry_after_secs),
+                _ => None,
+            };
+            return Ok(SyncResult {
+                pulled: pulled_stats,
+                pushed: SyncStats::default(),
+                conflicts: pull_conflicts,
+                last_synced_at: chrono::Utc::now().to_rfc3339(),
+                push_error: Some(e.to_string()),
+                rejected,
+                retry_after_secs,
+            });
+        }
+    };
+
+    // Read before anything is marked pushed, so a copy that can't be read
+    // leaves the changes queued
+    let copies = server_copies(&push_response.conflicts)?;
 
     // Update push revision
-    update_sync_state(&db, None, Some(push_response.server_revision))?;
+    clear_pushed(&db.conn(), &changes)?;
+    update_sync_state(db, None, Some(push_response.server_revision))?;
+
+    // Deletions every device has had time to pull can go for good
+    {
+        let mut conn = db.conn();
+        if settings::get(&conn, settings::PURGE_TOMBSTONES_AFTER_SYNC)?.as_deref() == Some("true") {
+            let tx = conn.transaction()?;
+            let days = retention_days(&tx)?;
+            purge_synced(&tx, days)?;
+            tx.commit()?;
+        }
+    }
+
+    let pushed_stats = push_response.pushed_stats();
 
     // Combine conflicts
     let mut all_conflicts: Vec<SyncConflict> = pull_conflicts;
     for c in push_response.conflicts {
-        all_conflicts.push(SyncConflict {
+        let conflict = SyncConflict {
             entity_type: c.entity_type,
             entity_id: c.entity_id,
             local_revision: c.local_revision,
             remote_revision: c.server_revision,
             resolution: c.resolution,
-        });
+        };
+        // The server kept its copy; the edit pushed from here lost
+        let pushed = changes.notes.iter().find(|n| conflict.entity_type == "note" && n.id == conflict.entity_id);
+        if let Some(pushed) = pushed {
+            archive_note_version(&db.conn(), pushed, "local", &conflict.resolution)?;
+            if let Some(kept) = copies.notes.iter().find(|n| n.id == pushed.id) {
+                let snapshots = NoteSnapshots {
+                    local: (&pushed.title, &pushed.content),
+                    remote: (&kept.title, &kept.content),
+                };
+                record_conflict(&db.conn(), &conflict, Some(snapshots))?;
+            }
+        }
+        all_conflicts.push(conflict);
     }
 
-    let pushed_stats = SyncStats {
-        notes: push_response.accepted as i32,
-        notebooks: 0,
-        tags: 0,
-    };
+    // Apply what the server kept now; a later pull may not bring it back if
+    // the cursors have already moved past it
+    let (applied, _) = merge_and_advance(db, copies, strategy, None)?;
+    pulled_stats += applied;
 
     Ok(SyncResult {
         pulled: pulled_stats,
         pushed: pushed_stats,
         conflicts: all_conflicts,
         last_synced_at: chrono::Utc::now().to_rfc3339(),
+        push_error: None,
+        rejected: Vec::new(),
+        retry_after_secs: None,
+    })
+}
+
+/// Ask the server for up to `limit` changes of each type past `since`;
+/// without a `device_id` the server records nothing
+async fn fetch_page(
+    client: &reqwest::Client,
+    server_url: &str,
+    device_id: Option<&str>,
+    token: Option<&str>,
+    retry: RetryPolicy,
+    since: SyncCursors,
+    limit: i64,
+) -> Result<RemotePage> {
+    let pull_req = PullRequest::new(device_id, since, limit);
+
+    let pull_request = client.post(format!("{}/api/sync/pull", server_url)).json(&pull_req);
+    let pull_response: PullResponse = checksum::verified(send_json(pull_request, token, retry).await?)?;
+
+    let payload = SyncPayload {
+        notes: pull_response.notes.into_iter().map(server_to_note).collect::<Result<_>>()?,
+        notebooks: pull_response.notebooks.into_iter().map(server_to_notebook).collect(),
+        tags: pull_response.tags.into_iter().map(server_to_tag).collect(),
+        reminders: pull_response.reminders.into_iter().map(server_to_reminder).collect(),
+        deleted: pull_response.deleted,
+        since_revision: since.min(),
+    };
+    Ok(RemotePage {
+        payload,
+        revision: pull_response.page_revision.unwrap_or(pull_response.server_revision),
+        has_more: pull_response.has_more,
     })
 }
 
-/// Check if server is reachable
+/// Pull from `server_url` and report what a sync would change, without
+/// merging or pushing anything
+#[tauri::command]
+pub async fn preview_sync(
+    db: State<'_, Database>,
+    server_url: String,
+    auth_token: Option<String>,
+    conflict_strategy: Option<ConflictStrategy>,
+) -> Result<SyncPreview> {
+    let server_url = validation::normalize_server_url(&server_url)?;
+    let client = http_client(&db.conn())?;
+    let token = self::auth_token(&db.conn(), auth_token)?;
+    let strategy = ConflictStrategy::resolve(&db.conn(), conflict_strategy)?;
+    let retry = RetryPolicy::from_settings(&db.conn())?;
+
+    let page_size =
+        settings::get_i64(&db.conn(), settings::SYNC_PULL_PAGE_SIZE, settings::DEFAULT_SYNC_PULL_PAGE_SIZE)?;
+
+    // Nothing is merged between pages here, so they all go into one merge
+    let first = get_sync_state(&db)?.pull_cursors;
+    let mut remote = SyncPayload {
+        since_revision: first.min(),
+        ..SyncPayload::default()
+    };
+    let mut server_revision = 0;
+    let mut since = Some(first);
+    while let Some(cursors) = since {
+        // A dry run, so the preview doesn't count as this device's pull
+        let page = fetch_page(&client, &server_url, None, token.as_deref(), retry, cursors, page_size).await?;
+        since = page.next(cursors)?;
+        server_revision = page.revision;
+        remote.notes.extend(page.payload.notes);
+        remote.notebooks.extend(page.payload.notebooks);
+        remote.tags.extend(page.payload.tags);
+        remote.reminders.extend(page.payload.reminders);
+        remote.deleted.extend(page.payload.deleted);
+    }
+
+    let mut preview = preview_merge(&db, remote, strategy)?;
+    preview.server_revision = server_revision;
+    Ok(preview)
+}
+
+/// Check if server is reachable. Rejected credentials are an error rather
+/// than unreachable so the UI can tell them apart from a server that's down.
 #[tauri::command]
-pub async fn check_server_connection(server_url: String) -> Result<bool> {
-    let client = reqwest::Client::new();
+pub async fn check_server_connection(
+    db: State<'_, Database>,
+    server_url: String,
+    auth_token: Option<String>,
+) -> Result<ServerConnection> {
+    let server_url = validation::normalize_server_url(&server_url)?;
+    let token = self::auth_token(&db.conn(), auth_token)?;
+    let client = http_client(&db.conn())?;
+    let reachable = server_reachable(&client, &server_url, token.as_deref()).await?;
+    Ok(ServerConnection { reachable, server_url })
+}
+
+/// Subscribe to the server's change notifications, a server-sent event
+/// stream of `revision` events
+pub async fn open_events(client: &reqwest::Client, server_url: &str, token: Option<&str>) -> Result<reqwest::Response> {
+    let request = client.get(format!("{}/api/sync/events", server_url));
+    send(request, token, RetryPolicy::NONE).await
+}
+
+/// Take the revisions of the complete `revision` events at the front of
+/// `buffer`, leaving a partly received event in it
+pub fn drain_revision_events(buffer: &mut String) -> Vec<i64> {
+    let mut revisions = Vec::new();
+    while let Some(end) = buffer.find("\n\n") {
+        let event: String = buffer.drain(..end + 2).collect();
+        let mut name = "message";
+        let mut data = None;
+        for line in event.lines() {
+            if let Some(value) = line.strip_prefix("event:") {
+                name = value.trim();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                data = value.trim().parse::<i64>().ok();
+            }
+        }
+        if let (Some(revision), "revision") = (data, name) {
+            revisions.push(revision);
+        }
+    }
+    revisions
+}
 
-    match client
+pub async fn server_reachable(client: &reqwest::Client, server_url: &str, token: Option<&str>) -> Result<bool> {
+    let request = client
         .get(format!("{}/health", server_url))
-        .timeout(std::time::Duration::from_secs(5))
-        .send()
-        .await
-    {
-        Ok(resp) => Ok(resp.status().is_success()),
+        .timeout(std::time::Duration::from_secs(5));
+    match send(request, token, RetryPolicy::NONE).await {
+        Ok(_) => Ok(true),
+        Err(AppError::Sync(message)) if message == UNAUTHORIZED => Err(AppError::Sync(message)),
         Err(_) => Ok(false),
     }
 }
+
+#[derive(Debug, Deserialize)]
+struct RevisionResponse {
+    revision: i64,
+}
+
+/// Local sync state, compared against the server's global revision when
+/// `server_url` is given. Nothing is pulled.
+#[tauri::command]
+pub async fn get_sync_status(db: State<'_, Database>, server_url: Option<String>) -> Result<SyncStatus> {
+    sync_status(&db, server_url.as_deref()).await
+}
+
+/// What `get_sync_status` reports
+pub async fn sync_status(db: &Database, server_url: Option<&str>) -> Result<SyncStatus> {
+    let state = get_sync_state(db)?;
+    // A push only moves the push cursors; its rows come back on the next pull
+    let local_revision = state.last_pull_revision.max(state.last_push_revision);
+    let mut status = SyncStatus {
+        status: SyncStatusKind::of(state.pending_changes, local_revision, None),
+        pending_changes: state.pending_changes,
+        last_synced_at: state.last_synced_at,
+        local_revision,
+        server_revision: None,
+    };
+    let Some(server_url) = server_url else {
+        return Ok(status);
+    };
+
+    let server_url = validation::normalize_server_url(server_url)?;
+    let token = self::auth_token(&db.conn(), None)?;
+    let client = http_client(&db.conn())?;
+    let request = client
+        .get(format!("{}/api/sync/revision", server_url))
+        .timeout(std::time::Duration::from_secs(5));
+    match send_json::<RevisionResponse>(request, token.as_deref(), RetryPolicy::NONE).await {
+        Ok(response) => {
+            status.status = SyncStatusKind::of(state.pending_changes, local_revision, Some(response.revision));
+            status.server_revision = Some(response.revision);
+        }
+        Err(AppError::Sync(message)) if message == UNAUTHORIZED => return Err(AppError::Sync(message)),
+        Err(_) => status.status = SyncStatusKind::Offline,
+    }
+    Ok(status)
+}
+
+/// A device the server has seen pull or push
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct ServerDevice {
+    pub device_id: String,
+    pub user_id: Option<String>,
+    /// Everything up to this revision has been pulled by the device
+    pub last_pull_revision: i64,
+    pub last_push_at: Option<String>,
+    pub last_seen_at: String,
+    pub first_seen_at: String,
+}
+
+/// The devices syncing with `server_url`, most recently seen first
+#[tauri::command]
+pub async fn list_server_devices(db: State<'_, Database>, server_url: String) -> Result<Vec<ServerDevice>> {
+    let server_url = validation::normalize_server_url(&server_url)?;
+    let token = self::auth_token(&db.conn(), None)?;
+    let client = http_client(&db.conn())?;
+    let request = client
+        .get(format!("{}/api/devices", server_url))
+        .timeout(std::time::Duration::from_secs(10));
+    send_json(request, token.as_deref(), RetryPolicy::NONE).await
+}
+
+/// What's waiting to be pushed, by type
+#[tauri::command]
+pub fn get_push_queue_summary(db: State<'_, Database>) -> Result<PushQueueSummary> {
+    push_queue(&db.conn())
+}
+
+/// Sync right away if anything is queued and the server (`server_url`, the
+/// stored one when omitted) answers. Returns what's still queued.
+#[tauri::command]
+pub async fn flush_push_queue(
+    app: AppHandle,
+    db: State<'_, Database>,
+    auto_sync: State<'_, AutoSync>,
+    server_url: Option<String>,
+    auth_token: Option<String>,
+) -> Result<PushQueueSummary> {
+    let queue = push_queue(&db.conn())?;
+    if queue.total() == 0 {
+        return Ok(queue);
+    }
+    let server_url = match server_url {
+        Some(server_url) => server_url,
+        None => settings::get(&db.conn(), settings::SERVER_URL)?
+            .ok_or_else(|| AppError::Validation("No server URL to push to".to_string()))?,
+    };
+    let server_url = validation::normalize_server_url(&server_url)?;
+    let token = self::auth_token(&db.conn(), auth_token)?;
+    let client = http_client(&db.conn())?;
+    if !server_reachable(&client, &server_url, token.as_deref()).await? {
+        return Ok(queue);
+    }
+
+    let _running = auto_sync.begin()?;
+    run_sync_notifying(&app, &db, &server_url, token, None).await?;
+    push_queue(&db.conn())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+    use crate::test_support::{allow_writes, fail_writes};
+
+    fn insert_note(db: &Database, id: &str, updated_at: &str) {
+        let conn = db.conn();
+        conn.execute(
+            "INSERT INTO notes (id, title, content, updated_at) VALUES (?, 'Shared', '- [ ] task', ?)",
+            params![id, updated_at],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO reminders (id, note_id, due_date) VALUES (?, ?, '2030-01-01T00:00:00Z')",
+            params![uuid::Uuid::new_v4().to_string(), id],
+        )
+        .unwrap();
+        tasks::refresh_note_tasks(&conn, id).unwrap();
+    }
+
+    fn count(db: &Database, sql: &str, id: &str) -> i64 {
+        db.conn().query_row(sql, params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_hard_delete_propagates_to_other_device() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        insert_note(&device_a, &id, "2024-01-01T00:00:00+00:00");
+        merge_remote_changes(&device_b, get_changes_since(&device_a, 0).unwrap(), ConflictStrategy::Lww).unwrap();
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM notes WHERE id = ?", &id), 1);
+
+        assert_eq!(count(&device_a, "SELECT COUNT(*) FROM note_tasks WHERE note_id = ?", &id), 1);
+        notes::hard_delete_note(&device_a, &id).unwrap();
+        assert_eq!(count(&device_a, "SELECT COUNT(*) FROM reminders WHERE note_id = ?", &id), 0);
+        assert_eq!(count(&device_a, "SELECT COUNT(*) FROM note_tasks WHERE note_id = ?", &id), 0);
+
+        let changes = get_changes_since(&device_a, 0).unwrap();
+        assert!(changes.notes.is_empty());
+        assert_eq!(changes.deleted.len(), 1);
+        assert_eq!(changes.deleted[0].entity_id, id);
+
+        let (stats, conflicts) = merge_remote_changes(&device_b, changes, ConflictStrategy::Lww).unwrap();
+        assert_eq!(stats.deleted, 1);
+        assert!(conflicts.is_empty());
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM notes WHERE id = ?", &id), 0);
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM notes_fts WHERE id = ?", &id), 0);
+    }
+
+    #[test]
+    fn test_tombstone_blocks_stale_copy() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        insert_note(&device_a, &id, "2024-01-01T00:00:00+00:00");
+        insert_note(&device_b, &id, "2024-01-01T00:00:00+00:00");
+        let stale = get_changes_since(&device_b, 0).unwrap();
+
+        notes::hard_delete_note(&device_a, &id).unwrap();
+        merge_remote_changes(&device_a, stale, ConflictStrategy::Lww).unwrap();
+
+        assert_eq!(count(&device_a, "SELECT COUNT(*) FROM notes WHERE id = ?", &id), 0);
+    }
+
+    #[test]
+    fn test_local_edit_after_remote_delete_wins() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        insert_note(&device, &id, "2030-06-01 12:00:00");
+
+        let payload = SyncPayload {
+            notes: vec![],
+            notebooks: vec![],
+            tags: vec![],
+            reminders: vec![],
+            deleted: vec![DeletedEntity {
+                entity_type: "note".to_string(),
+                entity_id: id.clone(),
+                revision: 5,
+                deleted_at: "2030-05-01T00:00:00Z".to_string(),
+            }],
+            since_revision: 0,
+        };
+        let (stats, conflicts) = merge_remote_changes(&device, payload, ConflictStrategy::Lww).unwrap();
+
+        assert_eq!(stats.deleted, 0);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id = ?", &id), 1);
+    }
+
+    #[test]
+    fn test_reminders_sync_and_wait_for_their_note() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        insert_note(&device_a, &id, "2024-01-01T00:00:00+00:00");
+
+        // The reminder arrives before its note
+        let mut changes = get_changes_since(&device_a, 0).unwrap();
+        assert_eq!(changes.reminders.len(), 1);
+        let reminder_id = changes.reminders[0].id.clone();
+        let notes = std::mem::take(&mut changes.notes);
+        let (stats, _) = merge_remote_changes(&device_b, changes, ConflictStrategy::Lww).unwrap();
+        assert_eq!(stats.reminders, 0);
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM deferred_reminders WHERE id = ?", &reminder_id), 1);
+
+        let payload = SyncPayload {
+            notes,
+            notebooks: vec![],
+            tags: vec![],
+            reminders: vec![],
+            deleted: vec![],
+            since_revision: 0,
+        };
+        let (stats, _) = merge_remote_changes(&device_b, payload, ConflictStrategy::Lww).unwrap();
+        assert_eq!(stats.reminders, 1);
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM reminders WHERE note_id = ?", &id), 1);
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM deferred_reminders WHERE id = ?", &reminder_id), 0);
+
+        // A newer copy of the note keeps its reminders
+        device_a
+            .conn()
+            .execute("UPDATE notes SET title = 'Edited', revision = revision + 1 WHERE id = ?", params![id])
+            .unwrap();
+        let mut changes = get_changes_since(&device_a, 0).unwrap();
+        changes.reminders.clear();
+        let (stats, _) = merge_remote_changes(&device_b, changes, ConflictStrategy::Lww).unwrap();
+        assert_eq!(stats.notes, 1);
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM reminders WHERE note_id = ?", &id), 1);
+    }
+
+    fn remote_reminder(id: &str, note_id: Option<&str>, revision: i64, message: &str) -> Reminder {
+        Reminder {
+            id: id.to_string(),
+            note_id: note_id.map(str::to_string),
+            message: message.to_string(),
+            due_date: "2030-01-01T09:00:00+02:00".to_string(),
+            completed: false,
+            notified: false,
+            revision,
+            created_at: "2030-01-01T00:00:00Z".to_string(),
+            updated_at: "2030-01-01T00:00:00Z".to_string(),
+            deleted_at: None,
+            recurrence: None,
+            snoozed_from: None,
+        }
+    }
+
+    fn merge_reminders(db: &Database, reminders: Vec<Reminder>) -> (SyncStats, Vec<SyncConflict>) {
+        let payload = SyncPayload { reminders, ..SyncPayload::default() };
+        merge_remote_changes(db, payload, ConflictStrategy::Lww).unwrap()
+    }
+
+    fn reminder_message(db: &Database, id: &str) -> Option<String> {
+        db.conn()
+            .query_row("SELECT message FROM reminders WHERE id = ?", params![id], |row| row.get(0))
+            .optional()
+            .unwrap()
+    }
+
+    fn tombstone(db: &Database, entity_type: &str, id: &str) {
+        db.conn()
+            .execute(
+                "INSERT INTO deleted_entities (entity_type, entity_id, revision, deleted_at)
+                 VALUES (?, ?, 1, '2030-02-01T00:00:00Z')",
+                params![entity_type, id],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_standalone_reminder_merges_with_its_due_date_in_utc() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let (stats, _) = merge_reminders(&device, vec![remote_reminder("alone", None, 3, "Alone")]);
+        assert_eq!(stats.reminders, 1);
+        let due: String = device
+            .conn()
+            .query_row("SELECT due_date FROM reminders WHERE id = 'alone'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(due, "2030-01-01T07:00:00Z");
+    }
+
+    #[test]
+    fn test_older_remote_reminder_loses_and_is_reported() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        merge_reminders(&device, vec![remote_reminder("alone", None, 3, "Alone")]);
+
+        let (stats, conflicts) = merge_reminders(&device, vec![remote_reminder("alone", None, 2, "Older")]);
+        assert_eq!(stats.reminders, 0);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].entity_type, "reminder");
+        assert_eq!(conflicts[0].resolution, "local_wins");
+        assert_eq!(reminder_message(&device, "alone").as_deref(), Some("Alone"));
+    }
+
+    #[test]
+    fn test_same_remote_reminder_changes_nothing_and_a_newer_one_replaces_it() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        merge_reminders(&device, vec![remote_reminder("alone", None, 3, "Alone")]);
+
+        let (stats, conflicts) = merge_reminders(&device, vec![remote_reminder("alone", None, 3, "Alone")]);
+        assert_eq!((stats.reminders, conflicts.len()), (0, 0));
+        let (stats, _) = merge_reminders(&device, vec![remote_reminder("alone", None, 4, "Newer")]);
+        assert_eq!(stats.reminders, 1);
+        assert_eq!(reminder_message(&device, "alone").as_deref(), Some("Newer"));
+    }
+
+    #[test]
+    fn test_reminder_deleted_here_after_the_remote_edit_stays_deleted() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        tombstone(&device, "reminder", "gone");
+        let (stats, _) = merge_reminders(&device, vec![remote_reminder("gone", None, 5, "Gone")]);
+        assert_eq!(stats.reminders, 0);
+        assert_eq!(reminder_message(&device, "gone"), None);
+    }
+
+    #[test]
+    fn test_reminder_for_a_note_deleted_here_is_dropped_not_deferred() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        tombstone(&device, "note", "deleted-note");
+        let (stats, _) = merge_reminders(&device, vec![remote_reminder("orphan", Some("deleted-note"), 1, "Orphan")]);
+        assert_eq!(stats.reminders, 0);
+        assert_eq!(reminder_message(&device, "orphan"), None);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM deferred_reminders WHERE id = ?", "orphan"), 0);
+    }
+
+    #[test]
+    fn test_deferred_reminder_keeps_the_latest_copy_until_its_note_arrives() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        merge_reminders(&device, vec![remote_reminder("early", Some("later-note"), 1, "First")]);
+        merge_reminders(&device, vec![remote_reminder("early", Some("later-note"), 2, "Second")]);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM deferred_reminders WHERE id = ?", "early"), 1);
+        let (stats, _) = merge_reminders(&device, vec![]);
+        assert_eq!(stats.reminders, 0);
+
+        insert_note(&device, "later-note", "2030-01-01T00:00:00Z");
+        let (stats, _) = merge_reminders(&device, vec![]);
+        assert_eq!(stats.reminders, 1);
+        assert_eq!(reminder_message(&device, "early").as_deref(), Some("Second"));
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM deferred_reminders WHERE id = ?", "early"), 0);
+    }
+
+    #[test]
+    fn test_device_id_is_generated_once() {
+        let device = Database::in_memory();
+        let conn = device.conn();
+        let id = device_id(&conn).unwrap();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        assert_eq!(device_id(&conn).unwrap(), id);
+        assert_ne!(device_id(&Database::in_memory().conn()).unwrap(), id);
+    }
+
+    #[test]
+    fn test_device_id_can_only_be_replaced_by_a_uuid() {
+        let device = Database::in_memory();
+        let conn = device.conn();
+        let id = device_id(&conn).unwrap();
+
+        for bad in ["", "laptop", "not-a-uuid-at-all"] {
+            assert!(matches!(settings::set(&conn, settings::DEVICE_ID, bad), Err(AppError::Validation(_))));
+        }
+        assert_eq!(device_id(&conn).unwrap(), id);
+        let chosen = uuid::Uuid::new_v4().to_string();
+        settings::set(&conn, settings::DEVICE_ID, &chosen).unwrap();
+        assert_eq!(device_id(&conn).unwrap(), chosen);
+    }
+
+    #[test]
+    fn test_resetting_the_device_id_makes_a_new_device() {
+        let device = Database::in_memory();
+        let conn = device.conn();
+        let id = device_id(&conn).unwrap();
+        settings::reset(&conn, settings::DEVICE_ID).unwrap();
+        let fresh = device_id(&conn).unwrap();
+        assert_ne!(fresh, id);
+        assert_eq!(settings::get(&conn, settings::DEVICE_ID).unwrap(), Some(fresh));
+    }
+
+    #[test]
+    fn test_auth_token_falls_back_to_setting() {
+        let device = Database::in_memory();
+        let conn = device.conn();
+        assert_eq!(auth_token(&conn, None).unwrap(), None);
+
+        settings::set(&conn, settings::SYNC_AUTH_TOKEN, "stored").unwrap();
+        assert_eq!(auth_token(&conn, None).unwrap().as_deref(), Some("stored"));
+        assert_eq!(auth_token(&conn, Some("given".to_string())).unwrap().as_deref(), Some("given"));
+        assert_eq!(auth_token(&conn, Some(" ".to_string())).unwrap(), None);
+
+        for status in [reqwest::StatusCode::UNAUTHORIZED, reqwest::StatusCode::FORBIDDEN] {
+            match check_status(status) {
+                Err(AppError::Sync(message)) => assert_eq!(message, UNAUTHORIZED),
+                other => panic!("{} gave {:?}", status, other),
+            }
+        }
+        assert!(check_status(reqwest::StatusCode::INTERNAL_SERVER_ERROR).is_err());
+        assert!(check_status(reqwest::StatusCode::OK).is_ok());
+    }
+
+    /// Answers every request with `status`, noting the Authorization header it came with
+    fn refusing_server(status: u16) -> (String, std::sync::Arc<std::sync::Mutex<Vec<Option<String>>>>) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::<Option<String>>::new()));
+        let headers = seen.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut authorization = None;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("authorization") {
+                            authorization = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                headers.lock().unwrap().push(authorization);
+                let response = format!("HTTP/1.1 {} X\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}", status);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, seen)
+    }
+
+    /// A device with a stored token and one note waiting to be pushed
+    fn device_with_stored_token() -> Database {
+        let device = Database::in_memory();
+        settings::set(&device.conn(), settings::SYNC_AUTH_TOKEN, "stored").unwrap();
+        device.conn().execute("INSERT INTO notes (id, title) VALUES ('n1', 'Waiting')", []).unwrap();
+        device
+    }
+
+    #[test]
+    fn test_refused_token_is_sent_once_and_nothing_is_merged() {
+        let device = device_with_stored_token();
+        let before = get_sync_state(&device).unwrap();
+
+        for status in [401, 403] {
+            let (url, seen) = refusing_server(status);
+            match tauri::async_runtime::block_on(run_sync(&device, &url, None, None)) {
+                Err(AppError::Sync(message)) => assert_eq!(message, UNAUTHORIZED),
+                other => panic!("{} gave {:?}", status, other),
+            }
+            // Not retried, and the stored token was the one sent
+            assert_eq!(*seen.lock().unwrap(), vec![Some("Bearer stored".to_string())]);
+        }
+        let after = get_sync_state(&device).unwrap();
+        assert_eq!((after.pull_cursors, after.pending_changes), (before.pull_cursors, before.pending_changes));
+    }
+
+    #[test]
+    fn test_blank_token_passed_in_sends_none_rather_than_the_stored_one() {
+        let device = device_with_stored_token();
+        let (url, seen) = refusing_server(401);
+        assert!(tauri::async_runtime::block_on(run_sync(&device, &url, Some("  ".to_string()), None)).is_err());
+        assert_eq!(*seen.lock().unwrap(), vec![None]);
+    }
+
+    #[test]
+    fn test_preview_pulls_as_no_device() {
+        let body = |device_id| serde_json::to_value(PullRequest::new(device_id, SyncCursors::all(4), 50)).unwrap();
+        let preview = body(None);
+        assert_eq!((preview["device_id"].as_str(), preview["dry_run"].as_bool()), (Some(""), Some(true)));
+        let sync = body(Some("laptop"));
+        assert_eq!((sync["device_id"].as_str(), sync["dry_run"].as_bool()), (Some("laptop"), Some(false)));
+    }
+
+    #[test]
+    fn test_drain_revision_events() {
+        // Keep-alive comments and other events are skipped; a cut-off event waits
+        let mut buffer = ":\n\nevent: revision\ndata: 41\n\nevent: other\ndata: 1\n\nevent: revision\nda".to_string();
+        assert_eq!(drain_revision_events(&mut buffer), vec![41]);
+        buffer.push_str("ta: 42\n\n");
+        assert_eq!(drain_revision_events(&mut buffer), vec![42]);
+        assert!(buffer.is_empty());
+
+        // However the stream is cut into chunks, each event is read once,
+        // including when a chunk ends between the two newlines
+        let stream = "event: revision\ndata: 43\n\n:\n\nevent: revision\ndata: 44\n\nevent: revision\ndata: 45\n\n";
+        for size in [1, 2, 3, 7, 16, stream.len()] {
+            let mut buffer = String::new();
+            let mut revisions = Vec::new();
+            for chunk in stream.as_bytes().chunks(size) {
+                buffer.push_str(std::str::from_utf8(chunk).unwrap());
+                revisions.extend(drain_revision_events(&mut buffer));
+            }
+            assert_eq!(revisions, vec![43, 44, 45], "chunks of {}", size);
+            assert!(buffer.is_empty());
+        }
+
+        // An event without a number is skipped rather than read as 0
+        let mut buffer = "event: revision\ndata: soon\n\nevent: revision\ndata: 46\n\n".to_string();
+        assert_eq!(drain_revision_events(&mut buffer), vec![46]);
+    }
+
+    #[test]
+    fn test_pushed_stats_per_type() {
+        let response: PushResponse = serde_json::from_str(
+            r#"{"accepted":6,"conflicts":[],"server_revision":9,
+                "accepted_counts":{"notes":0,"notebooks":5,"tags":0,"reminders":0,"deleted":1}}"#,
+        )
+        .unwrap();
+        let stats = response.pushed_stats();
+        assert_eq!((stats.notes, stats.notebooks, stats.deleted), (0, 5, 1));
+
+        // Counted from the per-entity results when there are no counts
+        let response: PushResponse = serde_json::from_str(
+            r#"{"accepted":3,"conflicts":[],"server_revision":9,"results":[
+                {"id":"a","entity_type":"note","status":"accepted","server_revision":7},
+                {"id":"b","entity_type":"note","status":"conflict","server_revision":4},
+                {"id":"t","entity_type":"tag","status":"accepted","server_revision":8},
+                {"id":"c","entity_type":"note","status":"deleted","server_revision":9}]}"#,
+        )
+        .unwrap();
+        let stats = response.pushed_stats();
+        assert_eq!((stats.notes, stats.tags, stats.deleted), (1, 1, 1));
+
+        // Older servers only send the total
+        let response: PushResponse =
+            serde_json::from_str(r#"{"accepted":6,"conflicts":[],"server_revision":9}"#).unwrap();
+        assert_eq!(response.pushed_stats().notes, 6);
+    }
+
+    #[test]
+    fn test_pushed_stats_prefer_counts_over_results() {
+        // Counts from before reminders and deletions were counted leave those at zero
+        let response: PushResponse = serde_json::from_str(
+            r#"{"accepted":2,"conflicts":[],"server_revision":9,"accepted_counts":{"notes":0,"notebooks":2,"tags":0},
+                "results":[{"id":"a","entity_type":"note","status":"accepted","server_revision":7}]}"#,
+        )
+        .unwrap();
+        assert_eq!(response.pushed_stats(), SyncStats { notebooks: 2, ..SyncStats::default() });
+    }
+
+    #[test]
+    fn test_pushed_stats_skip_skipped_and_unknown_results() {
+        let response: PushResponse = serde_json::from_str(
+            r#"{"accepted":1,"conflicts":[],"server_revision":9,"accepted_counts":null,"results":[
+                {"id":"a","entity_type":"note","status":"skipped","server_revision":7},
+                {"id":"b","entity_type":"attachment","status":"accepted","server_revision":8}]}"#,
+        )
+        .unwrap();
+        assert_eq!(response.pushed_stats(), SyncStats::default());
+    }
+
+    #[test]
+    fn test_push_answered_only_with_conflicts_reports_nothing_pushed() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let push = r#"{"accepted":0,"accepted_counts":{"notes":0,"notebooks":0,"tags":0,"reminders":0,"deleted":0},
+            "conflicts":[{"entity_type":"note","entity_id":"a","local_revision":1,"server_revision":40,
+            "resolution":"server_wins"}],"server_revision":40}"#;
+        let pull = r#"{"notes":[],"notebooks":[],"tags":[],"server_revision":5}"#;
+        let (url, _) = stub_server_answering(vec![pull.to_string()], 200, Some(push.to_string()));
+        let result = tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert_eq!(result.pushed, SyncStats::default());
+        assert_eq!(result.conflicts.len(), 1);
+    }
+
+    /// What a device gets back after its payload went through the server
+    fn through_server(changes: SyncPayload) -> SyncPayload {
+        let notes: Vec<ServerNote> = changes.notes.iter().map(|n| note_to_server(n).unwrap()).collect();
+        let notebooks: Vec<ServerNotebook> = changes.notebooks.iter().map(notebook_to_server).collect();
+        let notes: Vec<ServerNote> = serde_json::from_str(&serde_json::to_string(&notes).unwrap()).unwrap();
+        let notebooks: Vec<ServerNotebook> =
+            serde_json::from_str(&serde_json::to_string(&notebooks).unwrap()).unwrap();
+        SyncPayload {
+            notes: notes.into_iter().map(|n| server_to_note(n).unwrap()).collect(),
+            notebooks: notebooks.into_iter().map(server_to_notebook).collect(),
+            ..changes
+        }
+    }
+
+    #[test]
+    fn test_pinned_notes_survive_server_round_trip() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        insert_note(&device_a, &id, "2024-01-01T00:00:00+00:00");
+        device_a
+            .conn()
+            .execute("UPDATE notes SET is_pinned = 1, pinned_order = 2 WHERE id = ?", params![id])
+            .unwrap();
+
+        let changes = through_server(get_changes_since(&device_a, 0).unwrap());
+        merge_remote_changes(&device_b, changes, ConflictStrategy::Lww).unwrap();
+        let (pinned, order): (bool, Option<i64>) = device_b
+            .conn()
+            .query_row("SELECT is_pinned, pinned_order FROM notes WHERE id = ?", params![id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!(pinned);
+        assert_eq!(order, Some(2));
+
+        // Servers that predate the flag leave it unpinned rather than failing
+        let legacy = serde_json::json!({
+            "id": id, "title": "", "content": "", "notebook_id": null, "tags": "[]",
+            "status": "active", "created_at": "", "updated_at": "", "revision": 1, "is_deleted": false
+        });
+        assert!(!serde_json::from_value::<ServerNote>(legacy).unwrap().is_pinned);
+    }
+
+    fn pin(device: &Database, id: &str) -> (bool, Option<i64>) {
+        device
+            .conn()
+            .query_row("SELECT is_pinned, pinned_order FROM notes WHERE id = ?", params![id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap()
+    }
+
+    /// Sends everything on `from` to `to` by way of the server
+    fn sync_through_server(from: &Database, to: &Database) {
+        let changes = through_server(get_changes_since(from, 0).unwrap());
+        merge_remote_changes(to, changes, ConflictStrategy::Lww).unwrap();
+    }
+
+    /// A note pinned first on `device_a` and synced to `device_b`
+    fn pinned_on_both(device_a: &Database, device_b: &Database) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        insert_note(device_a, &id, "2024-01-01T00:00:00+00:00");
+        device_a
+            .conn()
+            .execute("UPDATE notes SET is_pinned = 1, pinned_order = 0, revision = 2 WHERE id = ?", params![id])
+            .unwrap();
+        sync_through_server(device_a, device_b);
+        id
+    }
+
+    fn unpin(device: &Database, id: &str) {
+        device
+            .conn()
+            .execute("UPDATE notes SET is_pinned = 0, pinned_order = NULL, revision = 3 WHERE id = ?", params![id])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_pin_order_of_zero_syncs_as_an_order() {
+        let _guard = crypto::test_guard();
+        let (device_a, device_b) = (Database::in_memory(), Database::in_memory());
+        let id = pinned_on_both(&device_a, &device_b);
+        assert_eq!(pin(&device_b, &id), (true, Some(0)));
+    }
+
+    #[test]
+    fn test_unpinning_reaches_the_other_device() {
+        let _guard = crypto::test_guard();
+        let (device_a, device_b) = (Database::in_memory(), Database::in_memory());
+        let id = pinned_on_both(&device_a, &device_b);
+        unpin(&device_a, &id);
+        sync_through_server(&device_a, &device_b);
+        assert_eq!(pin(&device_b, &id), (false, None));
+    }
+
+    #[test]
+    fn test_stale_pinned_copy_doesnt_pin_over_a_newer_local_edit() {
+        let _guard = crypto::test_guard();
+        let (device_a, device_b) = (Database::in_memory(), Database::in_memory());
+        let id = pinned_on_both(&device_a, &device_b);
+        unpin(&device_a, &id);
+        sync_through_server(&device_a, &device_b);
+
+        let mut stale_notes = through_server(get_changes_since(&device_a, 0).unwrap()).notes;
+        stale_notes[0].is_pinned = true;
+        stale_notes[0].pinned_order = Some(5);
+        stale_notes[0].revision = 2;
+        device_b.conn().execute("UPDATE notes SET title = 'Local', revision = 4 WHERE id = ?", params![id]).unwrap();
+        let payload = SyncPayload { notes: stale_notes, ..SyncPayload::default() };
+        merge_remote_changes(&device_b, payload, ConflictStrategy::Lww).unwrap();
+        assert_eq!(pin(&device_b, &id), (false, None));
+    }
+
+    #[test]
+    fn test_servers_without_pin_ordering_leave_it_unset() {
+        let legacy = serde_json::json!({
+            "id": "n1", "title": "", "content": "", "notebook_id": null, "tags": "[]", "status": "active",
+            "created_at": "", "updated_at": "", "revision": 1, "is_deleted": false, "is_pinned": true
+        });
+        let legacy: ServerNote = serde_json::from_value(legacy).unwrap();
+        assert_eq!((legacy.is_pinned, legacy.pinned_order), (true, None));
+    }
+
+    #[test]
+    fn test_encrypted_notes_reach_the_server_as_ciphertext() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, &uuid::Uuid::new_v4().to_string(), "2024-01-01T00:00:00+00:00");
+        let note = get_changes_since(&device, 0).unwrap().notes.remove(0);
+
+        let salt = crypto::init_encryption("correct horse", None).unwrap();
+        let sent = note_to_server(&note).unwrap();
+        assert!(sent.encrypted);
+        assert!(!sent.title.contains("Shared") && !sent.content.contains("task"));
+
+        let pulled = server_to_note(sent.clone()).unwrap();
+        assert_eq!(crypto::maybe_decrypt(&pulled.title).unwrap(), "Shared");
+        assert_eq!(crypto::maybe_decrypt(&pulled.content).unwrap(), "- [ ] task");
+
+        // Another password can't read it, and a locked device says so
+        crypto::init_encryption("battery staple", Some(&salt)).unwrap();
+        assert!(matches!(server_to_note(sent.clone()), Err(AppError::Encryption(_))));
+        crypto::clear_encryption();
+        match server_to_note(sent) {
+            Err(AppError::Sync(message)) => assert_eq!(message, LOCKED),
+            other => panic!("pulled while locked: {:?}", other.map(|n| n.title)),
+        }
+        assert!(!note_to_server(&note).unwrap().encrypted);
+    }
+
+    /// An empty note stored encrypted already, as the server gets it, with encryption unlocked
+    fn sent_encrypted_note() -> ServerNote {
+        let device = Database::in_memory();
+        crypto::init_encryption("correct horse", None).unwrap();
+        insert_note(&device, "n1", "2024-01-01T00:00:00+00:00");
+        let mut note = get_changes_since(&device, 0).unwrap().notes.remove(0);
+        note.title = crypto::encrypt("Shared").unwrap();
+        note.content = String::new();
+        note_to_server(&note).unwrap()
+    }
+
+    #[test]
+    fn test_note_stored_encrypted_is_sent_with_one_layer() {
+        let _guard = crypto::test_guard();
+        let pulled = server_to_note(sent_encrypted_note()).unwrap();
+        assert_eq!(crypto::maybe_decrypt(&pulled.title).unwrap(), "Shared");
+        assert_eq!(crypto::maybe_decrypt(&pulled.content).unwrap(), "");
+        crypto::clear_encryption();
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_is_an_error_not_garbage() {
+        let _guard = crypto::test_guard();
+        let mut tampered = sent_encrypted_note();
+        let flipped = if tampered.title.as_bytes()[20] == b'A' { "B" } else { "A" };
+        tampered.title.replace_range(20..21, flipped);
+        assert!(matches!(server_to_note(tampered), Err(AppError::Encryption(_))));
+        crypto::clear_encryption();
+    }
+
+    #[test]
+    fn test_locked_device_merges_nothing_from_an_encrypted_page() {
+        let _guard = crypto::test_guard();
+        let sent = sent_encrypted_note();
+        crypto::clear_encryption();
+        let locked = Database::in_memory();
+        let page = format!(
+            r#"{{"notes":[{}],"tags":[],"notebooks":[],"server_revision":4,"has_more":false,"page_revision":4}}"#,
+            serde_json::to_string(&sent).unwrap()
+        );
+        let (url, pushes) = stub_server_paged(vec![page], 200);
+        match tauri::async_runtime::block_on(run_sync(&locked, &url, None, None)) {
+            Err(AppError::Sync(message)) => assert_eq!(message, LOCKED),
+            other => panic!("synced while locked: {:?}", other.map(|r| r.pulled)),
+        }
+        assert_eq!(count(&locked, "SELECT COUNT(*) FROM notes WHERE id = ?", "n1"), 0);
+        assert_eq!(get_sync_state(&locked).unwrap().last_pull_revision, 0);
+        assert_eq!(pushes.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_every_field_survives_the_server_mapping() {
+        let _guard = crypto::test_guard();
+        // Through JSON, the way a push reaches the server and a pull comes back
+        fn through_server<T: Serialize, S: serde::de::DeserializeOwned>(value: T) -> S {
+            serde_json::from_value(serde_json::to_value(value).unwrap()).unwrap()
+        }
+
+        let note = Note {
+            id: "n1".to_string(),
+            title: "Title".to_string(),
+            content: "Body".to_string(),
+            notebook_id: Some("nb".to_string()),
+            tags: vec!["work".to_string(), "later".to_string()],
+            status: NoteStatus::Archived,
+            is_pinned: true,
+            revision: 7,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-02-01T00:00:00Z".to_string(),
+            deleted_at: Some("2024-01-15T00:00:00Z".to_string()),
+            pinned_order: Some(3),
+        };
+        let pulled = server_to_note(through_server(note_to_server(&note).unwrap())).unwrap();
+        assert_eq!(serde_json::to_value(pulled).unwrap(), serde_json::to_value(&note).unwrap());
+
+        let notebook = Notebook {
+            id: "nb".to_string(),
+            name: "Work".to_string(),
+            color: Some("blue".to_string()),
+            icon: Some("📁".to_string()),
+            parent_id: Some("root".to_string()),
+            revision: 4,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-02-01T00:00:00Z".to_string(),
+            deleted_at: Some("2024-01-15T00:00:00Z".to_string()),
+            is_archived: true,
+            sync_excluded: false,
+        };
+        let pulled = server_to_notebook(through_server(notebook_to_server(&notebook)));
+        assert_eq!(serde_json::to_value(pulled).unwrap(), serde_json::to_value(&notebook).unwrap());
+
+        let tag = Tag {
+            id: "t1".to_string(),
+            name: "work".to_string(),
+            color: Some("red".to_string()),
+            revision: 2,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-02-01T00:00:00Z".to_string(),
+            deleted_at: Some("2024-01-15T00:00:00Z".to_string()),
+        };
+        let pulled = server_to_tag(through_server(tag_to_server(&tag)));
+        assert_eq!(serde_json::to_value(pulled).unwrap(), serde_json::to_value(&tag).unwrap());
+
+        // Older servers only say whether a row is deleted
+        let mut legacy = serde_json::to_value(tag_to_server(&tag)).unwrap();
+        legacy.as_object_mut().unwrap().remove("deleted_at");
+        let pulled = server_to_tag(serde_json::from_value(legacy).unwrap());
+        assert_eq!(pulled.deleted_at.as_deref(), Some("2024-02-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_every_reminder_field_survives_the_server_mapping() {
+        use crate::models::{Recurrence, RecurrenceFrequency};
+
+        let reminder = Reminder {
+            id: "r1".to_string(),
+            note_id: None,
+            message: "Rent".to_string(),
+            due_date: "2024-03-01T09:00:00Z".to_string(),
+            completed: true,
+            notified: true,
+            revision: 3,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-02-01T00:00:00Z".to_string(),
+            deleted_at: Some("2024-02-01T00:00:00Z".to_string()),
+            recurrence: Some(Recurrence {
+                frequency: RecurrenceFrequency::Monthly,
+                interval: 2,
+                until: Some("2025-01-01T00:00:00Z".to_string()),
+                start: Some("2024-01-01T09:00:00Z".to_string()),
+            }),
+            snoozed_from: Some("2024-02-28T09:00:00Z".to_string()),
+        };
+        let sent = serde_json::to_value(reminder_to_server(&reminder)).unwrap();
+        let pulled = server_to_reminder(serde_json::from_value(sent).unwrap());
+        assert_eq!(serde_json::to_value(pulled).unwrap(), serde_json::to_value(&reminder).unwrap());
+    }
+
+    #[test]
+    fn test_pulled_live_notebook_keeps_no_deletion_time() {
+        // Even if a server sends one
+        let notebook = r#"{"id":"nb","name":"Work","color":null,"parent_id":null,"created_at":"2024-01-01T00:00:00Z",
+            "updated_at":"2024-02-01T00:00:00Z","revision":4,"is_deleted":false,"deleted_at":"2024-01-15T00:00:00Z"}"#;
+        let pulled = server_to_notebook(serde_json::from_str(notebook).unwrap());
+        assert_eq!(pulled.deleted_at, None);
+    }
+
+    #[test]
+    fn test_notebook_from_an_older_server_comes_back_without_icon_or_archiving() {
+        let notebook = r#"{"id":"nb","name":"Work","color":null,"parent_id":null,"created_at":"2024-01-01T00:00:00Z",
+            "updated_at":"2024-02-01T00:00:00Z","revision":4,"is_deleted":false}"#;
+        let pulled = server_to_notebook(serde_json::from_str(notebook).unwrap());
+        // What a server from before icons and archiving left out comes back empty
+        assert_eq!((pulled.icon, pulled.is_archived, pulled.sync_excluded), (None, false, false));
+    }
+
+    #[test]
+    fn test_notebook_icon_survives_server_round_trip() {
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        device_a
+            .conn()
+            .execute("INSERT INTO notebooks (id, name, icon) VALUES (?, 'Travel', '✈️')", params![id])
+            .unwrap();
+
+        let changes = through_server(get_changes_since(&device_a, 0).unwrap());
+        merge_remote_changes(&device_b, changes, ConflictStrategy::Lww).unwrap();
+        let icon: Option<String> = device_b
+            .conn()
+            .query_row("SELECT icon FROM notebooks WHERE id = ?", params![id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(icon.as_deref(), Some("✈️"));
+    }
+
+    fn icon(device: &Database, id: &str) -> Option<String> {
+        device
+            .conn()
+            .query_row("SELECT icon FROM notebooks WHERE id = ?", params![id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_notebook_icons_that_wouldnt_pass_validation_arent_taken_in() {
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        device_a
+            .conn()
+            .execute_batch(
+                "INSERT INTO notebooks (id, name, icon) VALUES
+                     ('named', 'Named', 'folder-open'), ('family', 'Family', '👨‍👩‍👧'),
+                     ('bloated', 'Bloated', '<svg viewBox=\"0 0 10 10\"></svg>');",
+            )
+            .unwrap();
+        sync_through_server(&device_a, &device_b);
+        assert_eq!(icon(&device_b, "named").as_deref(), Some("folder-open"));
+        assert_eq!(icon(&device_b, "family").as_deref(), Some("👨‍👩‍👧"));
+        assert_eq!(icon(&device_b, "bloated"), None);
+    }
+
+    #[test]
+    fn test_clearing_a_notebook_icon_reaches_the_other_device() {
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        device_a
+            .conn()
+            .execute("INSERT INTO notebooks (id, name, icon) VALUES ('named', 'Named', 'folder-open')", [])
+            .unwrap();
+        sync_through_server(&device_a, &device_b);
+        device_a
+            .conn()
+            .execute("UPDATE notebooks SET icon = NULL, revision = revision + 1 WHERE id = 'named'", [])
+            .unwrap();
+        sync_through_server(&device_a, &device_b);
+        assert_eq!(icon(&device_b, "named"), None);
+    }
+
+    #[test]
+    fn test_pull_cursor_follows_revisions_not_counts() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        for n in 0..3 {
+            insert_note(&device_a, &uuid::Uuid::new_v4().to_string(), "2024-01-01T00:00:00+00:00");
+            device_a.conn().execute("UPDATE notes SET revision = ? WHERE revision = 1", params![900 + n]).unwrap();
+        }
+
+        apply_pull(&device_b, get_changes_since(&device_a, 0).unwrap(), None, ConflictStrategy::Lww).unwrap();
+        let cursor = get_sync_state(&device_b).unwrap().last_pull_revision;
+        assert_eq!(cursor, 902);
+        // The next pull only asks for what came after
+        assert!(get_changes_since(&device_a, cursor).unwrap().notes.is_empty());
+
+        // An explicit server revision wins, and the cursor never moves back
+        apply_pull(&device_b, SyncPayload::default(), Some(950), ConflictStrategy::Lww).unwrap();
+        apply_pull(&device_b, SyncPayload::default(), Some(10), ConflictStrategy::Lww).unwrap();
+        assert_eq!(get_sync_state(&device_b).unwrap().last_pull_revision, 950);
+    }
+
+    fn pull_cursor(device: &Database) -> i64 {
+        get_sync_state(device).unwrap().last_pull_revision
+    }
+
+    /// A pulled page holding one note at revision 30
+    fn page_at_revision_30() -> (String, SyncPayload) {
+        let device_a = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        insert_note(&device_a, &id, "2024-01-01T00:00:00+00:00");
+        device_a.conn().execute("UPDATE notes SET revision = 30 WHERE id = ?", params![id]).unwrap();
+        let pulled = get_changes_since(&device_a, 0).unwrap();
+        (id, pulled)
+    }
+
+    #[test]
+    fn test_failed_merge_leaves_the_pull_cursor() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let (id, pulled) = page_at_revision_30();
+
+        fail_writes(&device.conn(), "INSERT ON notes");
+        assert!(apply_pull(&device, pulled, None, ConflictStrategy::Lww).is_err());
+        allow_writes(&device.conn());
+        assert_eq!(pull_cursor(&device), 0);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id = ?", &id), 0);
+    }
+
+    #[test]
+    fn test_row_losing_to_a_newer_local_copy_still_moves_the_cursor() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let (id, pulled) = page_at_revision_30();
+        insert_note(&device, &id, "2024-01-01T00:00:00+00:00");
+        device.conn().execute("UPDATE notes SET revision = 40 WHERE id = ?", params![id]).unwrap();
+
+        let (stats, conflicts) = apply_pull(&device, pulled, None, ConflictStrategy::Lww).unwrap();
+        assert_eq!((stats.notes, conflicts.len()), (0, 1));
+        assert_eq!(pull_cursor(&device), 30);
+    }
+
+    #[test]
+    fn test_tombstones_move_every_cursor_and_an_empty_page_none() {
+        let device = Database::in_memory();
+        let payload = SyncPayload {
+            deleted: vec![DeletedEntity {
+                entity_type: "tag".to_string(),
+                entity_id: "gone".to_string(),
+                revision: 45,
+                deleted_at: "2024-02-01T00:00:00Z".to_string(),
+            }],
+            ..SyncPayload::default()
+        };
+        apply_pull(&device, payload, None, ConflictStrategy::Lww).unwrap();
+        assert_eq!(get_sync_state(&device).unwrap().pull_cursors, SyncCursors::all(45));
+
+        // Without a server revision
+        apply_pull(&device, SyncPayload::default(), None, ConflictStrategy::Lww).unwrap();
+        assert_eq!(pull_cursor(&device), 45);
+    }
+
+    #[test]
+    fn test_conflict_strategies() {
+        let _guard = crypto::test_guard();
+        let id = uuid::Uuid::new_v4().to_string();
+        let titles = |db: &Database| -> Vec<String> {
+            let conn = db.conn();
+            let mut stmt = conn
+                .prepare("SELECT title || ':' || content || ':' || tags FROM notes ORDER BY created_at, title")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+        };
+        // Both devices edited revision 2 of the same note; the remote edit is newer
+        let setup = |strategy: ConflictStrategy| {
+            let device = Database::in_memory();
+            device
+                .conn()
+                .execute(
+                    "INSERT INTO notes (id, title, content, tags, revision, created_at, updated_at)
+                     VALUES (?, 'Plan', 'local', '[]', 2, '2024-01-01T00:00:00Z', '2024-03-01T00:00:00Z')",
+                    params![id],
+                )
+                .unwrap();
+            let mut remote = get_changes_since(&device, 0).unwrap();
+            remote.notes[0].content = "remote".to_string();
+            remote.notes[0].updated_at = "2024-03-02T00:00:00Z".to_string();
+            let (_, conflicts) = merge_remote_changes(&device, remote, strategy).unwrap();
+            let resolutions: Vec<String> = conflicts.into_iter().map(|c| c.resolution).collect();
+            let tagged = count(&device, "SELECT COUNT(*) FROM tags WHERE name = ?", CONFLICT_TAG);
+            assert_eq!(tagged, (strategy == ConflictStrategy::KeepBoth) as i64);
+            (titles(&device), resolutions)
+        };
+
+        assert_eq!(setup(ConflictStrategy::Lww), (vec!["Plan:remote:[]".to_string()], vec![]));
+        let (notes, resolutions) = setup(ConflictStrategy::LocalWins);
+        assert_eq!((notes, resolutions), (vec!["Plan:local:[]".to_string()], vec!["local_wins".to_string()]));
+        assert_eq!(setup(ConflictStrategy::RemoteWins).0, vec!["Plan:remote:[]"]);
+
+        let (notes, resolutions) = setup(ConflictStrategy::KeepBoth);
+        assert_eq!(resolutions, vec!["keep_both"]);
+        assert_eq!(notes[0], "Plan:remote:[]");
+        assert_eq!(notes[1], "Plan (conflicted copy from 2024-03-01):local:[\"conflict\"]");
+
+        assert!(ConflictStrategy::parse("keep_both").is_ok());
+        assert!(ConflictStrategy::parse("newest").is_err());
+    }
+
+    #[test]
+    fn test_stored_conflict_strategy_applies_when_none_is_passed() {
+        let device = Database::in_memory();
+        let conn = device.conn();
+        assert_eq!(ConflictStrategy::resolve(&conn, None).unwrap(), ConflictStrategy::Lww);
+        assert!(matches!(
+            settings::set(&conn, settings::SYNC_CONFLICT_STRATEGY, "newest"),
+            Err(AppError::Validation(_))
+        ));
+        settings::set(&conn, settings::SYNC_CONFLICT_STRATEGY, "keep_both").unwrap();
+        assert_eq!(ConflictStrategy::resolve(&conn, None).unwrap(), ConflictStrategy::KeepBoth);
+        let explicit = ConflictStrategy::resolve(&conn, Some(ConflictStrategy::RemoteWins)).unwrap();
+        assert_eq!(explicit, ConflictStrategy::RemoteWins);
+    }
+
+    /// A local note already tagged as a conflict, and a local notebook
+    fn tagged_conflict(device: &Database) {
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notes (id, title, content, tags, revision, created_at, updated_at)
+                 VALUES ('n1', 'Plan', 'local', '[\"conflict\"]', 2, '2024-01-01T00:00:00Z', '2024-03-01T00:00:00Z');
+                 INSERT INTO notebooks (id, name, revision, updated_at) VALUES ('nb', 'Local', 2, '2024-03-01T00:00:00Z');",
+            )
+            .unwrap();
+    }
+
+    fn count_all(device: &Database, sql: &str) -> i64 {
+        device.conn().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_keep_both_doesnt_tag_a_conflict_twice_and_leaves_notebooks_lww() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        tagged_conflict(&device);
+        let mut remote = get_changes_since(&device, 0).unwrap();
+        remote.notes[0].content = "remote".to_string();
+        remote.notes[0].tags = vec![];
+        remote.notes[0].updated_at = "2024-03-02T00:00:00Z".to_string();
+        remote.notebooks[0].name = "Remote".to_string();
+        remote.notebooks[0].updated_at = "2024-03-02T00:00:00Z".to_string();
+        merge_remote_changes(&device, remote, ConflictStrategy::KeepBoth).unwrap();
+
+        let copy_tags: String = device
+            .conn()
+            .query_row("SELECT tags FROM notes WHERE id != 'n1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(copy_tags, "[\"conflict\"]");
+        // No notebook copy, the newer name wins
+        assert_eq!(count_all(&device, "SELECT COUNT(*) FROM notebooks"), 1);
+        assert_eq!(count_all(&device, "SELECT COUNT(*) FROM notebooks WHERE name = 'Remote'"), 1);
+    }
+
+    #[test]
+    fn test_same_edit_from_both_sides_isnt_a_conflict() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        tagged_conflict(&device);
+        let mut same = get_changes_since(&device, 0).unwrap();
+        same.notebooks.clear();
+        same.notes[0].updated_at = "2024-03-03T00:00:00Z".to_string();
+        let (_, conflicts) = merge_remote_changes(&device, same, ConflictStrategy::KeepBoth).unwrap();
+        assert!(conflicts.is_empty());
+        assert_eq!(count_all(&device, "SELECT COUNT(*) FROM notes"), 1);
+    }
+
+    #[test]
+    fn test_conflicts_are_kept_until_resolved() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        device
+            .conn()
+            .execute(
+                "INSERT INTO notes (id, title, content, tags, revision, created_at, updated_at)
+                 VALUES (?, 'Plan', 'local', '[]', 3, '2024-01-01T00:00:00Z', '2024-03-01T00:00:00Z')",
+                params![id],
+            )
+            .unwrap();
+        let mut remote = get_changes_since(&device, 0).unwrap();
+        remote.notes[0].content = "remote".to_string();
+        remote.notes[0].revision = 2;
+        merge_remote_changes(&device, remote, ConflictStrategy::Lww).unwrap();
+
+        let conn = device.conn();
+        let pending = unresolved_conflicts(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].resolution, "local_wins");
+        assert_eq!(pending[0].local_content.as_deref(), Some("local"));
+        assert_eq!(pending[0].remote_content.as_deref(), Some("remote"));
+
+        // Picking the discarded side restores it above both revisions
+        resolve(&conn, &pending[0].id, ConflictChoice::Remote, None).unwrap();
+        let (content, revision): (String, i64) = conn
+            .query_row("SELECT content, revision FROM notes WHERE id = ?", params![id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(content, "remote");
+        assert!(revision > 3);
+        assert!(unresolved_conflicts(&conn).unwrap().is_empty());
+        assert!(matches!(
+            resolve(&conn, &pending[0].id, ConflictChoice::Local, None),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    /// Two stale pulls of a note, and one of each of two notebooks, all losing to the local copies
+    fn stale_conflicts(device: &Database) {
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notes (id, title, content, revision, updated_at)
+                     VALUES ('n1', 'Plan', 'local', 3, '2024-03-01T00:00:00Z');
+                 INSERT INTO notebooks (id, name, revision, updated_at) VALUES
+                     ('nb1', 'Kept', 3, '2024-03-01T00:00:00Z'), ('nb2', 'Gone', 3, '2024-03-01T00:00:00Z');",
+            )
+            .unwrap();
+        for content in ["first", "second"] {
+            let mut remote = get_changes_since(device, 0).unwrap();
+            remote.notes[0].content = content.to_string();
+            remote.notes[0].revision = 2;
+            for notebook in &mut remote.notebooks {
+                notebook.revision = 2;
+            }
+            if content == "second" {
+                remote.notebooks.clear();
+            }
+            merge_remote_changes(device, remote, ConflictStrategy::Lww).unwrap();
+        }
+    }
+
+    fn conflict_on(conn: &Connection, entity_id: &str) -> String {
+        let pending = unresolved_conflicts(conn).unwrap();
+        pending.into_iter().find(|c| c.entity_id == entity_id).unwrap().id
+    }
+
+    #[test]
+    fn test_refused_resolutions_leave_the_conflict_open() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        stale_conflicts(&device);
+        let conn = device.conn();
+        assert_eq!(unresolved_conflicts(&conn).unwrap().iter().filter(|c| c.entity_id == "n1").count(), 2);
+
+        let note_conflict = conflict_on(&conn, "n1");
+        assert!(matches!(
+            resolve(&conn, &note_conflict, ConflictChoice::Merged, None),
+            Err(AppError::Validation(_))
+        ));
+        let notebook_conflict = conflict_on(&conn, "nb1");
+        let refused = resolve(&conn, &notebook_conflict, ConflictChoice::Remote, None);
+        assert!(matches!(refused, Err(AppError::Validation(_))));
+        conn.execute("DELETE FROM notebooks WHERE id = 'nb2'", []).unwrap();
+        let deleted_conflict = conflict_on(&conn, "nb2");
+        assert!(matches!(resolve(&conn, &deleted_conflict, ConflictChoice::Local, None), Err(AppError::NotFound(_))));
+        assert_eq!(unresolved_conflicts(&conn).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_merged_resolution_settles_every_conflict_on_the_note() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        stale_conflicts(&device);
+        let conn = device.conn();
+        let note_conflict = conflict_on(&conn, "n1");
+        resolve(&conn, &note_conflict, ConflictChoice::Merged, Some("local and first".to_string())).unwrap();
+
+        let (content, revision, needs_push): (String, i64, bool) = conn
+            .query_row("SELECT content, revision, needs_push FROM notes WHERE id = 'n1'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((content.as_str(), needs_push), ("local and first", true));
+        assert!(revision > 3);
+        let mut left: Vec<String> = unresolved_conflicts(&conn).unwrap().into_iter().map(|c| c.entity_id).collect();
+        left.sort();
+        assert_eq!(left, vec!["nb1", "nb2"]);
+    }
+
+    #[test]
+    fn test_keeping_a_notebooks_local_side_makes_it_the_newest_revision() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        stale_conflicts(&device);
+        let conn = device.conn();
+        let notebook_conflict = conflict_on(&conn, "nb1");
+        resolve(&conn, &notebook_conflict, ConflictChoice::Local, None).unwrap();
+        let revision: i64 =
+            conn.query_row("SELECT revision FROM notebooks WHERE id = 'nb1'", [], |row| row.get(0)).unwrap();
+        assert_eq!(revision, 4);
+        assert!(unresolved_conflicts(&conn).unwrap().iter().all(|c| c.entity_id != "nb1"));
+    }
+
+    /// A sync server whose pull returns revision 5 and whose push always
+    /// answers `push_status` (accepting at revision 900 on 200). Counts push
+    /// attempts.
+    fn stub_server(push_status: u16) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        let pull = r#"{"notes":[],"notebooks":[],"tags":[],"server_revision":5}"#;
+        stub_server_paged(vec![pull.to_string()], push_status)
+    }
+
+    /// Answers pulls with `pulls` in turn, repeating the last one
+    fn stub_server_paged(
+        pulls: Vec<String>,
+        push_status: u16,
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        stub_server_answering(pulls, push_status, None)
+    }
+
+    /// Same, answering a successful push with `push_body` when given
+    fn stub_server_answering(
+        pulls: Vec<String>,
+        push_status: u16,
+        push_body: Option<String>,
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let pushes = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = pushes.clone();
+        std::thread::spawn(move || {
+            let mut served = 0;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let (mut request_line, mut length) = (String::new(), 0);
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+
+                let (status, body) = if request_line.contains("/pull") {
+                    served += 1;
+                    (200, pulls[(served - 1).min(pulls.len() - 1)].as_str())
+                } else {
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    match (push_status, &push_body) {
+                        (200, Some(body)) => (200, body.as_str()),
+                        (200, None) => (200, r#"{"accepted":1,"conflicts":[],"server_revision":900}"#),
+                        (422, _) => (
+                            422,
+                            r#"{"error":"1 pushed entities failed validation","rejected":[{"entity_type":"note",
+                            "id":"n1","field":"status","message":"\"banana\" is not a status"}]}"#,
+                        ),
+                        _ => (push_status, "{}"),
+                    }
+                };
+                let retry_after = if status == 429 { "Retry-After: 120\r\n" } else { "" };
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    retry_after,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, pushes)
+    }
+
+    #[test]
+    fn test_pull_merges_every_page() {
+        let device = Database::in_memory();
+        let page = |notebook: &str, revision: i64, has_more: bool| {
+            format!(
+                r#"{{"notes":[],"tags":[],"notebooks":[{{"id":"{0}","name":"{0}","color":null,"parent_id":null,
+                "created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z","revision":{1},
+                "is_deleted":false}}],"server_revision":9,"has_more":{2},"page_revision":{1}}}"#,
+                notebook, revision, has_more
+            )
+        };
+        let (url, _) = stub_server_paged(vec![page("first", 3, true), page("second", 9, false)], 200);
+
+        let result = tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert_eq!(result.pulled.notebooks, 2);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notebooks WHERE id IN ('first', ?)", "second"), 2);
+        assert_eq!(get_sync_state(&device).unwrap().last_pull_revision, 9);
+
+        // A page that doesn't move the cursor would be asked for forever
+        let device = Database::in_memory();
+        let (url, _) = stub_server_paged(vec![page("stuck", 0, true)], 200);
+        assert!(tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).is_err());
+    }
+
+    fn signed(body: &str) -> String {
+        let value: serde_json::Value = serde_json::from_str(body).unwrap();
+        checksum::sign(&value).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_pull_cut_short_fails_before_anything_is_merged_or_pushed() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let mut pull: serde_json::Value =
+            serde_json::from_str(&signed(r#"{"notes":[],"notebooks":[],"tags":[],"server_revision":5}"#)).unwrap();
+        pull["server_revision"] = 6.into();
+        let (url, pushes) = stub_server_paged(vec![pull.to_string()], 200);
+        match tauri::async_runtime::block_on(run_sync(&device, &url, None, None)) {
+            Err(AppError::Sync(message)) => assert!(message.contains("checksum mismatch"), "{}", message),
+            other => panic!("altered pull accepted: {:?}", other.map(|r| r.pulled)),
+        }
+        assert_eq!(get_sync_state(&device).unwrap().last_pull_revision, 0);
+        assert_eq!(pushes.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_altered_push_answer_leaves_the_changes_queued() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let pull = signed(r#"{"notes":[],"notebooks":[],"tags":[],"server_revision":5}"#);
+        let mut push: serde_json::Value =
+            serde_json::from_str(&signed(r#"{"accepted":1,"conflicts":[],"server_revision":900}"#)).unwrap();
+        push["accepted"] = 3.into();
+        let (url, _) = stub_server_answering(vec![pull], 200, Some(push.to_string()));
+        let result = tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert!(result.push_error.unwrap().contains("checksum mismatch"));
+        assert_eq!(result.pushed, SyncStats::default());
+        assert_eq!(unpushed_changes(&device).unwrap().notes.len(), 1);
+    }
+
+    fn notebook_json(id: &str, revision: i64) -> String {
+        format!(
+            r#"{{"id":"{0}","name":"{0}","color":null,"parent_id":null,"created_at":"2024-01-01T00:00:00Z",
+            "updated_at":"2024-01-01T00:00:00Z","revision":{1},"is_deleted":false}}"#,
+            id, revision
+        )
+    }
+
+    #[test]
+    fn test_failed_page_keeps_the_earlier_pages_merged() {
+        let device = Database::in_memory();
+        let first = format!(
+            r#"{{"notes":[],"tags":[],"notebooks":[{}],"server_revision":9,"has_more":true,"page_revision":3}}"#,
+            notebook_json("first", 3)
+        );
+        let (url, pushes) = stub_server_paged(vec![first, "not json".to_string()], 200);
+        assert!(tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).is_err());
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notebooks WHERE id = ?", "first"), 1);
+        // The cursor stays at the end of the earlier page
+        assert_eq!(get_sync_state(&device).unwrap().last_pull_revision, 3);
+        assert_eq!(pushes.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_server_without_paging_answers_with_everything_at_once() {
+        let device = Database::in_memory();
+        let legacy =
+            format!(r#"{{"notes":[],"tags":[],"notebooks":[{}],"server_revision":12}}"#, notebook_json("all", 12));
+        let (url, _) = stub_server_paged(vec![legacy], 200);
+        let result = tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert_eq!(result.pulled.notebooks, 1);
+        assert_eq!(get_sync_state(&device).unwrap().last_pull_revision, 12);
+    }
+
+    #[test]
+    fn test_pull_page_size_must_be_a_positive_number() {
+        let device = Database::in_memory();
+        for bad in ["0", "-5", "lots"] {
+            assert!(matches!(
+                settings::set(&device.conn(), settings::SYNC_PULL_PAGE_SIZE, bad),
+                Err(AppError::Validation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_compressed_pull_is_decoded() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut accept_encoding = String::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = header.to_lowercase().strip_prefix("accept-encoding:") {
+                    accept_encoding = value.trim().to_string();
+                }
+            }
+            let body = r#"{"notes":[],"tags":[],"notebooks":[{"id":"nb","name":"Packed","color":null,"parent_id":null,
+                "created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z","revision":4,
+                "is_deleted":false}],"server_revision":4,"has_more":false,"page_revision":4}"#;
+            let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+            gzip.write_all(body.as_bytes()).unwrap();
+            let gzip = gzip.finish().unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                gzip.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&gzip).unwrap();
+            accept_encoding
+        });
+
+        let client = reqwest::Client::new();
+        let page = tauri::async_runtime::block_on(fetch_page(
+            &client,
+            &url,
+            Some("device"),
+            None,
+            RetryPolicy::NONE,
+            SyncCursors::all(0),
+            100,
+        ))
+        .unwrap();
+        assert!(server.join().unwrap().contains("gzip"));
+        assert_eq!(page.payload.notebooks[0].name, "Packed");
+    }
+
+    /// Answers one request with `body`, labelled as gzip
+    fn serve_as_gzip(body: Vec<u8>) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut header = String::from("-");
+            while !header.trim().is_empty() {
+                header.clear();
+                reader.read_line(&mut header).unwrap();
+            }
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+        url
+    }
+
+    fn fetch_one_page(url: String) -> Result<RemotePage> {
+        tauri::async_runtime::block_on(fetch_page(
+            &reqwest::Client::new(),
+            &url,
+            Some("device"),
+            None,
+            RetryPolicy::NONE,
+            SyncCursors::all(0),
+            100,
+        ))
+    }
+
+    const EMPTY_PAGE: &str =
+        r#"{"notes":[],"tags":[],"notebooks":[],"server_revision":4,"has_more":false,"page_revision":4}"#;
+
+    fn gzipped(body: &str) -> Vec<u8> {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(body.as_bytes()).unwrap();
+        gzip.finish().unwrap()
+    }
+
+    #[test]
+    fn test_gzip_pull_page_is_read() {
+        assert_eq!(fetch_one_page(serve_as_gzip(gzipped(EMPTY_PAGE))).unwrap().revision, 4);
+    }
+
+    #[test]
+    fn test_cut_short_or_uncompressed_gzip_page_is_an_error() {
+        let gzip = gzipped(EMPTY_PAGE);
+        assert!(fetch_one_page(serve_as_gzip(gzip[..gzip.len() / 2].to_vec())).is_err());
+        assert!(fetch_one_page(serve_as_gzip(EMPTY_PAGE.as_bytes().to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_failed_push_keeps_pull_and_push_cursor() {
+        // A long Retry-After isn't waited out in the request
+        for (status, expected_attempts) in [(503, 2), (400, 1), (422, 1), (429, 1)] {
+            let device = Database::in_memory();
+            settings::set(&device.conn(), settings::SYNC_RETRY_ATTEMPTS, "2").unwrap();
+            let (url, pushes) = stub_server(status);
+
+            let result = tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+            assert!(result.push_error.is_some());
+            assert_eq!(pushes.load(std::sync::atomic::Ordering::SeqCst), expected_attempts);
+            // Only a validation failure says which entities were at fault
+            let rejected: Vec<&str> = result.rejected.iter().map(|r| r.field.as_str()).collect();
+            assert_eq!(rejected, if status == 422 { vec!["status"] } else { vec![] });
+            assert_eq!(result.retry_after_secs, (status == 429).then_some(120));
+
+            let state = get_sync_state(&device).unwrap();
+            assert_eq!((state.last_pull_revision, state.last_push_revision), (5, 0));
+        }
+    }
+
+    #[test]
+    fn test_retry_attempts_setting_is_checked_and_capped() {
+        let device = Database::in_memory();
+        assert_eq!(RetryPolicy::from_settings(&device.conn()).unwrap().attempts, 3);
+        for bad in ["0", "-1", "many"] {
+            assert!(matches!(
+                settings::set(&device.conn(), settings::SYNC_RETRY_ATTEMPTS, bad),
+                Err(AppError::Validation(_))
+            ));
+        }
+        settings::set(&device.conn(), settings::SYNC_RETRY_ATTEMPTS, "50").unwrap();
+        assert_eq!(RetryPolicy::from_settings(&device.conn()).unwrap().attempts, 10);
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_with_little_jitter_and_is_capped() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            base_delay: std::time::Duration::from_millis(100),
+        };
+        for (retry, doubled) in [(0, 1), (3, 8), (40, 1 << 16)] {
+            let delay = policy.delay(retry).as_secs_f64() * 10.0;
+            assert!(delay >= f64::from(doubled) && delay < f64::from(doubled) + 1.0, "{}: {}", retry, delay);
+        }
+    }
+
+    /// Drops every connection before answering, counting them
+    fn dropping_server() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+        (url, connections)
+    }
+
+    #[test]
+    fn test_dropped_connection_is_a_network_error_retried_each_time() {
+        let (url, connections) = dropping_server();
+        let policy = RetryPolicy {
+            attempts: 3,
+            base_delay: std::time::Duration::ZERO,
+        };
+        let request = reqwest::Client::new().get(format!("{}/api/sync/revision", url));
+        let result = tauri::async_runtime::block_on(send(request, None, policy));
+        assert!(matches!(result, Err(AppError::Sync(_))));
+        assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_pull_that_keeps_failing_gives_up_without_merging() {
+        let device = Database::in_memory();
+        let (url, _) = dropping_server();
+        settings::set(&device.conn(), settings::SYNC_RETRY_ATTEMPTS, "1").unwrap();
+        assert!(tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).is_err());
+        assert_eq!(get_sync_state(&device).unwrap().last_pull_revision, 0);
+    }
+
+    #[test]
+    fn test_push_conflict_applies_the_server_copy() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+
+        let push = r#"{"accepted":0,"conflicts":[{"entity_type":"note","entity_id":"a","local_revision":1,
+            "server_revision":40,"resolution":"server_wins","server_copy":{"id":"a","title":"Kept","content":"server",
+            "notebook_id":null,"tags":"[]","status":"active","is_pinned":false,"created_at":"2024-01-01T00:00:00Z",
+            "updated_at":"2024-02-01T00:00:00Z","revision":40,"is_deleted":false}}],"server_revision":40}"#;
+        let pull = r#"{"notes":[],"notebooks":[],"tags":[],"server_revision":5}"#;
+        let (url, _) = stub_server_answering(vec![pull.to_string()], 200, Some(push.to_string()));
+
+        let result = tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.pulled.notes, 1);
+        assert_eq!(count(&device, "SELECT revision FROM notes WHERE id = ? AND content = 'server'", "a"), 40);
+
+        // The losing local edit can be reviewed and brought back
+        let stored = unresolved_conflicts(&device.conn()).unwrap();
+        assert_eq!(stored[0].local_content.as_deref(), Some("- [ ] task"));
+        assert_eq!(stored[0].remote_content.as_deref(), Some("server"));
+        assert_eq!(conflict_archive(&device.conn(), "a").unwrap()[0].source, "local");
+    }
+
+    fn server_conflict(entity_type: &str, id: &str, copy: Option<serde_json::Value>) -> ServerConflict {
+        ServerConflict {
+            entity_type: entity_type.to_string(),
+            entity_id: id.to_string(),
+            local_revision: 1,
+            server_revision: 40,
+            resolution: "server_wins".to_string(),
+            server_copy: copy,
+        }
+    }
+
+    #[test]
+    fn test_server_copies_skip_missing_and_unknown_and_read_taken_tag_names() {
+        let _guard = crypto::test_guard();
+        let holder = serde_json::json!({"id": "holder", "name": "work", "color": null,
+            "created_at": "2024-01-01T00:00:00Z", "updated_at": "2024-01-01T00:00:00Z", "revision": 40,
+            "is_deleted": false});
+        // Older servers send no copy; a taken tag name comes back as the tag holding it
+        let copies = server_copies(&[
+            server_conflict("note", "a", None),
+            server_conflict("attachment", "x", Some(serde_json::json!({}))),
+            server_conflict("tag", "t", Some(holder)),
+        ])
+        .unwrap();
+        assert!(copies.notes.is_empty());
+        assert_eq!(copies.tags.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["holder"]);
+    }
+
+    #[test]
+    fn test_broken_server_copy_is_named_in_the_error() {
+        let _guard = crypto::test_guard();
+        match server_copies(&[server_conflict("note", "a", Some(serde_json::json!({"id": "a"})))]) {
+            Err(AppError::Sync(message)) => assert!(message.contains("invalid server copy of note a"), "{}", message),
+            other => panic!("read a broken copy: {:?}", other.map(|c| c.notes.len())),
+        }
+    }
+
+    /// Syncs a device holding note `a` against a server answering the push with `push`
+    fn sync_with_push_answer(device: &Database, push: &str) -> Result<SyncResult> {
+        let pull = r#"{"notes":[],"notebooks":[],"tags":[],"server_revision":5}"#;
+        let (url, _) = stub_server_answering(vec![pull.to_string()], 200, Some(push.to_string()));
+        tauri::async_runtime::block_on(run_sync(device, &url, None, None))
+    }
+
+    #[test]
+    fn test_unreadable_server_copy_fails_the_sync_with_the_change_queued() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let push = r#"{"accepted":0,"conflicts":[{"entity_type":"note","entity_id":"a","local_revision":1,
+            "server_revision":40,"resolution":"server_wins","server_copy":{"id":"a"}}],"server_revision":40}"#;
+        assert!(sync_with_push_answer(&device, push).is_err());
+        assert_eq!(unpushed_changes(&device).unwrap().notes.len(), 1);
+    }
+
+    #[test]
+    fn test_push_conflict_without_a_copy_archives_the_local_edit_with_nothing_to_review() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let push = r#"{"accepted":0,"conflicts":[{"entity_type":"note","entity_id":"a","local_revision":1,
+            "server_revision":40,"resolution":"server_wins"}],"server_revision":40}"#;
+        let result = sync_with_push_answer(&device, push).unwrap();
+        assert_eq!((result.conflicts.len(), result.pulled.notes), (1, 0));
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id = ? AND content = '- [ ] task'", "a"), 1);
+        assert_eq!(conflict_archive(&device.conn(), "a").unwrap().len(), 1);
+        assert!(unresolved_conflicts(&device.conn()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cursors_per_entity_type() {
+        let device = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        device
+            .conn()
+            .execute_batch(&format!(
+                "INSERT INTO notebooks (id, name, revision) VALUES ('{id}', 'Work', 4);
+                 INSERT INTO tags (id, name, revision) VALUES ('{id}', 'work', 4);"
+            ))
+            .unwrap();
+        update_sync_state(&device, Some(10), Some(10)).unwrap();
+        mark_all_pushed(&device).unwrap();
+        assert_eq!(get_sync_state(&device).unwrap().pending_changes, 0);
+
+        // Rewinding tags resends tags only
+        reset_cursor(&device.conn(), "tag").unwrap();
+        let state = get_sync_state(&device).unwrap();
+        assert_eq!(state.push_cursors, SyncCursors { tags: 0, ..SyncCursors::all(10) });
+        assert_eq!((state.last_pull_revision, state.last_push_revision), (0, 0));
+        assert_eq!(state.pending_changes, 1);
+        let changes = unpushed_changes(&device).unwrap();
+        assert_eq!((changes.notebooks.len(), changes.tags.len()), (0, 1));
+
+        // Pulling advances the rewound cursor without moving the others back
+        apply_pull(&device, SyncPayload::default(), Some(7), ConflictStrategy::Lww).unwrap();
+        let pulled = get_sync_state(&device).unwrap().pull_cursors;
+        assert_eq!(pulled, SyncCursors { tags: 7, ..SyncCursors::all(10) });
+        assert!(reset_cursor(&device.conn(), "widget").is_err());
+    }
+
+    /// A device pushed and pulled up to revision 10, with a reminder and a note tombstone
+    fn synced_to_revision_10() -> Database {
+        let device = Database::in_memory();
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO deleted_entities (entity_type, entity_id, revision, deleted_at) VALUES
+                     ('reminder', 'r1', 3, '2024-01-01T00:00:00Z'), ('note', 'n1', 3, '2024-01-01T00:00:00Z');",
+            )
+            .unwrap();
+        update_sync_state(&device, Some(10), Some(10)).unwrap();
+        mark_all_pushed(&device).unwrap();
+        device
+    }
+
+    #[test]
+    fn test_resetting_an_unknown_cursor_is_refused_before_anything_moves() {
+        let device = synced_to_revision_10();
+        assert!(matches!(reset_cursor(&device.conn(), "notes"), Err(AppError::Validation(_))));
+        let state = get_sync_state(&device).unwrap();
+        assert_eq!((state.pull_cursors, state.pending_changes), (SyncCursors::all(10), 0));
+    }
+
+    #[test]
+    fn test_resetting_a_cursor_resends_only_that_types_tombstones() {
+        let device = synced_to_revision_10();
+        reset_cursor(&device.conn(), "reminder").unwrap();
+        let deleted = unpushed_changes(&device).unwrap().deleted;
+        assert_eq!(deleted.iter().map(|d| d.entity_id.as_str()).collect::<Vec<_>>(), vec!["r1"]);
+    }
+
+    #[test]
+    fn test_single_cursor_servers_get_the_oldest_cursor() {
+        let device = synced_to_revision_10();
+        reset_cursor(&device.conn(), "reminder").unwrap();
+        // So nothing is skipped
+        let request = PullRequest::new(Some("device"), get_sync_state(&device).unwrap().pull_cursors, 50);
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["last_sync_revision"], 0);
+        assert_eq!((body["since"]["reminders"].as_i64(), body["since"]["notes"].as_i64()), (Some(0), Some(10)));
+        assert_eq!(body["dry_run"], false);
+    }
+
+    #[test]
+    fn test_pulled_notebooks_skip_the_sibling_name_check() {
+        let device = Database::in_memory();
+        crate::commands::notebooks::insert_notebook(
+            &device.conn(),
+            crate::models::CreateNotebookInput {
+                id: None,
+                name: "Ideas".to_string(),
+                color: None,
+                icon: None,
+                parent_id: None,
+            },
+        )
+        .unwrap();
+
+        // Another device made its own "ideas" at the root; both are kept
+        let now = chrono::Utc::now().to_rfc3339();
+        let remote = Notebook {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: "ideas".to_string(),
+            color: None,
+            icon: None,
+            parent_id: None,
+            revision: 3,
+            created_at: now.clone(),
+            updated_at: now,
+            deleted_at: None,
+            is_archived: false,
+            sync_excluded: false,
+        };
+        let payload = SyncPayload {
+            notebooks: vec![remote],
+            ..SyncPayload::default()
+        };
+        let (stats, conflicts) = merge_remote_changes(&device, payload, ConflictStrategy::Lww).unwrap();
+        assert_eq!((stats.notebooks, conflicts.len()), (1, 0));
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notebooks WHERE name = ? COLLATE NOCASE", "ideas"), 2);
+    }
+
+    #[test]
+    fn test_pending_changes_follow_push_flags() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        device.conn().execute("INSERT INTO notes (id, title) VALUES (?, 'Draft')", params![id]).unwrap();
+        let edit = |title: &str| {
+            let input = UpdateNoteInput {
+                title: Some(title.to_string()),
+                content: None,
+                notebook_id: None,
+                tags: None,
+                status: None,
+                is_pinned: None,
+            };
+            notes::write_note_update(&device.conn(), &id, input).unwrap();
+        };
+        let pending = || get_sync_state(&device).unwrap().pending_changes;
+
+        edit("Edited");
+        assert_eq!(pending(), 1);
+        assert_eq!(unpushed_changes(&device).unwrap().notes[0].title, "Edited");
+
+        let (url, _) = stub_server(200);
+        tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert_eq!(pending(), 0);
+        assert_eq!(get_sync_state(&device).unwrap().last_push_revision, 900);
+
+        // Local revisions stay far below the server's, and still count
+        edit("Edited again");
+        assert_eq!(pending(), 1);
+        mark_all_pushed(&device).unwrap();
+
+        // Pulled rows aren't sent back
+        let mut remote = get_changes_since(&device, 0).unwrap();
+        remote.notes[0].title = "From elsewhere".to_string();
+        remote.notes[0].revision += 1;
+        apply_pull(&device, remote, Some(901), ConflictStrategy::Lww).unwrap();
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE title = ?", "From elsewhere"), 1);
+        assert_eq!(pending(), 0);
+    }
+
+    fn edit_title(device: &Database, id: &str, title: &str) {
+        let input = UpdateNoteInput {
+            title: Some(title.to_string()),
+            content: None,
+            notebook_id: None,
+            tags: None,
+            status: None,
+            is_pinned: None,
+        };
+        notes::write_note_update(&device.conn(), id, input).unwrap();
+    }
+
+    fn flagged(device: &Database, id: &str) -> i64 {
+        count(device, "SELECT needs_push FROM notes WHERE id = ?", id)
+    }
+
+    #[test]
+    fn test_edits_made_during_a_push_stay_queued_for_the_next() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        device.conn().execute("INSERT INTO notes (id, title) VALUES ('a', 'A'), ('b', 'B')", []).unwrap();
+        notes::hard_delete_note(&device, "b").unwrap();
+
+        let pushed = unpushed_changes(&device).unwrap();
+        assert_eq!((pushed.notes.len(), pushed.deleted.len()), (1, 1));
+        edit_title(&device, "a", "Edited during the push");
+        device.conn().execute("INSERT INTO notes (id, title) VALUES ('c', 'C')", []).unwrap();
+        clear_pushed(&device.conn(), &pushed).unwrap();
+        assert_eq!((flagged(&device, "a"), flagged(&device, "c")), (1, 1));
+        let next = unpushed_changes(&device).unwrap();
+        assert_eq!(next.notes.len(), 2);
+        assert!(next.deleted.is_empty());
+    }
+
+    #[test]
+    fn test_stale_pull_losing_to_a_local_edit_leaves_it_queued() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        device.conn().execute("INSERT INTO notes (id, title) VALUES ('a', 'A'), ('c', 'C')", []).unwrap();
+        mark_all_pushed(&device).unwrap();
+        edit_title(&device, "a", "Local");
+        let mut stale = get_changes_since(&device, 0).unwrap();
+        stale.notes.retain(|note| note.id == "a");
+        stale.notes[0].title = "Stale".to_string();
+        stale.notes[0].revision -= 1;
+        let (_, conflicts) = merge_remote_changes(&device, stale, ConflictStrategy::Lww).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((flagged(&device, "a"), flagged(&device, "c")), (1, 0));
+        assert_eq!(get_sync_state(&device).unwrap().pending_changes, 1);
+    }
+
+    #[test]
+    fn test_field_level_merge_keeps_both_edits() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let id = uuid::Uuid::new_v4().to_string();
+        insert_note(&device, &id, "2024-01-01T00:00:00+00:00");
+        device.conn().execute("UPDATE notes SET tags = '[\"work\",\"old\"]' WHERE id = ?", params![id]).unwrap();
+
+        // Pulled once, so both sides share this version
+        let mut synced = get_changes_since(&device, 0).unwrap();
+        synced.notes[0].revision = 10;
+        apply_pull(&device, synced.clone(), Some(10), ConflictStrategy::Lww).unwrap();
+        let mut phone = synced.notes.remove(0);
+
+        let edit_content = |content: &str| {
+            let input = UpdateNoteInput {
+                title: None,
+                content: Some(content.to_string()),
+                notebook_id: None,
+                tags: None,
+                status: None,
+                is_pinned: None,
+            };
+            notes::write_note_update(&device.conn(), &id, input).unwrap();
+        };
+        let pull = |note: &Note| {
+            let remote = SyncPayload {
+                notes: vec![note.clone()],
+                ..SyncPayload::default()
+            };
+            apply_pull(&device, remote, Some(note.revision), ConflictStrategy::Lww).unwrap()
+        };
+        let local = || -> (String, String, i64, bool) {
+            device
+                .conn()
+                .query_row("SELECT content, tags, revision, needs_push FROM notes WHERE id = ?", params![id], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .unwrap()
+        };
+
+        // Content edited here, tags on the phone
+        edit_content("laptop");
+        phone.tags = vec!["work".to_string(), "phone".to_string()];
+        phone.revision = 20;
+        let (stats, conflicts) = pull(&phone);
+        assert_eq!((stats.notes, conflicts.len()), (1, 0));
+        // Pushed back past the pulled revision, so the server takes it
+        assert_eq!(local(), ("laptop".to_string(), r#"["work","phone"]"#.to_string(), 21, true));
+
+        // Both rewrote the content: the later edit wins, and the other is kept for review
+        phone.content = "phone".to_string();
+        phone.revision = 30;
+        phone.updated_at = "2999-01-01T00:00:00+00:00".to_string();
+        let (_, conflicts) = pull(&phone);
+        assert_eq!(conflicts[0].resolution, "merged");
+        assert_eq!(local().0, "phone");
+        assert_eq!(unresolved_conflicts(&device.conn()).unwrap()[0].local_content.as_deref(), Some("laptop"));
+
+        // Without a base there's no telling who changed what: plain LWW
+        edit_content("laptop again");
+        device.conn().execute("DELETE FROM note_sync_base WHERE note_id = ?", params![id]).unwrap();
+        phone.revision = 40;
+        pull(&phone);
+        assert_eq!(local(), ("phone".to_string(), r#"["work","phone"]"#.to_string(), 40, false));
+    }
+
+    #[test]
+    fn test_sync_status_kind() {
+        assert_eq!(SyncStatusKind::of(0, 5, None), SyncStatusKind::UpToDate);
+        assert_eq!(SyncStatusKind::of(0, 5, Some(5)), SyncStatusKind::UpToDate);
+        assert_eq!(SyncStatusKind::of(3, 5, Some(5)), SyncStatusKind::LocalChanges);
+        assert_eq!(SyncStatusKind::of(0, 5, Some(8)), SyncStatusKind::ServerNewer);
+        assert_eq!(SyncStatusKind::of(3, 5, Some(8)), SyncStatusKind::Diverged);
+    }
+
+    fn status_against(device: &Database, url: &str) -> Result<SyncStatus> {
+        tauri::async_runtime::block_on(sync_status(device, Some(url)))
+    }
+
+    fn closed_url() -> String {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        format!("http://{}", closed)
+    }
+
+    #[test]
+    fn test_cursor_moved_by_a_push_alone_counts_as_where_the_device_stands() {
+        let device = Database::in_memory();
+        device
+            .conn()
+            .execute(
+                "UPDATE sync_state SET notes_push_revision = 8, notebooks_push_revision = 8, tags_push_revision = 8,
+                 reminders_push_revision = 8",
+                [],
+            )
+            .unwrap();
+        let (url, _) = stub_server_answering(vec![String::new()], 200, Some(r#"{"revision":8}"#.to_string()));
+        let answered = status_against(&device, &url).unwrap();
+        assert_eq!(
+            (answered.status, answered.local_revision, answered.server_revision),
+            (SyncStatusKind::UpToDate, 8, Some(8))
+        );
+    }
+
+    #[test]
+    fn test_status_reports_pending_changes_while_offline() {
+        let device = Database::in_memory();
+        device.conn().execute("INSERT INTO notes (id) VALUES ('n')", []).unwrap();
+        let offline = status_against(&device, &closed_url()).unwrap();
+        assert_eq!(
+            (offline.status, offline.pending_changes, offline.server_revision),
+            (SyncStatusKind::Offline, 1, None)
+        );
+        // An answer that can't be read is offline too
+        let (url, _) = stub_server_answering(vec![String::new()], 200, Some("not json".to_string()));
+        assert_eq!(status_against(&device, &url).unwrap().status, SyncStatusKind::Offline);
+    }
+
+    #[test]
+    fn test_status_refused_token_and_bad_url_are_errors() {
+        let device = Database::in_memory();
+        let (url, _) = stub_server(401);
+        assert!(matches!(status_against(&device, &url), Err(AppError::Sync(message)) if message == UNAUTHORIZED));
+        // The bad URL before any request
+        let (url, requests) = stub_server(200);
+        assert!(matches!(status_against(&device, &format!("{}?x=1", url)), Err(AppError::Validation(_))));
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_push_queue_summary() {
+        let device = Database::in_memory();
+        assert_eq!(push_queue(&device.conn()).unwrap(), PushQueueSummary::default());
+
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notebooks (id, name, updated_at) VALUES ('nb', 'Work', '2024-05-01T12:00:00+02:00');
+                 INSERT INTO notes (id, notebook_id, updated_at) VALUES ('n', 'nb', '2024-06-01 08:00:00');
+                 INSERT INTO deleted_entities (entity_type, entity_id, revision, deleted_at)
+                     VALUES ('tag', 't', 1, '2024-07-01T00:00:00Z');",
+            )
+            .unwrap();
+        let queue = push_queue(&device.conn()).unwrap();
+        assert_eq!((queue.notes, queue.notebooks, queue.tags, queue.deleted, queue.total()), (1, 1, 0, 1, 3));
+        assert_eq!(queue.oldest_change_at.as_deref(), Some("2024-05-01T10:00:00Z"));
+        assert_eq!(get_sync_state(&device).unwrap().pending_changes, 3);
+
+        mark_all_pushed(&device).unwrap();
+        assert_eq!(push_queue(&device.conn()).unwrap().total(), 0);
+    }
+
+    #[test]
+    fn test_push_queue_counts_trashed_notes_and_skips_unreadable_dates() {
+        let device = Database::in_memory();
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notes (id, updated_at, needs_push) VALUES ('pushed', '2020-01-01T00:00:00Z', 0);
+                 INSERT INTO notes (id, updated_at) VALUES ('garbled', 'yesterday');
+                 INSERT INTO notes (id, updated_at, status) VALUES ('trashed', '2024-03-01T00:00:00Z', 'trashed');",
+            )
+            .unwrap();
+        let queue = push_queue(&device.conn()).unwrap();
+        assert_eq!((queue.notes, queue.total()), (2, 2));
+        assert_eq!(queue.oldest_change_at.as_deref(), Some("2024-03-01T00:00:00Z"));
+
+        device.conn().execute("UPDATE notes SET needs_push = 0 WHERE id = 'trashed'", []).unwrap();
+        let queue = push_queue(&device.conn()).unwrap();
+        assert_eq!((queue.total(), queue.oldest_change_at), (1, None));
+    }
+
+    #[test]
+    fn test_only_a_refused_token_makes_the_server_check_an_error() {
+        let client = reqwest::Client::new();
+        let reachable = |url: &str| tauri::async_runtime::block_on(server_reachable(&client, url, Some("token")));
+        let (url, _) = stub_server(200);
+        assert!(reachable(&url).unwrap());
+        // Anything else means not reachable yet
+        let (url, _) = stub_server(503);
+        assert!(!reachable(&url).unwrap());
+        assert!(!reachable(&closed_url()).unwrap());
+        let (url, _) = stub_server(401);
+        assert!(matches!(reachable(&url), Err(AppError::Sync(message)) if message == UNAUTHORIZED));
+    }
+
+    #[test]
+    fn test_purge_synced_tombstones() {
+        let device = Database::in_memory();
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notebooks (id, name, deleted_at, needs_push) VALUES ('nb', 'Old', '2020-01-01T00:00:00Z', 0);
+                 INSERT INTO tags (id, name, deleted_at, needs_push) VALUES ('t', 'old', '2020-01-01T00:00:00Z', 0);
+                 INSERT INTO notes (id, notebook_id, status, deleted_at) VALUES
+                     ('pushed', 'nb', 'trashed', '2020-01-01T00:00:00Z'),
+                     ('unpushed', 'nb', 'trashed', '2020-01-01T00:00:00Z'),
+                     ('recent', NULL, 'trashed', datetime('now')),
+                     ('live', 'nb', 'active', NULL);
+                 UPDATE notes SET needs_push = 0 WHERE id != 'unpushed';
+                 INSERT INTO reminders (id, note_id, due_date, needs_push)
+                     VALUES ('r', 'pushed', '2030-01-01T00:00:00Z', 0);",
+            )
+            .unwrap();
+
+        let purged = purge_synced(&device.conn(), 30).unwrap();
+        assert_eq!(
+            purged,
+            vec![("note", "pushed".to_string()), ("notebook", "nb".to_string()), ("tag", "t".to_string())]
+        );
+        let left = |table: &str| count(&device, &format!("SELECT COUNT(*) FROM {} WHERE id != ?", table), "");
+        assert_eq!((left("notes"), left("notebooks"), left("tags"), left("reminders")), (3, 0, 0, 0));
+        // Nothing was queued for push
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM deleted_entities WHERE entity_id != ?", ""), 0);
+        assert!(purge_synced(&device.conn(), -1).is_err());
+
+        // A successful sync purges too, once enabled
+        let conn = device.conn();
+        settings::set(&conn, settings::PURGE_TOMBSTONES_AFTER_SYNC, "true").unwrap();
+        settings::set(&conn, settings::TOMBSTONE_RETENTION_DAYS, "1").unwrap();
+        conn.execute("UPDATE notes SET deleted_at = '2020-01-01T00:00:00Z' WHERE id = 'recent'", []).unwrap();
+        drop(conn);
+        let (url, _) = stub_server(200);
+        tauri::async_runtime::block_on(run_sync(&device, &url, None, None)).unwrap();
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE deleted_at IS NOT NULL AND id != ?", ""), 0);
+    }
+
+    /// Pushed soft-deletions: a notebook in SQLite's old format with a live note, one deleted
+    /// just now, and a tag
+    fn pushed_deletions() -> Database {
+        let device = Database::in_memory();
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notebooks (id, name, deleted_at, needs_push) VALUES
+                     ('old-format', 'Old', '2020-01-01 00:00:00', 0), ('fresh', 'Fresh', datetime('now'), 0);
+                 INSERT INTO notes (id, notebook_id, status) VALUES ('live', 'old-format', 'active');
+                 UPDATE notes SET needs_push = 0;
+                 INSERT INTO tags (id, name, deleted_at, needs_push) VALUES ('t', 'old', '2020-01-01T00:00:00Z', 0);",
+            )
+            .unwrap();
+        device
+    }
+
+    #[test]
+    fn test_failed_purge_of_synced_rows_purges_nothing() {
+        let device = pushed_deletions();
+        let left = |table: &str| count(&device, &format!("SELECT COUNT(*) FROM {} WHERE id != ?", table), "");
+        {
+            let mut conn = device.conn();
+            fail_writes(&conn, "DELETE ON tags");
+            let tx = conn.transaction().unwrap();
+            assert!(purge_synced(&tx, 30).is_err());
+            drop(tx);
+            allow_writes(&conn);
+        }
+        assert_eq!((left("notebooks"), left("tags")), (2, 1));
+    }
+
+    #[test]
+    fn test_purge_reads_old_dates_and_unfiles_live_notes() {
+        let device = pushed_deletions();
+        let purged = purge_synced(&device.conn(), 30).unwrap();
+        assert_eq!(purged, vec![("notebook", "old-format".to_string()), ("tag", "t".to_string())]);
+        // Unfiled, not deleted
+        let notebook: Option<String> = device
+            .conn()
+            .query_row("SELECT notebook_id FROM notes WHERE id = 'live'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(notebook, None);
+    }
+
+    #[test]
+    fn test_purge_of_zero_days_takes_what_was_deleted_just_now() {
+        let device = pushed_deletions();
+        purge_synced(&device.conn(), 30).unwrap();
+        assert_eq!(purge_synced(&device.conn(), 0).unwrap(), vec![("notebook", "fresh".to_string())]);
+        assert!(purge_synced(&device.conn(), 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_preview_merge_writes_nothing() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        device
+            .conn()
+            .execute_batch(
+                "INSERT INTO notes (id, title, content, revision, updated_at) VALUES
+                     ('a', 'A', 'local', 2, '2024-03-01T00:00:00Z'),
+                     ('b', 'B', 'local', 1, '2024-03-01T00:00:00Z');",
+            )
+            .unwrap();
+        // The remote copy of 'a' is behind this one; 'b' was only edited remotely
+        let mut remote = get_changes_since(&device, 0).unwrap();
+        for note in &mut remote.notes {
+            note.content = "remote".to_string();
+            note.revision = if note.id == "a" { 1 } else { 3 };
+        }
+        let mut created = remote.notes[0].clone();
+        created.id = "c".to_string();
+        remote.notes.push(created);
+        remote.notebooks.push(Notebook {
+            id: "nb".to_string(),
+            name: "Work".to_string(),
+            color: None,
+            icon: None,
+            parent_id: None,
+            revision: 1,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+            deleted_at: None,
+            is_archived: false,
+            sync_excluded: false,
+        });
+
+        let preview = preview_merge(&device, remote, ConflictStrategy::Lww).unwrap();
+        let counts = |created, updated, conflicted| PreviewCounts { created, updated, conflicted };
+        assert_eq!(preview.notes, counts(1, 1, 1));
+        assert_eq!(preview.notebooks, counts(1, 0, 0));
+        assert_eq!(preview.tags, PreviewCounts::default());
+        // 'b' is overwritten by the pull, so only 'a' would be pushed
+        assert_eq!(preview.to_push, 1);
+
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE content = ?", "local"), 2);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id != ?", "a"), 1);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notebooks WHERE id = ?", "nb"), 0);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM sync_conflicts WHERE entity_id = ?", "a"), 0);
+        assert_eq!(get_sync_state(&device).unwrap().pending_changes, 2);
+    }
+
+    #[test]
+    fn test_merge_is_atomic_with_pull_cursor() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        let notebook_id = uuid::Uuid::new_v4().to_string();
+        insert_note(&device_a, &uuid::Uuid::new_v4().to_string(), "2024-01-01T00:00:00+00:00");
+        device_a
+            .conn()
+            .execute_batch(&format!(
+                "INSERT INTO notebooks (id, name) VALUES ('{notebook_id}', 'Work');
+                 UPDATE notes SET notebook_id = '{notebook_id}';"
+            ))
+            .unwrap();
+
+        // A notebook missing from the payload fails the whole merge
+        let mut orphaned = get_changes_since(&device_a, 0).unwrap();
+        orphaned.notebooks.clear();
+        assert!(apply_pull(&device_b, orphaned, Some(40), ConflictStrategy::Lww).is_err());
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM notes WHERE id != ?", ""), 0);
+        assert_eq!(get_sync_state(&device_b).unwrap().last_pull_revision, 0);
+
+        // Notes may arrive ahead of their notebook within one payload
+        let (stats, _) =
+            apply_pull(&device_b, get_changes_since(&device_a, 0).unwrap(), Some(40), ConflictStrategy::Lww).unwrap();
+        assert_eq!((stats.notes, stats.notebooks), (1, 1));
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM notes WHERE notebook_id = ?", &notebook_id), 1);
+        assert_eq!(get_sync_state(&device_b).unwrap().last_pull_revision, 40);
+
+        // Re-pulling the notebook updates it in place, leaving its notes filed
+        let mut renamed = get_changes_since(&device_a, 0).unwrap();
+        renamed.notes.clear();
+        renamed.notebooks[0].revision += 1;
+        apply_pull(&device_b, renamed, None, ConflictStrategy::Lww).unwrap();
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM notes WHERE notebook_id = ?", &notebook_id), 1);
+    }
+
+    #[test]
+    fn test_pulled_tags_resolve_by_name() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        let remote = Database::in_memory();
+        let tag = |id: &str, name: &str, revision: i64, deleted: bool| Tag {
+            id: id.to_string(),
+            name: name.to_string(),
+            color: None,
+            revision,
+            created_at: "2026-01-01T00:00:00+00:00".to_string(),
+            updated_at: format!("2026-01-0{}T00:00:00+00:00", revision),
+            deleted_at: deleted.then(|| "2026-01-01T00:00:00+00:00".to_string()),
+        };
+        let pull = |payload: SyncPayload| apply_pull(&device, payload, None, ConflictStrategy::Lww).unwrap();
+        let tag_ids = |name: &str| -> Vec<String> {
+            let conn = device.conn();
+            let mut stmt = conn.prepare("SELECT id FROM tags WHERE name = ?").unwrap();
+            let ids = stmt.query_map(params![name], |row| row.get(0)).unwrap();
+            ids.collect::<rusqlite::Result<_>>().unwrap()
+        };
+        let remote_note = |id: &str, tags: &str| {
+            remote.conn().execute("INSERT INTO notes (id, tags) VALUES (?, ?)", params![id, tags]).unwrap();
+            get_changes_since(&remote, 0).unwrap().notes.into_iter().find(|n| n.id == id).unwrap()
+        };
+        pull(SyncPayload { tags: vec![tag("x", "old", 2, false)], ..Default::default() });
+
+        // A note tagged "new" arrives a page before the rename that makes X "new"
+        let page = SyncPayload { notes: vec![remote_note("n1", r#"["new"]"#)], ..Default::default() };
+        pull(page);
+        assert!(tag_ids("new").is_empty());
+        pull(SyncPayload { tags: vec![tag("x", "new", 3, false)], ..Default::default() });
+        assert_eq!(tag_ids("new"), vec!["x"]);
+        assert_eq!(tags::note_tag_names(&device.conn(), "n1").unwrap(), vec!["new"]);
+
+        // "work" made here offline gives way to the server's "work"
+        let input = CreateNoteInput {
+            id: None,
+            title: None,
+            content: None,
+            notebook_id: Some(None),
+            tags: Some(vec!["work".to_string()]),
+        };
+        let mine = notes::insert_note(&device.conn(), input).unwrap();
+        pull(SyncPayload { tags: vec![tag("w", "work", 4, false)], ..Default::default() });
+        assert_eq!(tag_ids("work"), vec!["w"]);
+        let linked: String = device
+            .conn()
+            .query_row("SELECT tag_id FROM note_tags WHERE note_id = ?", params![mine], |row| row.get(0))
+            .unwrap();
+        assert_eq!(linked, "w");
+
+        // A pulled note naming a deleted tag links to it without reviving it
+        pull(SyncPayload { tags: vec![tag("g", "gone", 5, true)], ..Default::default() });
+        pull(SyncPayload { notes: vec![remote_note("n2", r#"["gone"]"#)], ..Default::default() });
+        assert_eq!(tags::note_tag_names(&device.conn(), "n2").unwrap(), vec!["gone"]);
+        let (deleted, needs_push): (bool, bool) = device
+            .conn()
+            .query_row("SELECT deleted_at IS NOT NULL, needs_push FROM tags WHERE id = 'g'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((deleted, needs_push), (true, false));
+    }
+
+    #[test]
+    fn test_merge_large_payload() {
+        let _guard = crypto::test_guard();
+        let device_a = Database::in_memory();
+        let device_b = Database::in_memory();
+        {
+            let mut conn = device_a.conn();
+            let tx = conn.transaction().unwrap();
+            for _ in 0..1_000 {
+                tx.execute(
+                    "INSERT INTO notes (id, title, content, tags) VALUES (?, 'Note', '- [ ] task', '[\"work\"]')",
+                    params![uuid::Uuid::new_v4().to_string()],
+                )
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let payload = get_changes_since(&device_a, 0).unwrap();
+
+        // About a second in a debug build; a merge that went quadratic in the
+        // payload would take far longer
+        let started = std::time::Instant::now();
+        let (stats, _) = apply_pull(&device_b, payload, None, ConflictStrategy::Lww).unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed < std::time::Duration::from_secs(20), "merge of 1k notes took {:?}", elapsed);
+
+        assert_eq!(stats.notes, 1_000);
+        assert_eq!(count(&device_b, "SELECT COUNT(*) FROM note_tasks WHERE note_id != ?", ""), 1_000);
+    }
+
+    #[test]
+    fn test_sync_excluded_notebook_stays_local() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        device.conn().execute("INSERT INTO notebooks (id, name) VALUES ('private', 'Private')", []).unwrap();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        insert_note(&device, "b", "2024-01-01T00:00:00+00:00");
+        let mut remote = get_changes_since(&device, 0).unwrap();
+
+        crate::commands::notebooks::set_sync_excluded(&device.conn(), "private", true).unwrap();
+        let input = UpdateNoteInput {
+            title: None,
+            content: None,
+            notebook_id: Some("private".to_string()),
+            tags: None,
+            status: None,
+            is_pinned: None,
+        };
+        notes::write_note_update(&device.conn(), "a", input).unwrap();
+
+        // 'a' and its reminder stay here; tombstones tell other devices to drop them
+        let pending = unpushed_changes(&device).unwrap();
+        assert!(pending.notebooks.is_empty());
+        assert_eq!(pending.notes.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), ["b"]);
+        assert_eq!(pending.reminders.len(), 1);
+        let mut tombstones: Vec<_> =
+            pending.deleted.iter().map(|d| (d.entity_type.as_str(), d.entity_id.as_str())).collect();
+        tombstones.sort();
+        assert_eq!(tombstones, [("note", "a"), ("notebook", "private")]);
+        assert_eq!(push_queue(&device.conn()).unwrap().notes, 1);
+
+        // Neither a newer copy nor the echo of the tombstones touches it
+        for note in &mut remote.notes {
+            note.content = "remote".to_string();
+            note.revision = 50;
+            note.updated_at = "2099-01-01T00:00:00+00:00".to_string();
+        }
+        remote.deleted = pending.deleted.clone();
+        apply_pull(&device, remote, Some(50), ConflictStrategy::Lww).unwrap();
+        let content = |id| -> String {
+            device.conn().query_row("SELECT content FROM notes WHERE id = ?", params![id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!((content("a"), content("b")), ("- [ ] task".to_string(), "remote".to_string()));
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM reminders WHERE note_id = ?", "a"), 1);
+
+        // Shared again: queued for the next push, tombstones withdrawn
+        crate::commands::notebooks::set_sync_excluded(&device.conn(), "private", false).unwrap();
+        let pending = unpushed_changes(&device).unwrap();
+        assert_eq!(pending.notebooks.len(), 1);
+        assert!(pending.notes.iter().any(|n| n.id == "a"));
+        assert!(pending.deleted.is_empty());
+    }
+
+    /// Note `a` in a `private` notebook that's kept off the server, next to a `work` one
+    fn note_in_private_notebook(device: &Database) {
+        device
+            .conn()
+            .execute_batch("INSERT INTO notebooks (id, name) VALUES ('private', 'Private'), ('work', 'Work');")
+            .unwrap();
+        insert_note(device, "a", "2024-01-01T00:00:00+00:00");
+        device.conn().execute("UPDATE notes SET notebook_id = 'private' WHERE id = 'a'", []).unwrap();
+        assert!(crate::commands::notebooks::set_sync_excluded(&device.conn(), "private", true).unwrap());
+    }
+
+    fn private_tombstones(device: &Database) -> i64 {
+        count(device, "SELECT COUNT(*) FROM deleted_entities WHERE entity_id IN ('a', ?)", "private")
+    }
+
+    #[test]
+    fn test_excluding_a_notebook_twice_records_nothing_more() {
+        use crate::commands::notebooks::set_sync_excluded;
+
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        note_in_private_notebook(&device);
+        assert!(matches!(set_sync_excluded(&device.conn(), "nowhere", true), Err(AppError::NotFound(_))));
+        assert!(!set_sync_excluded(&device.conn(), "private", true).unwrap());
+        assert_eq!(private_tombstones(&device), 2);
+    }
+
+    #[test]
+    fn test_nothing_pulled_gets_filed_into_an_excluded_notebook() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        note_in_private_notebook(&device);
+        let mut pulled = SyncPayload::default();
+        pulled.notes.push(Note {
+            id: "c".to_string(),
+            notebook_id: Some("private".to_string()),
+            revision: 5,
+            ..stored_note(&device.conn(), "a").unwrap().unwrap().0
+        });
+        apply_pull(&device, pulled, Some(5), ConflictStrategy::Lww).unwrap();
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id = ?", "c"), 0);
+    }
+
+    #[test]
+    fn test_moving_a_note_out_of_an_excluded_notebook_queues_it_again() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        note_in_private_notebook(&device);
+        let input = UpdateNoteInput {
+            title: None,
+            content: None,
+            notebook_id: Some("work".to_string()),
+            tags: None,
+            status: None,
+            is_pinned: None,
+        };
+        notes::write_note_update(&device.conn(), "a", input).unwrap();
+        assert_eq!(private_tombstones(&device), 1);
+        assert!(unpushed_changes(&device).unwrap().notes.iter().any(|n| n.id == "a"));
+    }
+
+    #[test]
+    fn test_deleting_an_excluded_notebook_queues_its_notes_again() {
+        use crate::commands::notebooks::remove_notebook;
+        use crate::models::NotebookDeleteMode;
+
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        note_in_private_notebook(&device);
+        mark_all_pushed(&device).unwrap();
+        remove_notebook(&device.conn(), "private", NotebookDeleteMode::PromoteChildren, None, false).unwrap();
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM deleted_entities WHERE entity_id = ?", "a"), 0);
+        assert!(unpushed_changes(&device).unwrap().notes.iter().any(|n| n.id == "a"));
+    }
+
+    #[test]
+    fn test_conflict_losers_are_archived() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        device.conn().execute("UPDATE notes SET revision = 10 WHERE id = 'a'", []).unwrap();
+
+        // An older remote edit loses to the local copy, but isn't dropped
+        let mut remote = get_changes_since(&device, 0).unwrap();
+        remote.notes[0].content = "remote".to_string();
+        remote.notes[0].revision = 5;
+        let (_, conflicts) = merge_remote_changes(&device, remote, ConflictStrategy::LocalWins).unwrap();
+        assert_eq!(conflicts.len(), 1);
+
+        let archived = conflict_archive(&device.conn(), "a").unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!((archived[0].source.as_str(), archived[0].content.as_str()), ("remote", "remote"));
+        assert_eq!(archived[0].resolution, "local_wins");
+
+        // Back as a new revision of the note, then as a separate note
+        assert_eq!(restore_archived_version(&device.conn(), &archived[0].id, false).unwrap(), ("a".to_string(), false));
+        assert_eq!(count(&device, "SELECT revision FROM notes WHERE id = ? AND content = 'remote'", "a"), 11);
+        let (copy, created) = restore_archived_version(&device.conn(), &archived[0].id, true).unwrap();
+        assert!(created);
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id = ? AND content = 'remote'", &copy), 1);
+
+        // Only the latest versions of a note are kept
+        let (note, _) = stored_note(&device.conn(), "a").unwrap().unwrap();
+        for _ in 0..ARCHIVED_VERSIONS_PER_NOTE + 5 {
+            archive_note_version(&device.conn(), &note, "local", "server_wins").unwrap();
+        }
+        assert_eq!(conflict_archive(&device.conn(), "a").unwrap().len(), ARCHIVED_VERSIONS_PER_NOTE as usize);
+    }
+
+    #[test]
+    fn test_restoring_a_missing_archived_version_is_refused() {
+        let device = Database::in_memory();
+        assert!(matches!(restore_archived_version(&device.conn(), "nowhere", false), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_archived_versions_past_retention_go_and_the_cap_is_per_note() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let (note, _) = stored_note(&device.conn(), "a").unwrap().unwrap();
+        archive_note_version(&device.conn(), &Note { id: "b".to_string(), ..note.clone() }, "local", "server_wins")
+            .unwrap();
+        device
+            .conn()
+            .execute(
+                "INSERT INTO conflict_archive (id, note_id, title, content, revision, source, resolution, created_at)
+                 VALUES ('old', 'a', '', 'old', 1, 'remote', 'local_wins', datetime('now', '-91 days'))",
+                [],
+            )
+            .unwrap();
+
+        // The next time anything is archived
+        archive_note_version(&device.conn(), &note, "local", "server_wins").unwrap();
+        assert_eq!(conflict_archive(&device.conn(), "a").unwrap().len(), 1);
+        for _ in 0..ARCHIVED_VERSIONS_PER_NOTE {
+            archive_note_version(&device.conn(), &note, "local", "server_wins").unwrap();
+        }
+        assert_eq!(conflict_archive(&device.conn(), "a").unwrap().len(), ARCHIVED_VERSIONS_PER_NOTE as usize);
+        assert_eq!(conflict_archive(&device.conn(), "b").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_encrypted_archived_versions_are_listed_as_plaintext() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "a", "2024-01-01T00:00:00+00:00");
+        let (note, _) = stored_note(&device.conn(), "a").unwrap().unwrap();
+        crypto::init_encryption("correct horse", None).unwrap();
+        let encrypted = Note {
+            title: crypto::encrypt("Secret").unwrap(),
+            content: crypto::encrypt("hidden").unwrap(),
+            ..note
+        };
+        archive_note_version(&device.conn(), &encrypted, "remote", "local_wins").unwrap();
+        let newest = conflict_archive(&device.conn(), "a").unwrap().remove(0);
+        assert_eq!((newest.title.as_str(), newest.content.as_str()), ("Secret", "hidden"));
+        crypto::clear_encryption();
+    }
+
+    #[test]
+    fn test_restoring_a_version_whose_note_is_gone_makes_a_new_note() {
+        let _guard = crypto::test_guard();
+        let device = Database::in_memory();
+        insert_note(&device, "b", "2024-01-01T00:00:00+00:00");
+        let (note, _) = stored_note(&device.conn(), "b").unwrap().unwrap();
+        archive_note_version(&device.conn(), &note, "local", "server_wins").unwrap();
+        device.conn().execute("DELETE FROM notes WHERE id = 'b'", []).unwrap();
+
+        let archived = conflict_archive(&device.conn(), "b").unwrap();
+        let (restored, created) = restore_archived_version(&device.conn(), &archived[0].id, false).unwrap();
+        assert!(created && restored != "b");
+        assert_eq!(count(&device, "SELECT COUNT(*) FROM notes WHERE id = ? AND content = '- [ ] task'", &restored), 1);
+    }
+}
@@ -0,0 +1,393 @@
+//! Tasks extracted from note content
+//!
+//! Checkbox items (`- [ ] ...`) are parsed out of each note on save into
+//! the `note_tasks` table, so open tasks can be listed across all notes.
+//! Toggling a task rewrites the checkbox in the note itself.
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use ts_rs::TS;
+
+use crate::crypto;
+use crate::db::Database;
+use crate::error::{AppError, Result};
+use crate::events::{self, ChangeEvent, ChangeKind};
+use crate::markdown::{self, TaskItem};
+
+// =============================================================================
+// Types
+// =============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../src/lib/bindings/")]
+pub struct NoteTask {
+    pub id: String,
+    pub note_id: String,
+    pub note_title: String,
+    /// 1-based line number within the note content
+    pub line_no: i64,
+    pub text: String,
+    pub checked: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const TASK_COLUMNS: &str =
+    "t.id, t.note_id, n.title, t.line_no, t.text, t.checked, t.created_at, t.updated_at";
+
+fn row_to_task(row: &rusqlite::Row) -> rusqlite::Result<NoteTask> {
+    let raw_title: String = row.get(2)?;
+    let raw_text: String = row.get(4)?;
+
+    Ok(NoteTask {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        note_title: crypto::maybe_decrypt(&raw_title).unwrap_or(raw_title),
+        line_no: row.get(3)?,
+        text: crypto::maybe_decrypt(&raw_text).unwrap_or(raw_text),
+        checked: row.get::<_, i32>(5)? != 0,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+// =============================================================================
+// Extraction
+// =============================================================================
+
+struct StoredTask {
+    id: String,
+    line_no: i64,
+    text: String,
+    checked: bool,
+}
+
+/// Bring a note's rows in `note_tasks` in line with its (decrypted) content.
+///
+/// A task keeps its id as long as it can be found again: first on the same
+/// line with the same text, then, when edits above it shifted the lines, by
+/// text alone on the nearest line.
+pub fn sync_note_tasks(conn: &Connection, note_id: &str, content: &str) -> Result<()> {
+    let stored: Vec<StoredTask> = {
+        let mut stmt = conn.prepare("SELECT id, line_no, text, checked FROM note_tasks WHERE note_id = ?")?;
+        let rows = stmt.query_map(params![note_id], |row| {
+            let raw_text: String = row.get(2)?;
+            Ok(StoredTask {
+                id: row.get(0)?,
+                line_no: row.get(1)?,
+                text: crypto::maybe_decrypt(&raw_text).unwrap_or(raw_text),
+                checked: row.get::<_, i32>(3)? != 0,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let items = markdown::task_items(content);
+    let mut matches: Vec<Option<usize>> = vec![None; items.len()];
+    let mut taken = vec![false; stored.len()];
+
+    for (i, item) in items.iter().enumerate() {
+        if let Some(j) = (0..stored.len())
+            .find(|&j| !taken[j] && stored[j].line_no == item.line_no && stored[j].text == item.text)
+        {
+            matches[i] = Some(j);
+            taken[j] = true;
+        }
+    }
+    for (i, item) in items.iter().enumerate() {
+        if matches[i].is_some() {
+            continue;
+        }
+        if let Some(j) = (0..stored.len())
+            .filter(|&j| !taken[j] && stored[j].text == item.text)
+            .min_by_key(|&j| (stored[j].line_no - item.line_no).abs())
+        {
+            matches[i] = Some(j);
+            taken[j] = true;
+        }
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+
+    for (task, _) in stored.iter().zip(&taken).filter(|(_, taken)| !**taken) {
+        conn.execute("DELETE FROM note_tasks WHERE id = ?", params![task.id])?;
+    }
+
+    for (item, matched) in items.iter().zip(matches) {
+        match matched {
+            Some(j) => {
+                let task = &stored[j];
+                if task.line_no != item.line_no || task.checked != item.checked {
+                    conn.execute(
+                        "UPDATE note_tasks SET line_no = ?, checked = ?, updated_at = ? WHERE id = ?",
+                        params![item.line_no, item.checked as i32, now, task.id],
+                    )?;
+                }
+            }
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                let text = crypto::maybe_encrypt(&item.text)?;
+                conn.execute(
+                    "INSERT INTO note_tasks (id, note_id, line_no, text, checked, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                    params![id, note_id, item.line_no, text, item.checked as i32, now, now],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-extract the tasks of a note from what's stored in the database,
+/// e.g. after sync or import wrote its content directly
+pub fn refresh_note_tasks(conn: &Connection, note_id: &str) -> Result<()> {
+    let raw_content: String = conn.query_row(
+        "SELECT content FROM notes WHERE id = ?",
+        params![note_id],
+        |row| row.get(0),
+    )?;
+    let content = crypto::maybe_decrypt(&raw_content).unwrap_or(raw_content);
+    sync_note_tasks(conn, note_id, &content)
+}
+
+/// Re-extract the tasks of every note.
+/// Useful for migration or if the table gets out of sync. Returns the number of tasks.
+pub fn rebuild_tasks_index(conn: &Connection) -> Result<usize> {
+    let ids: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT id FROM notes")?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        ids.collect::<rusqlite::Result<_>>()?
+    };
+
+    for id in &ids {
+        refresh_note_tasks(conn, id)?;
+    }
+
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM note_tasks", [], |row| row.get(0))?;
+    Ok(count as usize)
+}
+
+// =============================================================================
+// Queries
+// =============================================================================
+
+/// Unchecked tasks of active notes, pinned and recently edited notes first
+pub fn list_open_tasks(db: &Database, limit: Option<i64>) -> Result<Vec<NoteTask>> {
+    let conn = db.conn();
+
+    let sql = format!(
+        "SELECT {} FROM note_tasks t
+         JOIN notes n ON n.id = t.note_id
+         WHERE t.checked = 0 AND n.deleted_at IS NULL AND n.status = 'active'
+         ORDER BY n.is_pinned DESC, n.pinned_order ASC, n.updated_at DESC, t.line_no
+         LIMIT ?",
+        TASK_COLUMNS
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let tasks = stmt
+        .query_map(params![limit.unwrap_or(100)], row_to_task)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(tasks)
+}
+
+pub fn list_note_tasks(db: &Database, note_id: &str) -> Result<Vec<NoteTask>> {
+    let conn = db.conn();
+
+    let sql = format!(
+        "SELECT {} FROM note_tasks t
+         JOIN notes n ON n.id = t.note_id
+         WHERE t.note_id = ?
+         ORDER BY t.line_no",
+        TASK_COLUMNS
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let tasks = stmt
+        .query_map(params![note_id], row_to_task)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(tasks)
+}
+
+// =============================================================================
+// Toggle
+// =============================================================================
+
+/// Flip the checkbox on `line_no` of a note, rewriting the note's content.
+///
+/// If the content no longer has the stored task on that line, the task is
+/// looked up by its text instead.
+pub fn toggle_note_task(db: &Database, note_id: &str, line_no: i64) -> Result<NoteTask> {
+    let mut conn = db.conn();
+    let tx = conn.transaction()?;
+
+    let raw_content: String = tx
+        .query_row(
+            "SELECT content FROM notes WHERE id = ? AND deleted_at IS NULL",
+            params![note_id],
+            |row| row.get(0),
+        )
+        .map_err(|_| AppError::NotFound(format!("Note {} not found", note_id)))?;
+    let mut content = crypto::maybe_decrypt(&raw_content).unwrap_or(raw_content);
+
+    let stored_text: Option<String> = tx
+        .query_row(
+            "SELECT text FROM note_tasks WHERE note_id = ? AND line_no = ?",
+            params![note_id, line_no],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .map(|raw| crypto::maybe_decrypt(&raw).unwrap_or(raw));
+
+    let items = markdown::task_items(&content);
+    let item: TaskItem = items
+        .iter()
+        .find(|t| t.line_no == line_no && stored_text.as_ref().is_none_or(|s| *s == t.text))
+        .or_else(|| {
+            let text = stored_text.as_ref()?;
+            items.iter().find(|t| t.text == *text)
+        })
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("No task on line {} of note {}", line_no, note_id)))?;
+
+    markdown::set_task_checked(&mut content, &item, !item.checked);
+
+    let now = chrono::Utc::now().to_rfc3339();
+    tx.execute(
+        "UPDATE notes SET content = ?, excerpt = ?, revision = revision + 1, needs_push = 1, updated_at = ? WHERE id = ?",
+        params![
+            crypto::maybe_encrypt(&content)?,
+            crypto::maybe_encrypt(&markdown::excerpt(&content))?,
+            now,
+            note_id
+        ],
+    )?;
+    sync_note_tasks(&tx, note_id, &content)?;
+
+    let sql = format!(
+        "SELECT {} FROM note_tasks t
+         JOIN notes n ON n.id = t.note_id
+         WHERE t.note_id = ? AND t.line_no = ?",
+        TASK_COLUMNS
+    );
+    let task = tx.query_row(&sql, params![note_id, item.line_no], row_to_task)?;
+
+    tx.commit()?;
+    Ok(task)
+}
+
+// =============================================================================
+// Tauri Commands
+// =============================================================================
+
+/// Open tasks across all notes
+#[tauri::command]
+pub fn get_open_tasks(db: State<'_, Database>, limit: Option<i64>) -> Result<Vec<NoteTask>> {
+    list_open_tasks(&db, limit)
+}
+
+/// All tasks of one note, in document order
+#[tauri::command]
+pub fn get_tasks_for_note(db: State<'_, Database>, note_id: String) -> Result<Vec<NoteTask>> {
+    list_note_tasks(&db, &note_id)
+}
+
+/// Check or uncheck a task
+#[tauri::command]
+pub fn toggle_task(app: AppHandle, db: State<'_, Database>, note_id: String, line_no: i64) -> Result<NoteTask> {
+    let task = toggle_note_task(&db, &note_id, line_no)?;
+    events::emit(&app, ChangeEvent::Note, &note_id, ChangeKind::Updated);
+    Ok(task)
+}
+
+/// Rebuild the tasks table from note content
+#[tauri::command]
+pub fn rebuild_tasks(db: State<'_, Database>) -> Result<usize> {
+    rebuild_tasks_index(&db.conn())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_note(db: &Database, content: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let conn = db.conn();
+        conn.execute(
+            "INSERT INTO notes (id, title, content) VALUES (?, 'Note', ?)",
+            params![id, content],
+        )
+        .unwrap();
+        sync_note_tasks(&conn, &id, content).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_sync_keeps_ids_when_lines_shift() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let note_id = insert_note(&db, "- [ ] first\n- [ ] second");
+        let before = list_note_tasks(&db, &note_id).unwrap();
+
+        sync_note_tasks(&db.conn(), &note_id, "# Added header\n\n- [ ] first\n- [x] second\n- [ ] third").unwrap();
+        let after = list_note_tasks(&db, &note_id).unwrap();
+
+        assert_eq!(after.len(), 3);
+        assert_eq!(after[0].id, before[0].id);
+        assert_eq!(after[0].line_no, 3);
+        assert_eq!(after[1].id, before[1].id);
+        assert!(after[1].checked);
+        assert_eq!(after[2].text, "third");
+    }
+
+    #[test]
+    fn test_sync_removes_deleted_tasks() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let note_id = insert_note(&db, "- [ ] keep\n- [ ] drop");
+
+        sync_note_tasks(&db.conn(), &note_id, "- [ ] keep").unwrap();
+        let tasks = list_note_tasks(&db, &note_id).unwrap();
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].text, "keep");
+    }
+
+    #[test]
+    fn test_toggle_rewrites_content() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let note_id = insert_note(&db, "Groceries\n- [ ] milk\n- [ ] eggs\n");
+
+        let task = toggle_note_task(&db, &note_id, 3).unwrap();
+        assert!(task.checked);
+        assert_eq!(task.text, "eggs");
+
+        let (content, revision): (String, i64) = db
+            .conn()
+            .query_row("SELECT content, revision FROM notes WHERE id = ?", params![note_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(content, "Groceries\n- [ ] milk\n- [x] eggs\n");
+        assert_eq!(revision, 2);
+
+        let open = list_open_tasks(&db, None).unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].text, "milk");
+    }
+
+    #[test]
+    fn test_toggle_missing_task() {
+        let _guard = crypto::test_guard();
+        let db = Database::in_memory();
+        let note_id = insert_note(&db, "no tasks here");
+
+        let result = toggle_note_task(&db, &note_id, 1);
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the unit tests
+
+use rusqlite::{params, Connection};
+
+use crate::commands::tags::row_to_tag;
+use crate::models::Tag;
+
+/// Make every write matching `event` fail the way a full disk would, until
+/// `allow_writes`. `event` is the trigger's event and condition, e.g.
+/// `"UPDATE OF deleted_at ON tags WHEN NEW.id = 'x'"`.
+pub fn fail_writes(conn: &Connection, event: &str) {
+    conn.execute_batch(&format!(
+        "CREATE TEMP TRIGGER fail_writes BEFORE {} BEGIN SELECT RAISE(ABORT, 'disk full'); END",
+        event
+    ))
+    .unwrap();
+}
+
+/// Undo `fail_writes`
+pub fn allow_writes(conn: &Connection) {
+    conn.execute_batch("DROP TRIGGER fail_writes").unwrap();
+}
+
+/// The tag with `id`, deleted or not
+pub fn tag_by_id(conn: &Connection, id: &str) -> Tag {
+    tag_where(conn, "id", id)
+}
+
+/// The tag named `name`, deleted or not
+pub fn tag_by_name(conn: &Connection, name: &str) -> Tag {
+    tag_where(conn, "name", name)
+}
+
+fn tag_where(conn: &Connection, column: &str, value: &str) -> Tag {
+    conn.query_row(
+        &format!(
+            "SELECT id, name, color, revision, created_at, updated_at, deleted_at FROM tags WHERE {} = ?",
+            column
+        ),
+        params![value],
+        row_to_tag,
+    )
+    .unwrap()
+}
@@ -0,0 +1,390 @@
+//! Input validation shared by the write commands
+
+use rusqlite::{params, Connection};
+
+use crate::error::{AppError, Result};
+use crate::settings;
+
+/// Longest accepted note title, in characters
+pub const MAX_TITLE_CHARS: usize = 1000;
+
+/// Resolve the id for a new row in `table`.
+///
+/// Without a requested id a fresh UUID is minted. A caller-supplied id (used
+/// by importers to recreate relationships faithfully) must be a well-formed
+/// UUID that isn't already taken, including by soft-deleted rows.
+pub fn new_entity_id(conn: &Connection, table: &str, requested: Option<String>) -> Result<String> {
+    let Some(id) = requested else {
+        return Ok(uuid::Uuid::new_v4().to_string());
+    };
+
+    if uuid::Uuid::parse_str(&id).is_err() {
+        return Err(AppError::Validation(format!("'{}' is not a valid UUID", id)));
+    }
+
+    let taken = conn
+        .prepare(&format!("SELECT 1 FROM {} WHERE id = ?", table))?
+        .exists(params![&id])?;
+    if taken {
+        return Err(AppError::Conflict(format!("Id {} already exists in {}", id, table)));
+    }
+
+    Ok(id)
+}
+
+pub fn note_title(title: &str) -> Result<()> {
+    if title.chars().count() > MAX_TITLE_CHARS {
+        return Err(AppError::Validation(format!(
+            "Title is longer than {} characters",
+            MAX_TITLE_CHARS
+        )));
+    }
+    Ok(())
+}
+
+/// Reject content above the configured size limit (before encryption)
+pub fn note_content(conn: &Connection, content: &str) -> Result<()> {
+    let limit = settings::get_i64(conn, settings::MAX_CONTENT_BYTES, settings::DEFAULT_MAX_CONTENT_BYTES)?;
+    if content.len() as i64 > limit {
+        return Err(AppError::Validation(format!(
+            "Content is {} bytes, above the limit of {} bytes",
+            content.len(),
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Tag names can't contain quotes or commas, so a list of them typed or
+/// shown as comma-separated text reads back as the same tags. Lookups
+/// don't depend on it: they match whole names through note_tags.
+pub fn tag_name(name: &str) -> Result<()> {
+    if name.contains('"') || name.contains(',') {
+        return Err(AppError::Validation(format!(
+            "Tag name '{}' can't contain quotes or commas",
+            name
+        )));
+    }
+    Ok(())
+}
+
+pub fn tag_names(tags: &[String]) -> Result<()> {
+    tags.iter().try_for_each(|tag| tag_name(tag))
+}
+
+/// Named colors accepted for tags and notebooks besides `#RRGGBB` values;
+/// the same set the UI draws note colors from
+pub const COLOR_PALETTE: &[&str] = &[
+    "red", "orange", "amber", "yellow", "lime", "green", "teal", "cyan",
+    "blue", "indigo", "violet", "purple", "fuchsia", "pink", "rose",
+];
+
+/// Colors are either `#RRGGBB` hex or a palette name
+pub fn color(value: &str) -> Result<()> {
+    let is_hex = value.len() == 7
+        && value.starts_with('#')
+        && value[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex || COLOR_PALETTE.contains(&value) {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Color '{}' must be #RRGGBB or one of: {}",
+            value,
+            COLOR_PALETTE.join(", ")
+        )))
+    }
+}
+
+/// Palette color picked from a hash of `name`, stable across runs and
+/// platforms (FNV-1a rather than std's randomly keyed hasher)
+pub fn palette_color_for(name: &str) -> &'static str {
+    let hash = name
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    COLOR_PALETTE[(hash % COLOR_PALETTE.len() as u64) as usize]
+}
+
+/// Longest icon-set identifier accepted for a notebook icon
+pub const MAX_ICON_NAME_CHARS: usize = 64;
+
+/// Notebook icons are either an icon-set name (ASCII letters, digits, `-`
+/// and `_`) or a single emoji
+pub fn notebook_icon(icon: &str) -> Result<()> {
+    if is_valid_icon(icon) {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Notebook icon must be an icon name of at most {} characters or a single emoji",
+            MAX_ICON_NAME_CHARS
+        )))
+    }
+}
+
+pub fn is_valid_icon(icon: &str) -> bool {
+    let is_name = !icon.is_empty()
+        && icon.len() <= MAX_ICON_NAME_CHARS
+        && icon.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    is_name || is_single_emoji(icon)
+}
+
+const ZWJ: char = '\u{200D}';
+const VARIATION_SELECTOR: char = '\u{FE0F}';
+const KEYCAP: char = '\u{20E3}';
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_emoji_modifier(c: char) -> bool {
+    c == VARIATION_SELECTOR
+        || ('\u{1F3FB}'..='\u{1F3FF}').contains(&c) // skin tones
+        || ('\u{E0020}'..='\u{E007F}').contains(&c) // tag sequences (subdivision flags)
+}
+
+fn is_emoji_base(c: char) -> bool {
+    matches!(c,
+        '\u{1F000}'..='\u{1F1E5}'
+        | '\u{1F200}'..='\u{1FAFF}'
+        | '\u{2300}'..='\u{23FF}'
+        | '\u{2600}'..='\u{27BF}'
+        | '\u{2B00}'..='\u{2BFF}'
+        | '\u{2190}'..='\u{21FF}'
+        | '\u{00A9}' | '\u{00AE}' | '\u{203C}' | '\u{2049}' | '\u{2122}' | '\u{2139}'
+        | '\u{3030}' | '\u{303D}' | '\u{3297}' | '\u{3299}')
+}
+
+/// One emoji grapheme: a flag, a keycap, or emoji joined by ZWJ, each with
+/// optional modifiers. Approximates the Unicode emoji sequence grammar
+/// closely enough to reject text, markup and multiple emoji.
+fn is_single_emoji(s: &str) -> bool {
+    let chars: Vec<char> = s.chars().collect();
+    match chars.as_slice() {
+        [] => false,
+        [a, b] if is_regional_indicator(*a) && is_regional_indicator(*b) => true,
+        [digit, rest @ ..] if digit.is_ascii_digit() || *digit == '#' || *digit == '*' => {
+            matches!(rest, [KEYCAP] | [VARIATION_SELECTOR, KEYCAP])
+        }
+        _ => s.split(ZWJ).all(|part| {
+            let mut part_chars = part.chars();
+            part_chars.next().is_some_and(is_emoji_base) && part_chars.all(is_emoji_modifier)
+        }),
+    }
+}
+
+/// Canonical form of a sync server URL: trimmed and without trailing
+/// slashes, so request paths can be appended to it. Only http(s) URLs with a
+/// host and no query or fragment are accepted.
+pub fn normalize_server_url(url: &str) -> Result<String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let invalid = |reason: &str| AppError::Validation(format!("Invalid server URL '{}': {}", url.trim(), reason));
+
+    let parsed = reqwest::Url::parse(trimmed).map_err(|e| invalid(&e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid("it must start with http:// or https://"));
+    }
+    if parsed.host_str().unwrap_or_default().is_empty() {
+        return Err(invalid("it has no host"));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid("it can't have a query or fragment"));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Parse an RFC3339 filter bound into the `YYYY-MM-DD HH:MM:SS` UTC form
+/// SQLite's `datetime()` produces, so it compares correctly against stored
+/// timestamps in either format once they go through `datetime()` as well.
+pub fn filter_timestamp(field: &str, value: &str) -> Result<String> {
+    let parsed = chrono::DateTime::parse_from_rfc3339(value)
+        .map_err(|_| AppError::Validation(format!("{} must be an RFC3339 timestamp, got '{}'", field, value)))?;
+    Ok(parsed.with_timezone(&chrono::Utc).format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn test_notebook_icon_names_and_emoji() {
+        for good in ["folder", "book-open", "icon_42", "📁", "❤️", "👍🏽", "👩‍💻", "🇦🇷", "1️⃣", "🏴󠁧󠁢󠁳󠁣󠁴󠁿"] {
+            assert!(is_valid_icon(good), "rejected {:?}", good);
+        }
+        let svg = format!("<svg>{}</svg>", "x".repeat(2000));
+        let too_long = "a".repeat(MAX_ICON_NAME_CHARS + 1);
+        for bad in ["", "two words", "📁📁", "a📁", "🇦", svg.as_str(), too_long.as_str()] {
+            assert!(matches!(notebook_icon(bad), Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_normalize_server_url() {
+        for (given, expected) in [
+            ("http://localhost:3000", "http://localhost:3000"),
+            ("  https://sync.example.com/// ", "https://sync.example.com"),
+            ("https://example.com/viny/", "https://example.com/viny"),
+        ] {
+            assert_eq!(normalize_server_url(given).unwrap(), expected);
+        }
+        for bad in ["", "localhost:3000", "sync.example.com", "ftp://example.com", "http://", "http://x.com/?a=1"] {
+            assert!(matches!(normalize_server_url(bad), Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_server_url_accepts_ipv6_paths_and_padding() {
+        for (given, expected) in [
+            ("http://[::1]:3000/", "http://[::1]:3000"),
+            ("https://example.com/viny//", "https://example.com/viny"),
+            ("\thttp://192.168.1.10:3000\n", "http://192.168.1.10:3000"),
+        ] {
+            assert_eq!(normalize_server_url(given).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_server_url_refuses_bad_ports_schemes_and_fragments() {
+        let bad_urls = ["   ", "/", "https://example.com:99999", "javascript:alert(1)", "http://exa mple.com", "http://x.com#top"];
+        for bad in bad_urls {
+            assert!(matches!(normalize_server_url(bad), Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_server_url_refusal_quotes_what_was_entered_without_padding() {
+        match normalize_server_url("  ftp://example.com ") {
+            Err(AppError::Validation(message)) => assert!(message.contains("'ftp://example.com'"), "{}", message),
+            other => panic!("accepted: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_colors_hex_or_palette() {
+        for good in ["#1a2B3c", "#000000", "blue", "rose"] {
+            assert!(color(good).is_ok(), "rejected {:?}", good);
+        }
+        for bad in ["", "blueish", "Blue", "#fff", "#12345g", "1a2b3c7", "#1a2b3c4d"] {
+            assert!(matches!(color(bad), Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+
+        assert_eq!(palette_color_for("work"), palette_color_for("work"));
+        let spread: std::collections::HashSet<_> =
+            ["work", "home", "ideas", "reading", "travel", "music"].iter().map(|n| palette_color_for(n)).collect();
+        assert!(spread.len() > 1);
+    }
+
+    #[test]
+    fn test_new_entity_id_generates_uuid() {
+        let db = Database::in_memory();
+        let id = new_entity_id(&db.conn(), "notes", None).unwrap();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+    }
+
+    #[test]
+    fn test_new_entity_id_uses_supplied_id() {
+        let db = Database::in_memory();
+        let requested = uuid::Uuid::new_v4().to_string();
+        let id = new_entity_id(&db.conn(), "notebooks", Some(requested.clone())).unwrap();
+        assert_eq!(id, requested);
+    }
+
+    #[test]
+    fn test_new_entity_id_rejects_malformed() {
+        let db = Database::in_memory();
+        for bad in ["", "not-a-uuid", "1234", "zzzzzzzz-zzzz-zzzz-zzzz-zzzzzzzzzzzz"] {
+            let result = new_entity_id(&db.conn(), "tags", Some(bad.to_string()));
+            assert!(matches!(result, Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_new_entity_id_detects_conflict() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        // Soft-deleted rows still own their id
+        conn.execute(
+            "INSERT INTO tags (id, name, deleted_at) VALUES (?, 'old', datetime('now'))",
+            params![&id],
+        )
+        .unwrap();
+
+        let result = new_entity_id(&conn, "tags", Some(id.clone()));
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        // The same id is still free in other tables
+        assert_eq!(new_entity_id(&conn, "notes", Some(id.clone())).unwrap(), id);
+    }
+
+    #[test]
+    fn test_filter_timestamp_normalizes_to_utc() {
+        assert_eq!(
+            filter_timestamp("created_after", "2024-03-01T10:30:00+02:00").unwrap(),
+            "2024-03-01 08:30:00"
+        );
+        assert_eq!(
+            filter_timestamp("created_after", "2024-03-01T08:30:00.123456Z").unwrap(),
+            "2024-03-01 08:30:00"
+        );
+    }
+
+    #[test]
+    fn test_filter_timestamp_rejects_invalid() {
+        for bad in ["", "yesterday", "2024-03-01", "2024-03-01 08:30:00", "2024-13-01T00:00:00Z"] {
+            let result = filter_timestamp("updated_before", bad);
+            assert!(matches!(result, Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_filter_timestamp_matches_both_stored_formats() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        conn.execute_batch(
+            "INSERT INTO notes (id, created_at) VALUES ('rfc', '2024-03-15T09:00:00.5+00:00');
+             INSERT INTO notes (id, created_at) VALUES ('sqlite', '2024-03-20 18:00:00');
+             INSERT INTO notes (id, created_at) VALUES ('april', '2024-04-01T00:00:00+00:00');",
+        )
+        .unwrap();
+
+        let after = filter_timestamp("created_after", "2024-03-01T00:00:00Z").unwrap();
+        let before = filter_timestamp("created_before", "2024-04-01T00:00:00Z").unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id FROM notes WHERE datetime(created_at) >= ? AND datetime(created_at) < ? ORDER BY id")
+            .unwrap();
+        let ids: Vec<String> = stmt
+            .query_map(params![after, before], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+
+        assert_eq!(ids, vec!["rfc", "sqlite"]);
+    }
+
+    #[test]
+    fn test_note_title_limit() {
+        assert!(note_title(&"é".repeat(MAX_TITLE_CHARS)).is_ok());
+        assert!(matches!(note_title(&"a".repeat(MAX_TITLE_CHARS + 1)), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_note_content_uses_configured_limit() {
+        let db = Database::in_memory();
+        let conn = db.conn();
+        assert!(note_content(&conn, &"a".repeat(1024)).is_ok());
+
+        settings::set(&conn, settings::MAX_CONTENT_BYTES, "100").unwrap();
+        assert!(note_content(&conn, &"a".repeat(100)).is_ok());
+        // The limit is in bytes, not characters
+        assert!(matches!(note_content(&conn, &"é".repeat(51)), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_tag_names_reject_quotes_and_commas() {
+        assert!(tag_names(&["work".to_string(), "to read".to_string()]).is_ok());
+        for bad in ["a\"b", "one,two"] {
+            assert!(matches!(tag_name(bad), Err(AppError::Validation(_))), "accepted {:?}", bad);
+        }
+    }
+}
@@ -831,6 +831,11 @@ fn test_tag_name_case_sensitivity() {
     // Attempting to insert same name with different case
     // This might succeed or fail depending on SQLite collation settings
     // For this test, we just verify the first tag was inserted
+    let _ = conn.execute(
+        "INSERT INTO tags (id, name, revision, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?)",
+        params![id2, "important", 1, ts, ts],
+    );
     let count: i32 = conn
         .query_row("SELECT COUNT(*) FROM tags WHERE name = 'Important'", [], |row| row.get(0))
         .unwrap();
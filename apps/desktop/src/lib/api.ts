@@ -21,6 +21,8 @@ import type {
   SyncPayload,
   SyncStats,
   SyncConflict,
+  ServerConnection,
+  ServerDevice,
   SearchOptions,
   SearchResult,
   ExportStats,
@@ -179,12 +181,19 @@ export async function syncWithServer(serverUrl: string): Promise<SyncResult> {
 }
 
 /**
- * Check if server is reachable
+ * Check if server is reachable, returning the URL in the canonical form to store
  */
-export async function checkServerConnection(serverUrl: string): Promise<boolean> {
+export async function checkServerConnection(serverUrl: string): Promise<ServerConnection> {
   return invoke('check_server_connection', { serverUrl });
 }
 
+/**
+ * Devices the server has seen sync, most recently seen first
+ */
+export async function listServerDevices(serverUrl: string): Promise<ServerDevice[]> {
+  return invoke('list_server_devices', { serverUrl });
+}
+
 // ============================================================================
 // Search API
 // ============================================================================
@@ -254,6 +263,8 @@ export type {
   SyncPayload,
   SyncStats,
   SyncConflict,
+  ServerConnection,
+  ServerDevice,
   SyncRequest,
   SyncResult,
   SearchOptions,
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Reminder } from "./Reminder";
+
+/**
+ * A reminder with the title of the note it belongs to, `None` when standalone
+ */
+export type AgendaReminder = { reminder: Reminder, note_title: string | null, };
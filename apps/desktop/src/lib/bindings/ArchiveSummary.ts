@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of `archive_notes` / `unarchive_notes`
+ */
+export type ArchiveSummary = { 
+/**
+ * Notes whose status changed
+ */
+archived: number, 
+/**
+ * Ids that are trashed or don't exist
+ */
+skipped: Array<string>, };
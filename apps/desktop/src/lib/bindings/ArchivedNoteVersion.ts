@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A note version that lost an automatic conflict resolution, kept in
+ * `conflict_archive` so `restore_conflict_version` can bring it back
+ */
+export type ArchivedNoteVersion = { id: string, note_id: string, title: string, content: string, tags: Array<string>, revision: bigint, 
+/**
+ * Which side lost: "local" or "remote"
+ */
+source: string, 
+/**
+ * How the sync resolved the conflict
+ */
+resolution: string, created_at: string, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CreateNoteInput } from "./CreateNoteInput";
+import type { CreateNotebookInput } from "./CreateNotebookInput";
+import type { CreateTagInput } from "./CreateTagInput";
+import type { UpdateNoteInput } from "./UpdateNoteInput";
+import type { UpdateNotebookInput } from "./UpdateNotebookInput";
+
+/**
+ * One write in a batch, tagged by `op`
+ */
+export type BatchOp = { "op": "create_note", input: CreateNoteInput, } | { "op": "update_note", id: string, input: UpdateNoteInput, } | { "op": "delete_note", id: string, hard: boolean | null, } | { "op": "create_notebook", input: CreateNotebookInput, } | { "op": "update_notebook", id: string, input: UpdateNotebookInput, } | { "op": "create_tag", input: CreateTagInput, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Event names, one per entity type
+ */
+export type ChangeEvent = "note:changed" | "notebook:changed" | "tag:changed" | "reminder:changed";
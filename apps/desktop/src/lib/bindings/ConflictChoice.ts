@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome picked by the user in `resolve_conflict`
+ */
+export type ConflictChoice = "local" | "remote" | "merged";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How a pulled note that conflicts with a local edit is resolved.
+ * Notebooks, tags and reminders always use LWW.
+ */
+export type ConflictStrategy = "lww" | "local_wins" | "remote_wins" | "keep_both";
@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type CreateNoteInput = { title: string | null, content: string | null, notebook_id: string | null, tags: Array<string> | null, };
+export type CreateNoteInput = { 
+/**
+ * Caller-supplied UUID (e.g. from an importer); minted when omitted
+ */
+id: string | null, title: string | null, content: string | null, 
+/**
+ * Omitted: the `default_notebook_id` setting applies. `null`: no notebook
+ */
+notebook_id?: string | null, tags: Array<string> | null, };
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type CreateNotebookInput = { name: string, color: string | null, icon: string | null, parent_id: string | null, };
+export type CreateNotebookInput = { 
+/**
+ * Caller-supplied UUID (e.g. from an importer); minted when omitted
+ */
+id: string | null, name: string, color: string | null, icon: string | null, parent_id: string | null, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Recurrence } from "./Recurrence";
+
+export type CreateReminderInput = { 
+/**
+ * Omit for a standalone reminder
+ */
+note_id: string | null, message: string | null, 
+/**
+ * RFC3339 timestamp, or a phrase like "tomorrow 9am"
+ */
+due_date: string, recurrence?: Recurrence, };
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type CreateTagInput = { name: string, color: string | null, };
+export type CreateTagInput = { 
+/**
+ * Caller-supplied UUID (e.g. from an importer); minted when omitted
+ */
+id: string | null, name: string, color: string | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SkippedTag } from "./SkippedTag";
+import type { Tag } from "./Tag";
+
+/**
+ * Outcome of `create_tags`
+ */
+export type CreateTagsResult = { created: Array<Tag>, skipped: Array<SkippedTag>, };
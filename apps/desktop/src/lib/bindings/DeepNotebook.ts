@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A notebook nested deeper than the configured limit
+ */
+export type DeepNotebook = { notebook_id: string, name: string, 
+/**
+ * Root notebooks are depth 1
+ */
+depth: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Tombstone left behind by a hard delete
+ */
+export type DeletedEntity = { entity_type: string, entity_id: string, revision: bigint, deleted_at: string, };
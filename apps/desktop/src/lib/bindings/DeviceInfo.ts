@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * This device as the sync server sees it
+ */
+export type DeviceInfo = { device_id: string, last_synced_at: string | null, last_pull_revision: bigint, last_push_revision: bigint, };
@@ -1,6 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Note } from "./Note";
 import type { Notebook } from "./Notebook";
+import type { Reminder } from "./Reminder";
 import type { Tag } from "./Tag";
 
-export type ExportData = { version: string, exported_at: string, notes: Array<Note>, notebooks: Array<Notebook>, tags: Array<Tag>, };
+export type ExportData = { version: string, exported_at: string, notes: Array<Note>, notebooks: Array<Notebook>, tags: Array<Tag>, 
+/**
+ * Missing from archives written before version 1.1
+ */
+reminders: Array<Reminder>, };
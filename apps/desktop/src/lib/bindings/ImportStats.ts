@@ -1,3 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OrphanReport } from "./OrphanReport";
 
-export type ImportStats = { notes_imported: number, notebooks_imported: number, tags_imported: number, notes_skipped: number, notebooks_skipped: number, tags_skipped: number, };
+export type ImportStats = { notes_imported: number, notebooks_imported: number, tags_imported: number, notes_skipped: number, notebooks_skipped: number, tags_skipped: number, reminders_imported: number, 
+/**
+ * Already present (without overwrite), or their note is in neither the
+ * archive nor this database
+ */
+reminders_skipped: number, 
+/**
+ * The archive was a notebook export rather than a full backup
+ */
+partial: boolean, 
+/**
+ * Orphaned rows cleaned up after the import
+ */
+orphans: OrphanReport, };
@@ -1,4 +1,21 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { NoteStatus } from "./NoteStatus";
+import type { TagMode } from "./TagMode";
 
-export type ListNotesFilter = { notebook_id: string | null, status: NoteStatus | null, tag: string | null, search: string | null, limit: bigint | null, offset: bigint | null, };
+export type ListNotesFilter = { notebook_id: string | null, status: NoteStatus | null, 
+/**
+ * Single tag; kept for older callers, combined with `tags`
+ */
+tag: string | null, tags: Array<string> | null, 
+/**
+ * How `tags` combine; defaults to `all`
+ */
+tag_mode: TagMode | null, search: string | null, limit: bigint | null, offset: bigint | null, 
+/**
+ * Return the stored plain-text excerpt in `content` instead of the full body
+ */
+summary: boolean | null, 
+/**
+ * RFC3339 bounds; `*_after` is inclusive, `*_before` exclusive
+ */
+created_after: string | null, created_before: string | null, updated_after: string | null, updated_before: string | null, };
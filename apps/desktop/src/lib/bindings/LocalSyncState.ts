@@ -1,3 +1,16 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncCursors } from "./SyncCursors";
 
-export type LocalSyncState = { last_pull_revision: bigint, last_push_revision: bigint, last_synced_at: string | null, pending_changes: number, };
+export type LocalSyncState = { 
+/**
+ * Lowest of `pull_cursors`
+ */
+last_pull_revision: bigint, 
+/**
+ * Lowest of `push_cursors`
+ */
+last_push_revision: bigint, last_synced_at: string | null, 
+/**
+ * Rows and tombstones flagged with `needs_push`
+ */
+pending_changes: number, pull_cursors: SyncCursors, push_cursors: SyncCursors, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type NoteTask = { id: string, note_id: string, note_title: string, 
+/**
+ * 1-based line number within the note content
+ */
+line_no: bigint, text: string, checked: boolean, created_at: string, updated_at: string, };
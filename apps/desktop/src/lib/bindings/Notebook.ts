@@ -1,3 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Notebook = { id: string, name: string, color: string | null, icon: string | null, parent_id: string | null, revision: bigint, created_at: string, updated_at: string, deleted_at: string | null, };
+export type Notebook = { id: string, name: string, color: string | null, icon: string | null, parent_id: string | null, revision: bigint, created_at: string, updated_at: string, deleted_at: string | null, 
+/**
+ * Set by `archive_notebook`; archived notebooks are hidden from `list_notebooks`
+ */
+is_archived: boolean, 
+/**
+ * Set by `set_notebook_sync_excluded`; the notebook and its notes never
+ * leave this device
+ */
+sync_excluded: boolean, };
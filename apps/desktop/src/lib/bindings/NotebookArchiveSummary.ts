@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of `archive_notebook` / `unarchive_notebook`
+ */
+export type NotebookArchiveSummary = { 
+/**
+ * Notebooks whose archived flag changed
+ */
+notebook_ids: Array<string>, 
+/**
+ * Notes whose status changed
+ */
+note_ids: Array<string>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Note counts for one sidebar entry; `notebook_id` is `None` for unfiled notes
+ */
+export type NotebookCount = { notebook_id: string | null, active_count: number, archived_count: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What `delete_notebook` does with the notebook's children and notes
+ */
+export type NotebookDeleteMode = "promote_children" | "delete_children" | "move_notes_to_parent";
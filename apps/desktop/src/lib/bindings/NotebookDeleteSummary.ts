@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of `delete_notebook`
+ */
+export type NotebookDeleteSummary = { 
+/**
+ * Ids of every notebook deleted, the requested one first
+ */
+notebooks_deleted: Array<string>, 
+/**
+ * Child notebooks reparented to the deleted notebook's parent
+ */
+children_promoted: Array<string>, 
+/**
+ * Notes moved out of deleted notebooks
+ */
+notes_moved: number, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TagUsage } from "./TagUsage";
+
+/**
+ * Dashboard figures for a notebook (and its subtree when recursive)
+ */
+export type NotebookStats = { notebook_id: string, active_count: number, archived_count: number, trashed_count: number, 
+/**
+ * Most recent `updated_at` among the notes
+ */
+last_updated_at: string | null, 
+/**
+ * Total size of note content as stored, in bytes
+ */
+total_content_bytes: bigint, 
+/**
+ * Up to 10 most used tags, most frequent first
+ */
+top_tags: Array<TagUsage>, };
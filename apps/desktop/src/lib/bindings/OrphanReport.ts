@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Rows fixed by `cleanup_orphans`, per category
+ */
+export type OrphanReport = { 
+/**
+ * FTS rows whose note no longer exists
+ */
+fts_rows: number, 
+/**
+ * Reminders pointing at a missing note
+ */
+reminders: number, 
+/**
+ * Notes whose notebook was hard-deleted (moved to no notebook)
+ */
+notebook_refs: number, 
+/**
+ * Notebook icons that failed validation and were cleared
+ */
+invalid_icons: number, };
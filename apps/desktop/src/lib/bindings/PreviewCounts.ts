@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How many rows of one type a pull would create, update or conflict on
+ */
+export type PreviewCounts = { created: number, updated: number, conflicted: number, };
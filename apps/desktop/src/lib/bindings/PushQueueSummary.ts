@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Local changes waiting to be pushed, e.g. while the server is unreachable
+ */
+export type PushQueueSummary = { notes: number, notebooks: number, tags: number, reminders: number, 
+/**
+ * Hard deletions
+ */
+deleted: number, 
+/**
+ * When the longest-waiting change was made (UTC), None when empty
+ */
+oldest_change_at: string | null, };
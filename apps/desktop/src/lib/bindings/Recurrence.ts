@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RecurrenceFrequency } from "./RecurrenceFrequency";
+
+/**
+ * Repeat rule for a reminder; the FREQ / INTERVAL / UNTIL subset of RRULE
+ */
+export type Recurrence = { frequency: RecurrenceFrequency, 
+/**
+ * Repeat every `interval` periods
+ */
+interval: number, 
+/**
+ * No occurrences after this RFC3339 timestamp
+ */
+until: string | null, 
+/**
+ * First occurrence, which later ones are counted from; taken from the
+ * reminder's due date when the rule is saved without one
+ */
+start: string | null, };
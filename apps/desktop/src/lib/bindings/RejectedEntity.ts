@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A pushed entity that failed the server's validation, e.g. content above
+ * its size limit
+ */
+export type RejectedEntity = { entity_type: string, id: string, field: string, message: string, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Recurrence } from "./Recurrence";
+
+export type Reminder = { id: string, 
+/**
+ * `None` for a standalone reminder
+ */
+note_id: string | null, message: string, due_date: string, completed: boolean, notified: boolean, revision: bigint, created_at: string, updated_at: string, deleted_at: string | null, recurrence: Recurrence | null, 
+/**
+ * Due date before the reminder was first snoozed; cleared when it's
+ * rescheduled or moves to its next occurrence
+ */
+snoozed_from: string | null, };
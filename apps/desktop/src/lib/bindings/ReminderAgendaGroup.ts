@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AgendaReminder } from "./AgendaReminder";
+
+/**
+ * Upcoming reminders of one notebook; `notebook_id` is `None` for notes
+ * outside any notebook and for standalone reminders
+ */
+export type ReminderAgendaGroup = { notebook_id: string | null, notebook_name: string | null, reminders: Array<AgendaReminder>, };
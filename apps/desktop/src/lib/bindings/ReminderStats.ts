@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Counts behind the reminders dashboard widget. Deleted reminders are
+ * never counted; "active" means not completed.
+ */
+export type ReminderStats = { overdue: bigint, 
+/**
+ * Active reminders due on the client's current calendar day
+ */
+due_today: bigint, 
+/**
+ * Active reminders due in the next 7 days
+ */
+due_this_week: bigint, 
+/**
+ * Active reminders not yet due
+ */
+upcoming: bigint, completed_last_30_days: bigint, total_active: bigint, 
+/**
+ * Share of completed-or-overdue reminders that were completed in the
+ * last 30 days; `None` when there are neither
+ */
+completion_rate: number | null, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of `check_server_connection`
+ */
+export type ServerConnection = { reachable: boolean, 
+/**
+ * The URL that was checked, in the canonical form to store
+ */
+server_url: string, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A device the server has seen pull or push
+ */
+export type ServerDevice = { device_id: string, user_id: string | null, 
+/**
+ * Everything up to this revision has been pulled by the device
+ */
+last_pull_revision: bigint, last_push_at: string | null, last_seen_at: string, first_seen_at: string, };
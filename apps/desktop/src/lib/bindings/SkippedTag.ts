@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A name `create_tags` didn't insert, and why
+ */
+export type SkippedTag = { name: string, reason: string, };
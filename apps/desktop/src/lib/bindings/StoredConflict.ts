@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A conflict kept in `sync_conflicts` until the user resolves it. The
+ * snapshots are only set for notes.
+ */
+export type StoredConflict = { id: string, entity_type: string, entity_id: string, local_revision: bigint, remote_revision: bigint, local_title: string | null, local_content: string | null, remote_title: string | null, remote_content: string | null, 
+/**
+ * How the sync resolved it
+ */
+resolution: string, created_at: string, };
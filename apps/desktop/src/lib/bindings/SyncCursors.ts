@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A revision per entity type. Deletions follow the cursor of the type
+ * they delete.
+ */
+export type SyncCursors = { notes: bigint, notebooks: bigint, tags: bigint, reminders: bigint, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Payload of `sync:failed`
+ */
+export type SyncFailed = { error: string, 
+/**
+ * Background syncs failed in a row, including this one; 0 for a live
+ * pull or a rate-limited sync, which don't back off
+ */
+failures: number, 
+/**
+ * How long the server asked to wait, when it rate-limited the sync
+ */
+retry_after_secs: bigint | null, };
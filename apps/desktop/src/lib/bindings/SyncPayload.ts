@@ -1,6 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeletedEntity } from "./DeletedEntity";
 import type { Note } from "./Note";
 import type { Notebook } from "./Notebook";
+import type { Reminder } from "./Reminder";
 import type { Tag } from "./Tag";
 
-export type SyncPayload = { notes: Array<Note>, notebooks: Array<Notebook>, tags: Array<Tag>, since_revision: bigint, };
+export type SyncPayload = { notes: Array<Note>, notebooks: Array<Notebook>, tags: Array<Tag>, reminders: Array<Reminder>, 
+/**
+ * Tombstones of hard-deleted entities
+ */
+deleted: Array<DeletedEntity>, since_revision: bigint, };
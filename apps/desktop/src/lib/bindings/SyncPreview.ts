@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PreviewCounts } from "./PreviewCounts";
+
+/**
+ * What a sync would do, from `preview_sync`
+ */
+export type SyncPreview = { notes: PreviewCounts, notebooks: PreviewCounts, tags: PreviewCounts, reminders: PreviewCounts, 
+/**
+ * Hard deletions the pull would apply
+ */
+deleted: number, 
+/**
+ * Rows and tombstones the push would send
+ */
+to_push: number, server_revision: bigint, };
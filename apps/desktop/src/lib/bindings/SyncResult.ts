@@ -1,5 +1,19 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RejectedEntity } from "./RejectedEntity";
 import type { SyncConflict } from "./SyncConflict";
 import type { SyncStats } from "./SyncStats";
 
-export type SyncResult = { pulled: SyncStats, pushed: SyncStats, conflicts: Array<SyncConflict>, last_synced_at: string, };
+export type SyncResult = { pulled: SyncStats, pushed: SyncStats, conflicts: Array<SyncConflict>, last_synced_at: string, 
+/**
+ * Set when the pull was applied but the push failed; the push cursor
+ * didn't move, so retrying pushes the same changes
+ */
+push_error: string | null, 
+/**
+ * Entities the server refused to store, when that's why the push failed
+ */
+rejected: Array<RejectedEntity>, 
+/**
+ * Seconds the server asked to wait, when it rate-limited the push
+ */
+retry_after_secs: bigint | null, };
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type SyncStats = { notes: number, notebooks: number, tags: number, };
+export type SyncStats = { notes: number, notebooks: number, tags: number, reminders: number, 
+/**
+ * Hard deletions applied
+ */
+deleted: number, };
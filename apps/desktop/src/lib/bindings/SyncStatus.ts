@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncStatusKind } from "./SyncStatusKind";
+
+/**
+ * Outcome of `get_sync_status`
+ */
+export type SyncStatus = { status: SyncStatusKind, pending_changes: number, last_synced_at: string | null, 
+/**
+ * Newest server revision this device has pulled or pushed up to
+ */
+local_revision: bigint, 
+/**
+ * The server's global revision, when it was asked and answered
+ */
+server_revision: bigint | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where this device stands against the server, for the status bar
+ */
+export type SyncStatusKind = "up_to_date" | "local_changes" | "server_newer" | "diverged" | "offline";
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TagWithCount } from "./TagWithCount";
+
+/**
+ * Result of `suggest_tags`
+ */
+export type TagSuggestions = { tags: Array<TagWithCount>, 
+/**
+ * A tag named exactly like the prefix (ignoring case) exists
+ */
+exact_match: boolean, };
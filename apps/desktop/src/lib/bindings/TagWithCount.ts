@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Tag } from "./Tag";
+
+/**
+ * A tag with the number of non-trashed notes using it
+ */
+export type TagWithCount = { tag: Tag, note_count: bigint, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { NoteStatus } from "./NoteStatus";
+
+/**
+ * Trashed note with the context the trash view shows
+ */
+export type TrashedNoteView = { 
+/**
+ * Name of the notebook the note was in, if it still exists
+ */
+notebook_name: string | null, 
+/**
+ * Whole days since the note was trashed
+ */
+days_in_trash: number, id: string, title: string, content: string, notebook_id: string | null, tags: Array<string>, status: NoteStatus, is_pinned: boolean, revision: bigint, created_at: string, updated_at: string, deleted_at: string | null, 
+/**
+ * Position among pinned notes (set by `reorder_pinned_notes`)
+ */
+pinned_order: bigint | null, };
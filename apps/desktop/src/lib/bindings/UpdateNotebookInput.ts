@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type UpdateNotebookInput = { name: string | null, color: string | null, icon: string | null, parent_id: string | null, };
+export type UpdateNotebookInput = { name: string | null, 
+/**
+ * Omitted keeps the current value; `null` clears it
+ */
+color?: string | null, icon?: string | null, 
+/**
+ * Omitted keeps the current parent; `null` moves the notebook to the root
+ */
+parent_id?: string | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Recurrence } from "./Recurrence";
+
+export type UpdateReminderInput = { message: string | null, due_date: string | null, completed: boolean | null, notified: boolean | null, 
+/**
+ * Omitted keeps the current rule; `null` makes the reminder one-shot
+ */
+recurrence?: Recurrence | null, };
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type UpdateTagInput = { name: string | null, color: string | null, };
+export type UpdateTagInput = { name: string | null, 
+/**
+ * Omitted keeps the current value; `null` clears it
+ */
+color?: string | null, };
@@ -6,30 +6,83 @@ export type { NoteStatus } from './NoteStatus';
 export type { CreateNoteInput } from './CreateNoteInput';
 export type { UpdateNoteInput } from './UpdateNoteInput';
 export type { ListNotesFilter } from './ListNotesFilter';
+export type { TagMode } from './TagMode';
+export type { ArchiveSummary } from './ArchiveSummary';
+export type { TrashedNoteView } from './TrashedNoteView';
 
 export type { Notebook } from './Notebook';
 export type { CreateNotebookInput } from './CreateNotebookInput';
 export type { UpdateNotebookInput } from './UpdateNotebookInput';
+export type { NotebookCount } from './NotebookCount';
+export type { NotebookDeleteMode } from './NotebookDeleteMode';
+export type { NotebookDeleteSummary } from './NotebookDeleteSummary';
+export type { NotebookArchiveSummary } from './NotebookArchiveSummary';
+export type { NotebookStats } from './NotebookStats';
+export type { TagUsage } from './TagUsage';
 
 export type { Tag } from './Tag';
+export type { TagWithCount } from './TagWithCount';
+export type { TagSuggestions } from './TagSuggestions';
 export type { CreateTagInput } from './CreateTagInput';
+export type { SkippedTag } from './SkippedTag';
+export type { CreateTagsResult } from './CreateTagsResult';
 export type { UpdateTagInput } from './UpdateTagInput';
 
+// Reminder types
+export type { Reminder } from './Reminder';
+export type { CreateReminderInput } from './CreateReminderInput';
+export type { UpdateReminderInput } from './UpdateReminderInput';
+export type { Recurrence } from './Recurrence';
+export type { RecurrenceFrequency } from './RecurrenceFrequency';
+export type { AgendaReminder } from './AgendaReminder';
+export type { ReminderAgendaGroup } from './ReminderAgendaGroup';
+export type { ReminderStats } from './ReminderStats';
+
 // Sync types
 export type { SyncState } from './SyncState';
 export type { SyncRequest } from './SyncRequest';
 export type { SyncResult } from './SyncResult';
 export type { SyncStats } from './SyncStats';
+export type { SyncPreview } from './SyncPreview';
+export type { PreviewCounts } from './PreviewCounts';
 export type { SyncConflict } from './SyncConflict';
+export type { RejectedEntity } from './RejectedEntity';
+export type { ConflictStrategy } from './ConflictStrategy';
+export type { StoredConflict } from './StoredConflict';
+export type { ConflictChoice } from './ConflictChoice';
+export type { ArchivedNoteVersion } from './ArchivedNoteVersion';
 export type { SyncPayload } from './SyncPayload';
 export type { LocalSyncState } from './LocalSyncState';
+export type { PushQueueSummary } from './PushQueueSummary';
+export type { SyncStatus } from './SyncStatus';
+export type { SyncStatusKind } from './SyncStatusKind';
+export type { SyncCursors } from './SyncCursors';
+export type { DeviceInfo } from './DeviceInfo';
+export type { ServerDevice } from './ServerDevice';
+export type { ServerConnection } from './ServerConnection';
+export type { DeletedEntity } from './DeletedEntity';
 
 // Search types
 export type { SearchResult } from './SearchResult';
 export type { SearchOptions } from './SearchOptions';
 
+// Task types
+export type { NoteTask } from './NoteTask';
+
 // Export/Import types
 export type { ExportData } from './ExportData';
 export type { ExportStats } from './ExportStats';
 export type { ImportOptions } from './ImportOptions';
 export type { ImportStats } from './ImportStats';
+
+// Maintenance types
+export type { OrphanReport } from './OrphanReport';
+export type { DeepNotebook } from './DeepNotebook';
+
+// Batch types
+export type { BatchOp } from './BatchOp';
+
+// Event types
+export type { ChangeEvent } from './ChangeEvent';
+export type { ChangeKind } from './ChangeKind';
+export type { EntityChanged } from './EntityChanged';
@@ -0,0 +1,73 @@
+//! Integrity checksums for sync bodies, matching the desktop client's
+//!
+//! SHA-256 over the body minus its `checksum` field, with object keys sorted
+//! at every level, so both sides hash the same bytes whatever order their
+//! structs declare fields in.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, Result};
+
+/// Hex SHA-256 of `body`, ignoring its top-level `checksum` field
+pub fn of(body: &Value) -> String {
+    let mut canonical = String::new();
+    write_canonical(body, true, &mut canonical);
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+fn write_canonical(value: &Value, top: bool, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().filter(|key| !(top && *key == "checksum")).collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(&map[key], false, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, false, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Serialize a response with its checksum added
+pub fn sign(body: &impl Serialize) -> Result<Value> {
+    let mut value = serde_json::to_value(body).map_err(|e| AppError::Internal(e.to_string()))?;
+    let checksum = of(&value);
+    if let Value::Object(map) = &mut value {
+        map.insert("checksum".to_string(), Value::from(checksum));
+    }
+    Ok(value)
+}
+
+/// Decode a request after checking its checksum; clients that predate
+/// checksums don't send one
+pub fn verified<T: DeserializeOwned>(body: Value) -> Result<T> {
+    if let Some(expected) = body.get("checksum").and_then(Value::as_str) {
+        let actual = of(&body);
+        if actual != expected {
+            return Err(AppError::BadRequest(format!(
+                "checksum mismatch (expected {}, got {})",
+                expected, actual
+            )));
+        }
+    }
+    serde_json::from_value(body).map_err(|e| AppError::BadRequest(e.to_string()))
+}
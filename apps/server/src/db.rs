@@ -1,18 +1,213 @@
-use rusqlite::{params, Connection};
-use std::sync::Mutex;
+use rusqlite::{backup::Backup, params, Connection, OpenFlags, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
 
-use crate::error::Result;
-use crate::models::{Note, Notebook, Tag};
+use crate::error::{AppError, Result};
+use crate::metrics;
+use crate::models::{
+    AcceptedCounts, Change, Conflict, DeletedEntity, Device, EntityResult, IntegrityReport, ListQuery, Note, Notebook, PushRequest,
+    PushResponse, Reminder, SyncCursors, TableCounts, Tag, VacuumReport,
+};
 
 pub struct Database {
     conn: Mutex<Connection>,
+    /// Read-only connection for pulls and listings. With WAL they read the
+    /// last committed state instead of waiting for a push to finish.
+    reader: Mutex<Connection>,
+    /// Held by a vacuum or integrity check, so only one runs at a time
+    maintenance: Mutex<()>,
+}
+
+/// How long a statement waits for a lock held by another connection, e.g.
+/// the sqlite3 CLI, before failing with SQLITE_BUSY
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Columns added after the first release; `CREATE TABLE IF NOT EXISTS` leaves
+/// older databases without them
+fn migrate(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "notes", "is_pinned", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "notes", "pinned_order", "INTEGER")?;
+    add_column_if_missing(conn, "notebooks", "icon", "TEXT")?;
+    add_column_if_missing(conn, "notes", "encrypted", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "notebooks", "is_archived", "INTEGER NOT NULL DEFAULT 0")?;
+    for table in ["notes", "notebooks", "tags", "reminders"] {
+        add_column_if_missing(conn, table, "deleted_at", "TEXT")?;
+    }
+    // Older databases defaulted to an empty string, which isn't a JSON array.
+    // Their status column has no CHECK; pushes are validated instead.
+    conn.execute("UPDATE notes SET tags = '[]' WHERE tags = ''", [])?;
+    Ok(())
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?", table))?
+        .exists(params![column])?;
+
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+
+    Ok(())
+}
+
+const NOTE_COLUMNS: &str = "id, title, content, notebook_id, tags, status, is_pinned, pinned_order,
+    created_at, updated_at, revision, is_deleted, encrypted, deleted_at";
+
+fn row_to_note(row: &rusqlite::Row) -> rusqlite::Result<Note> {
+    Ok(Note {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        content: row.get(2)?,
+        notebook_id: row.get(3)?,
+        tags: row.get(4)?,
+        status: row.get(5)?,
+        is_pinned: row.get(6)?,
+        pinned_order: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        revision: row.get(10)?,
+        is_deleted: row.get(11)?,
+        encrypted: row.get(12)?,
+        deleted_at: row.get(13)?,
+    })
+}
+
+const NOTEBOOK_COLUMNS: &str = "id, name, color, icon, parent_id, created_at, updated_at, revision, is_deleted,
+    is_archived, deleted_at";
+
+fn row_to_notebook(row: &rusqlite::Row) -> rusqlite::Result<Notebook> {
+    Ok(Notebook {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        icon: row.get(3)?,
+        parent_id: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        revision: row.get(7)?,
+        is_deleted: row.get(8)?,
+        is_archived: row.get(9)?,
+        deleted_at: row.get(10)?,
+    })
+}
+
+const TAG_COLUMNS: &str = "id, name, color, created_at, updated_at, revision, is_deleted, deleted_at";
+
+fn row_to_tag(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        revision: row.get(5)?,
+        is_deleted: row.get(6)?,
+        deleted_at: row.get(7)?,
+    })
+}
+
+const REMINDER_COLUMNS: &str = "id, note_id, message, due_date, completed, notified, recurrence, snoozed_from,
+    created_at, updated_at, revision, is_deleted, deleted_at";
+
+fn row_to_reminder(row: &rusqlite::Row) -> rusqlite::Result<Reminder> {
+    Ok(Reminder {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        message: row.get(2)?,
+        due_date: row.get(3)?,
+        completed: row.get(4)?,
+        notified: row.get(5)?,
+        recurrence: row.get(6)?,
+        snoozed_from: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        revision: row.get(10)?,
+        is_deleted: row.get(11)?,
+        deleted_at: row.get(12)?,
+    })
+}
+
+/// The stored entity as JSON, for telling a device what the server kept
+fn entity_json(conn: &Connection, entity_type: &str, id: &str) -> Result<Option<serde_json::Value>> {
+    fn row<T: serde::Serialize>(
+        conn: &Connection,
+        table: &str,
+        columns: &str,
+        id: &str,
+        map: fn(&rusqlite::Row) -> rusqlite::Result<T>,
+    ) -> Result<Option<serde_json::Value>> {
+        let entity = conn
+            .query_row(&format!("SELECT {} FROM {} WHERE id = ?", columns, table), [id], map)
+            .optional()?;
+        entity
+            .map(|entity| serde_json::to_value(entity).map_err(|e| AppError::Internal(e.to_string())))
+            .transpose()
+    }
+    match entity_type {
+        "note" => row(conn, "notes", NOTE_COLUMNS, id, row_to_note),
+        "notebook" => row(conn, "notebooks", NOTEBOOK_COLUMNS, id, row_to_notebook),
+        "tag" => row(conn, "tags", TAG_COLUMNS, id, row_to_tag),
+        "reminder" => row(conn, "reminders", REMINDER_COLUMNS, id, row_to_reminder),
+        _ => Ok(None),
+    }
+}
+
+/// Conditions on entity tables and on `deleted_entities` for what was
+/// deleted at least `older_than_days` ago and, once devices are known, what
+/// every device has pulled (at or below `synced_through`). Rows pushed by
+/// older clients have no `deleted_at`; their `updated_at` is when they were
+/// deleted.
+fn purgeable(older_than_days: i64, synced_through: Option<i64>) -> (String, String) {
+    let aged = |column: &str| {
+        let mut condition = format!("julianday('now') - julianday({}) >= {}", column, older_than_days);
+        if let Some(revision) = synced_through {
+            condition.push_str(&format!(" AND revision <= {}", revision));
+        }
+        condition
+    };
+    (format!("is_deleted = 1 AND {}", aged("COALESCE(deleted_at, updated_at)")), aged("deleted_at"))
+}
+
+fn global_revision(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("SELECT global_revision FROM sync_state WHERE id = 1", [], |row| row.get(0))?)
+}
+
+/// Bytes the database and its WAL take up on disk; 0 for an in-memory one
+fn size_on_disk(conn: &Connection) -> u64 {
+    let Some(path) = conn.path().filter(|path| !path.is_empty()) else {
+        return 0;
+    };
+    [path.to_string(), format!("{}-wal", path)]
+        .iter()
+        .filter_map(|file| std::fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Revision every known device has pulled up to; None before any device pulled
+fn synced_through(conn: &Connection) -> Result<Option<i64>> {
+    Ok(conn.query_row("SELECT MIN(last_pull_revision) FROM devices", [], |row| row.get(0))?)
 }
 
 impl Database {
     pub fn new(path: &str) -> Result<Self> {
         let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA foreign_keys = ON;")?;
+
+        // Opened after the writer, so the file exists and is in WAL mode
+        let reader = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        reader.busy_timeout(BUSY_TIMEOUT)?;
+
         let db = Self {
             conn: Mutex::new(conn),
+            reader: Mutex::new(reader),
+            maintenance: Mutex::new(()),
         };
         db.init_schema()?;
         Ok(db)
@@ -27,23 +222,30 @@ impl Database {
                 title TEXT NOT NULL DEFAULT '',
                 content TEXT NOT NULL DEFAULT '',
                 notebook_id TEXT,
-                tags TEXT NOT NULL DEFAULT '',
-                status TEXT NOT NULL DEFAULT 'active',
+                tags TEXT NOT NULL DEFAULT '[]',
+                status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'trashed')),
+                is_pinned INTEGER NOT NULL DEFAULT 0,
+                pinned_order INTEGER,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
                 revision INTEGER NOT NULL DEFAULT 1,
-                is_deleted INTEGER NOT NULL DEFAULT 0
+                is_deleted INTEGER NOT NULL DEFAULT 0,
+                encrypted INTEGER NOT NULL DEFAULT 0,
+                deleted_at TEXT
             );
 
             CREATE TABLE IF NOT EXISTS notebooks (
                 id TEXT PRIMARY KEY,
                 name TEXT NOT NULL,
                 color TEXT,
+                icon TEXT,
                 parent_id TEXT,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
                 revision INTEGER NOT NULL DEFAULT 1,
-                is_deleted INTEGER NOT NULL DEFAULT 0
+                is_deleted INTEGER NOT NULL DEFAULT 0,
+                is_archived INTEGER NOT NULL DEFAULT 0,
+                deleted_at TEXT
             );
 
             CREATE TABLE IF NOT EXISTS tags (
@@ -53,7 +255,24 @@ impl Database {
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
                 revision INTEGER NOT NULL DEFAULT 1,
-                is_deleted INTEGER NOT NULL DEFAULT 0
+                is_deleted INTEGER NOT NULL DEFAULT 0,
+                deleted_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS reminders (
+                id TEXT PRIMARY KEY,
+                note_id TEXT,
+                message TEXT NOT NULL DEFAULT '',
+                due_date TEXT NOT NULL,
+                completed INTEGER NOT NULL DEFAULT 0,
+                notified INTEGER NOT NULL DEFAULT 0,
+                recurrence TEXT,
+                snoozed_from TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 1,
+                is_deleted INTEGER NOT NULL DEFAULT 0,
+                deleted_at TEXT
             );
 
             CREATE TABLE IF NOT EXISTS sync_state (
@@ -63,72 +282,86 @@ impl Database {
 
             INSERT OR IGNORE INTO sync_state (id, global_revision) VALUES (1, 0);
 
+            CREATE TABLE IF NOT EXISTS deleted_entities (
+                entity_type TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                deleted_at TEXT NOT NULL,
+                revision INTEGER NOT NULL,
+                PRIMARY KEY (entity_type, entity_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS devices (
+                device_id TEXT PRIMARY KEY,
+                user_id TEXT,
+                last_pull_revision INTEGER NOT NULL DEFAULT 0,
+                last_push_at TEXT,
+                last_seen_at TEXT NOT NULL,
+                first_seen_at TEXT NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_notes_revision ON notes(revision);
             CREATE INDEX IF NOT EXISTS idx_notebooks_revision ON notebooks(revision);
             CREATE INDEX IF NOT EXISTS idx_tags_revision ON tags(revision);
+            CREATE INDEX IF NOT EXISTS idx_reminders_revision ON reminders(revision);
+            CREATE INDEX IF NOT EXISTS idx_deleted_entities_revision ON deleted_entities(revision);
             "#,
         )?;
+        migrate(&conn)?;
         Ok(())
     }
 
     pub fn get_global_revision(&self) -> Result<i64> {
-        let conn = self.conn.lock().unwrap();
-        let rev: i64 = conn.query_row(
-            "SELECT global_revision FROM sync_state WHERE id = 1",
-            [],
-            |row| row.get(0),
-        )?;
-        Ok(rev)
+        global_revision(&*self.try_read()?)
     }
 
-    fn increment_global_revision(&self, conn: &Connection) -> Result<i64> {
+    fn increment_global_revision(conn: &Connection) -> Result<i64> {
         conn.execute(
             "UPDATE sync_state SET global_revision = global_revision + 1 WHERE id = 1",
             [],
         )?;
-        let rev: i64 = conn.query_row(
-            "SELECT global_revision FROM sync_state WHERE id = 1",
-            [],
-            |row| row.get(0),
-        )?;
-        Ok(rev)
+        global_revision(conn)
     }
 
     // Notes
-    pub fn get_notes_since(&self, revision: i64) -> Result<Vec<Note>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, title, content, notebook_id, tags, status, created_at, updated_at, revision, is_deleted
-             FROM notes WHERE revision > ?",
-        )?;
+    /// Rows past `revision`, oldest first; `limit` caps how many (all when None)
+    pub fn get_notes_since(&self, revision: i64, limit: Option<i64>) -> Result<Vec<Note>> {
+        let _timer = metrics::query("notes_since");
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM notes WHERE revision > ? ORDER BY revision LIMIT ?",
+            NOTE_COLUMNS
+        ))?;
 
         let notes = stmt
-            .query_map([revision], |row| {
-                Ok(Note {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    content: row.get(2)?,
-                    notebook_id: row.get(3)?,
-                    tags: row.get(4)?,
-                    status: row.get(5)?,
-                    created_at: row.get(6)?,
-                    updated_at: row.get(7)?,
-                    revision: row.get(8)?,
-                    is_deleted: row.get(9)?,
-                })
-            })?
+            .query_map(params![revision, limit.unwrap_or(-1)], row_to_note)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(notes)
     }
 
-    pub fn get_all_notes(&self) -> Result<Vec<Note>> {
-        self.get_notes_since(0)
+    /// A page of notes for the list endpoint, with the total that match
+    pub fn list_notes(&self, query: &ListQuery) -> Result<(Vec<Note>, usize)> {
+        self.list("notes", NOTE_COLUMNS, query, row_to_note)
+    }
+
+    /// The note with `id`, deleted or not
+    pub fn get_note(&self, id: &str) -> Result<Option<Note>> {
+        let conn = self.reader.lock().unwrap();
+        let note = conn
+            .query_row(&format!("SELECT {} FROM notes WHERE id = ?", NOTE_COLUMNS), [id], row_to_note)
+            .optional()?;
+        Ok(note)
     }
 
     pub fn upsert_note(&self, note: &Note) -> Result<(bool, i64)> {
-        let conn = self.conn.lock().unwrap();
+        Self::write_note(&self.conn.lock().unwrap(), note)
+    }
 
+    /// Store `note` unless the stored copy is newer. Returns whether the
+    /// stored copy was kept instead (a conflict), and the note's revision
+    /// afterwards. A write at the stored revision comes from a device that
+    /// has seen that copy, so it's taken without a conflict.
+    fn write_note(conn: &Connection, note: &Note) -> Result<(bool, i64)> {
         // Check for conflict
         let existing: Option<i64> = conn
             .query_row(
@@ -138,77 +371,75 @@ impl Database {
             )
             .ok();
 
-        let has_conflict = existing.map(|r| r >= note.revision).unwrap_or(false);
-
         // LWW: accept if incoming revision is higher or equal
-        if existing.is_none() || note.revision >= existing.unwrap() {
-            let new_rev = self.increment_global_revision(&conn)?;
+        if let Some(current) = existing {
+            if note.revision < current {
+                return Ok((true, current));
+            }
+        }
 
-            conn.execute(
-                r#"INSERT INTO notes (id, title, content, notebook_id, tags, status, created_at, updated_at, revision, is_deleted)
-                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
-                   ON CONFLICT(id) DO UPDATE SET
-                       title = excluded.title,
-                       content = excluded.content,
-                       notebook_id = excluded.notebook_id,
-                       tags = excluded.tags,
-                       status = excluded.status,
-                       updated_at = excluded.updated_at,
-                       revision = ?9,
-                       is_deleted = excluded.is_deleted"#,
-                params![
-                    note.id,
-                    note.title,
-                    note.content,
-                    note.notebook_id,
-                    note.tags,
-                    note.status,
-                    note.created_at,
-                    note.updated_at,
-                    new_rev,
-                    note.is_deleted
-                ],
-            )?;
+        let new_rev = Self::increment_global_revision(conn)?;
 
-            Ok((has_conflict, new_rev))
-        } else {
-            Ok((true, existing.unwrap()))
-        }
+        conn.execute(
+            r#"INSERT INTO notes (id, title, content, notebook_id, tags, status, is_pinned, pinned_order,
+                                 created_at, updated_at, revision, is_deleted, encrypted, deleted_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+               ON CONFLICT(id) DO UPDATE SET
+                   title = excluded.title,
+                   content = excluded.content,
+                   notebook_id = excluded.notebook_id,
+                   tags = excluded.tags,
+                   status = excluded.status,
+                   is_pinned = excluded.is_pinned,
+                   pinned_order = excluded.pinned_order,
+                   updated_at = excluded.updated_at,
+                   revision = ?11,
+                   is_deleted = excluded.is_deleted,
+                   encrypted = excluded.encrypted,
+                   deleted_at = excluded.deleted_at"#,
+            params![
+                note.id,
+                note.title,
+                note.content,
+                note.notebook_id,
+                note.tags,
+                note.status,
+                note.is_pinned,
+                note.pinned_order,
+                note.created_at,
+                note.updated_at,
+                new_rev,
+                note.is_deleted,
+                note.encrypted,
+                note.deleted_at
+            ],
+        )?;
+
+        Ok((false, new_rev))
     }
 
     // Notebooks
-    pub fn get_notebooks_since(&self, revision: i64) -> Result<Vec<Notebook>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, color, parent_id, created_at, updated_at, revision, is_deleted
-             FROM notebooks WHERE revision > ?",
-        )?;
+    pub fn get_notebooks_since(&self, revision: i64, limit: Option<i64>) -> Result<Vec<Notebook>> {
+        let _timer = metrics::query("notebooks_since");
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM notebooks WHERE revision > ? ORDER BY revision LIMIT ?",
+            NOTEBOOK_COLUMNS
+        ))?;
 
         let notebooks = stmt
-            .query_map([revision], |row| {
-                Ok(Notebook {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    color: row.get(2)?,
-                    parent_id: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    revision: row.get(6)?,
-                    is_deleted: row.get(7)?,
-                })
-            })?
+            .query_map(params![revision, limit.unwrap_or(-1)], row_to_notebook)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(notebooks)
     }
 
-    pub fn get_all_notebooks(&self) -> Result<Vec<Notebook>> {
-        self.get_notebooks_since(0)
+    /// A page of notebooks for the list endpoint, with the total that match
+    pub fn list_notebooks(&self, query: &ListQuery) -> Result<(Vec<Notebook>, usize)> {
+        self.list("notebooks", NOTEBOOK_COLUMNS, query, row_to_notebook)
     }
 
-    pub fn upsert_notebook(&self, notebook: &Notebook) -> Result<(bool, i64)> {
-        let conn = self.conn.lock().unwrap();
-
+    fn write_notebook(conn: &Connection, notebook: &Notebook) -> Result<(bool, i64)> {
         let existing: Option<i64> = conn
             .query_row(
                 "SELECT revision FROM notebooks WHERE id = ?",
@@ -217,71 +448,68 @@ impl Database {
             )
             .ok();
 
-        let has_conflict = existing.map(|r| r >= notebook.revision).unwrap_or(false);
+        if let Some(current) = existing {
+            if notebook.revision < current {
+                return Ok((true, current));
+            }
+        }
 
-        if existing.is_none() || notebook.revision >= existing.unwrap() {
-            let new_rev = self.increment_global_revision(&conn)?;
+        let new_rev = Self::increment_global_revision(conn)?;
 
-            conn.execute(
-                r#"INSERT INTO notebooks (id, name, color, parent_id, created_at, updated_at, revision, is_deleted)
-                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-                   ON CONFLICT(id) DO UPDATE SET
-                       name = excluded.name,
-                       color = excluded.color,
-                       parent_id = excluded.parent_id,
-                       updated_at = excluded.updated_at,
-                       revision = ?7,
-                       is_deleted = excluded.is_deleted"#,
-                params![
-                    notebook.id,
-                    notebook.name,
-                    notebook.color,
-                    notebook.parent_id,
-                    notebook.created_at,
-                    notebook.updated_at,
-                    new_rev,
-                    notebook.is_deleted
-                ],
-            )?;
+        conn.execute(
+            r#"INSERT INTO notebooks (id, name, color, icon, parent_id, created_at, updated_at, revision, is_deleted,
+                                     is_archived, deleted_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+               ON CONFLICT(id) DO UPDATE SET
+                   name = excluded.name,
+                   color = excluded.color,
+                   icon = excluded.icon,
+                   parent_id = excluded.parent_id,
+                   updated_at = excluded.updated_at,
+                   revision = ?8,
+                   is_deleted = excluded.is_deleted,
+                   is_archived = excluded.is_archived,
+                   deleted_at = excluded.deleted_at"#,
+            params![
+                notebook.id,
+                notebook.name,
+                notebook.color,
+                notebook.icon,
+                notebook.parent_id,
+                notebook.created_at,
+                notebook.updated_at,
+                new_rev,
+                notebook.is_deleted,
+                notebook.is_archived,
+                notebook.deleted_at
+            ],
+        )?;
 
-            Ok((has_conflict, new_rev))
-        } else {
-            Ok((true, existing.unwrap()))
-        }
+        Ok((false, new_rev))
     }
 
     // Tags
-    pub fn get_tags_since(&self, revision: i64) -> Result<Vec<Tag>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, color, created_at, updated_at, revision, is_deleted
-             FROM tags WHERE revision > ?",
-        )?;
+    pub fn get_tags_since(&self, revision: i64, limit: Option<i64>) -> Result<Vec<Tag>> {
+        let _timer = metrics::query("tags_since");
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM tags WHERE revision > ? ORDER BY revision LIMIT ?",
+            TAG_COLUMNS
+        ))?;
 
         let tags = stmt
-            .query_map([revision], |row| {
-                Ok(Tag {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    color: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                    revision: row.get(5)?,
-                    is_deleted: row.get(6)?,
-                })
-            })?
+            .query_map(params![revision, limit.unwrap_or(-1)], row_to_tag)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(tags)
     }
 
-    pub fn get_all_tags(&self) -> Result<Vec<Tag>> {
-        self.get_tags_since(0)
+    /// A page of tags for the list endpoint, with the total that match
+    pub fn list_tags(&self, query: &ListQuery) -> Result<(Vec<Tag>, usize)> {
+        self.list("tags", TAG_COLUMNS, query, row_to_tag)
     }
 
-    pub fn upsert_tag(&self, tag: &Tag) -> Result<(bool, i64)> {
-        let conn = self.conn.lock().unwrap();
-
+    fn write_tag(conn: &Connection, tag: &Tag) -> Result<(bool, i64)> {
         let existing: Option<i64> = conn
             .query_row(
                 "SELECT revision FROM tags WHERE id = ?",
@@ -290,34 +518,878 @@ impl Database {
             )
             .ok();
 
-        let has_conflict = existing.map(|r| r >= tag.revision).unwrap_or(false);
+        if let Some(current) = existing {
+            if tag.revision < current {
+                return Ok((true, current));
+            }
+        }
+
+        let new_rev = Self::increment_global_revision(conn)?;
+
+        conn.execute(
+            r#"INSERT INTO tags (id, name, color, created_at, updated_at, revision, is_deleted, deleted_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+               ON CONFLICT(id) DO UPDATE SET
+                   name = excluded.name,
+                   color = excluded.color,
+                   updated_at = excluded.updated_at,
+                   revision = ?6,
+                   is_deleted = excluded.is_deleted,
+                   deleted_at = excluded.deleted_at"#,
+            params![
+                tag.id,
+                tag.name,
+                tag.color,
+                tag.created_at,
+                tag.updated_at,
+                new_rev,
+                tag.is_deleted,
+                tag.deleted_at
+            ],
+        )?;
+
+        Ok((false, new_rev))
+    }
 
-        if existing.is_none() || tag.revision >= existing.unwrap() {
-            let new_rev = self.increment_global_revision(&conn)?;
+    /// Id of another tag already holding `tag`'s name
+    fn tag_named(conn: &Connection, tag: &Tag) -> Result<Option<String>> {
+        let id = conn
+            .query_row(
+                "SELECT id FROM tags WHERE name = ? AND id != ?",
+                params![tag.name, tag.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
 
+    /// Keep `holder` for a pushed tag with the same name, bringing it back if
+    /// it was deleted and the pushed one isn't. Returns its revision.
+    fn reuse_tag(conn: &Connection, holder: &str, tag: &Tag) -> Result<i64> {
+        let deleted: bool = conn.query_row("SELECT is_deleted FROM tags WHERE id = ?", [holder], |row| row.get(0))?;
+        if deleted && !tag.is_deleted {
+            let new_rev = Self::increment_global_revision(conn)?;
             conn.execute(
-                r#"INSERT INTO tags (id, name, color, created_at, updated_at, revision, is_deleted)
-                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-                   ON CONFLICT(id) DO UPDATE SET
-                       name = excluded.name,
-                       color = excluded.color,
-                       updated_at = excluded.updated_at,
-                       revision = ?6,
-                       is_deleted = excluded.is_deleted"#,
-                params![
-                    tag.id,
-                    tag.name,
-                    tag.color,
-                    tag.created_at,
-                    tag.updated_at,
-                    new_rev,
-                    tag.is_deleted
-                ],
+                "UPDATE tags SET is_deleted = 0, deleted_at = NULL, updated_at = ?, revision = ? WHERE id = ?",
+                params![tag.updated_at, new_rev, holder],
             )?;
+            return Ok(new_rev);
+        }
+        let revision = conn.query_row("SELECT revision FROM tags WHERE id = ?", [holder], |row| row.get(0))?;
+        Ok(revision)
+    }
+
+    // Reminders
+    pub fn get_reminders_since(&self, revision: i64, limit: Option<i64>) -> Result<Vec<Reminder>> {
+        let _timer = metrics::query("reminders_since");
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM reminders WHERE revision > ? ORDER BY revision LIMIT ?",
+            REMINDER_COLUMNS
+        ))?;
+
+        let reminders = stmt
+            .query_map(params![revision, limit.unwrap_or(-1)], row_to_reminder)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(reminders)
+    }
+
+    /// A page of reminders for the list endpoint, with the total that match
+    pub fn list_reminders(&self, query: &ListQuery) -> Result<(Vec<Reminder>, usize)> {
+        self.list("reminders", REMINDER_COLUMNS, query, row_to_reminder)
+    }
+
+    fn write_reminder(conn: &Connection, reminder: &Reminder) -> Result<(bool, i64)> {
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT revision FROM reminders WHERE id = ?",
+                [&reminder.id],
+                |row| row.get(0),
+            )
+            .ok();
+
+        if let Some(current) = existing {
+            if reminder.revision < current {
+                return Ok((true, current));
+            }
+        }
+
+        let new_rev = Self::increment_global_revision(conn)?;
+
+        conn.execute(
+            r#"INSERT INTO reminders (id, note_id, message, due_date, completed, notified, recurrence, snoozed_from, created_at, updated_at, revision, is_deleted, deleted_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+               ON CONFLICT(id) DO UPDATE SET
+                   note_id = excluded.note_id,
+                   message = excluded.message,
+                   due_date = excluded.due_date,
+                   completed = excluded.completed,
+                   notified = excluded.notified,
+                   recurrence = excluded.recurrence,
+                   snoozed_from = excluded.snoozed_from,
+                   updated_at = excluded.updated_at,
+                   revision = ?11,
+                   is_deleted = excluded.is_deleted,
+                   deleted_at = excluded.deleted_at"#,
+            params![
+                reminder.id,
+                reminder.note_id,
+                reminder.message,
+                reminder.due_date,
+                reminder.completed,
+                reminder.notified,
+                reminder.recurrence,
+                reminder.snoozed_from,
+                reminder.created_at,
+                reminder.updated_at,
+                new_rev,
+                reminder.is_deleted,
+                reminder.deleted_at
+            ],
+        )?;
+
+        Ok((false, new_rev))
+    }
+
+    // Hard deletions
+    /// Deletions past the cursor of the type they delete, oldest first;
+    /// `limit` caps how many (all when None)
+    pub fn get_deletions_since(&self, since: &SyncCursors, limit: Option<i64>) -> Result<Vec<DeletedEntity>> {
+        let _timer = metrics::query("deletions_since");
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT entity_type, entity_id, deleted_at, revision
+             FROM deleted_entities
+             WHERE revision > CASE entity_type WHEN 'note' THEN ?1 WHEN 'notebook' THEN ?2 WHEN 'tag' THEN ?3
+                 WHEN 'reminder' THEN ?4 ELSE ?5 END
+             ORDER BY revision LIMIT ?6",
+        )?;
+
+        let deleted = stmt
+            .query_map(
+                params![since.notes, since.notebooks, since.tags, since.reminders, since.min(), limit.unwrap_or(-1)],
+                |row| {
+                    Ok(DeletedEntity {
+                        entity_type: row.get(0)?,
+                        entity_id: row.get(1)?,
+                        deleted_at: row.get(2)?,
+                        revision: row.get(3)?,
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(deleted)
+    }
+
+    // Purging
+    /// Per table: all rows, soft-deleted rows (every tombstone counts), and
+    /// those deleted at least `older_than_days` ago
+    pub fn deletion_stats(&self, older_than_days: i64) -> Result<(TableCounts, TableCounts, TableCounts)> {
+        let conn = self.conn.lock().unwrap();
+        let synced_through = synced_through(&conn)?;
+        let counts = |rows: &str, tombstones: &str| -> Result<TableCounts> {
+            let count = |table: &str, condition: &str| -> Result<usize> {
+                let sql = format!("SELECT COUNT(*) FROM {} WHERE {}", table, condition);
+                Ok(conn.query_row(&sql, [], |row| row.get::<_, i64>(0))? as usize)
+            };
+            Ok(TableCounts {
+                notes: count("notes", rows)?,
+                notebooks: count("notebooks", rows)?,
+                tags: count("tags", rows)?,
+                reminders: count("reminders", rows)?,
+                deletions: count("deleted_entities", tombstones)?,
+            })
+        };
+        let (rows, tombstones) = purgeable(older_than_days, synced_through);
+        Ok((counts("1", "1")?, counts("is_deleted = 1", "1")?, counts(&rows, &tombstones)?))
+    }
+
+    /// Hard-delete soft-deleted rows and tombstones deleted at least
+    /// `older_than_days` ago that every device has pulled, in one
+    /// transaction. Returns counts per table.
+    pub fn purge_deleted(&self, older_than_days: i64) -> Result<TableCounts> {
+        let _timer = metrics::query("purge_deleted");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let (rows, tombstones) = purgeable(older_than_days, synced_through(&tx)?);
+        let purge = |table: &str, condition: &str| -> Result<usize> {
+            Ok(tx.execute(&format!("DELETE FROM {} WHERE {}", table, condition), [])?)
+        };
+        let purged = TableCounts {
+            notes: purge("notes", &rows)?,
+            notebooks: purge("notebooks", &rows)?,
+            tags: purge("tags", &rows)?,
+            reminders: purge("reminders", &rows)?,
+            deletions: purge("deleted_entities", &tombstones)?,
+        };
+        tx.commit()?;
+        Ok(purged)
+    }
+
+    // Maintenance
+    /// The maintenance lock, or a conflict when another operation holds it
+    fn begin_maintenance(&self) -> Result<MutexGuard<'_, ()>> {
+        self.maintenance.try_lock().map_err(|e| match e {
+            TryLockError::WouldBlock => AppError::Conflict("another maintenance operation is running".to_string()),
+            TryLockError::Poisoned(_) => AppError::Internal("maintenance lock is poisoned".to_string()),
+        })
+    }
 
-            Ok((has_conflict, new_rev))
+    /// Rebuild the database to drop free pages, then fold the WAL back into
+    /// it and truncate it. Blocks pushes until done.
+    pub fn vacuum(&self) -> Result<VacuumReport> {
+        let _maintenance = self.begin_maintenance()?;
+        let _timer = metrics::query("vacuum");
+        let conn = self.try_lock()?;
+        let started = Instant::now();
+        let bytes_before = size_on_disk(&conn);
+        conn.execute_batch("VACUUM")?;
+        // Busy readers can keep some frames; the next checkpoint gets them
+        let (busy, _, _): (i64, i64, i64) =
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        Ok(VacuumReport {
+            bytes_before,
+            bytes_after: size_on_disk(&conn),
+            checkpointed: busy == 0,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// `PRAGMA integrity_check` and `PRAGMA quick_check`, on the reader so
+    /// pushes carry on meanwhile
+    pub fn integrity_check(&self) -> Result<IntegrityReport> {
+        let _maintenance = self.begin_maintenance()?;
+        let _timer = metrics::query("integrity_check");
+        let conn = self.try_read()?;
+        let started = Instant::now();
+        let check = |pragma: &str| -> Result<Vec<String>> {
+            Ok(conn
+                .prepare(&format!("PRAGMA {}", pragma))?
+                .query_map([], |row| row.get(0))?
+                .collect::<std::result::Result<Vec<String>, _>>()?)
+        };
+        let integrity_check = check("integrity_check")?;
+        let quick_check = check("quick_check")?;
+        Ok(IntegrityReport {
+            ok: integrity_check == ["ok"] && quick_check == ["ok"],
+            integrity_check,
+            quick_check,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Copy the database to a new file at `dest` with the online backup
+    /// API. The copy is read from its own connection in one step, i.e. one
+    /// read transaction, which WAL lets pushes and pulls carry on past.
+    pub fn backup(&self, dest: &Path) -> Result<()> {
+        let _timer = metrics::query("backup");
+        let path = match self.try_read()?.path() {
+            Some(path) if !path.is_empty() => path.to_string(),
+            _ => return Err(AppError::Internal("an in-memory database can't be backed up".to_string())),
+        };
+        let source = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        source.busy_timeout(BUSY_TIMEOUT)?;
+        let mut copy = Connection::open(dest)?;
+        Backup::new(&source, &mut copy)?.run_to_completion(i32::MAX, Duration::ZERO, None)?;
+        Ok(())
+    }
+
+    // Devices
+    /// A pull by `device_id`, which has merged everything up to `revision`
+    pub fn record_pull(&self, device_id: &str, revision: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            r#"INSERT INTO devices (device_id, last_pull_revision, last_seen_at, first_seen_at)
+               VALUES (?1, ?2, ?3, ?3)
+               ON CONFLICT(device_id) DO UPDATE SET
+                   last_pull_revision = MAX(last_pull_revision, excluded.last_pull_revision),
+                   last_seen_at = excluded.last_seen_at"#,
+            params![device_id, revision, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_push(&self, device_id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            r#"INSERT INTO devices (device_id, last_push_at, last_seen_at, first_seen_at)
+               VALUES (?1, ?2, ?2, ?2)
+               ON CONFLICT(device_id) DO UPDATE SET
+                   last_push_at = excluded.last_push_at,
+                   last_seen_at = excluded.last_seen_at"#,
+            params![device_id, now],
+        )?;
+        Ok(())
+    }
+
+    // Diagnostics
+    /// The connection, or an error instead of a panic when a thread died
+    /// holding it
+    fn try_lock(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| AppError::Internal("database connection is poisoned".to_string()))
+    }
+
+    /// Rows of `table` in revision order, filtered and paged by `query`,
+    /// along with how many match in all
+    fn list<T>(
+        &self,
+        table: &str,
+        columns: &str,
+        query: &ListQuery,
+        map: fn(&rusqlite::Row) -> rusqlite::Result<T>,
+    ) -> Result<(Vec<T>, usize)> {
+        let _timer = metrics::query("list");
+        let conn = self.reader.lock().unwrap();
+        let filter = if query.include_deleted {
+            "revision > ?1"
         } else {
-            Ok((true, existing.unwrap()))
+            "revision > ?1 AND is_deleted = 0"
+        };
+
+        let total: usize = conn.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE {}", table, filter),
+            [query.since],
+            |row| row.get(0),
+        )?;
+        let rows = conn
+            .prepare(&format!(
+                "SELECT {} FROM {} WHERE {} ORDER BY revision LIMIT ?2 OFFSET ?3",
+                columns, table, filter
+            ))?
+            .query_map(params![query.since, query.limit.unwrap_or(-1), query.offset], map)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok((rows, total))
+    }
+
+    fn try_read(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.reader
+            .lock()
+            .map_err(|_| AppError::Internal("database reader is poisoned".to_string()))
+    }
+
+    pub fn row_counts(&self) -> Result<TableCounts> {
+        let conn = self.try_read()?;
+        let count = |table: &str| -> Result<usize> {
+            let sql = format!("SELECT COUNT(*) FROM {}", table);
+            Ok(conn.query_row(&sql, [], |row| row.get::<_, i64>(0))? as usize)
+        };
+        Ok(TableCounts {
+            notes: count("notes")?,
+            notebooks: count("notebooks")?,
+            tags: count("tags")?,
+            reminders: count("reminders")?,
+            deletions: count("deleted_entities")?,
+        })
+    }
+
+    /// Size of the database, from its page count
+    pub fn size_bytes(&self) -> Result<i64> {
+        let conn = self.try_lock()?;
+        Ok(conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Write to the database and roll back, to tell whether writes succeed
+    pub fn write_probe(&self) -> Result<()> {
+        let mut conn = self.try_lock()?;
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE sync_state SET global_revision = global_revision WHERE id = 1", [])?;
+        tx.rollback()?;
+        Ok(())
+    }
+
+    /// Revision every known device has pulled up to
+    pub fn get_synced_through(&self) -> Result<Option<i64>> {
+        synced_through(&*self.try_read()?)
+    }
+
+    /// Known devices, most recently seen first
+    pub fn get_devices(&self) -> Result<Vec<Device>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT device_id, user_id, last_pull_revision, last_push_at, last_seen_at, first_seen_at
+             FROM devices ORDER BY last_seen_at DESC",
+        )?;
+
+        let devices = stmt
+            .query_map([], |row| {
+                Ok(Device {
+                    device_id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    last_pull_revision: row.get(2)?,
+                    last_push_at: row.get(3)?,
+                    last_seen_at: row.get(4)?,
+                    first_seen_at: row.get(5)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(devices)
+    }
+
+    /// Forget a device, e.g. one that was retired and would otherwise hold
+    /// back purges forever. Returns false when it wasn't known.
+    pub fn remove_device(&self, device_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM devices WHERE device_id = ?", [device_id])? > 0)
+    }
+
+    /// Drop the entity and keep a tombstone so other devices learn about it.
+    /// Returns the tombstone's revision, or None for unknown entity types.
+    fn write_deletion(conn: &Connection, deleted: &DeletedEntity) -> Result<Option<i64>> {
+        let table = match deleted.entity_type.as_str() {
+            "note" => "notes",
+            "notebook" => "notebooks",
+            "tag" => "tags",
+            "reminder" => "reminders",
+            _ => return Ok(None),
+        };
+
+        let new_rev = Self::increment_global_revision(conn)?;
+
+        conn.execute(
+            &format!("DELETE FROM {} WHERE id = ?", table),
+            [&deleted.entity_id],
+        )?;
+        if table == "notes" {
+            // Devices drop a deleted note's reminders along with it
+            conn.execute("DELETE FROM reminders WHERE note_id = ?", [&deleted.entity_id])?;
+        }
+        conn.execute(
+            r#"INSERT INTO deleted_entities (entity_type, entity_id, deleted_at, revision)
+               VALUES (?1, ?2, ?3, ?4)
+               ON CONFLICT(entity_type, entity_id) DO UPDATE SET
+                   deleted_at = excluded.deleted_at,
+                   revision = ?4"#,
+            params![
+                deleted.entity_type,
+                deleted.entity_id,
+                deleted.deleted_at,
+                new_rev
+            ],
+        )?;
+
+        Ok(Some(new_rev))
+    }
+
+    /// The latest change of every entity past `revision`, oldest first
+    pub fn changes_since(&self, revision: i64) -> Result<Vec<Change>> {
+        let _timer = metrics::query("changes_since");
+        let conn = self.reader.lock().unwrap();
+        let changes = conn
+            .prepare(
+                "SELECT 'note', id, revision, is_deleted FROM notes WHERE revision > ?1
+                 UNION ALL SELECT 'notebook', id, revision, is_deleted FROM notebooks WHERE revision > ?1
+                 UNION ALL SELECT 'tag', id, revision, is_deleted FROM tags WHERE revision > ?1
+                 UNION ALL SELECT 'reminder', id, revision, is_deleted FROM reminders WHERE revision > ?1
+                 UNION ALL SELECT entity_type, entity_id, revision, 1 FROM deleted_entities WHERE revision > ?1
+                 ORDER BY revision",
+            )?
+            .query_map([revision], |row| {
+                Ok(Change {
+                    entity_type: row.get(0)?,
+                    entity_id: row.get(1)?,
+                    revision: row.get(2)?,
+                    deleted: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(changes)
+    }
+
+    // Push
+    /// Store a whole push in one transaction, so a failure leaves none of it
+    /// applied. Each stored entity still gets its own revision, which pulls
+    /// page by.
+    pub fn apply_push(&self, req: &PushRequest) -> Result<PushResponse> {
+        let _timer = metrics::query("apply_push");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut conflicts = Vec::new();
+        let mut accepted = AcceptedCounts::default();
+        let mut results = Vec::new();
+
+        // Whether the entity was accepted; a conflict also reports what the server kept
+        // `copy_id` is the entity whose server copy goes with a conflict,
+        // normally the pushed one
+        let mut record = |entity_type: &str,
+                          id: &str,
+                          copy_id: &str,
+                          local_revision: i64,
+                          (conflict, server_rev): (bool, i64)| {
+            let status = if conflict { "conflict" } else { "accepted" };
+            results.push(EntityResult::new(entity_type, id, status, server_rev));
+            if conflict {
+                conflicts.push(Conflict {
+                    entity_type: entity_type.to_string(),
+                    entity_id: id.to_string(),
+                    local_revision,
+                    server_revision: server_rev,
+                    resolution: "server_wins".to_string(),
+                    server_copy: entity_json(&tx, entity_type, copy_id)?,
+                });
+            }
+            Ok::<_, AppError>(!conflict)
+        };
+
+        for note in &req.notes {
+            if record("note", &note.id, &note.id, note.revision, Self::write_note(&tx, note)?)? {
+                accepted.notes += 1;
+            }
+        }
+        for notebook in &req.notebooks {
+            if record("notebook", &notebook.id, &notebook.id, notebook.revision, Self::write_notebook(&tx, notebook)?)? {
+                accepted.notebooks += 1;
+            }
         }
+        for tag in &req.tags {
+            // A name another tag already has (two devices created it offline)
+            // isn't stored twice: the device gets that tag back instead
+            let (copy_id, outcome) = match Self::tag_named(&tx, tag)? {
+                Some(holder) => {
+                    let revision = Self::reuse_tag(&tx, &holder, tag)?;
+                    (holder, (true, revision))
+                }
+                None => (tag.id.clone(), Self::write_tag(&tx, tag)?),
+            };
+            if record("tag", &tag.id, &copy_id, tag.revision, outcome)? {
+                accepted.tags += 1;
+            }
+        }
+        // Reminders after notes, so a reminder's note from the same push is
+        // in place first. There's no foreign key: a note may still arrive in
+        // a later push.
+        for reminder in &req.reminders {
+            if record("reminder", &reminder.id, &reminder.id, reminder.revision, Self::write_reminder(&tx, reminder)?)? {
+                accepted.reminders += 1;
+            }
+        }
+
+        // Hard deletions after upserts, so a deletion wins over a copy of the
+        // same entity in this push
+        for deleted in &req.deleted {
+            let (status, revision) = match Self::write_deletion(&tx, deleted)? {
+                Some(revision) => ("deleted", revision),
+                None => ("skipped", global_revision(&tx)?),
+            };
+            results.push(EntityResult::new(&deleted.entity_type, &deleted.entity_id, status, revision));
+            if status == "deleted" {
+                accepted.deleted += 1;
+            }
+        }
+
+        let server_revision = global_revision(&tx)?;
+        tx.commit()?;
+        Ok(PushResponse {
+            accepted: accepted.total(),
+            accepted_counts: accepted,
+            conflicts,
+            results,
+            server_revision,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn open() -> (TempDir, Database) {
+        let dir = TempDir::new().unwrap();
+        let db = Database::new(dir.path().join("server.db").to_str().unwrap()).unwrap();
+        (dir, db)
+    }
+
+    fn tag(id: &str, name: &str) -> Tag {
+        Tag {
+            id: id.to_string(),
+            name: name.to_string(),
+            color: None,
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            updated_at: "2026-01-01T00:00:00Z".to_string(),
+            revision: 1,
+            is_deleted: false,
+            deleted_at: None,
+        }
+    }
+
+    fn push(tags: Vec<Tag>) -> PushRequest {
+        PushRequest {
+            device_id: "device".to_string(),
+            notes: Vec::new(),
+            notebooks: Vec::new(),
+            tags,
+            reminders: Vec::new(),
+            deleted: Vec::new(),
+        }
+    }
+
+    fn note(id: &str, revision: i64) -> Note {
+        Note {
+            id: id.to_string(),
+            title: "Title".to_string(),
+            content: String::new(),
+            notebook_id: None,
+            tags: "[]".to_string(),
+            status: "active".to_string(),
+            is_pinned: false,
+            pinned_order: None,
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            updated_at: "2026-01-01T00:00:00Z".to_string(),
+            revision,
+            is_deleted: false,
+            deleted_at: None,
+            encrypted: false,
+        }
+    }
+
+    fn reminder(id: &str, note_id: &str, revision: i64) -> Reminder {
+        Reminder {
+            id: id.to_string(),
+            note_id: Some(note_id.to_string()),
+            message: "Call back".to_string(),
+            due_date: "2026-02-01T09:00:00Z".to_string(),
+            completed: false,
+            notified: false,
+            recurrence: None,
+            snoozed_from: None,
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            updated_at: "2026-01-01T00:00:00Z".to_string(),
+            revision,
+            is_deleted: false,
+            deleted_at: None,
+        }
+    }
+
+    fn push_notes(notes: Vec<Note>) -> PushRequest {
+        PushRequest {
+            notes,
+            ..push(Vec::new())
+        }
+    }
+
+    fn push_reminders(reminders: Vec<Reminder>) -> PushRequest {
+        PushRequest {
+            reminders,
+            ..push(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_push_at_the_stored_revision_is_accepted() {
+        let (_dir, db) = open();
+        let stored = db.apply_push(&push_notes(vec![note("n", 1), note("other", 1)])).unwrap();
+        assert_eq!(stored.server_revision, 2);
+
+        // The device has seen revision 1 and edits on top of it
+        let mut edited = note("n", 1);
+        edited.title = "Edited".to_string();
+        let response = db.apply_push(&push_notes(vec![edited])).unwrap();
+        assert_eq!((response.accepted, response.conflicts.len()), (1, 0));
+        assert_eq!(response.results[0].status, "accepted");
+
+        // Behind the stored revision, the server keeps its copy
+        let response = db.apply_push(&push_notes(vec![note("n", 2)])).unwrap();
+        assert_eq!((response.accepted, response.conflicts.len()), (0, 1));
+        assert_eq!(response.conflicts[0].server_copy.as_ref().unwrap()["title"], "Edited");
+    }
+
+    #[test]
+    fn test_push_reports_every_entity() {
+        let (_dir, db) = open();
+        db.apply_push(&push_notes(vec![note("old", 1)])).unwrap();
+        db.apply_push(&push_notes(vec![note("old", 1)])).unwrap();
+
+        let deletion = |entity_type: &str, id: &str| DeletedEntity {
+            entity_type: entity_type.to_string(),
+            entity_id: id.to_string(),
+            revision: 1,
+            deleted_at: "2026-01-02T00:00:00Z".to_string(),
+        };
+        let request = PushRequest {
+            deleted: vec![deletion("note", "gone"), deletion("attachment", "x")],
+            ..push_notes(vec![note("new", 1), note("old", 1)])
+        };
+        let response = db.apply_push(&request).unwrap();
+        let results: Vec<(&str, &str)> =
+            response.results.iter().map(|r| (r.id.as_str(), r.status.as_str())).collect();
+        assert_eq!(results, [("new", "accepted"), ("old", "conflict"), ("gone", "deleted"), ("x", "skipped")]);
+
+        // Counts and the old total agree with the results; a skipped
+        // deletion is at the revision the push ended on
+        let counts = &response.accepted_counts;
+        assert_eq!((counts.notes, counts.deleted, response.accepted), (1, 1, 2));
+        assert_eq!(response.results[3].server_revision, response.server_revision);
+        assert_eq!(response.results[1].server_revision, response.conflicts[0].server_revision);
+    }
+
+    #[test]
+    fn test_push_of_a_taken_tag_name_returns_the_holder() {
+        let (_dir, db) = open();
+        db.apply_push(&push(vec![tag("a", "work")])).unwrap();
+
+        let response = db.apply_push(&push(vec![tag("b", "work")])).unwrap();
+        assert_eq!(response.accepted, 0);
+        let conflict = &response.conflicts[0];
+        assert_eq!(conflict.entity_id, "b");
+        assert_eq!(conflict.server_copy.as_ref().unwrap()["id"], "a");
+        let ids: Vec<String> = db.get_tags_since(0, None).unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["a"]);
+
+        // A deleted holder comes back for a live tag of the same name
+        let mut deleted = tag("a", "work");
+        deleted.revision = 10;
+        deleted.is_deleted = true;
+        db.apply_push(&push(vec![deleted])).unwrap();
+        let response = db.apply_push(&push(vec![tag("c", "work")])).unwrap();
+        let copy = response.conflicts[0].server_copy.clone().unwrap();
+        assert_eq!((copy["id"].as_str(), copy["is_deleted"].as_bool()), (Some("a"), Some(false)));
+        assert_eq!(response.conflicts[0].server_revision, response.server_revision);
+    }
+
+    #[test]
+    fn test_a_poisoned_reader_is_an_error() {
+        let (_dir, db) = open();
+        assert_eq!(db.get_synced_through().unwrap(), None);
+
+        // A panic while reading leaves the reader poisoned
+        let db = std::sync::Arc::new(db);
+        let holder = db.clone();
+        std::thread::spawn(move || {
+            let _reader = holder.reader.lock().unwrap();
+            panic!("reader poisoned on purpose");
+        })
+        .join()
+        .unwrap_err();
+
+        assert!(matches!(db.get_synced_through(), Err(AppError::Internal(_))));
+        assert!(db.row_counts().is_err());
+    }
+
+    #[test]
+    fn test_reminders_sync_without_their_note() {
+        let (_dir, db) = open();
+
+        // The note isn't on the server yet; the reminder is kept anyway
+        let response = db.apply_push(&push_reminders(vec![reminder("r", "later", 1)])).unwrap();
+        assert_eq!((response.accepted_counts.reminders, response.server_revision), (1, 1));
+        let pulled = db.get_reminders_since(0, None).unwrap();
+        assert_eq!((pulled[0].note_id.as_deref(), pulled[0].revision), (Some("later"), 1));
+
+        // A reminder and its note in one push both land
+        let both = PushRequest {
+            notes: vec![note("n", 1)],
+            reminders: vec![reminder("s", "n", 1)],
+            ..push(Vec::new())
+        };
+        assert_eq!(db.apply_push(&both).unwrap().accepted, 2);
+
+        // An edit made behind the stored revision loses, like a note's
+        let mut completed = reminder("r", "later", 1);
+        completed.completed = true;
+        db.apply_push(&push_reminders(vec![completed])).unwrap();
+        let stale = db.apply_push(&push_reminders(vec![reminder("r", "later", 0)])).unwrap();
+        assert_eq!(stale.conflicts[0].entity_type, "reminder");
+        assert_eq!(stale.conflicts[0].server_copy.as_ref().unwrap()["completed"], true);
+
+        let since = db.get_reminders_since(3, None).unwrap();
+        assert_eq!(since.len(), 1);
+        assert!(since[0].completed);
+    }
+
+    #[test]
+    fn test_a_failed_push_stores_nothing() {
+        let (_dir, db) = open();
+        db.apply_push(&push_notes(vec![note("before", 1)])).unwrap();
+
+        // The fourth note fails halfway through the push
+        db.conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "CREATE TEMP TRIGGER fail_fourth BEFORE INSERT ON notes WHEN NEW.id = 'n3'
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END",
+            )
+            .unwrap();
+        let notes = (0..6).map(|i| note(&format!("n{}", i), 1)).collect();
+        let batch = PushRequest {
+            tags: vec![tag("t", "work")],
+            ..push_notes(notes)
+        };
+        assert!(db.apply_push(&batch).is_err());
+
+        assert_eq!(db.get_global_revision().unwrap(), 1);
+        let ids: Vec<String> = db.get_notes_since(0, None).unwrap().into_iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec!["before"]);
+        assert!(db.get_tags_since(0, None).unwrap().is_empty());
+
+        // Retried once the failure is gone, the whole push goes in
+        db.conn.lock().unwrap().execute_batch("DROP TRIGGER fail_fourth").unwrap();
+        let response = db.apply_push(&batch).unwrap();
+        assert_eq!((response.accepted, response.server_revision), (7, 8));
+    }
+
+    #[test]
+    fn test_a_first_release_database_is_brought_up_to_date() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE notes (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '',
+                     content TEXT NOT NULL DEFAULT '', notebook_id TEXT, tags TEXT NOT NULL DEFAULT '', status TEXT NOT NULL DEFAULT 'active',
+                     created_at TEXT NOT NULL, updated_at TEXT NOT NULL, revision INTEGER NOT NULL DEFAULT 1,
+                     is_deleted INTEGER NOT NULL DEFAULT 0);
+                 CREATE TABLE notebooks (id TEXT PRIMARY KEY, name TEXT NOT NULL, color TEXT, parent_id TEXT,
+                     created_at TEXT NOT NULL, updated_at TEXT NOT NULL, revision INTEGER NOT NULL DEFAULT 1,
+                     is_deleted INTEGER NOT NULL DEFAULT 0);
+                 INSERT INTO notes (id, created_at, updated_at)
+                     VALUES ('n', '2025-01-01T00:00:00Z', '2025-01-01T00:00:00Z');
+                 INSERT INTO notebooks (id, name, created_at, updated_at)
+                     VALUES ('nb', 'Work', '2025-01-01T00:00:00Z', '2025-01-01T00:00:00Z');",
+            )
+            .unwrap();
+
+        // What older rows didn't have comes back empty, and their tag lists as JSON
+        let db = Database::new(path.to_str().unwrap()).unwrap();
+        let note = db.get_notes_since(0, None).unwrap().remove(0);
+        assert_eq!((note.tags.as_str(), note.is_pinned, note.deleted_at), ("[]", false, None));
+        let notebook = db.get_notebooks_since(0, None).unwrap().remove(0);
+        assert_eq!((notebook.icon, notebook.is_archived, notebook.deleted_at), (None, false, None));
+
+        // Opening it again changes nothing
+        drop(db);
+        let db = Database::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(db.get_notes_since(0, None).unwrap().len(), 1);
+
+        // Only new databases can check the status themselves
+        let (_dir, fresh) = open();
+        let insert = "INSERT INTO notes (id, status, created_at, updated_at) VALUES ('x', 'banana', '', '')";
+        assert!(fresh.conn.lock().unwrap().execute(insert, []).is_err());
+    }
+
+    #[test]
+    fn test_maintenance_runs_one_at_a_time() {
+        let (_dir, db) = open();
+        let running = db.begin_maintenance().unwrap();
+        assert!(matches!(db.vacuum(), Err(AppError::Conflict(_))));
+        assert!(matches!(db.integrity_check(), Err(AppError::Conflict(_))));
+        // Syncing isn't held up by it
+        assert_eq!(db.apply_push(&push_notes(vec![note("n", 1)])).unwrap().accepted, 1);
+
+        drop(running);
+        assert!(db.integrity_check().unwrap().ok);
+        assert!(db.vacuum().unwrap().bytes_after > 0);
     }
 }
@@ -1,10 +1,14 @@
+use std::time::Duration;
+
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 
+use crate::models::EntityError;
+
 #[derive(Debug, thiserror::Error)]
 #[allow(dead_code)]
 pub enum AppError {
@@ -17,16 +21,50 @@ pub enum AppError {
     #[error("Invalid request: {0}")]
     BadRequest(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("{} entities failed validation", .0.len())]
+    Rejected(Vec<EntityError>),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
+
+    /// Too many requests from one client; it may try again after the wait
+    #[error("Too many requests; retry in {}s", retry_after_secs(.0))]
+    RateLimited(Duration),
+}
+
+/// Whole seconds for `Retry-After`, at least one
+fn retry_after_secs(wait: &Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let AppError::Rejected(errors) = &self {
+            let body = Json(json!({
+                "error": self.to_string(),
+                "rejected": errors,
+            }));
+            return (StatusCode::UNPROCESSABLE_ENTITY, body).into_response();
+        }
+        if let AppError::RateLimited(wait) = &self {
+            let body = Json(json!({ "error": self.to_string() }));
+            let retry_after = [(header::RETRY_AFTER, retry_after_secs(wait).to_string())];
+            return (StatusCode::TOO_MANY_REQUESTS, retry_after, body).into_response();
+        }
+
         let (status, message) = match &self {
             AppError::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::Rejected(_) | AppError::RateLimited(_) => unreachable!("answered above"),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
             AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
         };
 
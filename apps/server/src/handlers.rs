@@ -1,9 +1,34 @@
-use axum::{extract::State, Json};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::error::Result;
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    response::IntoResponse,
+    Json,
+};
+use futures_util::stream::{Stream, StreamExt};
+use serde_json::Value;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::io::ReaderStream;
+
+use crate::checksum;
+use crate::db::Database;
+use crate::error::{AppError, Result};
+use crate::metrics::METRICS;
 use crate::models::*;
+use crate::openapi::ErrorBody;
+use crate::validation;
 use crate::AppState;
 
+#[utoipa::path(
+    get, path = "/health", tag = "health",
+    responses((status = 200, body = HealthResponse))
+)]
 pub async fn health() -> Json<HealthResponse> {
     Json(HealthResponse {
         status: "ok".to_string(),
@@ -11,127 +36,765 @@ pub async fn health() -> Json<HealthResponse> {
     })
 }
 
+/// Database diagnostics for monitoring. Failing checks are reported, not
+/// raised, so a broken database still gets an answer.
+#[utoipa::path(
+    get, path = "/health/details", tag = "health", security(("admin_token" = [])),
+    responses((status = 200, body = HealthDetails), (status = 401, body = ErrorBody))
+)]
+pub async fn health_details(State(state): State<AppState>, headers: HeaderMap) -> Result<Json<HealthDetails>> {
+    require_admin(&state, &headers)?;
+    fn check<T>(errors: &mut Vec<String>, result: Result<T>) -> Option<T> {
+        result.map_err(|e| errors.push(e.to_string())).ok()
+    }
+    let mut errors = Vec::new();
+    let revision = check(&mut errors, state.db.get_global_revision());
+    let rows = check(&mut errors, state.db.row_counts());
+    let database_bytes = check(&mut errors, state.db.size_bytes());
+    let writable = check(&mut errors, state.db.write_probe()).is_some();
+
+    Ok(Json(HealthDetails {
+        status: if errors.is_empty() { "ok" } else { "degraded" }.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        revision,
+        rows,
+        database_bytes,
+        writable,
+        errors,
+    }))
+}
+
+/// Just the global revision, so a client can tell whether there is anything
+/// to pull without pulling
+#[utoipa::path(
+    get, path = "/api/sync/revision", tag = "sync",
+    responses((status = 200, body = RevisionResponse), (status = 429, body = ErrorBody))
+)]
+pub async fn revision(State(state): State<AppState>) -> Result<Json<RevisionResponse>> {
+    let revision = state.db.get_global_revision()?;
+    Ok(Json(RevisionResponse { revision }))
+}
+
+#[utoipa::path(
+    post, path = "/api/sync/pull", tag = "sync", request_body = PullRequest,
+    responses(
+        (status = 200, description = "Changes since the cursors, with a `checksum` field", body = PullResponse),
+        (status = 400, body = ErrorBody),
+        (status = 429, body = ErrorBody),
+    )
+)]
 pub async fn pull(
     State(state): State<AppState>,
     Json(req): Json<PullRequest>,
-) -> Result<Json<PullResponse>> {
-    tracing::info!(
-        "Pull request from device {} since revision {}",
-        req.device_id,
-        req.last_sync_revision
-    );
+) -> Result<Json<Value>> {
+    let since = req.cursors();
+    tracing::info!("Pull request from device {} since {:?} (limit {:?})", req.device_id, since, req.limit);
 
-    let notes = state.db.get_notes_since(req.last_sync_revision)?;
-    let notebooks = state.db.get_notebooks_since(req.last_sync_revision)?;
-    let tags = state.db.get_tags_since(req.last_sync_revision)?;
+    if req.device_id.trim().is_empty() && !req.dry_run {
+        return Err(AppError::BadRequest("device_id must not be empty".to_string()));
+    }
+    if matches!(req.limit, Some(limit) if limit <= 0) {
+        return Err(AppError::BadRequest("limit must be positive".to_string()));
+    }
+
+    // Pushes commit while a pull reads, so take the revision first and leave
+    // out newer rows: the next pull gets them together with the rest of
+    // their push
     let server_revision = state.db.get_global_revision()?;
 
+    // One row past the limit tells whether a type has more
+    let fetch = req.limit.map(|limit| limit + 1);
+    let mut notes = state.db.get_notes_since(since.notes, fetch)?;
+    let mut notebooks = state.db.get_notebooks_since(since.notebooks, fetch)?;
+    let mut tags = state.db.get_tags_since(since.tags, fetch)?;
+    let mut reminders = state.db.get_reminders_since(since.reminders, fetch)?;
+    let mut deleted = state.db.get_deletions_since(&since, fetch)?;
+
+    // Revisions are global, so cutting every type at the same revision leaves
+    // nothing behind below it: the device can move all its cursors there
+    let page_end = req.limit.and_then(|limit| {
+        let limit = limit as usize;
+        [
+            page_end(limit, notes.iter().map(|n| n.revision)),
+            page_end(limit, notebooks.iter().map(|nb| nb.revision)),
+            page_end(limit, tags.iter().map(|t| t.revision)),
+            page_end(limit, reminders.iter().map(|r| r.revision)),
+            page_end(limit, deleted.iter().map(|d| d.revision)),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    });
+    let page_revision = page_end.map_or(server_revision, |end| end.min(server_revision));
+    notes.retain(|n| n.revision <= page_revision);
+    notebooks.retain(|nb| nb.revision <= page_revision);
+    tags.retain(|t| t.revision <= page_revision);
+    reminders.retain(|r| r.revision <= page_revision);
+    deleted.retain(|d| d.revision <= page_revision);
+
     tracing::info!(
-        "Returning {} notes, {} notebooks, {} tags (server rev: {})",
+        "Returning {} notes, {} notebooks, {} tags, {} reminders, {} deletions (server rev: {})",
         notes.len(),
         notebooks.len(),
         tags.len(),
+        reminders.len(),
+        deleted.len(),
         server_revision
     );
 
-    Ok(Json(PullResponse {
+    // What this page returns may never get merged, so the device counts as
+    // synced only through the cursors it sent: those pages are in
+    if !req.dry_run {
+        state.db.record_pull(&req.device_id, since.min())?;
+    }
+    METRICS.pull();
+
+    let response = PullResponse {
         notes,
         notebooks,
         tags,
+        reminders,
+        deleted,
         server_revision,
-    }))
+        has_more: page_end.is_some(),
+        page_revision,
+    };
+    Ok(Json(checksum::sign(&response)?))
 }
 
+/// Revision of the last row that fits in a page, when there are more rows
+/// than `limit` (revisions come oldest first)
+fn page_end(limit: usize, revisions: impl Iterator<Item = i64>) -> Option<i64> {
+    let revisions: Vec<i64> = revisions.take(limit + 1).collect();
+    if revisions.len() > limit {
+        Some(revisions[limit - 1])
+    } else {
+        None
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/sync/push", tag = "sync", request_body = PushRequest,
+    responses(
+        (status = 200, description = "Outcome per entity, with a `checksum` field", body = PushResponse),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Entities failed validation; nothing was stored", body = ErrorBody),
+        (status = 429, body = ErrorBody),
+    )
+)]
 pub async fn push(
     State(state): State<AppState>,
-    Json(req): Json<PushRequest>,
-) -> Result<Json<PushResponse>> {
+    Json(body): Json<Value>,
+) -> Result<Json<Value>> {
+    let req: PushRequest = checksum::verified(body)?;
     tracing::info!(
-        "Push request from device {}: {} notes, {} notebooks, {} tags",
+        "Push request from device {}: {} notes, {} notebooks, {} tags, {} reminders, {} deletions",
         req.device_id,
         req.notes.len(),
         req.notebooks.len(),
-        req.tags.len()
+        req.tags.len(),
+        req.reminders.len(),
+        req.deleted.len()
     );
 
-    let mut conflicts = Vec::new();
-    let mut accepted = 0;
-
-    // Process notes
-    for note in &req.notes {
-        let (had_conflict, server_rev) = state.db.upsert_note(note)?;
-        if had_conflict {
-            conflicts.push(Conflict {
-                entity_type: "note".to_string(),
-                entity_id: note.id.clone(),
-                local_revision: note.revision,
-                server_revision: server_rev,
-                resolution: "server_wins".to_string(),
-            });
-        } else {
-            accepted += 1;
-        }
+    if req.device_id.trim().is_empty() {
+        return Err(AppError::BadRequest("device_id must not be empty".to_string()));
     }
 
-    // Process notebooks
-    for notebook in &req.notebooks {
-        let (had_conflict, server_rev) = state.db.upsert_notebook(notebook)?;
-        if had_conflict {
-            conflicts.push(Conflict {
-                entity_type: "notebook".to_string(),
-                entity_id: notebook.id.clone(),
-                local_revision: notebook.revision,
-                server_revision: server_rev,
-                resolution: "server_wins".to_string(),
-            });
-        } else {
-            accepted += 1;
-        }
+    // Refuse the whole push rather than store part of it
+    let rejected = validation::push_request(&req, state.max_content_bytes);
+    if !rejected.is_empty() {
+        tracing::warn!("Rejecting push from device {}: {} invalid entities", req.device_id, rejected.len());
+        METRICS.rejected_push();
+        return Err(AppError::Rejected(rejected));
     }
 
-    // Process tags
-    for tag in &req.tags {
-        let (had_conflict, server_rev) = state.db.upsert_tag(tag)?;
-        if had_conflict {
-            conflicts.push(Conflict {
-                entity_type: "tag".to_string(),
-                entity_id: tag.id.clone(),
-                local_revision: tag.revision,
-                server_revision: server_rev,
-                resolution: "server_wins".to_string(),
-            });
-        } else {
-            accepted += 1;
+    let response = state.db.apply_push(&req)?;
+    state.db.record_push(&req.device_id)?;
+    METRICS.push(&response);
+    if response.accepted > 0 {
+        // Nobody listening is fine
+        let _ = state.revisions.send(response.server_revision);
+    }
+    // Results come in push order, deletions last
+    let deleted = req
+        .notes
+        .iter()
+        .map(|n| n.is_deleted)
+        .chain(req.notebooks.iter().map(|nb| nb.is_deleted))
+        .chain(req.tags.iter().map(|t| t.is_deleted))
+        .chain(req.reminders.iter().map(|r| r.is_deleted))
+        .chain(req.deleted.iter().map(|_| true));
+    for (result, deleted) in response.results.iter().zip(deleted) {
+        if matches!(result.status.as_str(), "accepted" | "deleted") {
+            publish(&state, &result.entity_type, &result.id, result.server_revision, deleted);
         }
     }
 
-    let server_revision = state.db.get_global_revision()?;
-
     tracing::info!(
         "Push complete: {} accepted, {} conflicts, server rev: {}",
-        accepted,
-        conflicts.len(),
-        server_revision
+        response.accepted,
+        response.conflicts.len(),
+        response.server_revision
     );
 
-    Ok(Json(PushResponse {
-        accepted,
-        conflicts,
-        server_revision,
+    Ok(Json(checksum::sign(&response)?))
+}
+
+/// Server-sent events: a `revision` event carrying the new global revision
+/// after every push that changed something, so devices pull right away
+/// instead of waiting for their next sync
+#[utoipa::path(
+    get, path = "/api/sync/events", tag = "sync",
+    responses((status = 200, description = "`revision` events", content_type = "text/event-stream", body = String))
+)]
+pub async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    let receiver = state.revisions.subscribe();
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(revision) => {
+                    let event = Event::default().event("revision").data(revision.to_string());
+                    return Some((Ok(event), receiver));
+                }
+                // Missed events only carried older revisions than the next one
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Tell change feed subscribers, if any, about an entity's new revision
+fn publish(state: &AppState, entity_type: &str, entity_id: &str, revision: i64, deleted: bool) {
+    let _ = state.changes.send(Change {
+        entity_type: entity_type.to_string(),
+        entity_id: entity_id.to_string(),
+        revision,
+        deleted,
+    });
+}
+
+/// What a change feed stream still has to send
+struct Feed {
+    db: Arc<Database>,
+    receiver: broadcast::Receiver<Change>,
+    /// Read from the database, sent before any live change
+    backlog: VecDeque<Change>,
+    /// Revision of the last change sent; older live ones are repeats
+    sent: i64,
+}
+
+/// Server-sent events: a `change` event per entity reaching a new revision,
+/// with the revision as event id. A client reconnecting with `Last-Event-ID`
+/// first gets what it missed, from the database.
+#[utoipa::path(
+    get, path = "/api/changes/stream", tag = "sync",
+    params(("Last-Event-ID" = Option<i64>, Header, description = "Revision to replay changes after")),
+    responses(
+        (status = 200, description = "`change` events", content_type = "text/event-stream", body = Change),
+        (status = 400, body = ErrorBody),
+    )
+)]
+pub async fn change_stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .map(|value| value.to_str().ok().and_then(|value| value.trim().parse::<i64>().ok()))
+        .map(|revision| revision.ok_or_else(|| AppError::BadRequest("Last-Event-ID must be a revision".to_string())))
+        .transpose()?;
+
+    // Subscribe before reading the backlog, so nothing falls in between
+    let receiver = state.changes.subscribe();
+    let sent = match last_event_id {
+        Some(revision) => revision,
+        None => state.db.get_global_revision()?,
+    };
+    let feed = Feed {
+        backlog: state.db.changes_since(sent)?.into(),
+        db: state.db.clone(),
+        receiver,
+        sent,
+    };
+
+    let stream = futures_util::stream::unfold(feed, |mut feed| async move {
+        loop {
+            let change = match feed.backlog.pop_front() {
+                Some(change) => change,
+                None => match feed.receiver.recv().await {
+                    // Revisions commit in order but may be published out of
+                    // order; a gap means one is yet to come
+                    Ok(change) if change.revision <= feed.sent + 1 => change,
+                    // Catch up from the database; on failure the client
+                    // reconnects with the last id it got
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        feed.backlog = feed.db.changes_since(feed.sent).ok()?.into();
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+            if change.revision <= feed.sent {
+                continue;
+            }
+            feed.sent = change.revision;
+            let event = Event::default()
+                .event("change")
+                .id(change.revision.to_string())
+                .json_data(&change)
+                .unwrap_or_default();
+            return Some((Ok(event), feed));
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn check_list_query(query: &ListQuery) -> Result<()> {
+    if query.since < 0 {
+        return Err(AppError::BadRequest("since must not be negative".to_string()));
+    }
+    if matches!(query.limit, Some(limit) if limit <= 0) {
+        return Err(AppError::BadRequest("limit must be positive".to_string()));
+    }
+    if query.offset < 0 {
+        return Err(AppError::BadRequest("offset must not be negative".to_string()));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get, path = "/api/notes", tag = "entities", params(ListQuery),
+    responses((status = 200, body = ListResponse<Note>), (status = 400, body = ErrorBody))
+)]
+pub async fn list_notes(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<Note>>> {
+    check_list_query(&query)?;
+    let (notes, total) = state.db.list_notes(&query)?;
+    Ok(Json(ListResponse::new(notes, total, &query)))
+}
+
+/// A note that isn't deleted
+fn live_note(state: &AppState, id: &str) -> Result<Note> {
+    match state.db.get_note(id)? {
+        Some(note) if !note.is_deleted => Ok(note),
+        _ => Err(AppError::NotFound(format!("note {}", id))),
+    }
+}
+
+/// Validate and store a note written through the notes endpoints, the same
+/// way a push would, and tell listening devices to pull
+fn save_note(state: &AppState, mut note: Note) -> Result<Note> {
+    let rejected = validation::note(&note, state.max_content_bytes);
+    if !rejected.is_empty() {
+        return Err(AppError::Rejected(rejected));
+    }
+    let (had_conflict, revision) = state.db.upsert_note(&note)?;
+    if had_conflict {
+        return Err(AppError::Conflict(format!("note {} changed while it was being saved", note.id)));
+    }
+    note.revision = revision;
+    let _ = state.revisions.send(revision);
+    publish(state, "note", &note.id, revision, note.is_deleted);
+    Ok(note)
+}
+
+#[utoipa::path(
+    get, path = "/api/notes/{id}", tag = "entities", params(("id" = String, Path)),
+    responses((status = 200, body = Note), (status = 404, body = ErrorBody))
+)]
+pub async fn get_note(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<Note>> {
+    Ok(Json(live_note(&state, &id)?))
+}
+
+#[utoipa::path(
+    post, path = "/api/notes", tag = "entities", request_body = CreateNoteRequest,
+    responses(
+        (status = 201, body = Note),
+        (status = 409, description = "A note with the id exists", body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+pub async fn create_note(
+    State(state): State<AppState>,
+    Json(req): Json<CreateNoteRequest>,
+) -> Result<(StatusCode, Json<Note>)> {
+    let id = req.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if state.db.get_note(&id)?.is_some() {
+        return Err(AppError::Conflict(format!("note {} already exists", id)));
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    let note = Note {
+        id,
+        title: req.title,
+        content: req.content,
+        notebook_id: req.notebook_id,
+        tags: serde_json::to_string(&req.tags).map_err(|e| AppError::Internal(e.to_string()))?,
+        status: req.status.unwrap_or_else(|| "active".to_string()),
+        is_pinned: req.is_pinned,
+        pinned_order: None,
+        created_at: now.clone(),
+        updated_at: now,
+        revision: 1,
+        is_deleted: false,
+        deleted_at: None,
+        encrypted: false,
+    };
+    Ok((StatusCode::CREATED, Json(save_note(&state, note)?)))
+}
+
+#[utoipa::path(
+    put, path = "/api/notes/{id}", tag = "entities", params(("id" = String, Path)), request_body = UpdateNoteRequest,
+    responses(
+        (status = 200, body = Note),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "The note moved past `revision`, or is encrypted", body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+pub async fn update_note(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateNoteRequest>,
+) -> Result<Json<Note>> {
+    let mut note = live_note(&state, &id)?;
+    if matches!(req.revision, Some(revision) if revision != note.revision) {
+        return Err(AppError::Conflict(format!("note {} is at revision {}", id, note.revision)));
+    }
+    // Only devices hold the key an encrypted note's text needs
+    if note.encrypted && (req.title.is_some() || req.content.is_some()) {
+        return Err(AppError::Conflict(format!("note {} is encrypted; its title and content can't be changed here", id)));
+    }
+
+    if let Some(title) = req.title {
+        note.title = title;
+    }
+    if let Some(content) = req.content {
+        note.content = content;
+    }
+    if let Some(notebook_id) = req.notebook_id {
+        note.notebook_id = notebook_id;
+    }
+    if let Some(tags) = req.tags {
+        note.tags = serde_json::to_string(&tags).map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    if let Some(status) = req.status {
+        note.status = status;
+    }
+    if let Some(is_pinned) = req.is_pinned {
+        note.is_pinned = is_pinned;
+    }
+    note.updated_at = chrono::Utc::now().to_rfc3339();
+    // Past the stored revision, so the upsert takes it like a newer push
+    note.revision += 1;
+    Ok(Json(save_note(&state, note)?))
+}
+
+/// Soft delete, so devices pull the deletion like one made on a device
+#[utoipa::path(
+    delete, path = "/api/notes/{id}", tag = "entities", params(("id" = String, Path)),
+    responses((status = 204), (status = 404, body = ErrorBody))
+)]
+pub async fn delete_note(State(state): State<AppState>, Path(id): Path<String>) -> Result<StatusCode> {
+    let mut note = live_note(&state, &id)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    note.is_deleted = true;
+    note.deleted_at = Some(now.clone());
+    note.updated_at = now;
+    note.revision += 1;
+    save_note(&state, note)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get, path = "/api/notebooks", tag = "entities", params(ListQuery),
+    responses((status = 200, body = ListResponse<Notebook>), (status = 400, body = ErrorBody))
+)]
+pub async fn list_notebooks(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<Notebook>>> {
+    check_list_query(&query)?;
+    let (notebooks, total) = state.db.list_notebooks(&query)?;
+    Ok(Json(ListResponse::new(notebooks, total, &query)))
+}
+
+#[utoipa::path(
+    get, path = "/api/tags", tag = "entities", params(ListQuery),
+    responses((status = 200, body = ListResponse<Tag>), (status = 400, body = ErrorBody))
+)]
+pub async fn list_tags(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<Tag>>> {
+    check_list_query(&query)?;
+    let (tags, total) = state.db.list_tags(&query)?;
+    Ok(Json(ListResponse::new(tags, total, &query)))
+}
+
+/// Prometheus metrics; only routed when enabled in the config
+#[utoipa::path(
+    get, path = "/metrics", tag = "health", security(("admin_token" = [])),
+    responses((status = 200, description = "Prometheus text format", content_type = "text/plain", body = String))
+)]
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse> {
+    if state.metrics_require_token {
+        require_admin(&state, &headers)?;
+    }
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], METRICS.render()))
+}
+
+/// Reject requests without the admin bearer token
+fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<()> {
+    let Some(expected) = &state.admin_token else {
+        return Err(AppError::Unauthorized("admin endpoints are disabled; set an admin token".to_string()));
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if given != Some(expected.as_str()) {
+        return Err(AppError::Unauthorized("invalid admin token".to_string()));
+    }
+    Ok(())
+}
+
+/// How much deleted data a purge could drop
+#[utoipa::path(
+    get, path = "/api/admin/stats", tag = "admin", params(StatsQuery), security(("admin_token" = [])),
+    responses((status = 200, body = AdminStats), (status = 401, body = ErrorBody))
+)]
+pub async fn admin_stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<AdminStats>> {
+    require_admin(&state, &headers)?;
+    let older_than_days = query.older_than_days.unwrap_or(state.purge_min_days);
+    let (rows, deleted, purgeable) = state.db.deletion_stats(older_than_days)?;
+    Ok(Json(AdminStats {
+        rows,
+        deleted,
+        purgeable,
+        synced_through: state.db.get_synced_through()?,
+        purge_min_days: state.purge_min_days,
     }))
 }
 
-pub async fn list_notes(State(state): State<AppState>) -> Result<Json<Vec<Note>>> {
-    let notes = state.db.get_all_notes()?;
-    Ok(Json(notes))
+/// Hard-delete what was deleted more than `older_than_days` ago. The window
+/// can't be shorter than the safety margin, so every device has had the
+/// chance to pull the deletions.
+#[utoipa::path(
+    post, path = "/api/admin/purge", tag = "admin", request_body = PurgeRequest, security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Rows dropped per table", body = TableCounts),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+pub async fn admin_purge(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PurgeRequest>,
+) -> Result<Json<TableCounts>> {
+    require_admin(&state, &headers)?;
+    if req.older_than_days < state.purge_min_days {
+        return Err(AppError::BadRequest(format!(
+            "older_than_days must be at least {}",
+            state.purge_min_days
+        )));
+    }
+    let purged = state.db.purge_deleted(req.older_than_days)?;
+    tracing::info!("Purged deleted rows older than {} days: {:?}", req.older_than_days, purged);
+    Ok(Json(purged))
+}
+
+/// Shrink the database back down after heavy churn
+#[utoipa::path(
+    post, path = "/api/admin/vacuum", tag = "admin", security(("admin_token" = [])),
+    responses(
+        (status = 200, body = VacuumReport),
+        (status = 401, body = ErrorBody),
+        (status = 409, description = "Another maintenance operation is running", body = ErrorBody),
+    )
+)]
+pub async fn admin_vacuum(State(state): State<AppState>, headers: HeaderMap) -> Result<Json<VacuumReport>> {
+    require_admin(&state, &headers)?;
+    let report = blocking(&state, Database::vacuum).await?;
+    tracing::info!("Vacuumed the database: {:?}", report);
+    Ok(Json(report))
+}
+
+#[utoipa::path(
+    post, path = "/api/admin/integrity-check", tag = "admin", security(("admin_token" = [])),
+    responses(
+        (status = 200, body = IntegrityReport),
+        (status = 401, body = ErrorBody),
+        (status = 409, description = "Another maintenance operation is running", body = ErrorBody),
+    )
+)]
+pub async fn admin_integrity_check(State(state): State<AppState>, headers: HeaderMap) -> Result<Json<IntegrityReport>> {
+    require_admin(&state, &headers)?;
+    let report = blocking(&state, Database::integrity_check).await?;
+    if !report.ok {
+        tracing::warn!("Integrity check found problems: {:?}", report);
+    }
+    Ok(Json(report))
+}
+
+/// A backup in the temp directory, removed once dropped
+struct TempBackup(PathBuf);
+
+impl Drop for TempBackup {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            tracing::warn!("Failed to remove backup {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// A consistent copy of the database file, e.g. for a nightly
+/// `curl -OJ` with the admin token
+#[utoipa::path(
+    get, path = "/api/admin/backup", tag = "admin", security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "The SQLite database, named for the time it was taken"),
+        (status = 401, body = ErrorBody),
+        (status = 409, description = "Another backup is running", body = ErrorBody),
+    )
+)]
+pub async fn admin_backup(State(state): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse> {
+    require_admin(&state, &headers)?;
+    let running = state
+        .backup
+        .clone()
+        .try_lock_owned()
+        .map_err(|_| AppError::Conflict("a backup is already running".to_string()))?;
+
+    let temp = TempBackup(std::env::temp_dir().join(format!("viny-backup-{}.db", uuid::Uuid::new_v4())));
+    let dest = temp.0.clone();
+    blocking(&state, move |db| db.backup(&dest)).await?;
+    let file = tokio::fs::File::open(&temp.0).await.map_err(|e| AppError::Internal(e.to_string()))?;
+    let length = file.metadata().await.map_err(|e| AppError::Internal(e.to_string()))?.len();
+    tracing::info!("Sending a {} byte backup", length);
+
+    // The stream owns the temp file and the lock, so both go once the
+    // download finishes or the client hangs up
+    let stream = ReaderStream::new(file).map(move |chunk| {
+        let _ = (&temp, &running);
+        chunk
+    });
+    let filename = format!("viny-server-{}.db", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+            (header::CONTENT_LENGTH, length.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(stream),
+    ))
+}
+
+/// Run a slow database operation off the async runtime
+async fn blocking<T: Send + 'static>(
+    state: &AppState,
+    operation: impl FnOnce(&Database) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let db = state.db.clone();
+    tokio::task::spawn_blocking(move || operation(&db))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
 }
 
-pub async fn list_notebooks(State(state): State<AppState>) -> Result<Json<Vec<Notebook>>> {
-    let notebooks = state.db.get_all_notebooks()?;
-    Ok(Json(notebooks))
+#[utoipa::path(
+    get, path = "/api/reminders", tag = "entities", params(ListQuery),
+    responses((status = 200, body = ListResponse<Reminder>), (status = 400, body = ErrorBody))
+)]
+pub async fn list_reminders(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<Reminder>>> {
+    check_list_query(&query)?;
+    let (reminders, total) = state.db.list_reminders(&query)?;
+    Ok(Json(ListResponse::new(reminders, total, &query)))
+}
+
+#[utoipa::path(
+    get, path = "/api/devices", tag = "admin",
+    responses((status = 200, body = Vec<Device>))
+)]
+pub async fn list_devices(State(state): State<AppState>) -> Result<Json<Vec<Device>>> {
+    let devices = state.db.get_devices()?;
+    Ok(Json(devices))
+}
+
+/// Forget a device so its cursor stops holding back purges
+#[utoipa::path(
+    delete, path = "/api/devices/{device_id}", tag = "admin", params(("device_id" = String, Path)), security(("admin_token" = [])),
+    responses((status = 200), (status = 401, body = ErrorBody), (status = 404, body = ErrorBody))
+)]
+pub async fn remove_device(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>> {
+    require_admin(&state, &headers)?;
+    if !state.db.remove_device(&device_id)? {
+        return Err(AppError::NotFound(format!("device {}", device_id)));
+    }
+    Ok(Json(serde_json::json!({ "removed": device_id })))
 }
 
-pub async fn list_tags(State(state): State<AppState>) -> Result<Json<Vec<Tag>>> {
-    let tags = state.db.get_all_tags()?;
-    Ok(Json(tags))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const TOKEN: &str = "test-admin-token";
+
+    fn state(dir: &TempDir) -> AppState {
+        let path = dir.path().join("server.db");
+        AppState {
+            db: Arc::new(Database::new(path.to_str().unwrap()).unwrap()),
+            revisions: broadcast::channel(16).0,
+            changes: broadcast::channel(16).0,
+            admin_token: Some(TOKEN.to_string()),
+            purge_min_days: 30,
+            max_content_bytes: 1024 * 1024,
+            metrics_require_token: false,
+            backup: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    fn admin() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("Bearer {}", TOKEN).parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_health_details_degrades_instead_of_failing() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+        let Json(details) = health_details(State(state.clone()), admin()).await.unwrap();
+        assert_eq!((details.status.as_str(), details.writable), ("ok", true));
+        assert!(details.errors.is_empty());
+
+        // A table gone missing breaks the row counts, and only them
+        let conn = rusqlite::Connection::open(dir.path().join("server.db")).unwrap();
+        conn.execute_batch("DROP TABLE reminders").unwrap();
+        let Json(details) = health_details(State(state), admin()).await.unwrap();
+        assert_eq!(details.status, "degraded");
+        assert!(details.rows.is_none());
+        assert_eq!(details.revision, Some(0));
+        assert!(details.writable);
+        assert_eq!(details.errors.len(), 1);
+        assert!(details.errors[0].contains("reminders"), "{:?}", details.errors);
+    }
 }
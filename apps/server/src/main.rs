@@ -1,62 +1,170 @@
+mod checksum;
+mod config;
 mod db;
 mod error;
 mod handlers;
+mod metrics;
 mod models;
+mod openapi;
+mod ratelimit;
+mod validation;
 
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
+use tokio::sync::broadcast;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use crate::config::Config;
 use crate::db::Database;
+use crate::models::Change;
+use crate::ratelimit::RateLimiter;
 
 #[derive(Clone)]
 pub struct AppState {
     db: Arc<Database>,
+    /// The global revision after each push that changed something
+    revisions: broadcast::Sender<i64>,
+    /// Every entity that reached a new revision, for the change feed
+    changes: broadcast::Sender<Change>,
+    /// Bearer token for the admin endpoints; they refuse every request without one
+    admin_token: Option<String>,
+    /// Smallest `older_than_days` a purge accepts, so devices that sync
+    /// rarely still get to pull the deletions first
+    purge_min_days: i64,
+    /// Largest note content a push may carry, in bytes
+    max_content_bytes: usize,
+    /// Whether `/metrics` asks for the admin token
+    metrics_require_token: bool,
+    /// Held while a backup is taken and downloaded, so only one runs
+    backup: Arc<tokio::sync::Mutex<()>>,
 }
 
 #[tokio::main]
 async fn main() {
+    // Load configuration before anything else, so a bad setting stops startup
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("viny-server: {e}");
+            std::process::exit(1);
+        }
+    };
+
     // Initialize tracing
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
+        .with(tracing_subscriber::EnvFilter::new(&config.log_level))
         .with(tracing_subscriber::fmt::layer())
         .init();
+    tracing::info!("Effective configuration: {:?}", config);
 
     // Initialize database
-    let db = Database::new("viny-server.db").expect("Failed to initialize database");
-    let state = AppState { db: Arc::new(db) };
+    let db = Database::new(&config.database_path).expect("Failed to initialize database");
+    let (revisions, _) = broadcast::channel(16);
+    let (changes, _) = broadcast::channel(256);
+    let state = AppState {
+        db: Arc::new(db),
+        revisions,
+        changes,
+        admin_token: config.admin_token.clone(),
+        purge_min_days: config.purge_min_days,
+        max_content_bytes: config.max_content_bytes,
+        metrics_require_token: config.metrics_require_token,
+        backup: Arc::new(tokio::sync::Mutex::new(())),
+    };
 
-    // CORS configuration
+    // CORS configuration; validated by Config::load
+    let origins = config.allowed_origins().expect("origins were validated");
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(if origins.is_empty() {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(origins)
+        })
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // Sync endpoints, limited per client address
+    let sync = Router::new()
+        .route("/api/sync/pull", post(handlers::pull))
+        .route("/api/sync/push", post(handlers::push))
+        .route("/api/sync/events", get(handlers::events))
+        .route("/api/sync/revision", get(handlers::revision));
+    let sync = if config.rate_limit_per_minute > 0 {
+        let limiter = Arc::new(RateLimiter::new(config.rate_limit_per_minute, config.rate_limit_burst));
+        sync.route_layer(middleware::from_fn_with_state(limiter, ratelimit::limit))
+    } else {
+        sync
+    };
+
     // Build router
     let app = Router::new()
         // Health check
         .route("/health", get(handlers::health))
-        // Sync endpoints
-        .route("/api/sync/pull", post(handlers::pull))
-        .route("/api/sync/push", post(handlers::push))
+        .route("/health/details", get(handlers::health_details))
+        .merge(sync)
         // Entity endpoints
-        .route("/api/notes", get(handlers::list_notes))
+        .route("/api/notes", get(handlers::list_notes).post(handlers::create_note))
+        .route(
+            "/api/notes/{id}",
+            get(handlers::get_note).put(handlers::update_note).delete(handlers::delete_note),
+        )
         .route("/api/notebooks", get(handlers::list_notebooks))
         .route("/api/tags", get(handlers::list_tags))
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
-
-    let addr = "0.0.0.0:3000";
-    tracing::info!("Starting server on {}", addr);
+        .route("/api/reminders", get(handlers::list_reminders))
+        .route("/api/devices", get(handlers::list_devices))
+        .route("/api/devices/{device_id}", delete(handlers::remove_device))
+        // Change feed
+        .route("/api/changes/stream", get(handlers::change_stream))
+        // Admin endpoints
+        .route("/api/admin/stats", get(handlers::admin_stats))
+        .route("/api/admin/purge", post(handlers::admin_purge))
+        .route("/api/admin/vacuum", post(handlers::admin_vacuum))
+        .route("/api/admin/integrity-check", post(handlers::admin_integrity_check))
+        .route("/api/admin/backup", get(handlers::admin_backup));
+    let app = if config.api_docs {
+        app.route("/api/openapi.json", get(openapi::openapi_json))
+            .route("/api/docs", get(openapi::docs))
+    } else {
+        app
+    };
+    let app = if config.metrics {
+        app.route("/metrics", get(handlers::metrics))
+            .route_layer(middleware::from_fn(metrics::track_requests))
+    } else {
+        app
+    };
+    let app = app.layer(DefaultBodyLimit::max(config.max_body_bytes)).layer(cors);
+    // Full pulls are large and compress well
+    let app = if config.compression {
+        app.layer(CompressionLayer::new())
+    } else {
+        app
+    };
+    let app = app.layer(TraceLayer::new_for_http()).with_state(state);
 
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let addr = config.socket_addr().expect("bind address was validated");
+    match config.tls().expect("TLS files were validated") {
+        Some(tls) => {
+            tracing::info!("Starting server on https://{}", addr);
+            let tls = axum_server::tls_rustls::RustlsConfig::from_config(Arc::new(tls));
+            axum_server::bind_rustls(addr, tls)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        }
+        None => {
+            tracing::info!("Starting server on http://{} (no TLS configured)", addr);
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        }
+    }
 }
@@ -0,0 +1,160 @@
+//! Sync counters and latency histograms, served in the Prometheus text format
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::models::PushResponse;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Upper bounds of the latency buckets, in seconds
+const BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; the last one is `+Inf`
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = BUCKETS.iter().position(|&le| seconds <= le).unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (le, count) in BUCKETS.iter().map(|le| le.to_string()).chain(["+Inf".to_string()]).zip(self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {cumulative}");
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    pulls: AtomicU64,
+    pushes: AtomicU64,
+    rejected_pushes: AtomicU64,
+    conflicts: AtomicU64,
+    accepted_notes: AtomicU64,
+    accepted_notebooks: AtomicU64,
+    accepted_tags: AtomicU64,
+    accepted_reminders: AtomicU64,
+    accepted_deletions: AtomicU64,
+    /// By method and route
+    requests: Mutex<BTreeMap<(String, String), Histogram>>,
+    /// By database operation
+    queries: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+/// Records how long a database operation took when dropped
+pub struct QueryTimer {
+    operation: &'static str,
+    started: Instant,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let mut queries = METRICS.queries.lock().unwrap();
+        queries.entry(self.operation).or_default().observe(self.started.elapsed());
+    }
+}
+
+/// Time a database operation until the returned timer goes out of scope
+pub fn query(operation: &'static str) -> QueryTimer {
+    QueryTimer {
+        operation,
+        started: Instant::now(),
+    }
+}
+
+/// Middleware timing every request by its route, so ids in paths don't
+/// make a series each
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    let mut requests = METRICS.requests.lock().unwrap();
+    requests.entry((method, route)).or_default().observe(started.elapsed());
+    response
+}
+
+impl Metrics {
+    pub fn pull(&self) {
+        self.pulls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn push(&self, response: &PushResponse) {
+        let counts = &response.accepted_counts;
+        self.pushes.fetch_add(1, Ordering::Relaxed);
+        self.conflicts.fetch_add(response.conflicts.len() as u64, Ordering::Relaxed);
+        self.accepted_notes.fetch_add(counts.notes as u64, Ordering::Relaxed);
+        self.accepted_notebooks.fetch_add(counts.notebooks as u64, Ordering::Relaxed);
+        self.accepted_tags.fetch_add(counts.tags as u64, Ordering::Relaxed);
+        self.accepted_reminders.fetch_add(counts.reminders as u64, Ordering::Relaxed);
+        self.accepted_deletions.fetch_add(counts.deleted as u64, Ordering::Relaxed);
+    }
+
+    pub fn rejected_push(&self) {
+        self.rejected_pushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: &AtomicU64| {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        };
+        counter(&mut out, "viny_pulls_total", "Pulls served.", &self.pulls);
+        counter(&mut out, "viny_pushes_total", "Pushes stored.", &self.pushes);
+        counter(&mut out, "viny_rejected_pushes_total", "Pushes refused by validation.", &self.rejected_pushes);
+        counter(&mut out, "viny_conflicts_total", "Pushed entities the server kept its own copy of.", &self.conflicts);
+
+        let _ = writeln!(out, "# HELP viny_accepted_entities_total Pushed entities stored.");
+        let _ = writeln!(out, "# TYPE viny_accepted_entities_total counter");
+        for (entity_type, value) in [
+            ("note", &self.accepted_notes),
+            ("notebook", &self.accepted_notebooks),
+            ("tag", &self.accepted_tags),
+            ("reminder", &self.accepted_reminders),
+            ("deletion", &self.accepted_deletions),
+        ] {
+            let value = value.load(Ordering::Relaxed);
+            let _ = writeln!(out, "viny_accepted_entities_total{{entity_type=\"{entity_type}\"}} {value}");
+        }
+
+        let name = "viny_request_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} HTTP request latency.\n# TYPE {name} histogram");
+        for ((method, route), histogram) in self.requests.lock().unwrap().iter() {
+            histogram.render(&mut out, name, &format!("method=\"{method}\",route=\"{route}\""));
+        }
+
+        let name = "viny_db_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} Database operation latency.\n# TYPE {name} histogram");
+        for (operation, histogram) in self.queries.lock().unwrap().iter() {
+            histogram.render(&mut out, name, &format!("operation=\"{operation}\""));
+        }
+        out
+    }
+}
@@ -1,7 +1,19 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Deserialize a field where an explicit `null` differs from an omitted
+/// one: omitted stays `None` (via `#[serde(default)]`), `null` becomes
+/// `Some(None)`
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
 
 // Sync models - same structure as desktop app
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Note {
     pub id: String,
     pub title: String,
@@ -9,25 +21,42 @@ pub struct Note {
     pub notebook_id: Option<String>,
     pub tags: String,
     pub status: String,
+    #[serde(default)]
+    pub is_pinned: bool,
+    #[serde(default)]
+    pub pinned_order: Option<i64>,
     pub created_at: String,
     pub updated_at: String,
     pub revision: i64,
     pub is_deleted: bool,
+    /// When it was deleted; clients that predate it only send `is_deleted`
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    /// Title and content are ciphertext from the device; stored as sent
+    #[serde(default)]
+    pub encrypted: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Notebook {
     pub id: String,
     pub name: String,
     pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
     pub parent_id: Option<String>,
     pub created_at: String,
     pub updated_at: String,
     pub revision: i64,
     pub is_deleted: bool,
+    /// Hidden from the notebook list; set by archiving on a device
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Tag {
     pub id: String,
     pub name: String,
@@ -36,49 +65,360 @@ pub struct Tag {
     pub updated_at: String,
     pub revision: i64,
     pub is_deleted: bool,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Reminder {
+    pub id: String,
+    pub note_id: Option<String>,
+    pub message: String,
+    pub due_date: String,
+    pub completed: bool,
+    pub notified: bool,
+    pub recurrence: Option<String>,
+    pub snoozed_from: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub revision: i64,
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+}
+
+/// Tombstone of a hard-deleted entity
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeletedEntity {
+    pub entity_type: String,
+    pub entity_id: String,
+    pub revision: i64,
+    pub deleted_at: String,
 }
 
 // Sync request/response
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PullRequest {
     pub device_id: String,
     pub last_sync_revision: i64,
+    /// Per-type revisions; `last_sync_revision` applies to every type when absent
+    #[serde(default)]
+    pub since: Option<SyncCursors>,
+    /// Most entities of each type to return; everything when absent
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Only looking (a sync preview): nothing is recorded, and `device_id`
+    /// may be empty
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct SyncCursors {
+    pub notes: i64,
+    pub notebooks: i64,
+    pub tags: i64,
+    pub reminders: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl SyncCursors {
+    pub fn all(revision: i64) -> Self {
+        Self {
+            notes: revision,
+            notebooks: revision,
+            tags: revision,
+            reminders: revision,
+        }
+    }
+
+    pub fn min(&self) -> i64 {
+        self.notes.min(self.notebooks).min(self.tags).min(self.reminders)
+    }
+}
+
+impl PullRequest {
+    pub fn cursors(&self) -> SyncCursors {
+        self.since.unwrap_or_else(|| SyncCursors::all(self.last_sync_revision))
+    }
+}
+
+/// Sent with a `checksum` field added, see `checksum::sign`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PullResponse {
     pub notes: Vec<Note>,
     pub notebooks: Vec<Notebook>,
     pub tags: Vec<Tag>,
+    pub reminders: Vec<Reminder>,
+    pub deleted: Vec<DeletedEntity>,
     pub server_revision: i64,
+    /// More changes are waiting past `page_revision`
+    pub has_more: bool,
+    /// Newest revision included; everything up to it has been returned
+    pub page_revision: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Checked against its `checksum` field when the client sends one
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PushRequest {
     pub device_id: String,
     pub notes: Vec<Note>,
     pub notebooks: Vec<Notebook>,
     pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
+    #[serde(default)]
+    pub deleted: Vec<DeletedEntity>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Sent with a `checksum` field added, see `checksum::sign`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PushResponse {
+    /// Total of `accepted_counts`, for clients that predate it
     pub accepted: usize,
+    pub accepted_counts: AcceptedCounts,
     pub conflicts: Vec<Conflict>,
+    /// What happened to each pushed entity, in push order
+    pub results: Vec<EntityResult>,
+    pub server_revision: i64,
+}
+
+/// Outcome of one pushed entity: `accepted`, `conflict` (the server kept its
+/// copy), or for deletions `deleted` or `skipped` (nothing left to delete)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EntityResult {
+    pub id: String,
+    pub entity_type: String,
+    pub status: String,
     pub server_revision: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl EntityResult {
+    pub fn new(entity_type: &str, id: &str, status: &str, server_revision: i64) -> Self {
+        Self {
+            id: id.to_string(),
+            entity_type: entity_type.to_string(),
+            status: status.to_string(),
+            server_revision,
+        }
+    }
+}
+
+/// Why a pushed entity was rejected; the push is refused with a list of these
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EntityError {
+    pub entity_type: String,
+    pub id: String,
+    pub field: String,
+    pub message: String,
+}
+
+/// Entities of each type a push stored; the rest come back as conflicts
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct AcceptedCounts {
+    pub notes: usize,
+    pub notebooks: usize,
+    pub tags: usize,
+    pub reminders: usize,
+    pub deleted: usize,
+}
+
+impl AcceptedCounts {
+    pub fn total(&self) -> usize {
+        self.notes + self.notebooks + self.tags + self.reminders + self.deleted
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Conflict {
     pub entity_type: String,
     pub entity_id: String,
     pub local_revision: i64,
     pub server_revision: i64,
     pub resolution: String,
+    /// The server's current copy of the entity, so the device can apply it
+    /// without another pull
+    #[schema(value_type = Option<Object>)]
+    pub server_copy: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    /// Window for `purgeable`; the purge safety margin when absent
+    pub older_than_days: Option<i64>,
+}
+
+/// Query of the list endpoints, e.g. `GET /api/notes?since=10&limit=50`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// Soft-deleted rows too
+    #[serde(default)]
+    pub include_deleted: bool,
+    /// Only rows changed after this revision
+    #[serde(default)]
+    pub since: i64,
+    /// Rows per page; all when absent
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// One page of a list endpoint
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListResponse<T> {
+    pub items: Vec<T>,
+    /// Rows matching the query across all pages
+    pub total: usize,
+    pub limit: Option<i64>,
+    pub offset: i64,
+}
+
+impl<T> ListResponse<T> {
+    pub fn new(items: Vec<T>, total: usize, query: &ListQuery) -> Self {
+        Self {
+            items,
+            total,
+            limit: query.limit,
+            offset: query.offset,
+        }
+    }
+}
+
+/// Body of `POST /api/notes`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateNoteRequest {
+    /// A fresh UUID when absent
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub notebook_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub is_pinned: bool,
+}
+
+/// Body of `PUT /api/notes/{id}`; absent fields are left as they are
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateNoteRequest {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    /// `null` moves the note out of its notebook
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<String>)]
+    pub notebook_id: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+    pub status: Option<String>,
+    pub is_pinned: Option<bool>,
+    /// The revision the change was made against; a 409 when the note has
+    /// moved on since
+    pub revision: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PurgeRequest {
+    pub older_than_days: i64,
+}
+
+/// A count per table; `deletions` is the tombstone table
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct TableCounts {
+    pub notes: usize,
+    pub notebooks: usize,
+    pub tags: usize,
+    pub reminders: usize,
+    pub deletions: usize,
+}
+
+/// An entity reaching a new revision, as sent by `/api/changes/stream`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Change {
+    pub entity_type: String,
+    pub entity_id: String,
+    pub revision: i64,
+    /// Soft-deleted, or gone for good
+    pub deleted: bool,
+}
+
+/// A device that has pulled or pushed
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Device {
+    pub device_id: String,
+    /// Owner, once the server has accounts; None until then
+    pub user_id: Option<String>,
+    /// Everything up to this revision has been pulled and merged by the
+    /// device, as of the cursors its last pull sent
+    pub last_pull_revision: i64,
+    pub last_push_at: Option<String>,
+    pub last_seen_at: String,
+    pub first_seen_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminStats {
+    /// Every row, deleted or not
+    pub rows: TableCounts,
+    /// Soft-deleted rows, and all tombstones
+    pub deleted: TableCounts,
+    /// Deleted longer ago than the purge safety margin and pulled by every
+    /// device, so a purge may drop them
+    pub purgeable: TableCounts,
+    /// Revision every known device has pulled up to; None before any pull
+    pub synced_through: Option<i64>,
+    /// Smallest `older_than_days` a purge accepts
+    pub purge_min_days: i64,
+}
+
+/// `POST /api/admin/vacuum`; sizes count the database file and its WAL
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VacuumReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// The whole WAL went back into the database; false when readers held
+    /// on to part of it
+    pub checkpointed: bool,
+    pub duration_ms: u64,
+}
+
+/// `POST /api/admin/integrity-check`; each check lists its problems, or
+/// just "ok"
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct IntegrityReport {
+    /// Both checks found nothing
+    pub ok: bool,
+    pub integrity_check: Vec<String>,
+    pub quick_check: Vec<String>,
+    pub duration_ms: u64,
+}
+
+/// `GET /health/details`; parts that failed are None and their errors listed
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthDetails {
+    /// "ok", or "degraded" when any check failed
+    pub status: String,
+    pub version: String,
+    pub revision: Option<i64>,
+    pub rows: Option<TableCounts>,
+    pub database_bytes: Option<i64>,
+    /// A write inside a rolled-back transaction succeeded
+    pub writable: bool,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RevisionResponse {
+    pub revision: i64,
+}
@@ -0,0 +1,115 @@
+//! OpenAPI description of the server, generated from the handlers and the
+//! models they take and return
+
+use axum::{response::Html, Json};
+use serde::Serialize;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::handlers;
+use crate::models::*;
+
+/// Body of every error response
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct ErrorBody {
+    pub error: String,
+    /// Why each entity failed validation; only with 422
+    pub rejected: Option<Vec<EntityError>>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Viny sync server", description = "Sync and REST API of the Viny notes server"),
+    paths(
+        handlers::health,
+        handlers::health_details,
+        handlers::pull,
+        handlers::push,
+        handlers::events,
+        handlers::revision,
+        handlers::change_stream,
+        handlers::list_notes,
+        handlers::create_note,
+        handlers::get_note,
+        handlers::update_note,
+        handlers::delete_note,
+        handlers::list_notebooks,
+        handlers::list_tags,
+        handlers::list_reminders,
+        handlers::list_devices,
+        handlers::remove_device,
+        handlers::admin_stats,
+        handlers::admin_purge,
+        handlers::admin_vacuum,
+        handlers::admin_integrity_check,
+        handlers::admin_backup,
+        handlers::metrics,
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&AdminToken),
+    tags(
+        (name = "sync", description = "Pull and push between devices"),
+        (name = "entities", description = "Reading and editing single entities"),
+        (name = "admin", description = "Maintenance; needs the admin bearer token"),
+        (name = "health"),
+    )
+)]
+pub struct ApiDoc;
+
+/// The `admin_token` scheme the admin endpoints refer to
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Swagger UI for `/api/openapi.json`, loaded from a CDN so the server
+/// doesn't have to bundle it
+pub async fn docs() -> Html<&'static str> {
+    Html(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>Viny sync server API</title>
+  <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>"##,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_parses_and_has_the_sync_routes() {
+        let json = ApiDoc::openapi().to_json().unwrap();
+        let spec: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        let paths = &spec["paths"];
+        assert!(paths["/api/sync/pull"]["post"].is_object());
+        assert!(paths["/api/sync/push"]["post"].is_object());
+        assert!(paths["/api/sync/revision"]["get"].is_object());
+        for schema in ["PullRequest", "PullResponse", "PushRequest", "PushResponse", "Note"] {
+            assert!(spec["components"]["schemas"][schema].is_object(), "{} is missing", schema);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Token buckets per client address in front of the sync endpoints, so a
+//! client stuck retrying can't take the server down
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::AppError;
+
+/// Buckets kept before full ones are dropped; a full bucket is the same as none
+const MAX_TRACKED: usize = 4096;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Most tokens a bucket holds, i.e. the burst allowed after a quiet spell
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            rate: f64::from(per_minute) / 60.0,
+            burst: f64::from(burst),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `client`, or say how long until one is free
+    fn check(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED && !buckets.contains_key(&client) {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst);
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+/// Middleware answering 429 with `Retry-After` once a client's bucket is empty
+pub async fn limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match limiter.check(addr.ip(), Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            tracing::warn!("Rate limiting {} on {}", addr.ip(), request.uri().path());
+            AppError::RateLimited(wait).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_at_the_rate_up_to_the_burst() {
+        // One token every two seconds, three at most
+        let limiter = RateLimiter::new(30, 3);
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+        let at = |secs: f64| start + Duration::from_secs_f64(secs);
+
+        for _ in 0..3 {
+            assert!(limiter.check(client, start).is_ok());
+        }
+        assert_eq!(limiter.check(client, start), Err(Duration::from_secs(2)));
+        // Each client has its own bucket
+        assert!(limiter.check(other, start).is_ok());
+
+        // Half a token after one second: the wait is for the other half
+        assert_eq!(limiter.check(client, at(1.0)), Err(Duration::from_secs(1)));
+        assert!(limiter.check(client, at(2.0)).is_ok());
+        assert!(limiter.check(client, at(2.0)).is_err());
+
+        // A long quiet spell refills no further than the burst
+        for _ in 0..3 {
+            assert!(limiter.check(client, at(600.0)).is_ok());
+        }
+        assert!(limiter.check(client, at(600.0)).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Checks on pushed entities before anything is stored
+
+use crate::models::*;
+
+/// Longest accepted entity id, in bytes
+pub const MAX_ID_BYTES: usize = 128;
+/// Longest tag or notebook name, color, icon or recurrence rule, in bytes
+pub const MAX_NAME_BYTES: usize = 1024;
+/// Longest note title or reminder message, in bytes. Titles may be
+/// ciphertext, which is longer than the plain text.
+pub const MAX_TITLE_BYTES: usize = 16 * 1024;
+/// Longest tag list of a note, in bytes
+pub const MAX_TAGS_BYTES: usize = 64 * 1024;
+
+const NOTE_STATUSES: &[&str] = &["active", "archived", "trashed"];
+const ENTITY_TYPES: &[&str] = &["note", "notebook", "tag", "reminder"];
+
+/// Collects the problems of one entity at a time
+struct Checker<'a> {
+    errors: &'a mut Vec<EntityError>,
+    entity_type: &'static str,
+    id: &'a str,
+}
+
+impl Checker<'_> {
+    fn fail(&mut self, field: &str, message: String) {
+        self.errors.push(EntityError {
+            entity_type: self.entity_type.to_string(),
+            id: truncated(self.id).to_string(),
+            field: field.to_string(),
+            message,
+        });
+    }
+
+    fn id(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.fail(field, "must not be empty".to_string());
+        } else if value.len() > MAX_ID_BYTES {
+            self.fail(field, format!("is longer than {} bytes", MAX_ID_BYTES));
+        }
+    }
+
+    fn optional_id(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.id(field, value);
+        }
+    }
+
+    fn size(&mut self, field: &str, value: &str, max: usize) {
+        if value.len() > max {
+            self.fail(field, format!("is {} bytes, above the limit of {} bytes", value.len(), max));
+        }
+    }
+
+    fn optional_size(&mut self, field: &str, value: Option<&str>, max: usize) {
+        if let Some(value) = value {
+            self.size(field, value, max);
+        }
+    }
+
+    fn timestamp(&mut self, field: &str, value: &str) {
+        if !is_timestamp(value) {
+            self.fail(field, format!("{:?} is not a timestamp", truncated(value)));
+        }
+    }
+
+    fn optional_timestamp(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.timestamp(field, value);
+        }
+    }
+}
+
+/// RFC 3339, or the zoneless form SQLite's `datetime('now')` writes
+fn is_timestamp(value: &str) -> bool {
+    chrono::DateTime::parse_from_rfc3339(value).is_ok()
+        || ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .any(|format| chrono::NaiveDateTime::parse_from_str(value, format).is_ok())
+}
+
+/// Keeps error messages short when the bad value is huge
+fn truncated(value: &str) -> &str {
+    match value.char_indices().nth(64) {
+        Some((end, _)) => &value[..end],
+        None => value,
+    }
+}
+
+fn check_note(errors: &mut Vec<EntityError>, note: &Note, max_content_bytes: usize) {
+    let mut check = Checker { errors, entity_type: "note", id: &note.id };
+    check.id("id", &note.id);
+    check.size("title", &note.title, MAX_TITLE_BYTES);
+    check.size("content", &note.content, max_content_bytes);
+    check.optional_id("notebook_id", note.notebook_id.as_deref());
+    check.size("tags", &note.tags, MAX_TAGS_BYTES);
+    if !NOTE_STATUSES.contains(&note.status.as_str()) {
+        check.fail("status", format!("{:?} is not one of {:?}", truncated(&note.status), NOTE_STATUSES));
+    }
+    check.timestamp("created_at", &note.created_at);
+    check.timestamp("updated_at", &note.updated_at);
+    check.optional_timestamp("deleted_at", note.deleted_at.as_deref());
+}
+
+/// Every problem with one note, e.g. from the notes endpoints
+pub fn note(note: &Note, max_content_bytes: usize) -> Vec<EntityError> {
+    let mut errors = Vec::new();
+    check_note(&mut errors, note, max_content_bytes);
+    errors
+}
+
+/// Every problem with the pushed entities; empty when the push can be stored
+pub fn push_request(req: &PushRequest, max_content_bytes: usize) -> Vec<EntityError> {
+    let mut errors = Vec::new();
+
+    for note in &req.notes {
+        check_note(&mut errors, note, max_content_bytes);
+    }
+
+    for notebook in &req.notebooks {
+        let mut check = Checker { errors: &mut errors, entity_type: "notebook", id: &notebook.id };
+        check.id("id", &notebook.id);
+        check.size("name", &notebook.name, MAX_NAME_BYTES);
+        check.optional_size("color", notebook.color.as_deref(), MAX_NAME_BYTES);
+        check.optional_size("icon", notebook.icon.as_deref(), MAX_NAME_BYTES);
+        check.optional_id("parent_id", notebook.parent_id.as_deref());
+        check.timestamp("created_at", &notebook.created_at);
+        check.timestamp("updated_at", &notebook.updated_at);
+        check.optional_timestamp("deleted_at", notebook.deleted_at.as_deref());
+    }
+
+    for tag in &req.tags {
+        let mut check = Checker { errors: &mut errors, entity_type: "tag", id: &tag.id };
+        check.id("id", &tag.id);
+        check.size("name", &tag.name, MAX_NAME_BYTES);
+        check.optional_size("color", tag.color.as_deref(), MAX_NAME_BYTES);
+        check.timestamp("created_at", &tag.created_at);
+        check.timestamp("updated_at", &tag.updated_at);
+        check.optional_timestamp("deleted_at", tag.deleted_at.as_deref());
+    }
+
+    for reminder in &req.reminders {
+        let mut check = Checker { errors: &mut errors, entity_type: "reminder", id: &reminder.id };
+        check.id("id", &reminder.id);
+        check.optional_id("note_id", reminder.note_id.as_deref());
+        check.size("message", &reminder.message, MAX_TITLE_BYTES);
+        check.timestamp("due_date", &reminder.due_date);
+        check.optional_size("recurrence", reminder.recurrence.as_deref(), MAX_NAME_BYTES);
+        check.optional_timestamp("snoozed_from", reminder.snoozed_from.as_deref());
+        check.timestamp("created_at", &reminder.created_at);
+        check.timestamp("updated_at", &reminder.updated_at);
+        check.optional_timestamp("deleted_at", reminder.deleted_at.as_deref());
+    }
+
+    for deleted in &req.deleted {
+        let mut check = Checker { errors: &mut errors, entity_type: "deletion", id: &deleted.entity_id };
+        if !ENTITY_TYPES.contains(&deleted.entity_type.as_str()) {
+            let entity_type = truncated(&deleted.entity_type);
+            check.fail("entity_type", format!("{:?} is not one of {:?}", entity_type, ENTITY_TYPES));
+        }
+        check.id("entity_id", &deleted.entity_id);
+        check.timestamp("deleted_at", &deleted.deleted_at);
+    }
+
+    errors
+}
@@ -0,0 +1,65 @@
+//! Downloads a backup from a running server and checks it holds the data
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+use common::{Server, TOKEN};
+
+#[test]
+fn test_backup_downloads_a_consistent_copy() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    let client = &server.client;
+
+    for i in 0..3 {
+        let response = client
+            .post(format!("{}/api/notes", server.url))
+            .header("content-type", "application/json")
+            .body(format!(r#"{{"title": "Note {}", "content": "Body {}"}}"#, i, i))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 201);
+    }
+
+    let unauthorized = client.get(format!("{}/api/admin/backup", server.url)).send().unwrap();
+    assert_eq!(unauthorized.status(), 401);
+
+    let response = client
+        .get(format!("{}/api/admin/backup", server.url))
+        .bearer_auth(TOKEN)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let disposition = response.headers()["content-disposition"].to_str().unwrap().to_string();
+    assert!(disposition.starts_with("attachment; filename=\"viny-server-"), "{}", disposition);
+    let bytes = response.bytes().unwrap();
+    assert!(bytes.starts_with(b"SQLite format 3\0"));
+
+    let path = dir.path().join("backup.db");
+    std::fs::write(&path, &bytes).unwrap();
+    let conn = Connection::open(&path).unwrap();
+    let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+    assert_eq!(count("SELECT COUNT(*) FROM notes"), 3);
+    assert_eq!(count("SELECT COUNT(*) FROM notes WHERE title = 'Note 2'"), 1);
+    assert_eq!(count("SELECT global_revision FROM sync_state WHERE id = 1"), 3);
+    assert_eq!(conn.query_row("PRAGMA integrity_check", [], |row| row.get::<_, String>(0)).unwrap(), "ok");
+
+    // The temp copy on the server goes once the body is done, which can be
+    // just after the last bytes reach us
+    let leftovers = || {
+        std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("viny-backup-"))
+            .count()
+    };
+    let finished = Instant::now();
+    while leftovers() > 0 {
+        assert!(finished.elapsed() < Duration::from_secs(5), "the temp backup was left behind");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,53 @@
+//! The server-sent change feed
+
+mod common;
+
+use std::io::Read;
+use std::time::Duration;
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::{note, push, Server};
+
+/// Read `count` change events off the stream: (id, entity id)
+fn read_changes(stream: &mut reqwest::blocking::Response, count: usize) -> Vec<(i64, String)> {
+    let mut text = String::new();
+    let mut changes = Vec::new();
+    let mut buffer = [0; 1024];
+    while changes.len() < count {
+        let read = stream.read(&mut buffer).unwrap();
+        assert!(read > 0, "the stream ended after {:?}", changes);
+        text.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+        while let Some(end) = text.find("\n\n") {
+            let event: String = text.drain(..end + 2).collect();
+            let field = |name: &str| event.lines().find_map(|line| line.strip_prefix(name)).map(str::trim);
+            if field("event:") == Some("change") {
+                let change: Value = serde_json::from_str(field("data:").unwrap()).unwrap();
+                let id = field("id:").unwrap().parse().unwrap();
+                changes.push((id, change["entity_id"].as_str().unwrap().to_string()));
+            }
+        }
+    }
+    changes
+}
+
+#[test]
+fn test_reconnect_replays_changes_since_the_last_event_id() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    server.post_json("/api/sync/push", &push("writer", vec![note("a", 1), note("b", 1), note("c", 1)]));
+
+    let client = reqwest::blocking::Client::builder().timeout(Duration::from_secs(10)).build().unwrap();
+    let stream_url = format!("{}/api/changes/stream", server.url);
+    let mut stream = client.get(&stream_url).header("last-event-id", "1").send().unwrap();
+    assert_eq!(stream.status(), 200);
+    assert_eq!(read_changes(&mut stream, 2), vec![(2, "b".to_string()), (3, "c".to_string())]);
+
+    // Then it goes live
+    server.post_json("/api/sync/push", &push("writer", vec![note("d", 1)]));
+    assert_eq!(read_changes(&mut stream, 1), vec![(4, "d".to_string())]);
+
+    let bad = client.get(&stream_url).header("last-event-id", "yesterday").send().unwrap();
+    assert_eq!(bad.status(), 400);
+}
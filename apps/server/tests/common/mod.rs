@@ -0,0 +1,129 @@
+//! The server binary on a free port, for tests that talk to it over HTTP
+
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+pub const TOKEN: &str = "test-admin-token";
+
+/// A running server with its own database, killed when dropped
+pub struct Server {
+    child: Child,
+    pub url: String,
+    pub client: reqwest::blocking::Client,
+}
+
+impl Server {
+    pub fn start(dir: &TempDir) -> Self {
+        Self::start_with(dir, &[])
+    }
+
+    /// Start with extra `VINY_*` settings on top of the test defaults
+    pub fn start_with(dir: &TempDir, env: &[(&str, &str)]) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let child = Command::new(env!("CARGO_BIN_EXE_viny-server"))
+            .env_remove("VINY_CONFIG")
+            .env("VINY_DATABASE_PATH", dir.path().join("server.db"))
+            .env("VINY_BIND_ADDRESS", format!("127.0.0.1:{}", port))
+            .env("VINY_ADMIN_TOKEN", TOKEN)
+            .env("VINY_LOG_LEVEL", "warn")
+            .envs(env.iter().copied())
+            .spawn()
+            .unwrap();
+        let server = Self {
+            child,
+            url: format!("http://127.0.0.1:{}", port),
+            client: reqwest::blocking::Client::new(),
+        };
+
+        let started = Instant::now();
+        while reqwest::blocking::get(format!("{}/health", server.url)).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "server didn't start");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        server
+    }
+
+    pub fn post(&self, path: &str, body: &Value) -> reqwest::blocking::Response {
+        self.client
+            .post(format!("{}{}", self.url, path))
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()
+            .unwrap()
+    }
+
+    pub fn put(&self, path: &str, body: &Value) -> reqwest::blocking::Response {
+        self.client
+            .put(format!("{}{}", self.url, path))
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()
+            .unwrap()
+    }
+
+    pub fn delete(&self, path: &str) -> reqwest::blocking::Response {
+        self.client.delete(format!("{}{}", self.url, path)).send().unwrap()
+    }
+
+    pub fn get(&self, path: &str) -> reqwest::blocking::Response {
+        self.client.get(format!("{}{}", self.url, path)).send().unwrap()
+    }
+
+    /// POST `body` and return the JSON answer, which must be a 2xx
+    pub fn post_json(&self, path: &str, body: &Value) -> Value {
+        let response = self.post(path, body);
+        assert!(response.status().is_success(), "{} answered {}", path, response.status());
+        serde_json::from_str(&response.text().unwrap()).unwrap()
+    }
+
+    pub fn get_json(&self, path: &str) -> Value {
+        let response = self.get(path);
+        assert!(response.status().is_success(), "{} answered {}", path, response.status());
+        serde_json::from_str(&response.text().unwrap()).unwrap()
+    }
+
+    /// Create a note through the REST endpoint, returning it
+    pub fn create_note(&self, title: &str) -> Value {
+        self.post_json("/api/notes", &serde_json::json!({ "title": title, "content": "" }))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A note as devices push it
+pub fn note(id: &str, revision: i64) -> Value {
+    serde_json::json!({
+        "id": id,
+        "title": format!("Note {}", id),
+        "content": "",
+        "notebook_id": null,
+        "tags": "[]",
+        "status": "active",
+        "is_pinned": false,
+        "created_at": "2026-01-01T00:00:00Z",
+        "updated_at": "2026-01-01T00:00:00Z",
+        "revision": revision,
+        "is_deleted": false,
+    })
+}
+
+/// A push of `notes` from `device_id`
+pub fn push(device_id: &str, notes: Vec<Value>) -> Value {
+    serde_json::json!({ "device_id": device_id, "notes": notes, "notebooks": [], "tags": [] })
+}
+
+/// A pull from `device_id` with every cursor at `since`
+pub fn pull(device_id: &str, since: i64, limit: Option<i64>) -> Value {
+    serde_json::json!({ "device_id": device_id, "last_sync_revision": since, "limit": limit })
+}
@@ -0,0 +1,66 @@
+//! Compressed responses, and the setting that turns them off
+
+mod common;
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::{note, pull, push, Server};
+
+/// Pull everything asking for `encoding`; the answer's Content-Encoding and
+/// body as sent
+fn raw_pull(server: &Server, encoding: Option<&str>) -> (Option<String>, Vec<u8>) {
+    let mut request = server
+        .client
+        .post(format!("{}/api/sync/pull", server.url))
+        .header("content-type", "application/json")
+        .body(pull("reader", 0, None).to_string());
+    if let Some(encoding) = encoding {
+        request = request.header("accept-encoding", encoding);
+    }
+    let response = request.send().unwrap();
+    assert_eq!(response.status(), 200);
+    let content_encoding = response
+        .headers()
+        .get("content-encoding")
+        .map(|value| value.to_str().unwrap().to_string());
+    (content_encoding, response.bytes().unwrap().to_vec())
+}
+
+fn json(bytes: &[u8]) -> Value {
+    serde_json::from_slice(bytes).unwrap()
+}
+
+#[test]
+fn test_pulls_are_compressed_only_when_asked_and_enabled() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    let notes = (0..20).map(|i| note(&format!("n{}", i), 1)).collect();
+    server.post_json("/api/sync/push", &push("writer", notes));
+
+    let (encoding, plain) = raw_pull(&server, None);
+    assert_eq!(encoding, None);
+
+    // Decodes to the same page, in fewer bytes
+    let (encoding, gzipped) = raw_pull(&server, Some("gzip"));
+    assert_eq!(encoding.as_deref(), Some("gzip"));
+    assert!(gzipped.len() < plain.len());
+    let mut decoded = Vec::new();
+    GzDecoder::new(gzipped.as_slice()).read_to_end(&mut decoded).unwrap();
+    assert_eq!(json(&decoded), json(&plain));
+
+    // Encodings the server doesn't have are answered uncompressed
+    let (encoding, body) = raw_pull(&server, Some("zstd"));
+    assert_eq!(encoding, None);
+    assert_eq!(json(&body), json(&plain));
+
+    // Switched off, nothing is compressed even when asked
+    drop(server);
+    let server = Server::start_with(&dir, &[("VINY_COMPRESSION", "false")]);
+    let (encoding, body) = raw_pull(&server, Some("gzip, br"));
+    assert_eq!(encoding, None);
+    assert_eq!(json(&body), json(&plain));
+}
@@ -0,0 +1,52 @@
+//! The REST endpoints for single notes
+
+mod common;
+
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use common::{pull, Server};
+
+#[test]
+fn test_missing_notes_are_404() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+
+    assert_eq!(server.get("/api/notes/nowhere").status(), 404);
+    assert_eq!(server.put("/api/notes/nowhere", &json!({ "title": "New" })).status(), 404);
+    assert_eq!(server.delete("/api/notes/nowhere").status(), 404);
+
+    // A deleted note is gone for these endpoints too
+    let id = server.create_note("Short-lived")["id"].as_str().unwrap().to_string();
+    let path = format!("/api/notes/{}", id);
+    assert_eq!(server.delete(&path).status(), 204);
+    assert_eq!(server.get(&path).status(), 404);
+    assert_eq!(server.delete(&path).status(), 404);
+}
+
+#[test]
+fn test_update_checks_the_revision_and_reaches_pulls() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    let note = server.create_note("Draft");
+    let path = format!("/api/notes/{}", note["id"].as_str().unwrap());
+    let revision = note["revision"].as_i64().unwrap();
+
+    let response = server.put(&path, &json!({ "title": "Final", "revision": revision }));
+    assert_eq!(response.status(), 200);
+    let updated: Value = serde_json::from_str(&response.text().unwrap()).unwrap();
+    assert_eq!(updated["title"], "Final");
+    assert!(updated["revision"].as_i64().unwrap() > revision);
+
+    // Editing from the revision it had before is a conflict, and changes nothing
+    let stale = server.put(&path, &json!({ "title": "Lost", "revision": revision }));
+    assert_eq!(stale.status(), 409);
+    assert_eq!(server.get_json(&path)["title"], "Final");
+
+    // Devices pull the edit like any other change
+    let page = server.post_json("/api/sync/pull", &pull("laptop", revision, None));
+    let notes = page["notes"].as_array().unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0]["title"], "Final");
+    assert_eq!(page["server_revision"], updated["revision"]);
+}
@@ -0,0 +1,189 @@
+//! Pull and push against a running server
+
+mod common;
+
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use common::{note, pull, push, Server, TOKEN};
+
+fn device_revision(server: &Server, device_id: &str) -> i64 {
+    let devices = server.get_json("/api/devices");
+    let device = devices.as_array().unwrap().iter().find(|d| d["device_id"] == device_id).unwrap();
+    device["last_pull_revision"].as_i64().unwrap()
+}
+
+#[test]
+fn test_device_counts_as_synced_through_the_cursors_it_sends() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    server.post_json("/api/sync/push", &push("writer", vec![note("a", 1), note("b", 1), note("c", 1)]));
+
+    // The page isn't merged yet when the server answers
+    let page: Value = server.post_json("/api/sync/pull", &pull("reader", 0, Some(2)));
+    assert_eq!(page["page_revision"], 2);
+    assert_eq!(device_revision(&server, "reader"), 0);
+
+    // Asking from past the page says it was
+    server.post_json("/api/sync/pull", &pull("reader", 2, Some(2)));
+    assert_eq!(device_revision(&server, "reader"), 2);
+
+    // Cursors never move a device back
+    server.post_json("/api/sync/pull", &pull("reader", 0, None));
+    assert_eq!(device_revision(&server, "reader"), 2);
+}
+
+#[test]
+fn test_removing_a_device_needs_the_admin_token_and_an_existing_device() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    server.post_json("/api/sync/pull", &pull("reader", 0, None));
+    server.post_json("/api/sync/push", &push("writer", vec![note("a", 1)]));
+
+    // Only pushes set the push time
+    let devices = server.get_json("/api/devices");
+    let device = |id: &str| devices.as_array().unwrap().iter().find(|d| d["device_id"] == id).unwrap().clone();
+    let pushed = |id: &str| device(id)["last_push_at"].clone();
+    assert!(pushed("reader").is_null());
+    assert!(pushed("writer").is_string());
+
+    let remove = |id: &str, token: Option<&str>| {
+        let request = server.client.delete(format!("{}/api/devices/{}", server.url, id));
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+        .send()
+        .unwrap()
+        .status()
+    };
+    assert_eq!(remove("reader", None), 401);
+    assert_eq!(remove("reader", Some("wrong")), 401);
+    assert_eq!(remove("nowhere", Some(TOKEN)), 404);
+    assert_eq!(remove("reader", Some(TOKEN)), 200);
+    assert_eq!(remove("reader", Some(TOKEN)), 404);
+
+    // A removed device that syncs again is back, from its new cursor
+    server.post_json("/api/sync/pull", &pull("reader", 1, None));
+    assert_eq!(device_revision(&server, "reader"), 1);
+}
+
+#[test]
+fn test_dry_run_pull_records_no_device() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    server.post_json("/api/sync/push", &push("writer", vec![note("a", 1)]));
+
+    let mut preview = pull("", 0, None);
+    preview["dry_run"] = Value::Bool(true);
+    let page = server.post_json("/api/sync/pull", &preview);
+    assert_eq!(page["notes"].as_array().unwrap().len(), 1);
+
+    let devices = server.get_json("/api/devices");
+    let ids: Vec<&str> = devices.as_array().unwrap().iter().map(|d| d["device_id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["writer"]);
+
+    // A real pull still needs to say who it is
+    assert_eq!(server.post("/api/sync/pull", &pull("", 0, None)).status(), 400);
+}
+
+#[test]
+fn test_pages_cut_every_type_at_one_revision() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start(&dir);
+    // Notes and tags take turns, so each type's revisions have gaps
+    for i in 0..5 {
+        server.post_json("/api/sync/push", &push("writer", vec![note(&format!("n{}", i), 1)]));
+        let mut tags = push("writer", Vec::new());
+        tags["tags"] = json!([{
+            "id": format!("t{}", i), "name": format!("tag {}", i), "color": null,
+            "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z",
+            "revision": 1, "is_deleted": false,
+        }]);
+        server.post_json("/api/sync/push", &tags);
+    }
+
+    let mut since = 0;
+    let mut pages = Vec::new();
+    loop {
+        let page = server.post_json("/api/sync/pull", &pull("reader", since, Some(2)));
+        let page_revision = page["page_revision"].as_i64().unwrap();
+        let mut revisions: Vec<i64> = ["notes", "tags"]
+            .iter()
+            .flat_map(|kind| page[kind].as_array().unwrap().iter().map(|e| e["revision"].as_i64().unwrap()))
+            .collect();
+        revisions.sort();
+
+        // Nothing at or below the cursor, nothing past the page, and no
+        // revision below the page's end is left for the next one
+        assert_eq!(revisions, ((since + 1)..=page_revision).collect::<Vec<_>>());
+        pages.push(revisions.len());
+        since = page_revision;
+        if !page["has_more"].as_bool().unwrap() {
+            break;
+        }
+        assert!(pages.len() < 10, "has_more never ended the loop");
+    }
+
+    assert_eq!(since, 10);
+    assert_eq!(pages.iter().sum::<usize>(), 10);
+    // Each type's second row decides where a page ends: notes at 1 and 3
+    // cut the first page at 3, whatever the tags have past it
+    assert_eq!(pages[0], 3);
+}
+
+#[test]
+fn test_invalid_pushes_are_refused_whole() {
+    let dir = TempDir::new().unwrap();
+    let server = Server::start_with(&dir, &[("VINY_MAX_CONTENT_BYTES", "100"), ("VINY_MAX_BODY_BYTES", "4096")]);
+
+    let mut banana = note("b", 1);
+    banana["updated_at"] = json!("banana");
+    let mut status = note("s", 1);
+    status["status"] = json!("lost");
+    let mut long = note("l", 1);
+    long["content"] = json!("x".repeat(101));
+    let notes = vec![note("fine", 1), note("", 1), note(&"i".repeat(200), 1), banana, status, long];
+    let response = server.post("/api/sync/push", &push("writer", notes));
+    assert_eq!(response.status(), 422);
+
+    let body: Value = serde_json::from_str(&response.text().unwrap()).unwrap();
+    let fields: Vec<(&str, &str)> = body["rejected"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["id"].as_str().unwrap(), e["field"].as_str().unwrap()))
+        .collect();
+    assert_eq!(fields.len(), 5, "{:?}", fields);
+    for field in [("", "id"), ("b", "updated_at"), ("s", "status"), ("l", "content")] {
+        assert!(fields.contains(&field), "{:?} missing from {:?}", field, fields);
+    }
+    // Long ids come back cut short
+    assert!(fields.iter().any(|(id, field)| *field == "id" && id.len() == 64));
+
+    // Nothing was stored, not even the valid note
+    assert_eq!(server.get_json("/api/sync/revision")["revision"], 0);
+
+    // A body over the limit is refused before it's read
+    let response = server.post("/api/sync/push", &push("writer", vec![note(&"x".repeat(5000), 1)]));
+    assert_eq!(response.status(), 413);
+    assert_eq!(server.post_json("/api/sync/push", &push("writer", vec![note("fine", 1)]))["accepted"], 1);
+}
+
+#[test]
+fn test_sync_is_rate_limited_with_retry_after() {
+    let dir = TempDir::new().unwrap();
+    let server =
+        Server::start_with(&dir, &[("VINY_RATE_LIMIT_PER_MINUTE", "1"), ("VINY_RATE_LIMIT_BURST", "2")]);
+
+    assert_eq!(server.get("/api/sync/revision").status(), 200);
+    assert_eq!(server.post("/api/sync/pull", &pull("laptop", 0, None)).status(), 200);
+    let limited = server.get("/api/sync/revision");
+    assert_eq!(limited.status(), 429);
+    // A token a minute, so close to a minute until the next one
+    let retry_after: u64 = limited.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((55..=60).contains(&retry_after), "Retry-After: {}", retry_after);
+
+    // Only the sync endpoints are limited
+    assert_eq!(server.get("/api/notes").status(), 200);
+}